@@ -0,0 +1,234 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use super::formats::{self, Entry, Format, PoState};
+use super::ids::{self, IdMap};
+
+/// The format of a catalog that's translated as Fluent and written back, such
+/// as a gettext `.pot` file or a Flutter `.arb` file, or `None` for Fluent
+pub fn format_of(path: &Path) -> Option<Format> {
+    Format::from_path(path).filter(|format| *format != Format::Fluent)
+}
+
+/// Where a locale's catalog is kept as Fluent during a run
+pub fn working_path(locale: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("tt-{}-{}.flt", std::process::id(), locale))
+}
+
+/// Write catalog entries as Fluent with the ids they're given in `ids`
+fn to_fluent(entries: &[Entry], ids: &IdMap) -> (String, IdMap) {
+    let keys: Vec<&str> = entries.iter().map(|e| e.key.as_str()).collect();
+    let (ids, map) = IdMap::assign(&keys, ids);
+    (formats::to_fluent(entries, &ids), map)
+}
+
+/// Convert the text of a source catalog into Fluent, such as an older version
+/// of it to diff against, giving its keys the ids they have in `ids`. The
+/// descriptions of ARB messages are for translators, so they're given as
+/// `context` for the backends that can use it.
+pub fn source_fluent(
+    text: &str,
+    name: &Path,
+    format: Format,
+    ids: &IdMap,
+    context: &str,
+) -> Result<(String, IdMap), Box<dyn Error>> {
+    let entries: Vec<Entry> = formats::parse_states(format, name, text)?
+        .into_iter()
+        .map(|(mut entry, _)| {
+            if format == Format::Arb {
+                entry.comment = entry.comment.map(|description| {
+                    let description: Vec<&str> = description.split_whitespace().collect();
+                    format!("{}: {}", context, description.join(" "))
+                });
+            }
+            entry
+        })
+        .collect();
+    Ok(to_fluent(&entries, ids))
+}
+
+/// Read a source catalog as Fluent. Keys that aren't valid message ids are
+/// given the same ids from run to run by keeping them in a mapping file next
+/// to the catalog, which is returned too.
+pub fn read_source(
+    path: &Path,
+    format: Format,
+    context: &str,
+) -> Result<(String, IdMap), Box<dyn Error>> {
+    let ids_path = ids::mapping_path(path);
+    let (fluent, map) = source_fluent(
+        &formats::read_text(path)?,
+        path,
+        format,
+        &IdMap::load(&ids_path)?,
+        context,
+    )?;
+    if !map.is_empty() || ids_path.exists() {
+        map.save(&ids_path)?;
+    }
+    Ok((fluent, map))
+}
+
+/// Read a locale's catalog as Fluent, leaving out the gettext messages that
+/// aren't translated and marking fuzzy ones with `needs_review`. A missing
+/// catalog has no translations.
+pub fn read_translations(
+    path: &Path,
+    format: Format,
+    ids: &IdMap,
+    needs_review: &str,
+) -> Result<String, Box<dyn Error>> {
+    if !path.exists() {
+        return Ok(String::new());
+    }
+    let entries: Vec<Entry> = formats::parse_states(format, path, &formats::read_text(path)?)?
+        .into_iter()
+        .filter_map(|(mut entry, state)| match state {
+            PoState::Untranslated => None,
+            PoState::Fuzzy => {
+                let comment = entry.comment.get_or_insert_with(String::new);
+                if !comment.is_empty() {
+                    comment.push('\n');
+                }
+                comment.push_str(needs_review);
+                Some(entry)
+            }
+            PoState::Translated => Some(entry),
+        })
+        .collect();
+    Ok(to_fluent(&entries, ids).0)
+}
+
+/// Write the Fluent translations of a locale as its catalog, in the order of
+/// the source. gettext catalogs have an entry for every message of the
+/// source, with translations marked with `needs_review` flagged fuzzy and
+/// messages that aren't translated yet left with an empty msgstr, while the
+/// other formats only have the messages that are translated.
+pub fn write_translations(
+    path: &Path,
+    format: Format,
+    locale: &str,
+    source: &fluent_syntax::ast::Resource,
+    translated: &fluent_syntax::ast::Resource,
+    ids: &IdMap,
+    needs_review: &str,
+) -> Result<(), Box<dyn Error>> {
+    let source = formats::from_fluent(source, ids);
+    let translated: Vec<(Entry, PoState)> = formats::from_fluent(translated, ids)
+        .into_iter()
+        .map(|mut entry| {
+            if format != Format::Po {
+                return (entry, PoState::Translated);
+            }
+            let lines: Vec<&str> = entry.comment.as_deref().unwrap_or("").lines().collect();
+            let state = if lines.iter().any(|line| line.contains(needs_review)) {
+                PoState::Fuzzy
+            } else {
+                PoState::Translated
+            };
+            let kept: Vec<&str> = lines
+                .into_iter()
+                .filter(|line| !line.contains(needs_review))
+                .collect();
+            entry.comment = Some(kept.join("\n")).filter(|comment| !comment.is_empty());
+            (entry, state)
+        })
+        .collect();
+
+    let mut entries: Vec<(&Entry, PoState)> = Vec::new();
+    for entry in source.iter() {
+        match translated.iter().find(|(t, _)| t.key == entry.key) {
+            Some((translation, state)) => entries.push((translation, *state)),
+            None if format == Format::Po => entries.push((entry, PoState::Untranslated)),
+            None => {}
+        }
+    }
+    // such as messages kept with --keep-removed
+    entries.extend(
+        translated
+            .iter()
+            .filter(|(t, _)| !source.iter().any(|entry| entry.key == t.key))
+            .map(|(entry, state)| (entry, *state)),
+    );
+    // gettext and Flutter write regions with an underscore, such as `pt_BR`
+    let language = locale.replace('-', "_");
+    std::fs::write(
+        path,
+        formats::render_translations(format, &entries, &language),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_translations() {
+        let dir = std::env::temp_dir().join(format!("tt-gettext-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pot = dir.join("messages.pot");
+        std::fs::write(
+            &pot,
+            "msgid \"Hello %s\"\nmsgstr \"\"\n\nmsgid \"Bye {name}\"\nmsgstr \"\"\n\nmsgid \"100% sure\"\nmsgstr \"\"\n",
+        )
+        .unwrap();
+        let (source, ids) = read_source(&pot, Format::Po, "tt-context").unwrap();
+        assert!(source.contains("{\"%s\"}"), "{}", source);
+        assert!(source.contains("{\"{name}\"}"), "{}", source);
+        assert!(source.contains("100% sure"), "{}", source);
+        assert!(ids::mapping_path(&pot).exists());
+
+        let po = dir.join("fr.po");
+        std::fs::write(
+            &po,
+            "msgid \"\"\nmsgstr \"\"\n\n#, fuzzy\nmsgid \"Hello %s\"\nmsgstr \"Salut %s\"\n\nmsgid \"Bye {name}\"\nmsgstr \"\"\n",
+        )
+        .unwrap();
+        let target = read_translations(&po, Format::Po, &ids, "tt-needs-review").unwrap();
+        assert!(target.contains("# tt-needs-review\n"), "{}", target);
+        assert!(!target.contains("Bye"), "{}", target);
+
+        let source = fluent_syntax::parser::parse(source.as_str()).unwrap();
+        let target = fluent_syntax::parser::parse(target.as_str()).unwrap();
+        write_translations(
+            &po,
+            Format::Po,
+            "fr",
+            &source,
+            &target,
+            &ids,
+            "tt-needs-review",
+        )
+        .unwrap();
+        let written = std::fs::read_to_string(&po).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(written.contains("\"Language: fr\\n\"\n"), "{}", written);
+        assert!(
+            written.contains("\n#, fuzzy\nmsgid \"Hello %s\"\nmsgstr \"Salut %s\"\n"),
+            "{}",
+            written
+        );
+        assert!(
+            written.contains("\nmsgid \"Bye {name}\"\nmsgstr \"\"\n"),
+            "{}",
+            written
+        );
+        assert!(!written.contains("tt-needs-review"), "{}", written);
+    }
+}
@@ -0,0 +1,69 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{Deserialize, Serialize};
+use std::io::prelude::*;
+use std::path::Path;
+
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct AuditConfig {
+    /// the file that audit entries are appended to, one JSON object per line
+    pub file: String,
+}
+
+impl Default for AuditConfig {
+    fn default() -> AuditConfig {
+        AuditConfig {
+            file: "tt-audit.log".to_owned(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AuditEntry<'a> {
+    timestamp: String,
+    user: String,
+    action: &'a str,
+    locale: &'a str,
+    details: &'a str,
+}
+
+/// The user running the tool, as best as we can tell
+pub fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_owned())
+}
+
+/// Append an entry to the audit trail
+pub fn record<P: AsRef<Path>>(
+    path: P,
+    action: &str,
+    locale: &str,
+    details: &str,
+) -> std::io::Result<()> {
+    let entry = AuditEntry {
+        timestamp: chrono::Local::now().to_rfc3339(),
+        user: current_user(),
+        action,
+        locale,
+        details,
+    };
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)
+}
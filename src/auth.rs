@@ -0,0 +1,247 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct AuthConfig {
+    /// how many days old a service account key can be before it should have
+    /// been rotated
+    pub max_key_age_days: Option<i64>,
+    /// warn when a key expires within this many days
+    pub expiry_warning_days: Option<i64>,
+}
+
+/// The parts of a service account key file that say which key it is
+#[derive(Deserialize, Debug)]
+pub struct KeyFile {
+    pub project_id: String,
+    pub private_key_id: String,
+    pub client_email: String,
+    /// where Google publishes the certificate of each of the account's keys
+    pub client_x509_cert_url: String,
+}
+
+impl KeyFile {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<KeyFile, Box<dyn Error>> {
+        let contents = std::fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("failed to read {}: {}", path.as_ref().display(), e))?;
+        serde_json::from_str(&contents).map_err(|e| {
+            Box::from(format!(
+                "{} isn't a service account key: {}",
+                path.as_ref().display(),
+                e
+            ))
+        })
+    }
+}
+
+/// When a key was created and when it expires, from its certificate
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Validity {
+    pub created: NaiveDateTime,
+    /// Google writes keys that never expire as expiring in 9999
+    pub expires: NaiveDateTime,
+}
+
+impl Validity {
+    pub fn never_expires(&self) -> bool {
+        self.expires.format("%Y").to_string() == "9999"
+    }
+}
+
+/// Read a DER value, returning its tag, its contents and whatever follows it
+fn read_der(der: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *der.first()?;
+    let first = *der.get(1)? as usize;
+    let (length, start) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let bytes = first & 0x7f;
+        if bytes == 0 || bytes > 4 {
+            return None;
+        }
+        let length = der
+            .get(2..2 + bytes)?
+            .iter()
+            .fold(0, |length, b| (length << 8) | *b as usize);
+        (length, 2 + bytes)
+    };
+    let end = start.checked_add(length)?;
+    Some((tag, der.get(start..end)?, der.get(end..)?))
+}
+
+fn read_time(tag: u8, contents: &[u8]) -> Option<NaiveDateTime> {
+    let text = std::str::from_utf8(contents).ok()?;
+    let format = match tag {
+        // UTCTime, with a two digit year
+        0x17 => "%y%m%d%H%M%SZ",
+        // GeneralizedTime
+        0x18 => "%Y%m%d%H%M%SZ",
+        _ => return None,
+    };
+    NaiveDateTime::parse_from_str(text, format).ok()
+}
+
+/// Read the validity of an X.509 certificate in DER
+fn certificate_validity(der: &[u8]) -> Option<Validity> {
+    let (_, certificate, _) = read_der(der)?;
+    let (_, tbs, _) = read_der(certificate)?;
+    let (tag, _, mut rest) = read_der(tbs)?;
+    // the version is optional, and tagged [0] when it's there
+    if tag == 0xa0 {
+        rest = read_der(rest)?.2;
+    }
+    // the algorithm and issuer come between the serial number and validity
+    let (_, _, rest) = read_der(rest)?;
+    let (_, _, rest) = read_der(rest)?;
+    let (_, validity, _) = read_der(rest)?;
+    let (created_tag, created, rest) = read_der(validity)?;
+    let (expires_tag, expires, _) = read_der(rest)?;
+    Some(Validity {
+        created: read_time(created_tag, created)?,
+        expires: read_time(expires_tag, expires)?,
+    })
+}
+
+fn pem_validity(pem: &str) -> Option<Validity> {
+    let body: String = pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .map(str::trim)
+        .collect();
+    certificate_validity(&base64::decode(&body).ok()?)
+}
+
+impl KeyFile {
+    /// Ask Google when the key was created and when it expires, from the
+    /// certificates it publishes for the account
+    pub fn validity(&self, http: &super::http::Settings) -> Result<Validity, Box<dyn Error>> {
+        let client = http.client()?;
+        let (status, body) =
+            super::dump::send(&client, "google", client.get(&self.client_x509_cert_url))?;
+        if !status.is_success() {
+            return Err(Box::from(format!(
+                "failed to get the certificates of {}: {}",
+                self.client_email, status
+            )));
+        }
+        let certificates: BTreeMap<String, String> = serde_json::from_str(&body)?;
+        let certificate = certificates.get(&self.private_key_id).ok_or_else(|| {
+            format!(
+                "key {} of {} has been deleted",
+                self.private_key_id, self.client_email
+            )
+        })?;
+        pem_validity(certificate).ok_or_else(|| {
+            Box::from(format!(
+                "failed to read the certificate of key {}",
+                self.private_key_id
+            ))
+        })
+    }
+}
+
+/// Whether signing in failed because Google turned the key or its account
+/// down, rather than something like the network
+pub fn rejected(error: &str) -> bool {
+    error.contains("invalid_grant") || error.contains("account disabled")
+}
+
+impl AuthConfig {
+    /// Describe what's wrong with a key that `validity` was read for `now`,
+    /// if anything is
+    pub fn problems(&self, validity: &Validity, now: NaiveDateTime) -> Vec<String> {
+        let mut problems = Vec::new();
+        let age = (now - validity.created).num_days();
+        if let Some(max) = self.max_key_age_days.filter(|max| age > *max) {
+            problems.push(format!(
+                "is {} days old, over the {} days that keys are rotated after",
+                age, max
+            ));
+        }
+        let left = (validity.expires - now).num_days();
+        if validity.expires <= now {
+            problems.push(format!("expired on {}", validity.expires.date()));
+        } else if let Some(warning) = self.expiry_warning_days.filter(|days| left <= *days) {
+            problems.push(format!(
+                "expires on {}, in {} days, within the {} day warning",
+                validity.expires.date(),
+                left,
+                warning
+            ));
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // made by `openssl req -x509 -newkey ed25519 -days 1000`
+    const CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBGzCBzqADAgECAgEBMAUGAytlcDANMQswCQYDVQQDDAJ0dDAeFw0yNjEwMTQx
+NjU3MTdaFw0yOTA3MTAxNjU3MTdaMA0xCzAJBgNVBAMMAnR0MCowBQYDK2VwAyEA
+WLy7zD/WNia0SLoZdMXZankW3d7s67QLqNbjDBNK3KqjUzBRMB0GA1UdDgQWBBSM
+TovF8feurIvq4gKiWRHix95BnjAfBgNVHSMEGDAWgBSMTovF8feurIvq4gKiWRHi
+x95BnjAPBgNVHRMBAf8EBTADAQH/MAUGAytlcANBAB7++0HTmQizu/m+JrAz0WzI
+qjGo4osMH1FamLHR6pDvpPR1AYYzCg+Vx4En5L0jtw9XcKnGMHdLDT6oDB3ILA8=
+-----END CERTIFICATE-----
+";
+
+    fn at(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn reads_key_validity() {
+        let validity = pem_validity(CERTIFICATE).unwrap();
+        assert_eq!(validity.created, at("2026-10-14 16:57:17"));
+        assert_eq!(validity.expires, at("2029-07-10 16:57:17"));
+        assert!(!validity.never_expires());
+        assert!(pem_validity("-----BEGIN CERTIFICATE-----\nMIIB\n").is_none());
+    }
+
+    #[test]
+    fn finds_key_problems() {
+        let validity = pem_validity(CERTIFICATE).unwrap();
+        let config = AuthConfig {
+            max_key_age_days: Some(90),
+            expiry_warning_days: Some(30),
+        };
+        assert!(config
+            .problems(&validity, at("2026-12-01 00:00:00"))
+            .is_empty());
+        assert_eq!(
+            config.problems(&validity, at("2029-07-01 00:00:00")),
+            vec![
+                "is 990 days old, over the 90 days that keys are rotated after".to_owned(),
+                "expires on 2029-07-10, in 9 days, within the 30 day warning".to_owned(),
+            ]
+        );
+        assert_eq!(
+            AuthConfig::default().problems(&validity, at("2030-01-01 00:00:00")),
+            vec!["expired on 2029-07-10".to_owned()]
+        );
+        assert!(rejected(
+            "failed to get access token: code 400: \"{\\\"error\\\":\\\"invalid_grant\\\",\\\"error_description\\\":\\\"Invalid grant: account disabled\\\"}\""
+        ));
+    }
+}
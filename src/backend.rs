@@ -0,0 +1,373 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::error::Error;
+
+use super::translate::{GlossaryConfig, LRLanguage, MimeType};
+
+/// A translation engine that messages can be sent to, from several threads at
+/// once
+pub trait Backend: Sync {
+    /// A short name for the backend, used in logs and reports
+    fn name(&self) -> &'static str;
+
+    /// List the languages the backend can translate into
+    fn available_languages(&self) -> Result<Vec<LRLanguage>, Box<dyn Error>>;
+
+    /// Get the name of the target language, in the target language
+    fn get_lang_name(&self) -> Result<String, Box<dyn Error>>;
+
+    /// Translate `phrase`, interpreting it as plain text or HTML according to
+    /// `mime_type`
+    fn translate<'c>(
+        &self,
+        phrase: &str,
+        mime_type: MimeType,
+        glossary: &Option<GlossaryConfig<'c>>,
+    ) -> Result<String, Box<dyn Error>>;
+
+    /// Translate several phrases together, such as a message and its
+    /// attributes, so that the backend sees them at once. Backends that can't
+    /// do that translate them one at a time.
+    fn translate_batch<'c>(
+        &self,
+        phrases: &[&str],
+        mime_type: MimeType,
+        glossary: &Option<GlossaryConfig<'c>>,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        phrases
+            .iter()
+            .map(|phrase| self.translate(phrase, mime_type, glossary))
+            .collect()
+    }
+
+    /// Translate several phrases together, with `context` saying where
+    /// they're shown so that short texts like "Book" can be told apart.
+    /// Backends that can't use it translate them without it.
+    fn translate_with_context<'c>(
+        &self,
+        phrases: &[&str],
+        mime_type: MimeType,
+        glossary: &Option<GlossaryConfig<'c>>,
+        _context: &str,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        self.translate_batch(phrases, mime_type, glossary)
+    }
+
+    /// How much can be sent in one request, for backends whose
+    /// `translate_batch` sends all of its phrases at once
+    fn batch_limit(&self) -> Option<BatchLimit> {
+        None
+    }
+}
+
+/// The most that a backend can be sent in one request
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BatchLimit {
+    pub texts: usize,
+    pub chars: usize,
+}
+
+impl BatchLimit {
+    /// Split messages, given as how many texts and characters each has, into
+    /// runs of messages that can be sent in one request each. A message is
+    /// never split up, so one that's over the limit is sent on its own.
+    pub fn batches(&self, messages: &[(usize, usize)]) -> Vec<std::ops::Range<usize>> {
+        let mut batches = Vec::new();
+        let mut start = 0;
+        let (mut texts, mut chars) = (0, 0);
+        for (i, (message_texts, message_chars)) in messages.iter().enumerate() {
+            if i > start
+                && (texts + message_texts > self.texts || chars + message_chars > self.chars)
+            {
+                batches.push(start..i);
+                start = i;
+                texts = 0;
+                chars = 0;
+            }
+            texts += message_texts;
+            chars += message_chars;
+        }
+        if start < messages.len() {
+            batches.push(start..messages.len());
+        }
+        batches
+    }
+}
+
+/// The built-in backends accepted by `--backend`, along with `exec:<path>`
+#[cfg(not(feature = "offline"))]
+pub const NAMES: &[&str] = &["google", "deepl", "pseudo"];
+#[cfg(feature = "offline")]
+pub const NAMES: &[&str] = &["google", "deepl", "pseudo", "offline"];
+
+/// Check that `name` is a backend that can be created
+pub fn validate_name(name: String) -> Result<(), String> {
+    if NAMES.contains(&name.as_str()) || (name.starts_with("exec:") && name.len() > "exec:".len()) {
+        Ok(())
+    } else {
+        Err(format!(
+            "unknown backend `{}`, expected one of {} or exec:<path>",
+            name,
+            NAMES.join(", ")
+        ))
+    }
+}
+
+/// How backends reach their services, which each run sets up from its own
+/// config and command line
+#[derive(Clone)]
+pub struct Connection {
+    pub http: super::http::Settings,
+    pub endpoints: super::endpoint::Endpoints,
+    /// the language that source files are written in, as the backends know it
+    pub source_language: String,
+    /// the labels that Google's translation requests are billed under
+    pub labels: std::collections::BTreeMap<String, String>,
+    /// how failed requests are sent again
+    pub retry: super::retry::Retry,
+    /// keeps requests to the run's `--qps`, for backends that don't go through
+    /// `retry`
+    pub throttle: super::throttle::Throttle,
+}
+
+impl Default for Connection {
+    fn default() -> Connection {
+        Connection {
+            http: super::http::Settings::default(),
+            endpoints: super::endpoint::Endpoints::default(),
+            source_language: super::languages::DEFAULT_SOURCE_LANGUAGE.to_owned(),
+            labels: Default::default(),
+            retry: super::retry::Retry::default(),
+            throttle: super::throttle::Throttle::default(),
+        }
+    }
+}
+
+/// Create the backend called `name` which translates into `language`. The
+/// Google backend needs a token and project id from the credentials file, and
+/// can use a `model` other than its default.
+pub fn create<'a>(
+    name: &str,
+    credentials: Option<&'a (String, String)>,
+    connection: &Connection,
+    model: Option<&str>,
+    language: &'a str,
+) -> Result<Box<dyn Backend + 'a>, Box<dyn Error>> {
+    match name {
+        "google" => {
+            let (token, project_id) =
+                credentials.ok_or(super::errors::Errors::MissingCredentialsFile)?;
+            Ok(Box::new(super::translate::Translator::new(
+                token, project_id, connection, model, language,
+            )?))
+        }
+        "deepl" => Ok(Box::new(super::deepl::DeepLTranslator::new(
+            connection, language,
+        )?)),
+        "pseudo" => Ok(Box::new(super::pseudo::PseudoTranslator::new(
+            &connection.source_language,
+            language,
+        ))),
+        #[cfg(feature = "offline")]
+        "offline" => Ok(Box::new(super::offline::OfflineTranslator::new(
+            &connection.source_language,
+            language,
+        )?)),
+        _ if name.starts_with("exec:") => Ok(Box::new(super::exec::ExecBackend::new(
+            &name["exec:".len()..],
+            connection,
+            language,
+        ))),
+        _ => Err(Box::from(super::errors::Errors::InvalidBackend)),
+    }
+}
+
+/// Whether the backend called `name` needs Google Cloud credentials
+pub fn needs_credentials(name: &str) -> bool {
+    name == "google"
+}
+
+/// Whether a backend failing with `error` should fall back to the next backend
+fn should_fall_back(error: &(dyn Error + 'static)) -> bool {
+    matches!(
+        error.downcast_ref::<super::errors::Errors>(),
+        Some(super::errors::Errors::UnsupportedLanguage)
+            | Some(super::errors::Errors::QuotaExceeded)
+            | Some(super::errors::Errors::InvalidLanguage)
+    )
+}
+
+/// A backend that failed to translate a message
+#[derive(Debug)]
+pub struct Failure {
+    pub backend: &'static str,
+    pub error: String,
+}
+
+/// The result of sending a message through a chain of backends
+pub struct Outcome {
+    /// the translations and the backend that produced them, if any succeeded
+    pub translation: Option<(Vec<String>, &'static str)>,
+    /// every backend that was tried and failed, in order
+    pub failures: Vec<Failure>,
+}
+
+/// An ordered list of backends; when one can't translate into the language or
+/// has run out of quota, the next one is tried
+pub struct Chain<'a> {
+    backends: Vec<Box<dyn Backend + 'a>>,
+}
+
+impl<'a> Chain<'a> {
+    /// Create each of the named backends, skipping any that can't translate
+    /// into `language` as long as at least one can
+    pub fn create(
+        names: &[&str],
+        credentials: Option<&'a (String, String)>,
+        connection: &Connection,
+        model: Option<&str>,
+        language: &'a str,
+    ) -> Result<Chain<'a>, Box<dyn Error>> {
+        let mut backends = Vec::new();
+        let mut last_error = None;
+        for name in names {
+            match create(name, credentials, connection, model, language) {
+                Ok(backend) => backends.push(backend),
+                Err(e) if should_fall_back(e.as_ref()) => {
+                    log::warn!("skipping backend {} for {}: {}", name, language, e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        match last_error {
+            Some(e) if backends.is_empty() => Err(e),
+            _ => Ok(Chain { backends }),
+        }
+    }
+
+    /// The names of the backends in the chain, in order
+    pub fn names(&self) -> Vec<&'static str> {
+        self.backends.iter().map(|b| b.name()).collect()
+    }
+
+    /// List the languages that any of the backends can translate into
+    pub fn available_languages(&self) -> Result<Vec<LRLanguage>, Box<dyn Error>> {
+        let mut languages: Vec<LRLanguage> = Vec::new();
+        for backend in self.backends.iter() {
+            for lang in backend.available_languages()? {
+                if !languages
+                    .iter()
+                    .any(|l| l.language_code == lang.language_code)
+                {
+                    languages.push(lang);
+                }
+            }
+        }
+        Ok(languages)
+    }
+
+    /// Get the name of the target language from the first backend that knows it
+    pub fn get_lang_name(&self) -> Result<String, Box<dyn Error>> {
+        let mut last_error = None;
+        for backend in self.backends.iter() {
+            match backend.get_lang_name() {
+                Ok(name) => return Ok(name),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| Box::from(super::errors::Errors::InvalidBackend)))
+    }
+
+    /// How much can be sent to every backend in the chain in one request, if
+    /// they can all be sent more than one message at a time
+    pub fn batch_limit(&self) -> Option<BatchLimit> {
+        self.backends
+            .iter()
+            .map(|backend| backend.batch_limit())
+            .try_fold(None, |smallest: Option<BatchLimit>, limit| {
+                let limit = limit?;
+                Some(Some(match smallest {
+                    Some(smallest) => BatchLimit {
+                        texts: smallest.texts.min(limit.texts),
+                        chars: smallest.chars.min(limit.chars),
+                    },
+                    None => limit,
+                }))
+            })
+            .flatten()
+    }
+
+    /// Translate a message's phrases with the first backend that can, in
+    /// `context` if it has one
+    pub fn translate<'c>(
+        &self,
+        phrases: &[&str],
+        mime_type: MimeType,
+        glossary: &Option<GlossaryConfig<'c>>,
+        context: Option<&str>,
+    ) -> Outcome {
+        let mut failures = Vec::new();
+        for backend in self.backends.iter() {
+            let translated = match context {
+                Some(context) => {
+                    backend.translate_with_context(phrases, mime_type, glossary, context)
+                }
+                None => backend.translate_batch(phrases, mime_type, glossary),
+            };
+            match translated {
+                Ok(t) => {
+                    return Outcome {
+                        translation: Some((t, backend.name())),
+                        failures,
+                    }
+                }
+                Err(e) => {
+                    let fall_back = should_fall_back(e.as_ref());
+                    failures.push(Failure {
+                        backend: backend.name(),
+                        error: format!("{}", e),
+                    });
+                    if !fall_back {
+                        break;
+                    }
+                }
+            }
+        }
+        Outcome {
+            translation: None,
+            failures,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_batches() {
+        let limit = BatchLimit {
+            texts: 3,
+            chars: 100,
+        };
+        assert_eq!(
+            limit.batches(&[(1, 10), (2, 10), (1, 10), (1, 90), (1, 200), (1, 5)]),
+            vec![0..2, 2..4, 4..5, 5..6]
+        );
+        assert_eq!(limit.batches(&[(5, 10), (1, 10)]), vec![0..1, 1..2]);
+        assert!(limit.batches(&[]).is_empty());
+    }
+}
@@ -0,0 +1,132 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cell::Cell;
+
+/// What Google charges per million characters, used for `--max-cost` unless
+/// the config says otherwise
+pub const DEFAULT_COST_PER_MILLION_CHARS: f64 = 20.0;
+
+/// A cap on how many characters a run can send to the backends. Once a
+/// message doesn't fit, it and every message after it are skipped, so that
+/// the run stops at the same point each time.
+pub struct Budget {
+    max_chars: Option<usize>,
+    used: Cell<usize>,
+    exhausted: Cell<bool>,
+}
+
+impl Budget {
+    /// Allow up to `max_chars` characters, or however many characters
+    /// `max_cost` buys at `cost_per_million_chars`, whichever is fewer
+    pub fn new(
+        max_chars: Option<usize>,
+        max_cost: Option<f64>,
+        cost_per_million_chars: f64,
+    ) -> Budget {
+        let cost_chars = max_cost.map(|cost| (cost / cost_per_million_chars * 1e6) as usize);
+        let max_chars = match (max_chars, cost_chars) {
+            (Some(chars), Some(cost_chars)) => Some(chars.min(cost_chars)),
+            (chars, cost_chars) => chars.or(cost_chars),
+        };
+        Budget {
+            max_chars,
+            used: Cell::new(0),
+            exhausted: Cell::new(false),
+        }
+    }
+
+    /// Spend `chars` characters if they fit in what's left of the budget
+    pub fn spend(&self, chars: usize) -> bool {
+        if self.exhausted.get() {
+            return false;
+        }
+        match self.max_chars {
+            Some(max) if self.used.get() + chars > max => {
+                self.exhausted.set(true);
+                false
+            }
+            _ => {
+                self.used.set(self.used.get() + chars);
+                true
+            }
+        }
+    }
+
+    /// Whether there's a limit on how much can be sent
+    pub fn limited(&self) -> bool {
+        self.max_chars.is_some()
+    }
+
+    /// Whether any message had to be skipped
+    pub fn exhausted(&self) -> bool {
+        self.exhausted.get()
+    }
+
+    /// How many characters have been sent so far
+    pub fn used(&self) -> usize {
+        self.used.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_the_smaller_limit() {
+        // $1 at $20 per million characters buys 50,000 characters
+        assert_eq!(Budget::new(Some(100), Some(1.0), 20.0).max_chars, Some(100));
+        assert_eq!(
+            Budget::new(Some(100_000), Some(1.0), 20.0).max_chars,
+            Some(50_000)
+        );
+        assert_eq!(Budget::new(None, Some(1.0), 20.0).max_chars, Some(50_000));
+        assert_eq!(Budget::new(Some(100), None, 20.0).max_chars, Some(100));
+        assert!(!Budget::new(None, None, 20.0).limited());
+    }
+
+    #[test]
+    fn stops_for_good_once_something_doesnt_fit() {
+        let budget = Budget::new(Some(10), None, DEFAULT_COST_PER_MILLION_CHARS);
+        assert!(budget.spend(6));
+        assert!(budget.spend(4));
+        assert_eq!(budget.used(), 10);
+        assert!(!budget.exhausted());
+
+        // nothing fits after a message that didn't, even a smaller one
+        assert!(!budget.spend(1));
+        assert!(budget.exhausted());
+        assert!(!budget.spend(0));
+        assert_eq!(budget.used(), 10);
+    }
+
+    #[test]
+    fn retries_spend_from_the_same_budget() {
+        // a message of 6 characters, translated again since its placeables
+        // changed, leaves no room for the next one
+        let budget = Budget::new(Some(15), None, DEFAULT_COST_PER_MILLION_CHARS);
+        assert!(budget.spend(6));
+        assert!(budget.spend(6));
+        assert!(!budget.spend(6));
+        assert!(budget.exhausted());
+        assert_eq!(budget.used(), 12);
+
+        // an unlimited budget still counts what's sent
+        let budget = Budget::new(None, None, DEFAULT_COST_PER_MILLION_CHARS);
+        assert!(budget.spend(1_000_000));
+        assert!(!budget.exhausted());
+        assert_eq!(budget.used(), 1_000_000);
+    }
+}
@@ -0,0 +1,242 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// How short strings such as buttons and headings are cased in translations
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct CasingConfig {
+    pub enabled: bool,
+    /// strings with more words than this are translated as they are
+    pub max_words: usize,
+    /// how Title Case strings are cased in each locale, instead of title case
+    /// for English and sentence case for everything else
+    pub title_case: BTreeMap<String, TitleCase>,
+}
+
+impl Default for CasingConfig {
+    fn default() -> CasingConfig {
+        CasingConfig {
+            enabled: true,
+            max_words: 5,
+            title_case: BTreeMap::new(),
+        }
+    }
+}
+
+/// What a Title Case string becomes in a locale
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum TitleCase {
+    /// Every Major Word Capitalised
+    Title,
+    /// Only the first word capitalised
+    Sentence,
+    /// however the backend translated it
+    Keep,
+}
+
+/// The casing of a source string
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Style {
+    Upper,
+    Title,
+    /// anything else, which is translated as it is
+    Other,
+}
+
+/// Words that stay lowercase inside an English title
+const MINOR_WORDS: &[&str] = &[
+    "a", "an", "and", "as", "at", "but", "by", "for", "in", "nor", "of", "on", "or", "the", "to",
+];
+
+/// Turkish and Azerbaijani pair dotted and dotless i differently
+fn dotted_i(locale: &str) -> bool {
+    let language = locale.split(['-', '_']).next().unwrap_or("");
+    language == "tr" || language == "az"
+}
+
+fn upper(text: &str, locale: &str) -> String {
+    if dotted_i(locale) {
+        text.chars()
+            .map(|c| match c {
+                'i' => "İ".to_owned(),
+                'ı' => "I".to_owned(),
+                c => c.to_uppercase().collect(),
+            })
+            .collect()
+    } else {
+        text.to_uppercase()
+    }
+}
+
+fn lower(text: &str, locale: &str) -> String {
+    if dotted_i(locale) {
+        text.chars()
+            .map(|c| match c {
+                'I' => "ı".to_owned(),
+                'İ' => "i".to_owned(),
+                c => c.to_lowercase().collect(),
+            })
+            .collect()
+    } else {
+        text.to_lowercase()
+    }
+}
+
+/// Capitalise the first letter of `word`
+fn capitalise(word: &str, locale: &str) -> String {
+    match word.char_indices().find(|(_, c)| c.is_alphabetic()) {
+        Some((i, c)) => format!(
+            "{}{}{}",
+            &word[..i],
+            upper(&c.to_string(), locale),
+            &word[i + c.len_utf8()..]
+        ),
+        None => word.to_owned(),
+    }
+}
+
+impl CasingConfig {
+    /// Work out the casing of a short string. Longer strings, and strings with
+    /// markup that changing the case could break, are always `Other`.
+    pub fn detect(&self, text: &str) -> Style {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let letters = text.chars().filter(|c| c.is_alphabetic()).count();
+        if !self.enabled
+            || words.is_empty()
+            || words.len() > self.max_words
+            || letters < 2
+            || text.contains(['<', '&'])
+        {
+            return Style::Other;
+        }
+        if !text.chars().any(char::is_lowercase) {
+            return Style::Upper;
+        }
+        let capitalised = |word: &&str| {
+            let mut letters = word.chars().filter(|c| c.is_alphabetic());
+            letters.next().map(char::is_uppercase).unwrap_or(true)
+                && letters.all(char::is_lowercase)
+        };
+        let major: Vec<&&str> = words
+            .iter()
+            .filter(|word| !MINOR_WORDS.contains(&word.to_lowercase().as_str()))
+            .collect();
+        if words.len() > 1 && major.len() > 1 && major.into_iter().all(capitalised) {
+            Style::Title
+        } else {
+            Style::Other
+        }
+    }
+
+    /// The text to send to the backend, in sentence case so that it's
+    /// translated like any other phrase, with the casing rules of `source`
+    pub fn prepare(&self, text: &str, style: Style, source: &str) -> String {
+        match style {
+            Style::Upper | Style::Title => capitalise(&lower(text, source), source),
+            Style::Other => text.to_owned(),
+        }
+    }
+
+    /// Case a translation the way that `locale` writes strings of `style`
+    pub fn apply(&self, translated: &str, style: Style, locale: &str) -> String {
+        match style {
+            Style::Upper => upper(translated, locale),
+            Style::Title => match self.title_case_for(locale) {
+                TitleCase::Title => translated
+                    .split(' ')
+                    .enumerate()
+                    .map(|(i, word)| {
+                        if i > 0 && MINOR_WORDS.contains(&lower(word, locale).as_str()) {
+                            word.to_owned()
+                        } else {
+                            capitalise(word, locale)
+                        }
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                TitleCase::Sentence => capitalise(translated, locale),
+                TitleCase::Keep => translated.to_owned(),
+            },
+            Style::Other => translated.to_owned(),
+        }
+    }
+
+    fn title_case_for(&self, locale: &str) -> TitleCase {
+        match self.title_case.get(locale) {
+            Some(title_case) => *title_case,
+            None if locale == "en" || locale.starts_with("en-") || locale.starts_with("en_") => {
+                TitleCase::Title
+            }
+            None => TitleCase::Sentence,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_casing() {
+        let casing = CasingConfig::default();
+        assert_eq!(casing.detect("SAVE CHANGES"), Style::Upper);
+        assert_eq!(casing.detect("OK"), Style::Upper);
+        assert_eq!(casing.detect("Save Your Changes"), Style::Title);
+        assert_eq!(casing.detect("Terms of Service"), Style::Title);
+        assert_eq!(casing.detect("Save changes"), Style::Other);
+        assert_eq!(casing.detect("Hello"), Style::Other);
+        assert_eq!(casing.detect("A"), Style::Other);
+        assert_eq!(casing.detect("<b>SAVE</b>"), Style::Other);
+        assert_eq!(
+            casing.detect("THIS IS FAR TOO LONG TO CHANGE"),
+            Style::Other
+        );
+    }
+
+    #[test]
+    fn recases_translations() {
+        let mut casing = CasingConfig::default();
+        casing.title_case.insert("de".to_owned(), TitleCase::Keep);
+        assert_eq!(
+            casing.prepare("SAVE CHANGES", Style::Upper, "en"),
+            "Save changes"
+        );
+        assert_eq!(
+            casing.prepare("Terms of Service", Style::Title, "en"),
+            "Terms of service"
+        );
+        assert_eq!(
+            casing.apply("Conditions d'utilisation", Style::Upper, "fr"),
+            "CONDITIONS D'UTILISATION"
+        );
+        assert_eq!(casing.apply("kaydet", Style::Upper, "tr"), "KAYDET");
+        assert_eq!(casing.apply("iptal", Style::Upper, "tr"), "İPTAL");
+        assert_eq!(
+            casing.apply("conditions d'utilisation", Style::Title, "fr"),
+            "Conditions d'utilisation"
+        );
+        assert_eq!(
+            casing.apply("terms of service", Style::Title, "en-GB"),
+            "Terms of Service"
+        );
+        assert_eq!(
+            casing.apply("Nutzungsbedingungen", Style::Title, "de"),
+            "Nutzungsbedingungen"
+        );
+    }
+}
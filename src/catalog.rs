@@ -0,0 +1,659 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+pub fn continue_parsing<'ast, P: AsRef<Path>>(
+    path: P,
+    r: Result<
+        fluent_syntax::ast::Resource<'ast>,
+        (
+            fluent_syntax::ast::Resource<'ast>,
+            Vec<fluent_syntax::parser::ParserError>,
+        ),
+    >,
+) -> fluent_syntax::ast::Resource<'ast> {
+    match r {
+        Ok(r) => r,
+        Err((r, errs)) => {
+            for err in errs {
+                log::warn!("parse error in {}: {:?}", path.as_ref().display(), err);
+            }
+            r
+        }
+    }
+}
+
+pub fn find_message<'ast>(
+    resource: &'ast fluent_syntax::ast::Resource<'ast>,
+    id: &str,
+) -> Option<&'ast fluent_syntax::ast::Message<'ast>> {
+    for entry in resource.body.iter() {
+        if let fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Message(
+            message,
+        )) = entry
+        {
+            if message.id.name == id {
+                return Some(message);
+            }
+        }
+    }
+    None
+}
+
+pub fn find_term<'ast>(
+    resource: &'ast fluent_syntax::ast::Resource<'ast>,
+    id: &str,
+) -> Option<&'ast fluent_syntax::ast::Term<'ast>> {
+    resource.body.iter().find_map(|entry| match entry {
+        fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Term(term))
+            if term.id.name == id =>
+        {
+            Some(term)
+        }
+        _ => None,
+    })
+}
+
+/// The ids of the terms in `resource` to translate like messages, such as
+/// `-tagline`, leaving out those marked `no_translate`
+pub fn translated_term_ids(
+    resource: &fluent_syntax::ast::Resource,
+    no_translate: &str,
+) -> Vec<String> {
+    resource
+        .body
+        .iter()
+        .filter_map(|entry| match entry {
+            fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Term(term))
+                if !has_marker(&term.comment, no_translate) =>
+            {
+                Some(format!("-{}", term.id.name))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Turn the terms of `resource` that are in `ids` into messages with those
+/// ids, so that they're translated and written like any other message while
+/// keeping the `-` that makes them terms
+pub fn terms_as_messages<'ast>(
+    resource: fluent_syntax::ast::Resource<'ast>,
+    ids: &'ast [String],
+) -> fluent_syntax::ast::Resource<'ast> {
+    let body = resource
+        .body
+        .into_iter()
+        .map(|entry| match entry {
+            fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Term(term)) => {
+                match ids.iter().find(|id| id[1..] == *term.id.name) {
+                    Some(id) => fluent_syntax::ast::ResourceEntry::Entry(
+                        fluent_syntax::ast::Entry::Message(fluent_syntax::ast::Message {
+                            id: fluent_syntax::ast::Identifier { name: id },
+                            value: Some(term.value),
+                            attributes: term.attributes,
+                            comment: term.comment,
+                        }),
+                    ),
+                    None => fluent_syntax::ast::ResourceEntry::Entry(
+                        fluent_syntax::ast::Entry::Term(term),
+                    ),
+                }
+            }
+            entry => entry,
+        })
+        .collect();
+    fluent_syntax::ast::Resource { body }
+}
+
+/// Check whether a message's comment contains `marker`, such as `tt-lang-name`
+pub fn has_marker<'ast>(comment: &Option<fluent_syntax::ast::Comment<'ast>>, marker: &str) -> bool {
+    if let Some(fluent_syntax::ast::Comment::Comment { content }) = comment {
+        content.iter().any(|c| c.contains(marker))
+    } else {
+        false
+    }
+}
+
+/// What follows `marker` in a comment such as `# tt-context: shown on the
+/// checkout button`, if there's anything
+pub fn marker_value<'ast>(
+    comment: &Option<fluent_syntax::ast::Comment<'ast>>,
+    marker: &str,
+) -> Option<String> {
+    if let Some(fluent_syntax::ast::Comment::Comment { content }) = comment {
+        content.iter().find_map(|line| {
+            let i = line.find(marker)?;
+            let value = line[i + marker.len()..].trim_start_matches(':').trim();
+            Some(value.to_owned()).filter(|value| !value.is_empty())
+        })
+    } else {
+        None
+    }
+}
+
+/// Where the outdated version of the source file comes from
+#[derive(Debug)]
+pub enum DiffSource {
+    File(PathBuf),
+    /// the source file as it was at a git revision
+    Git(String),
+}
+
+impl DiffSource {
+    /// Read the outdated source, along with a name to report parse errors with
+    pub fn read(&self, source: &Path) -> Result<(String, PathBuf), Box<dyn Error>> {
+        match self {
+            DiffSource::File(path) => Ok((read_or_empty(path)?, path.clone())),
+            DiffSource::Git(rev) => Ok((
+                super::git::show_file(rev, source)?,
+                PathBuf::from(format!("{}:{}", rev, source.display())),
+            )),
+        }
+    }
+}
+
+/// Read a file to parse, treating a missing file as empty
+pub fn read_or_empty<P: AsRef<Path>>(path: P) -> std::io::Result<String> {
+    if path.as_ref().exists() {
+        std::fs::read_to_string(path)
+    } else {
+        Ok(String::new())
+    }
+}
+
+/// Prepare a pattern for translating by stripping placeables, which are
+/// replaced with markers numbered in order, from 1
+pub fn source_text<'ast>(pattern: &fluent_syntax::ast::Pattern<'ast>) -> String {
+    let mut placeables = 0;
+    pattern
+        .elements
+        .iter()
+        .map(|pe| match pe {
+            fluent_syntax::ast::PatternElement::TextElement(s) => (*s).to_owned(),
+            fluent_syntax::ast::PatternElement::Placeable(_) => {
+                placeables += 1;
+                super::placeables::marker(placeables)
+            }
+        })
+        .collect()
+}
+
+/// The patterns of the variants of the select expressions in `pattern`, each
+/// followed by those of the selects in it
+pub fn variant_patterns<'p, 'ast>(
+    pattern: &'p fluent_syntax::ast::Pattern<'ast>,
+) -> Vec<&'p fluent_syntax::ast::Pattern<'ast>> {
+    let mut patterns = Vec::new();
+    for element in pattern.elements.iter() {
+        if let fluent_syntax::ast::PatternElement::Placeable(
+            fluent_syntax::ast::Expression::SelectExpression { variants, .. },
+        ) = element
+        {
+            for variant in variants.iter() {
+                patterns.push(&variant.value);
+                patterns.extend(variant_patterns(&variant.value));
+            }
+        }
+    }
+    patterns
+}
+
+/// The text of a message's value and attributes, which are translated together
+#[derive(Debug, Clone)]
+pub struct Segments {
+    pub value: Option<String>,
+    /// in the same order as the message's attributes
+    pub attributes: Vec<String>,
+    /// written above the translation, such as a marker that it needs review,
+    /// a line at a time
+    pub comment: Option<String>,
+    /// the existing translations of the value (if there is one) and each
+    /// attribute that haven't changed since they were translated, which are
+    /// kept rather than sent again, or empty to send them all
+    pub kept: Vec<Option<String>>,
+    /// the texts of the variants of the select expressions in the value (if
+    /// there is one) and each attribute, as `variant_patterns` lists them
+    pub variants: Vec<Vec<String>>,
+    /// the plural categories of the locale, which the variants of plural
+    /// selects are fitted to when they're written
+    pub plurals: Option<&'static [&'static str]>,
+}
+
+impl Segments {
+    /// Keep only those of `parts`, the value (if there is one) and each
+    /// attribute, that are to be sent to be translated
+    pub fn sent<T, I: IntoIterator<Item = T>>(&self, parts: I) -> Vec<T> {
+        parts
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| !matches!(self.kept.get(*i), Some(Some(_))))
+            .map(|(_, part)| part)
+            .collect()
+    }
+
+    /// The value (if there is one) followed by each attribute and then the
+    /// variants of each of them, leaving out those that are kept
+    pub fn texts(&self) -> Vec<&str> {
+        let parts = self.sent(self.value.iter().chain(self.attributes.iter()));
+        let variants = self.sent(self.variants.iter()).into_iter().flatten();
+        parts
+            .into_iter()
+            .chain(variants)
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Rebuild segments of the same shape from translations of `texts()`
+    /// and the kept translations
+    pub fn with_texts(&self, texts: Vec<String>) -> Segments {
+        let mut texts = texts.into_iter();
+        let kept = |i: usize| match self.kept.get(i) {
+            Some(Some(kept)) => Some(kept.clone()),
+            _ => None,
+        };
+        let parts = self.value.iter().count() + self.attributes.len();
+        let all: Vec<String> = (0..parts)
+            .filter_map(|i| kept(i).or_else(|| texts.next()))
+            .collect();
+        // kept translations already have their own variants
+        let variants = self
+            .variants
+            .iter()
+            .enumerate()
+            .map(|(i, variants)| match kept(i) {
+                Some(_) => Vec::new(),
+                None => variants.iter().filter_map(|_| texts.next()).collect(),
+            })
+            .collect();
+        let mut all = all.into_iter();
+        Segments {
+            value: self.value.as_ref().and_then(|_| all.next()),
+            attributes: all.collect(),
+            comment: self.comment.clone(),
+            kept: Vec::new(),
+            variants,
+            plurals: self.plurals,
+        }
+    }
+}
+
+/// Prepare a translation of `source` the way that `source_text` prepares
+/// `source`, with the placeables that are the same as one of the source's as
+/// its marker, so that it can be kept as it is
+fn translated_text<'ast>(
+    source: &fluent_syntax::ast::Pattern<'ast>,
+    translated: &fluent_syntax::ast::Pattern<'ast>,
+) -> String {
+    let write = |expression| {
+        let mut text: Vec<u8> = Vec::new();
+        super::writer::write_expression(&mut text, expression).expect("can write to a Vec");
+        String::from_utf8(text).expect("valid utf-8")
+    };
+    let mut placeables: Vec<Option<String>> = source
+        .elements
+        .iter()
+        .filter_map(|pe| match pe {
+            fluent_syntax::ast::PatternElement::Placeable(expression) => {
+                Some(Some(write(expression)))
+            }
+            _ => None,
+        })
+        .collect();
+    translated
+        .elements
+        .iter()
+        .map(|pe| match pe {
+            fluent_syntax::ast::PatternElement::TextElement(s) => (*s).to_owned(),
+            fluent_syntax::ast::PatternElement::Placeable(expression) => {
+                let text = write(expression);
+                match placeables.iter().position(|p| p.as_ref() == Some(&text)) {
+                    Some(i) => {
+                        placeables[i] = None;
+                        super::placeables::marker(i + 1)
+                    }
+                    None => text,
+                }
+            }
+        })
+        .collect()
+}
+
+/// The existing translations of the parts of `message` that `unchanged` says
+/// haven't changed, or nothing if there's nothing to keep or nothing left to
+/// translate
+fn kept_translations(
+    message: &fluent_syntax::ast::Message,
+    existing: &fluent_syntax::ast::Message,
+    unchanged: &[bool],
+) -> Vec<Option<String>> {
+    let translations = message
+        .value
+        .iter()
+        .map(|value| (value, existing.value.as_ref()))
+        .chain(message.attributes.iter().map(|attribute| {
+            let translation = existing
+                .attributes
+                .iter()
+                .find(|a| a.id.name == attribute.id.name)
+                .map(|a| &a.value);
+            (&attribute.value, translation)
+        }));
+    let kept: Vec<Option<String>> = translations
+        .zip(unchanged.iter())
+        .map(|((source, translation), unchanged)| {
+            translation
+                .filter(|_| *unchanged)
+                .map(|translation| translated_text(source, translation))
+        })
+        .collect();
+    if kept.iter().all(Option::is_some) || kept.iter().all(Option::is_none) {
+        Vec::new()
+    } else {
+        kept
+    }
+}
+
+/// What needs to be done for a message that needs translating
+#[derive(Debug)]
+pub enum Pending {
+    /// translate the value and attributes, as prepared by `source_text`
+    Text(Segments),
+    /// the message is marked `tt-lang-name`, so fill in the language's name
+    LanguageName,
+    /// the message has no value or attributes
+    Empty,
+}
+
+/// Which of the value (if there is one) and attributes of `message` are the
+/// same as they were in `source_outdated`, if it has attributes
+pub fn unchanged_parts(
+    source_outdated: &fluent_syntax::ast::Resource,
+    message: &fluent_syntax::ast::Message,
+) -> Option<Vec<bool>> {
+    let outdated = find_message(source_outdated, message.id.name)?;
+    if message.attributes.is_empty() {
+        return None;
+    }
+    let attributes = message.attributes.iter().map(|attribute| {
+        outdated
+            .attributes
+            .iter()
+            .any(|a| a.id.name == attribute.id.name && a.value == attribute.value)
+    });
+    Some(
+        message
+            .value
+            .iter()
+            .map(|value| outdated.value.as_ref() == Some(value))
+            .chain(attributes)
+            .collect(),
+    )
+}
+
+/// Check whether `message` is the same as it was in `source_outdated`
+pub fn unchanged_since(
+    source_outdated: &fluent_syntax::ast::Resource,
+    message: &fluent_syntax::ast::Message,
+) -> bool {
+    if let Some(outdated) = find_message(source_outdated, message.id.name) {
+        log::debug!("found existing term `{}` in diff", message.id.name);
+        log::debug!("message.value = {:?}", message.value);
+        log::debug!("outdated.value = {:?}", outdated.value);
+        log::debug!(
+            "message.value != outdated.value => {}",
+            message.value != outdated.value
+        );
+        message.value == outdated.value && message.attributes == outdated.attributes
+    } else {
+        false
+    }
+}
+
+/// How up to date a locale's translation of a message is
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum State {
+    /// the locale doesn't have the message at all
+    Missing,
+    /// the source message changed since the locale was last translated
+    Stale,
+    /// the existing translation is marked `tt-hand-translated`
+    HandTranslated,
+    /// the existing translation is marked `tt-hand-translated`, but the
+    /// source changed since it was translated
+    StaleHandTranslated,
+    /// the machine translation is up to date
+    Translated,
+}
+
+impl State {
+    pub fn name(self) -> &'static str {
+        match self {
+            State::Missing => "missing",
+            State::Stale => "stale",
+            State::HandTranslated => "hand-translated",
+            State::StaleHandTranslated => "stale-hand-translated",
+            State::Translated => "translated",
+        }
+    }
+
+    pub fn needs_translation(self) -> bool {
+        self == State::Missing || self == State::Stale
+    }
+}
+
+/// Work out how up to date `target_existing` is with a source message, where
+/// `unchanged` says whether the message is the same as when it was last
+/// translated
+pub fn classify<F: Fn(&fluent_syntax::ast::Message) -> bool>(
+    message: &fluent_syntax::ast::Message,
+    unchanged: &F,
+    target_existing: &fluent_syntax::ast::Resource,
+    markers: &super::config::Markers,
+) -> State {
+    if has_marker(&message.comment, &markers.no_translate) {
+        // it's copied from the source whenever the locale is written
+        return State::Translated;
+    }
+    match find_message(target_existing, message.id.name) {
+        None => State::Missing,
+        Some(existing) if has_marker(&existing.comment, &markers.hand_translated) => {
+            if unchanged(message) {
+                State::HandTranslated
+            } else {
+                State::StaleHandTranslated
+            }
+        }
+        Some(_) if unchanged(message) => State::Translated,
+        Some(_) => State::Stale,
+    }
+}
+
+/// Find the messages in `source` that need translating, in the order they
+/// appear. Messages are skipped if `unchanged` says they haven't changed since
+/// they were last translated, are marked as hand-translated in
+/// `target_existing`, or are marked to not be translated at all. Messages that
+/// `forced` says to translate again are only skipped for the last reason. The
+/// translations of the value and attributes of a changed message that
+/// `unchanged_parts` says are the same are kept.
+pub fn collect_pending<
+    'ast,
+    F: Fn(&fluent_syntax::ast::Message) -> bool,
+    G: Fn(&str) -> bool,
+    H: Fn(&fluent_syntax::ast::Message) -> Option<Vec<bool>>,
+>(
+    source: &'ast fluent_syntax::ast::Resource<'ast>,
+    unchanged: F,
+    unchanged_parts: H,
+    forced: G,
+    target_existing: &fluent_syntax::ast::Resource,
+    markers: &super::config::Markers,
+) -> Vec<(&'ast str, Pending)> {
+    let mut pending_translations = Vec::new();
+
+    for entry in source.body.iter() {
+        if let fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Message(
+            message,
+        )) = entry
+        {
+            let state = classify(message, &unchanged, target_existing, markers);
+            log::debug!("term `{}` is {:?}", message.id.name, state);
+
+            let forced =
+                forced(message.id.name) && !has_marker(&message.comment, &markers.no_translate);
+            if state.needs_translation() || forced {
+                // deal with language names
+                if has_marker(&message.comment, &markers.lang_name) {
+                    pending_translations.push((message.id.name, Pending::LanguageName));
+                } else if message.value.is_some() || !message.attributes.is_empty() {
+                    let segments = Segments {
+                        value: message.value.as_ref().map(source_text),
+                        attributes: message
+                            .attributes
+                            .iter()
+                            .map(|attribute| source_text(&attribute.value))
+                            .collect(),
+                        comment: None,
+                        variants: message
+                            .value
+                            .iter()
+                            .chain(message.attributes.iter().map(|attribute| &attribute.value))
+                            .map(|pattern| {
+                                variant_patterns(pattern)
+                                    .into_iter()
+                                    .map(source_text)
+                                    .collect()
+                            })
+                            .collect(),
+                        plurals: None,
+                        kept: match find_message(target_existing, message.id.name) {
+                            // the attributes of a term translated with
+                            // `terms_as_messages` are for selectors to use
+                            // rather than for reading, so they stay as they are
+                            _ if message.id.name.starts_with('-')
+                                && !message.attributes.is_empty() =>
+                            {
+                                std::iter::once(None)
+                                    .chain(
+                                        message
+                                            .attributes
+                                            .iter()
+                                            .map(|attribute| Some(source_text(&attribute.value))),
+                                    )
+                                    .collect()
+                            }
+                            Some(existing) if state == State::Stale && !forced => {
+                                unchanged_parts(message)
+                                    .map(|parts| kept_translations(message, existing, &parts))
+                                    .unwrap_or_default()
+                            }
+                            _ => Vec::new(),
+                        },
+                    };
+                    pending_translations.push((message.id.name, Pending::Text(segments)));
+                } else {
+                    pending_translations.push((message.id.name, Pending::Empty));
+                }
+            }
+        }
+    }
+
+    pending_translations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_terms_as_messages() {
+        let source = fluent_syntax::parser::parse(
+            "# tt-no-translate\n-brand = Firefox\n-tagline = Fast browser\n    .gender = masculine\nabout = { -brand }: { -tagline }\n",
+        )
+        .unwrap();
+        let ids = translated_term_ids(&source, "tt-no-translate");
+        assert_eq!(ids, vec!["-tagline".to_owned()]);
+        let source = terms_as_messages(source, &ids);
+        assert!(find_term(&source, "brand").is_some());
+        assert!(find_term(&source, "tagline").is_none());
+        let tagline = find_message(&source, "-tagline").unwrap();
+        assert_eq!(source_text(tagline.value.as_ref().unwrap()), "Fast browser");
+        assert_eq!(tagline.attributes.len(), 1);
+        let target = fluent_syntax::parser::parse("").unwrap();
+        let markers = super::super::config::Markers::default();
+        let pending = collect_pending(&source, |_| false, |_| None, |_| false, &target, &markers);
+        let segments = match &pending[0] {
+            ("-tagline", Pending::Text(segments)) => segments,
+            other => panic!("expected the tagline, got {:?}", other),
+        };
+        assert_eq!(segments.texts(), vec!["Fast browser".to_owned()]);
+
+        // it's written back as a term
+        let mut text: Vec<u8> = Vec::new();
+        super::super::writer::write_message(&mut text, tagline).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        let written = fluent_syntax::parser::parse(text.as_str()).unwrap();
+        assert!(find_term(&written, "tagline").is_some());
+    }
+
+    #[test]
+    fn translates_variants_and_changed_parts() {
+        let source = fluent_syntax::parser::parse(
+            "items = { $count ->\n        [one] One item\n       *[other] { $count } items\n    }\n    .title = Items\n    .label = Cart\n",
+        )
+        .unwrap();
+        let target =
+            fluent_syntax::parser::parse("items = Old\n    .title = Articles\n    .label = Old\n")
+                .unwrap();
+        let markers = super::super::config::Markers::default();
+        let pending = collect_pending(
+            &source,
+            |_| false,
+            |_| Some(vec![false, true, false]),
+            |_| false,
+            &target,
+            &markers,
+        );
+        let segments = match &pending[0].1 {
+            Pending::Text(segments) => segments,
+            other => panic!("expected text, got {:?}", other),
+        };
+        let marker = super::super::placeables::marker(1);
+        // the title is kept, and the variants are sent after the parts
+        assert_eq!(
+            segments.texts(),
+            vec![
+                marker.as_str(),
+                "Cart",
+                "One item",
+                &format!("{} items", marker)
+            ]
+        );
+        let translated = segments.with_texts(vec![
+            marker.clone(),
+            "Panier".to_owned(),
+            "Un article".to_owned(),
+            format!("{} articles", marker),
+        ]);
+        let mut written: Vec<u8> = Vec::new();
+        let message = find_message(&source, "items").unwrap();
+        super::super::writer::write_translated_message(&mut written, message, &translated).unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            "items = { $count ->\n        [one] Un article\n       *[other] { $count } articles\n    }\n    .title = Articles\n    .label = Panier"
+        );
+    }
+}
@@ -27,13 +27,74 @@ pub fn build_cli() -> App<'static, 'static> {
             .default_value("credentials.json")
             .help("the file containing the credentials for Google Cloud APIs. See https://developers.google.com/accounts/docs/application-default-credentials for more information.")
         )
+        .arg(Arg::with_name("api-endpoint")
+            .long("api-endpoint")
+            .value_name("URL")
+            .takes_value(true)
+            .validator(super::endpoint::validate)
+            .help("send Google translation requests here instead of https://translation.googleapis.com, such as a regional endpoint or a mock server")
+        )
+        .arg(Arg::with_name("token-endpoint")
+            .long("token-endpoint")
+            .value_name("URL")
+            .takes_value(true)
+            .validator(super::endpoint::validate)
+            .help("request Google OAuth tokens from here instead of https://www.googleapis.com/oauth2/v4/token")
+        )
+        .arg(Arg::with_name("location")
+            .long("location")
+            .value_name("LOCATION")
+            .takes_value(true)
+            .validator(super::endpoint::validate_location)
+            .help("the Google Cloud location to translate in and keep glossaries in, such as europe-west1 or global [default: us-central1]")
+        )
+        .arg(Arg::with_name("config")
+            .long("config")
+            .value_name("FILE")
+            .takes_value(true)
+            .help("the project configuration file to use [default: tt.toml, if it exists]")
+        )
+        .arg(Arg::with_name("workspace")
+            .long("workspace")
+            .takes_value(false)
+            .conflicts_with("config")
+            .help("run every project under the working directory that has a tt.toml, from its own directory, and summarise the results")
+        )
+        .arg(Arg::with_name("cache-dir")
+            .long("cache-dir")
+            .value_name("DIR")
+            .takes_value(true)
+            .help("the directory to keep cached files in [default: tt in the platform's cache directory]")
+        )
+        .arg(Arg::with_name("state-dir")
+            .long("state-dir")
+            .value_name("DIR")
+            .takes_value(true)
+            .help("the directory to keep state in between runs [default: tt in the platform's state directory]")
+        )
+        .arg(Arg::with_name("backend")
+            .short("b")
+            .long("backend")
+            .value_name("BACKEND")
+            .takes_value(true)
+            .use_delimiter(true)
+            .default_value("google")
+            .validator(super::backend::validate_name)
+            .help("the translation engines to use, separated by commas: google, deepl (using the DEEPL_AUTH_KEY environment variable), pseudo to pseudo-localize into en-XA, exec:<path> to use an external command, or offline when built with the offline feature. When a backend can't translate into the locale or is out of quota, the next one is tried")
+        )
         .arg(Arg::with_name("from")
             .short("f")
             .long("from")
             .value_name("FILE")
             .takes_value(true)
             .default_value("en.flt")
-            .help("the English translation file to take strings from")
+            .help("the source translation file to take strings from")
+        )
+        .arg(Arg::with_name("source-locale")
+            .long("source-locale")
+            .value_name("LOCALE")
+            .takes_value(true)
+            .help("the language that the source file is written in [default: en]")
         )
         .arg(Arg::with_name("diff")
             .short("d")
@@ -42,12 +103,26 @@ pub fn build_cli() -> App<'static, 'static> {
             .takes_value(true)
             .help("an optional English translation file to diff the terms from to mimimize re-translations")
         )
+        .arg(Arg::with_name("diff-git")
+            .long("diff-git")
+            .value_name("REV")
+            .takes_value(true)
+            .conflicts_with("diff")
+            .help("diff the terms against the English translation file as it was at a git revision (\"HEAD~1\", \"origin/main\", etc) instead of a separate file")
+        )
+        .arg(Arg::with_name("lock-file")
+            .long("lock-file")
+            .value_name("FILE")
+            .takes_value(true)
+            .help("the file recording which messages each locale is up to date with, so only changed messages are translated [default: tt.lock]")
+        )
         .arg(Arg::with_name("locale")
             .short("l")
             .long("locale")
             .value_name("LOCALE")
             .takes_value(true)
-            .help("the locale to translate into (\"fr\", \"it\", etc)")
+            .use_delimiter(true)
+            .help("the locales to translate into, separated by commas (\"fr\", \"it\", etc), or \"all\" for every language the backends support")
         )
         .arg(Arg::with_name("outpath")
             .short("o")
@@ -62,15 +137,561 @@ pub fn build_cli() -> App<'static, 'static> {
             .long("glossary")
             .value_name("GLOSSARY")
             .takes_value(true)
-            .help("The glossary name to use (stored in the us-central1 region)")
+            .help("The glossary name to use (stored in the --glossary-location region)")
+        )
+        .arg(Arg::with_name("glossary-location")
+            .long("glossary-location")
+            .value_name("LOCATION")
+            .takes_value(true)
+            .validator(super::endpoint::validate_location)
+            .help("the Google Cloud location that glossaries are kept in [default: the --location]")
+        )
+        .arg(Arg::with_name("model")
+            .long("model")
+            .value_name("MODEL")
+            .takes_value(true)
+            .help("the Google model to translate with, such as general/nmt, general/base or a custom model's id")
+        )
+        .arg(Arg::with_name("label")
+            .long("label")
+            .value_name("KEY=VALUE")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .validator(super::translate::validate_label)
+            .help("attach this label to each Google translation request, so that billing reports can be broken down by it")
         )
         .arg(Arg::with_name("ignore-case")
             .long("ignore-case")
             .takes_value(false)
             .help("Ignore case when using a glossary")
         )
+        .arg(Arg::with_name("mime-type")
+            .long("mime-type")
+            .value_name("TYPE")
+            .takes_value(true)
+            .possible_values(&["auto", "text", "html"])
+            .default_value("auto")
+            .help("whether messages are translated as plain text or HTML. auto uses HTML only for messages that contain markup")
+        )
+        .arg(Arg::with_name("deadline")
+            .long("deadline")
+            .value_name("SECONDS")
+            .takes_value(true)
+            .default_value("10")
+            .validator(|s| match s.parse::<f64>() {
+                Ok(seconds) if seconds >= 0.0 && seconds.is_finite() => Ok(()),
+                Ok(_) => Err("the deadline must be a positive number of seconds".to_owned()),
+                Err(e) => Err(format!("invalid deadline: {}", e)),
+            })
+            .help("log the size and retries of any message that takes longer than this to translate")
+        )
+        .arg(Arg::with_name("concurrency")
+            .long("concurrency")
+            .value_name("N")
+            .takes_value(true)
+            .default_value("1")
+            .validator(super::concurrency::validate)
+            .help("how many requests to send to the backends at once, or `auto` to send more at once until they push back")
+        )
+        .arg(Arg::with_name("connect-timeout")
+            .long("connect-timeout")
+            .value_name("SECONDS")
+            .takes_value(true)
+            .validator(validate_seconds)
+            .help("how long to wait for a backend to accept a connection")
+        )
+        .arg(Arg::with_name("timeout")
+            .long("timeout")
+            .value_name("SECONDS")
+            .takes_value(true)
+            .validator(validate_seconds)
+            .help("how long to wait for a backend's response before trying again")
+        )
+        .arg(Arg::with_name("gcs-bucket")
+            .long("gcs-bucket")
+            .value_name("BUCKET")
+            .takes_value(true)
+            .help("translate every locale at once with Google's batchTranslateText, sending the texts through this Cloud Storage bucket")
+        )
+        .arg(Arg::with_name("ca-cert")
+            .long("ca-cert")
+            .value_name("PEM")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("trust the root certificates in this PEM file too, such as a TLS-intercepting proxy's")
+        )
+        .arg(Arg::with_name("qps")
+            .long("qps")
+            .value_name("REQUESTS")
+            .takes_value(true)
+            .validator(|s| match s.parse::<f64>() {
+                Ok(qps) if qps > 0.0 && qps.is_finite() => Ok(()),
+                Ok(_) => Err("the requests per second must be a positive number".to_owned()),
+                Err(e) => Err(format!("invalid requests per second: {}", e)),
+            })
+            .help("send at most this many requests a second to the backends, over all the concurrent requests")
+        )
+        .arg(Arg::with_name("dump-http")
+            .long("dump-http")
+            .value_name("DIR")
+            .takes_value(true)
+            .global(true)
+            .help("write each request sent to the backends and each response to numbered files in this directory, without their credentials, for debugging")
+        )
+        .arg(Arg::with_name("max-chars")
+            .long("max-chars")
+            .value_name("CHARACTERS")
+            .takes_value(true)
+            .validator(|s| s.parse::<usize>().map(|_| ()).map_err(|e| format!("invalid character count: {}", e)))
+            .help("stop sending messages to the backends once the run would go over this many characters, writing what was translated so far")
+        )
+        .arg(Arg::with_name("max-cost")
+            .long("max-cost")
+            .value_name("DOLLARS")
+            .takes_value(true)
+            .validator(|s| match s.parse::<f64>() {
+                Ok(cost) if cost >= 0.0 && cost.is_finite() => Ok(()),
+                Ok(_) => Err("the cost must be a positive number".to_owned()),
+                Err(e) => Err(format!("invalid cost: {}", e)),
+            })
+            .help("stop sending messages to the backends once the run would cost more than this, writing what was translated so far")
+        )
+        .arg(Arg::with_name("report-junit")
+            .long("report-junit")
+            .value_name("FILE")
+            .takes_value(true)
+            .help("write the placeable mismatches, failed translations and stale messages of the run to a JUnit XML file, for CI systems to show as test failures")
+        )
+        .arg(Arg::with_name("report-expansion")
+            .long("report-expansion")
+            .value_name("FILE")
+            .takes_value(true)
+            .help("write how much longer each translation is than its source, by locale, to a JSON file")
+        )
+        .arg(Arg::with_name("sign")
+            .long("sign")
+            .value_name("KEY")
+            .takes_value(true)
+            .help("sign a manifest of the run and the hashes of the files it wrote with this ed25519 private key (PEM), for `tt verify-signature`")
+        )
+        .arg(Arg::with_name("dry-run")
+            .long("dry-run")
+            .takes_value(false)
+            .help("list the messages that would be translated and how many characters they are, without using any backends or writing anything")
+        )
+        .arg(Arg::with_name("interactive")
+            .long("interactive")
+            .takes_value(false)
+            .conflicts_with("dry-run")
+            .help("show each translation as it comes back and ask whether to accept it, edit it or skip it before it's written")
+        )
+        .arg(Arg::with_name("merge")
+            .long("merge")
+            .takes_value(false)
+            .help("update the existing translation file in place, only rewriting the messages that changed")
+        )
+        .arg(Arg::with_name("translate-terms")
+            .long("translate-terms")
+            .takes_value(false)
+            .help("translate terms like `-tagline` along with the messages, rather than copying them, except for those marked `tt-no-translate`")
+        )
+        .arg(Arg::with_name("expand-terms")
+            .long("expand-terms")
+            .takes_value(false)
+            .help("send references to terms whose value is plain text with the term's value, putting the reference back afterwards unless the term is marked `tt-expand: keep`")
+        )
+        .arg(Arg::with_name("force")
+            .long("force")
+            .takes_value(false)
+            .help("translate every message again, even unchanged and hand-translated ones, marking the hand-translated ones as needing review")
+        )
+        .arg(Arg::with_name("force-ids")
+            .long("force-ids")
+            .value_name("GLOB")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("translate the messages whose ids match this pattern again, where `*` matches any characters, like --force")
+        )
+        .arg(Arg::with_name("only")
+            .long("only")
+            .value_name("PATTERN")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .validator(super::filter::validate)
+            .help("only translate the messages whose ids match this pattern, where `*` matches any characters, or a regular expression after `re:`")
+        )
+        .arg(Arg::with_name("exclude")
+            .long("exclude")
+            .value_name("PATTERN")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .validator(super::filter::validate)
+            .help("don't translate the messages whose ids match this pattern, like --only")
+        )
+        .arg(Arg::with_name("section")
+            .long("section")
+            .value_name("PATTERN")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .validator(super::filter::validate)
+            .help("only translate the messages under a `##` group comment whose first line matches this pattern, like --only")
+        )
+        .arg(Arg::with_name("keep-removed")
+            .long("keep-removed")
+            .takes_value(false)
+            .requires("merge")
+            .help("when merging, keep messages that were removed from the source with a deprecation comment")
+        )
+        .arg(Arg::with_name("allow-source-target")
+            .long("allow-source-target")
+            .takes_value(false)
+            .help("allow translating into the source locale, which rewrites its file in tt's own formatting")
+        )
+        .arg(Arg::with_name("override-freeze")
+            .long("override-freeze")
+            .value_name("REASON")
+            .takes_value(true)
+            .help("modify the locale even though it is frozen, recording why in the audit trail")
+        )
+        .arg(Arg::with_name("progress-format")
+            .long("progress-format")
+            .value_name("FORMAT")
+            .takes_value(true)
+            .possible_values(&["bar", "json"])
+            .default_value("bar")
+            .help("how progress is shown. json writes newline-delimited events to stderr for other programs to read")
+        )
+        .arg(Arg::with_name("color")
+            .long("color")
+            .value_name("WHEN")
+            .takes_value(true)
+            .possible_values(&["auto", "always", "never"])
+            .default_value("auto")
+            .help("when to color output. auto colors it when writing to a terminal, unless NO_COLOR is set")
+        )
+        .subcommand(SubCommand::with_name("translate")
+            .about("translate the source into each locale, which is also what tt does without a subcommand")
+        )
         .subcommand(SubCommand::with_name("languages")
             .about("list all possible languages that the template can be translated into")
+            .arg(Arg::with_name("offline")
+                .long("offline")
+                .takes_value(false)
+                .help("list the languages from the last `tt languages` run, or the snapshot built into tt, without querying the backends")
+            )
+        )
+        .subcommand(SubCommand::with_name("status")
+            .about("report how much of the source each locale covers, and how many of its messages are missing or stale")
+            .arg(Arg::with_name("json")
+                .long("json")
+                .takes_value(false)
+                .help("print the report as JSON")
+            )
+        )
+        .subcommand(SubCommand::with_name("review")
+            .about("go through the queue of failed, low-confidence and flagged translations, accepting, editing or rejecting each one")
+            .arg(Arg::with_name("export")
+                .long("export")
+                .value_name("CSV")
+                .takes_value(true)
+                .conflicts_with("import")
+                .help("write the whole queue to a CSV file for reviewers, instead of reviewing it here")
+            )
+            .arg(Arg::with_name("import")
+                .long("import")
+                .value_name("CSV")
+                .takes_value(true)
+                .help("take the states and edits from a reviewed CSV file, updating the locales to match")
+            )
+            .subcommand(SubCommand::with_name("list")
+                .about("list the messages marked as needing review in each locale's file")
+            )
+            .subcommand(SubCommand::with_name("approve")
+                .about("mark a message that needs review as hand-translated, keeping its translation")
+                .arg(Arg::with_name("id")
+                    .value_name("ID")
+                    .takes_value(true)
+                    .required(true)
+                    .help("the id of the message to approve")
+                )
+            )
+        )
+        .subcommand(SubCommand::with_name("serve")
+            .about("serve a web page listing the translations that need review in each locale, where each can be edited and saved")
+            .arg(Arg::with_name("port")
+                .long("port")
+                .value_name("PORT")
+                .takes_value(true)
+                .default_value("8080")
+                .validator(|s| match s.parse::<u16>() {
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(format!("invalid port: {}", e)),
+                })
+                .help("the port to listen on")
+            )
+            .arg(Arg::with_name("bind")
+                .long("bind")
+                .value_name("ADDRESS")
+                .takes_value(true)
+                .default_value("127.0.0.1")
+                .help("the address to listen on, such as 0.0.0.0 to let anyone on the network review")
+            )
+        )
+        .subcommand(SubCommand::with_name("verify-signature")
+            .about("check that the provenance manifest was signed with the team's key and that the files it lists haven't changed since")
+            .arg(Arg::with_name("key")
+                .long("key")
+                .value_name("PUBLIC_KEY")
+                .takes_value(true)
+                .required(true)
+                .help("the ed25519 public key (PEM) that runs are signed with")
+            )
+            .arg(Arg::with_name("manifest")
+                .long("manifest")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("the manifest to check, instead of the one in the config")
+            )
+        )
+        .subcommand(SubCommand::with_name("lint")
+            .about("check Fluent files for duplicate ids, junk, references to messages and terms that don't exist, unused terms and ids that don't follow the naming convention")
+            .arg(Arg::with_name("files")
+                .value_name("FILE")
+                .multiple(true)
+                .help("the files to check, instead of the source and the translation of each locale")
+            )
+            .arg(Arg::with_name("enable")
+                .long("enable")
+                .value_name("RULE")
+                .takes_value(true)
+                .multiple(true)
+                .use_delimiter(true)
+                .possible_values(super::lint::RULES)
+                .help("run these rules even if the config turns them off")
+            )
+            .arg(Arg::with_name("disable")
+                .long("disable")
+                .value_name("RULE")
+                .takes_value(true)
+                .multiple(true)
+                .use_delimiter(true)
+                .possible_values(super::lint::RULES)
+                .help("don't run these rules")
+            )
+        )
+        .subcommand(SubCommand::with_name("graph")
+            .about("print which messages and terms refer to which, to see which terms are used the most and what a change affects")
+            .arg(Arg::with_name("file")
+                .value_name("FILE")
+                .help("the file to graph, instead of the source")
+            )
+            .arg(Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .takes_value(true)
+                .possible_values(&["dot", "json"])
+                .default_value("dot")
+                .help("Graphviz's DOT language or JSON")
+            )
+        )
+        .subcommand(SubCommand::with_name("add-locale")
+            .about("start translating into a new locale, checking that the backends can translate into it, creating its file and adding it to the config's locales")
+            .arg(Arg::with_name("new-locale")
+                .value_name("LOCALE")
+                .required(true)
+                .help("the locale to add, such as sv")
+            )
+            .arg(Arg::with_name("from-locale")
+                .long("from-locale")
+                .value_name("LOCALE")
+                .takes_value(true)
+                .help("start from a copy of a similar locale's translations, such as pt-BR for pt-PT")
+            )
+            .arg(Arg::with_name("translate")
+                .long("translate")
+                .help("translate the source into the new locale straight away")
+            )
+        )
+        .subcommand(SubCommand::with_name("migrate")
+            .about("upgrade tt.toml, the lock file and the review queue from older versions of tt")
+        )
+        .subcommand(SubCommand::with_name("check")
+            .about("check that every locale is up to date with the source, exiting with an error listing the messages that aren't")
+        )
+        .subcommand(SubCommand::with_name("compare")
+            .about("translate the pending messages with several backends and write a side-by-side report, without touching the translation file")
+            .arg(Arg::with_name("backends")
+                .long("backends")
+                .value_name("BACKENDS")
+                .takes_value(true)
+                .required(true)
+                .use_delimiter(true)
+                .validator(super::backend::validate_name)
+                .help("the translation engines to compare, separated by commas")
+            )
+            .arg(Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .takes_value(true)
+                .possible_values(&["csv", "html"])
+                .default_value("csv")
+                .help("the format to write the report in")
+            )
+            .arg(Arg::with_name("output")
+                .long("output")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("the file to write the report to [default: stdout]")
+            )
+        )
+        .subcommand(SubCommand::with_name("selftest")
+            .about("translate a small built-in sample into a locale with the configured backends and credentials, reporting whether each stage of the run works")
+            .arg(Arg::with_name("test-locale")
+                .value_name("LOCALE")
+                .default_value("fr")
+                .help("the locale to translate the sample into")
+            )
+        )
+        .subcommand(SubCommand::with_name("auth")
+            .about("check the Google credentials")
+            .subcommand(SubCommand::with_name("status")
+                .about("show which key each credentials file has, how old it is, when it expires and whether it can still sign in")
+            )
+        )
+        .subcommand(SubCommand::with_name("glossary")
+            .about("manage glossary entries")
+            .subcommand(SubCommand::with_name("suggest")
+                .about("find texts that are translated inconsistently and propose glossary entries for them")
+                .arg(Arg::with_name("file")
+                    .long("file")
+                    .value_name("FILE")
+                    .takes_value(true)
+                    .default_value("glossary-{locale}.csv")
+                    .help("the glossary CSV to append accepted entries to, where {locale} is replaced with the locale")
+                )
+                .arg(Arg::with_name("yes")
+                    .short("y")
+                    .long("yes")
+                    .takes_value(false)
+                    .help("accept the most common translation of every text without asking")
+                )
+            )
+        )
+        .subcommand(SubCommand::with_name("import")
+            .about("convert a JSON, gettext, Android, Apple, Flutter ARB or Java .properties catalog into a Fluent file, generating valid message ids for its keys, or merge the translations of a filled-in CSV spreadsheet or XLIFF file into the locales as hand-translated")
+            .arg(Arg::with_name("input")
+                .value_name("FILE")
+                .required(true)
+                .help("the catalog to import")
+            )
+            .arg(Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .takes_value(true)
+                .possible_values(super::formats::NAMES)
+                .help("the catalog's format [default: guessed from the file extension]")
+            )
+            .arg(Arg::with_name("output")
+                .long("output")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("the Fluent file to write [default: the input with a .flt extension]")
+            )
+            .arg(Arg::with_name("ids")
+                .long("ids")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("the file recording the original key of each generated id [default: the output with a .ids.json extension]")
+            )
+        )
+        .subcommand(SubCommand::with_name("export")
+            .about("convert a Fluent file back into a JSON, gettext, Android, Apple, Flutter ARB or Java .properties catalog, restoring the keys it was imported with, or write a CSV spreadsheet of every locale's translations with --format csv, or the messages each locale needs translated as XLIFF with --format xliff or xliff2")
+            .arg(Arg::with_name("input")
+                .value_name("FILE")
+                .help("the Fluent file to export, which csv and XLIFF don't take since they export from the source and every --locale")
+            )
+            .arg(Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .takes_value(true)
+                .possible_values(super::formats::NAMES)
+                .help("the format to write [default: guessed from the output's file extension]")
+            )
+            .arg(Arg::with_name("output")
+                .long("output")
+                .value_name("FILE")
+                .takes_value(true)
+                .required_unless("format")
+                .help("the catalog to write [default: the input with the format's extension]")
+            )
+            .arg(Arg::with_name("ids")
+                .long("ids")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("the id mapping written when the source was imported [default: the input with a .ids.json extension]")
+            )
+        )
+        .subcommand(SubCommand::with_name("convert")
+            .about("convert a catalog between any two of Fluent, JSON, gettext, Android, Apple .strings and .stringsdict, Flutter ARB and Java .properties, keeping comments and plurals where the formats allow")
+            .arg(Arg::with_name("in")
+                .long("in")
+                .value_name("FILE")
+                .takes_value(true)
+                .required(true)
+                .help("the catalog to convert, whose format is guessed from its file extension")
+            )
+            .arg(Arg::with_name("out")
+                .long("out")
+                .value_name("FILE")
+                .takes_value(true)
+                .required(true)
+                .help("the catalog to write, whose format is guessed from its file extension")
+            )
+            .arg(Arg::with_name("ids")
+                .long("ids")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("the file recording the original key of each generated id [default: the Fluent file with a .ids.json extension]")
+            )
+        )
+        .subcommand(SubCommand::with_name("init")
+            .about("set up a new project by generating a tt.toml")
+            .arg(Arg::with_name("yes")
+                .short("y")
+                .long("yes")
+                .takes_value(false)
+                .help("use the defaults instead of asking")
+            )
+            .arg(Arg::with_name("force")
+                .long("force")
+                .takes_value(false)
+                .help("overwrite an existing config file")
+            )
+        )
+        .subcommand(SubCommand::with_name("dirs")
+            .about("show where tt keeps its cache and state")
+            .arg(Arg::with_name("clear-cache")
+                .long("clear-cache")
+                .takes_value(false)
+                .help("delete everything in the cache directory")
+            )
+        )
+        .subcommand(SubCommand::with_name("watch")
+            .about("translate again whenever the source file or config changes, only translating the messages that changed")
+            .arg(Arg::with_name("debounce")
+                .long("debounce")
+                .value_name("MS")
+                .takes_value(true)
+                .default_value("500")
+                .validator(|ms| ms.parse::<u64>().map(|_| ()).map_err(|e| format!("invalid debounce: {}", e)))
+                .help("how long to wait for changes to settle before translating")
+            )
         )
         .subcommand(SubCommand::with_name("gen-completions")
             .about("generate shell completions")
@@ -81,3 +702,11 @@ pub fn build_cli() -> App<'static, 'static> {
             )
         )
 }
+
+fn validate_seconds(s: String) -> Result<(), String> {
+    match s.parse::<f64>() {
+        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Ok(()),
+        Ok(_) => Err("the number of seconds must be positive".to_owned()),
+        Err(e) => Err(format!("invalid number of seconds: {}", e)),
+    }
+}
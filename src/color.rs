@@ -0,0 +1,102 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// When output is colored
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum When {
+    /// when writing to a terminal, unless `NO_COLOR` is set
+    Auto,
+    Always,
+    Never,
+}
+
+impl When {
+    pub fn from_arg(arg: &str) -> When {
+        match arg {
+            "always" => When::Always,
+            "never" => When::Never,
+            _ => When::Auto,
+        }
+    }
+
+    /// Whether to color, given the value of `NO_COLOR` and whether stdout is
+    /// a terminal
+    fn enabled(self, no_color: Option<std::ffi::OsString>, is_terminal: bool) -> bool {
+        match self {
+            When::Always => true,
+            When::Never => false,
+            When::Auto => no_color.map(|v| v.is_empty()).unwrap_or(true) && is_terminal,
+        }
+    }
+}
+
+/// Colors output by what it means, so that the same thing looks the same in
+/// every command
+#[derive(Clone, Copy, Debug)]
+pub struct Palette {
+    enabled: bool,
+}
+
+impl Palette {
+    pub fn new(when: When) -> Palette {
+        Palette {
+            enabled: when.enabled(std::env::var_os("NO_COLOR"), atty::is(atty::Stream::Stdout)),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.enabled {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_owned()
+        }
+    }
+
+    /// Something translated, or about to be
+    pub fn added(&self, text: &str) -> String {
+        self.paint("32", text)
+    }
+
+    /// Something out of date with the source
+    pub fn stale(&self, text: &str) -> String {
+        self.paint("33", text)
+    }
+
+    /// Something missing or broken
+    pub fn failed(&self, text: &str) -> String {
+        self.paint("31", text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decides_when_to_color() {
+        assert!(When::Auto.enabled(None, true));
+        assert!(!When::Auto.enabled(None, false));
+        assert!(!When::Auto.enabled(Some("1".into()), true));
+        assert!(When::Auto.enabled(Some("".into()), true));
+        assert!(When::Always.enabled(Some("1".into()), false));
+        assert!(!When::Never.enabled(None, true));
+
+        assert_eq!(Palette { enabled: true }.failed("x"), "\x1b[31mx\x1b[0m");
+        assert_eq!(Palette { enabled: false }.added("x"), "x");
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::prelude::*;
+
+use super::backend::Backend;
+use super::protect::Protector;
+use super::translate::{GlossaryConfig, MimeType};
+
+/// One message, translated by each of the backends being compared
+pub struct Row {
+    pub id: String,
+    pub source: String,
+    /// one translation per backend, in the same order as the backends
+    pub translations: Vec<String>,
+}
+
+/// Translate `text` the same way a normal run would, in its message's
+/// `context` if it has one, putting any error in the report instead of the
+/// translation
+pub fn translate_cell(
+    backend: &dyn Backend,
+    protector: &Protector,
+    mime_type: Option<MimeType>,
+    glossary: &Option<GlossaryConfig>,
+    context: Option<&str>,
+    text: &str,
+) -> String {
+    let (protected, spans) = protector.protect(text);
+    let mime_type = mime_type.unwrap_or_else(|| MimeType::detect(&protected));
+    let translated = match context {
+        Some(context) => backend
+            .translate_with_context(&[&protected], mime_type, glossary, context)
+            .and_then(|mut t| {
+                t.pop()
+                    .ok_or_else(|| Box::from(super::errors::Errors::NoTranslations))
+            }),
+        None => backend.translate(&protected, mime_type, glossary),
+    };
+    match translated {
+        Ok(t) => protector.restore(&t, &spans),
+        Err(e) => format!("<error: {}>", e),
+    }
+}
+
+pub fn csv_field(field: &str) -> String {
+    if field.contains(&['"', ',', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace("\"", "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+pub fn write_csv<W: Write>(out: &mut W, backends: &[&str], rows: &[Row]) -> std::io::Result<()> {
+    let header: Vec<String> = ["id", "source"]
+        .iter()
+        .chain(backends.iter())
+        .map(|h| csv_field(h))
+        .collect();
+    writeln!(out, "{}", header.join(","))?;
+    for row in rows.iter() {
+        let fields: Vec<String> = [&row.id, &row.source]
+            .iter()
+            .copied()
+            .chain(row.translations.iter())
+            .map(|f| csv_field(f))
+            .collect();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+pub fn html_escape(text: &str) -> String {
+    text.replace("&", "&amp;")
+        .replace("<", "&lt;")
+        .replace(">", "&gt;")
+        .replace("\"", "&quot;")
+}
+
+pub fn write_html<W: Write>(
+    out: &mut W,
+    locale: &str,
+    backends: &[&str],
+    rows: &[Row],
+) -> std::io::Result<()> {
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>")?;
+    writeln!(out, "<head>")?;
+    writeln!(out, "<meta charset=\"utf-8\">")?;
+    writeln!(
+        out,
+        "<title>Translations into {}</title>",
+        html_escape(locale)
+    )?;
+    writeln!(
+        out,
+        "<style>table {{ border-collapse: collapse; }} th, td {{ border: 1px solid #ccc; padding: 4px; vertical-align: top; white-space: pre-wrap; }}</style>"
+    )?;
+    writeln!(out, "</head>")?;
+    writeln!(out, "<body>")?;
+    writeln!(out, "<table>")?;
+    write!(out, "<tr><th>id</th><th>source</th>")?;
+    for backend in backends.iter() {
+        write!(out, "<th>{}</th>", html_escape(backend))?;
+    }
+    writeln!(out, "</tr>")?;
+    for row in rows.iter() {
+        write!(
+            out,
+            "<tr><td>{}</td><td>{}</td>",
+            html_escape(&row.id),
+            html_escape(&row.source)
+        )?;
+        for translation in row.translations.iter() {
+            write!(out, "<td>{}</td>", html_escape(translation))?;
+        }
+        writeln!(out, "</tr>")?;
+    }
+    writeln!(out, "</table>")?;
+    writeln!(out, "</body>")?;
+    writeln!(out, "</html>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_write_csv() {
+        let rows = vec![Row {
+            id: "hello".to_owned(),
+            source: "Hello, \"world\"".to_owned(),
+            translations: vec!["Bonjour".to_owned(), "Salut\nmonde".to_owned()],
+        }];
+        let mut out = Vec::new();
+        write_csv(&mut out, &["google", "deepl"], &rows).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "id,source,google,deepl\nhello,\"Hello, \"\"world\"\"\",Bonjour,\"Salut\nmonde\"\n"
+        );
+    }
+}
@@ -0,0 +1,272 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/// The most requests `--concurrency auto` sends at once
+const MOST_AUTO: usize = 16;
+/// How many requests are timed at each level before deciding whether to send
+/// more at once, at least
+const WINDOW: usize = 4;
+/// How much slower requests can get than they were with fewer at once before
+/// it counts as the backend falling behind
+const LATENCY_RISE: f64 = 1.5;
+
+/// How many requests are sent to the backends at once
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Concurrency {
+    Fixed(usize),
+    /// start with one and send more at once until the backend pushes back
+    Auto,
+}
+
+impl Concurrency {
+    pub fn from_arg(arg: &str) -> Concurrency {
+        match arg {
+            "auto" => Concurrency::Auto,
+            n => Concurrency::Fixed(n.parse().unwrap_or(1)),
+        }
+    }
+
+    /// The most requests that can be sent at once
+    pub fn most(&self) -> usize {
+        match self {
+            Concurrency::Fixed(n) => *n,
+            Concurrency::Auto => MOST_AUTO,
+        }
+    }
+}
+
+/// Check that `arg` is a number of requests or `auto`
+pub fn validate(arg: String) -> Result<(), String> {
+    match arg.parse::<usize>() {
+        _ if arg == "auto" => Ok(()),
+        Ok(n) if n > 0 => Ok(()),
+        Ok(_) => Err("the concurrency must be at least 1".to_owned()),
+        Err(e) => Err(format!(
+            "invalid concurrency `{}`: {}, expected a number or auto",
+            arg, e
+        )),
+    }
+}
+
+/// Decides how many requests can be sent at once: doubling it each time the
+/// requests at the last level came back without being rate limited or slowing
+/// down, and settling at half of it once they are
+#[derive(Debug)]
+struct Control {
+    limit: usize,
+    most: usize,
+    adaptive: bool,
+    settled: bool,
+    /// changes with the limit, so that requests sent before the change aren't
+    /// counted against it
+    generation: usize,
+    /// how long each request at this level took
+    samples: Vec<Duration>,
+    /// how long requests took on average when fewer were sent at once
+    baseline: Option<Duration>,
+    /// how many rate limited responses there had been at the start of this
+    /// level
+    throttled: usize,
+}
+
+impl Control {
+    fn new(concurrency: Concurrency, throttled: usize) -> Control {
+        let adaptive = concurrency == Concurrency::Auto;
+        Control {
+            limit: if adaptive { 1 } else { concurrency.most() },
+            most: concurrency.most(),
+            adaptive,
+            settled: !adaptive,
+            generation: 0,
+            samples: Vec::new(),
+            baseline: None,
+            throttled,
+        }
+    }
+
+    /// Take into account a request sent at `generation` that took `latency`,
+    /// with `throttled` rate limited responses so far
+    fn record(&mut self, generation: usize, latency: Duration, throttled: usize) {
+        if !self.adaptive || generation != self.generation {
+            return;
+        }
+        if throttled > self.throttled {
+            self.settle((self.limit / 2).max(1), throttled, "being rate limited");
+            return;
+        }
+        self.samples.push(latency);
+        if self.samples.len() < self.limit.max(WINDOW) {
+            return;
+        }
+        let mean = self.samples.iter().sum::<Duration>() / self.samples.len() as u32;
+        match self.baseline {
+            Some(baseline) if !self.settled && mean > baseline.mul_f64(LATENCY_RISE) => {
+                self.settle((self.limit / 2).max(1), throttled, "requests slowing down");
+            }
+            baseline => {
+                self.baseline = Some(baseline.map_or(mean, |baseline| baseline.min(mean)));
+                let limit = if self.settled {
+                    self.limit
+                } else {
+                    (self.limit * 2).min(self.most)
+                };
+                if limit != self.limit {
+                    log::debug!("sending up to {} requests at once", limit);
+                }
+                self.change(limit, throttled);
+            }
+        }
+    }
+
+    fn settle(&mut self, limit: usize, throttled: usize, why: &str) {
+        log::info!(
+            "settled on sending {} request(s) at once after {}",
+            limit,
+            why
+        );
+        self.settled = true;
+        self.change(limit, throttled);
+    }
+
+    fn change(&mut self, limit: usize, throttled: usize) {
+        if limit != self.limit {
+            self.generation += 1;
+        }
+        self.limit = limit;
+        self.samples.clear();
+        self.throttled = throttled;
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    /// how many requests are being sent
+    active: usize,
+    control: Control,
+}
+
+/// Hands out turns at sending a request to the threads sending them, so that
+/// no more than the limit are sent at once
+#[derive(Debug)]
+pub struct Limiter {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl Limiter {
+    pub fn new(concurrency: Concurrency) -> Limiter {
+        Limiter {
+            state: Mutex::new(State {
+                active: 0,
+                control: Control::new(concurrency, super::retry::throttled()),
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Wait for a turn at sending a request, returning what to give back to
+    /// `release` when it's done
+    pub fn acquire(&self) -> usize {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        while state.active >= state.control.limit {
+            state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        state.active += 1;
+        state.control.generation
+    }
+
+    /// Give back a turn, with how long its request took if one was sent
+    pub fn release(&self, generation: usize, latency: Option<Duration>) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.active -= 1;
+        if let Some(latency) = latency {
+            state
+                .control
+                .record(generation, latency, super::retry::throttled());
+        }
+        self.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Record `n` requests that took `millis` at the current level
+    fn requests(control: &mut Control, n: usize, millis: u64, throttled: usize) {
+        let generation = control.generation;
+        for _ in 0..n {
+            control.record(generation, Duration::from_millis(millis), throttled);
+        }
+    }
+
+    #[test]
+    fn ramps_up_until_requests_slow_down() {
+        let mut control = Control::new(Concurrency::Auto, 0);
+        assert_eq!(control.limit, 1);
+        requests(&mut control, WINDOW, 100, 0);
+        assert_eq!(control.limit, 2);
+        requests(&mut control, WINDOW, 110, 0);
+        assert_eq!(control.limit, 4);
+        // requests sent before the change don't count
+        control.record(0, Duration::from_secs(10), 0);
+        requests(&mut control, WINDOW, 120, 0);
+        assert_eq!(control.limit, 8);
+        requests(&mut control, 8, 400, 0);
+        assert_eq!(control.limit, 4);
+        assert!(control.settled);
+        // and stays there, however quick requests get
+        requests(&mut control, WINDOW, 50, 0);
+        requests(&mut control, WINDOW, 50, 0);
+        assert_eq!(control.limit, 4);
+    }
+
+    #[test]
+    fn backs_off_when_rate_limited() {
+        let mut control = Control::new(Concurrency::Auto, 3);
+        requests(&mut control, WINDOW, 100, 3);
+        requests(&mut control, WINDOW, 100, 3);
+        requests(&mut control, WINDOW, 100, 3);
+        assert_eq!(control.limit, 8);
+        requests(&mut control, 8, 100, 3);
+        // never more than the most
+        requests(&mut control, 16, 100, 3);
+        assert_eq!(control.limit, MOST_AUTO);
+        requests(&mut control, 1, 100, 4);
+        assert_eq!(control.limit, 8);
+        assert!(control.settled);
+        // rate limited again even so
+        requests(&mut control, 1, 100, 6);
+        assert_eq!(control.limit, 4);
+        requests(&mut control, WINDOW, 100, 6);
+        assert_eq!(control.limit, 4);
+    }
+
+    #[test]
+    fn keeps_a_fixed_concurrency() {
+        let mut control = Control::new(Concurrency::Fixed(3), 0);
+        requests(&mut control, WINDOW, 100, 0);
+        requests(&mut control, WINDOW, 1000, 5);
+        assert_eq!(control.limit, 3);
+        assert_eq!(Concurrency::from_arg("3"), Concurrency::Fixed(3));
+        assert_eq!(Concurrency::from_arg("auto").most(), MOST_AUTO);
+        assert!(validate("auto".to_owned()).is_ok());
+        assert!(validate("8".to_owned()).is_ok());
+        assert!(validate("0".to_owned()).is_err());
+        assert!(validate("lots".to_owned()).is_err());
+    }
+}
@@ -0,0 +1,305 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+
+/// The config file that is used if one isn't explicitly given
+pub const DEFAULT_CONFIG_FILE: &str = "tt.toml";
+
+/// Project configuration, loaded from `tt.toml`
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// the version of the config's schema, which `tt migrate` updates
+    pub version: u64,
+    /// the source translation file to take strings from
+    pub source: Option<String>,
+    /// the language that the source file is written in, instead of English
+    pub source_locale: Option<String>,
+    /// an English translation file to diff against
+    pub diff: Option<String>,
+    /// a git revision to diff the source file against, instead of `diff`
+    pub diff_git: Option<String>,
+    /// where message hashes are kept between runs, instead of `tt.lock`
+    pub lock_file: Option<String>,
+    pub output: OutputConfig,
+    /// the locales to translate into when `--locale` isn't given
+    pub locales: Vec<String>,
+    /// the glossary to use for locales that aren't listed in `glossaries`
+    pub glossary: Option<String>,
+    /// the glossary to use for each locale
+    pub glossaries: BTreeMap<String, String>,
+    /// the Google model to translate with for locales that aren't listed in
+    /// `models`, such as `general/base` or a custom model's id
+    pub model: Option<String>,
+    /// the Google model to translate each locale with
+    pub models: BTreeMap<String, String>,
+    /// labels to attach to each Google translation request, for billing
+    pub labels: BTreeMap<String, String>,
+    pub ignore_case: bool,
+    /// `auto`, `text` or `html`
+    pub mime_type: Option<String>,
+    pub markers: Markers,
+    /// the ids of messages to translate before the others, where `*` matches
+    /// any characters
+    pub priority: Vec<String>,
+    /// where cached files are kept, instead of the platform's cache directory
+    pub cache_dir: Option<String>,
+    /// where state is kept between runs, instead of the platform's state directory
+    pub state_dir: Option<String>,
+    /// the backends to try, in order
+    pub backends: Vec<String>,
+    /// what the backends charge per million characters, for `--max-cost`
+    pub cost_per_million_chars: Option<f64>,
+    /// the most requests to send to the backends each second
+    pub qps: Option<f64>,
+    /// the base URL of Google's translation API, instead of
+    /// https://translation.googleapis.com
+    pub api_endpoint: Option<String>,
+    /// the URL to request Google OAuth tokens from
+    pub token_endpoint: Option<String>,
+    /// the Google Cloud location to translate in and keep glossaries in,
+    /// instead of us-central1
+    pub location: Option<String>,
+    /// the Google Cloud location that glossaries are kept in, instead of
+    /// `location`
+    pub glossary_location: Option<String>,
+    /// the credentials file for locales that aren't in a credentials group
+    pub credentials_file: Option<String>,
+    /// route groups of locales through different credentials
+    pub credentials: Vec<CredentialsGroup>,
+    pub protect: ProtectConfig,
+    pub casing: super::casing::CasingConfig,
+    pub machine_translated_notice: NoticeConfig,
+    pub freeze: super::freeze::FreezeConfig,
+    pub audit: super::audit::AuditConfig,
+    pub review: super::review::ReviewConfig,
+    pub lint: super::lint::LintConfig,
+    pub provenance: super::provenance::ProvenanceConfig,
+    pub placeables: super::placeables::PlaceablesConfig,
+    pub qa: super::qa::QaConfig,
+    pub hooks: super::hooks::HooksConfig,
+    pub length: super::length::LengthConfig,
+    pub spellcheck: super::spellcheck::SpellcheckConfig,
+    pub auth: super::auth::AuthConfig,
+    pub glossary_check: super::glossary::GlossaryCheckConfig,
+    pub memory: super::memory::MemoryConfig,
+    pub retry: super::retry::RetryConfig,
+    pub http: super::http::HttpConfig,
+    pub gcs_batch: super::gcs::GcsBatchConfig,
+    /// keys that `tt` doesn't know about, which are warned about rather than
+    /// silently ignored
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
+}
+
+/// Where translated files are written
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct OutputConfig {
+    /// the directory that translations are written into
+    pub dir: Option<String>,
+    /// the path of each translation inside `dir`, where `{locale}` is replaced
+    /// with the locale
+    pub layout: String,
+}
+
+impl Default for OutputConfig {
+    fn default() -> OutputConfig {
+        OutputConfig {
+            dir: None,
+            layout: "{locale}.flt".to_owned(),
+        }
+    }
+}
+
+/// The comments that mark messages for special treatment
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Markers {
+    /// replace the message with the name of the language
+    pub lang_name: String,
+    /// leave the existing translation alone
+    pub hand_translated: String,
+    /// a message that was removed from the source, kept by `--keep-removed`
+    pub deprecated: String,
+    /// translate the message before the others
+    pub priority: String,
+    /// queue the translation for review
+    pub review: String,
+    /// a translation that a person should check, such as one whose
+    /// placeables don't match the source's
+    pub needs_review: String,
+    /// the most characters that the translation can be, as `tt-max-length: 24`
+    pub max_length: String,
+    /// the version of the source that a message was translated from
+    pub source: String,
+    /// translate the message as plain text, whatever `--mime-type` says
+    pub plain: String,
+    /// copy the message into every locale as it is, such as a product name,
+    /// version number or URL
+    pub no_translate: String,
+    /// where the message is shown, for backends that can use it, as
+    /// `tt-context: shown on the checkout button`
+    pub context: String,
+    /// a translation that a backend wrote, followed by which one and when
+    pub machine_translated: String,
+    /// what `--expand-terms` writes in place of a reference to the term once
+    /// it's translated, as `tt-expand: keep`
+    pub expand: String,
+}
+
+impl Default for Markers {
+    fn default() -> Markers {
+        Markers {
+            lang_name: "tt-lang-name".to_owned(),
+            hand_translated: "tt-hand-translated".to_owned(),
+            deprecated: "tt-deprecated".to_owned(),
+            priority: "tt-priority: high".to_owned(),
+            review: "tt-review".to_owned(),
+            needs_review: "tt-needs-review".to_owned(),
+            max_length: "tt-max-length".to_owned(),
+            source: "tt-source".to_owned(),
+            plain: "tt-plain".to_owned(),
+            no_translate: "tt-no-translate".to_owned(),
+            context: "tt-context".to_owned(),
+            machine_translated: "tt-machine-translated".to_owned(),
+            expand: "tt-expand".to_owned(),
+        }
+    }
+}
+
+/// A message added to every locale that has machine translations in it, such
+/// as a disclaimer for the app to show in its settings
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct NoticeConfig {
+    pub id: String,
+    /// the English text of the notice, which is translated into each locale;
+    /// no notice is added without one
+    pub text: Option<String>,
+}
+
+impl Default for NoticeConfig {
+    fn default() -> NoticeConfig {
+        NoticeConfig {
+            id: "machine-translated-notice".to_owned(),
+            text: None,
+        }
+    }
+}
+
+/// Credentials (and optionally a project to bill) used for a group of locales
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct CredentialsGroup {
+    pub locales: Vec<String>,
+    /// the service account credentials file
+    pub file: String,
+    /// overrides the project id from the credentials file
+    pub project_id: Option<String>,
+}
+
+/// Spans of text that must appear verbatim in every translation
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ProtectConfig {
+    /// individual symbols such as ™ and ®
+    pub symbols: Vec<String>,
+    /// longer strings such as legal boilerplate sentences
+    pub spans: Vec<String>,
+    /// a file of terms such as brand names to keep as they are wherever
+    /// they're a whole word, one per line
+    pub terms_file: String,
+}
+
+impl Default for ProtectConfig {
+    fn default() -> ProtectConfig {
+        ProtectConfig {
+            symbols: ["™", "®", "©", "℠"]
+                .iter()
+                .map(|s| (*s).to_owned())
+                .collect(),
+            spans: Vec::new(),
+            terms_file: "protected-terms.txt".to_owned(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, Box<dyn Error>> {
+        let source = std::fs::read_to_string(path.as_ref())?;
+        let mut document = super::toml::parse(&source)
+            .map_err(|e| format!("failed to parse {}: {}", path.as_ref().display(), e))?;
+        let migrated = super::migrate::CONFIG
+            .migrate(&mut document)
+            .map_err(|e| format!("{}: {}", path.as_ref().display(), e))?;
+        if migrated.changed {
+            log::warn!(
+                "{} is from an older version of tt, run `tt migrate` to upgrade it",
+                path.as_ref().display()
+            );
+        }
+        let config: Config = serde_json::from_value(document)
+            .map_err(|e| format!("invalid config in {}: {}", path.as_ref().display(), e))?;
+        for key in config.unknown.keys() {
+            log::warn!(
+                "ignoring unknown key `{}` in {}",
+                key,
+                path.as_ref().display()
+            );
+        }
+        if let Some(mime_type) = &config.mime_type {
+            if !["auto", "text", "html"].contains(&mime_type.as_str()) {
+                return Err(Box::from(format!(
+                    "invalid config in {}: mime-type must be auto, text or html",
+                    path.as_ref().display()
+                )));
+            }
+        }
+        if !config.output.layout.contains("{locale}") {
+            return Err(Box::from(format!(
+                "invalid config in {}: the output layout must contain {{locale}}",
+                path.as_ref().display()
+            )));
+        }
+        Ok(config)
+    }
+
+    /// Find the credentials group that `locale` is routed through, if any
+    pub fn credentials_for(&self, locale: &str) -> Option<&CredentialsGroup> {
+        self.credentials
+            .iter()
+            .find(|group| group.locales.iter().any(|l| l == locale))
+    }
+
+    /// Find the glossary to use for `locale`, if any
+    pub fn glossary_for(&self, locale: &str) -> Option<&str> {
+        self.glossaries
+            .get(locale)
+            .or(self.glossary.as_ref())
+            .map(String::as_str)
+    }
+
+    /// Find the Google model to translate `locale` with, if any
+    pub fn model_for(&self, locale: &str) -> Option<&str> {
+        self.models
+            .get(locale)
+            .or(self.model.as_ref())
+            .map(String::as_str)
+    }
+}
@@ -0,0 +1,235 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::Deserialize;
+use std::error::Error;
+
+use reqwest::blocking::Client;
+
+use super::translate::{GlossaryConfig, LRLanguage, MimeType};
+
+#[derive(Deserialize)]
+struct DLTranslation {
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct DLData {
+    translations: Vec<DLTranslation>,
+}
+
+#[derive(Deserialize)]
+struct DLLanguage {
+    language: String,
+    name: String,
+}
+
+/// Turn one of DeepL's language codes (`DE`, `PT-BR`) into ours (`de`, `pt-BR`)
+fn from_deepl_code(code: &str) -> String {
+    let mut parts = code.splitn(2, '-');
+    let language = parts.next().unwrap_or_default().to_lowercase();
+    match parts.next() {
+        Some(region) => format!("{}-{}", language, region.to_uppercase()),
+        None => language,
+    }
+}
+
+/// Translates using the DeepL API, authenticating with the key in the
+/// `DEEPL_AUTH_KEY` environment variable
+pub struct DeepLTranslator<'a> {
+    client: Client,
+    auth_key: String,
+    source_language: String,
+    retry: super::retry::Retry,
+    language: &'a str,
+}
+
+impl<'a> DeepLTranslator<'a> {
+    pub fn new(
+        connection: &super::backend::Connection,
+        language: &'a str,
+    ) -> Result<DeepLTranslator<'a>, Box<dyn Error>> {
+        let auth_key = std::env::var("DEEPL_AUTH_KEY").map_err(|_| {
+            log::error!("the DEEPL_AUTH_KEY environment variable must be set to use DeepL");
+            super::errors::Errors::MissingApiKey
+        })?;
+        Ok(DeepLTranslator {
+            client: connection.http.client()?,
+            auth_key,
+            source_language: connection.source_language.clone(),
+            retry: connection.retry.clone(),
+            language,
+        })
+    }
+
+    /// Free API keys end with `:fx` and use a different host
+    fn endpoint(&self, path: &str) -> String {
+        let host = if self.auth_key.ends_with(":fx") {
+            "https://api-free.deepl.com"
+        } else {
+            "https://api.deepl.com"
+        };
+        format!("{}/v2/{}", host, path)
+    }
+
+    /// Translate `phrases` in one request, with `context` for DeepL to take
+    /// into account without translating it
+    fn translate_texts<'c>(
+        &self,
+        phrases: &[&str],
+        mime_type: MimeType,
+        glossary: &Option<GlossaryConfig<'c>>,
+        context: Option<&str>,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        // don't translate into the source language, just copy it over
+        if self.language == self.source_language {
+            return Ok(phrases.iter().map(|phrase| (*phrase).to_owned()).collect());
+        }
+        if glossary.is_some() {
+            log::debug!("Google glossaries can't be used with DeepL, ignoring");
+        }
+
+        let target_lang = self.language.to_uppercase();
+        // DeepL only takes the language of the source, not its variant
+        let source_lang = self
+            .source_language
+            .split('-')
+            .next()
+            .unwrap_or_default()
+            .to_uppercase();
+        // each phrase is sent as its own `text`, and translated in order
+        let mut form: Vec<(&str, &str)> = phrases.iter().map(|phrase| ("text", *phrase)).collect();
+        form.push(("source_lang", source_lang.as_str()));
+        form.push(("target_lang", target_lang.as_str()));
+        if mime_type == MimeType::Html {
+            form.push(("tag_handling", "html"));
+        }
+        if let Some(context) = context {
+            form.push(("context", context));
+        }
+        let res = self.send(self.client.post(&self.endpoint("translate")).form(&form))?;
+
+        let res: DLData = serde_json::from_str(&res)?;
+        if res.translations.len() != phrases.len() {
+            return Err(Box::from(super::errors::Errors::NoTranslations));
+        }
+        phrases
+            .iter()
+            .zip(res.translations)
+            .map(|(phrase, translation)| {
+                super::translate::finish(phrase, &translation.text, mime_type)
+            })
+            .collect()
+    }
+
+    fn send(&self, request: reqwest::blocking::RequestBuilder) -> Result<String, Box<dyn Error>> {
+        let (status, res) = self.retry.send(
+            &self.client,
+            "deepl",
+            request.header(
+                reqwest::header::AUTHORIZATION,
+                format!("DeepL-Auth-Key {}", self.auth_key),
+            ),
+        )?;
+
+        if !status.is_success() {
+            eprintln!("query error: {}", res);
+            // DeepL uses 456 for an exhausted character quota
+            return Err(Box::from(
+                if status.as_u16() == 456 || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                    super::errors::Errors::QuotaExceeded
+                } else if status == reqwest::StatusCode::BAD_REQUEST && res.contains("target_lang")
+                {
+                    super::errors::Errors::UnsupportedLanguage
+                } else {
+                    super::errors::Errors::FailedQuery
+                },
+            ));
+        }
+        Ok(res)
+    }
+}
+
+impl<'a> super::backend::Backend for DeepLTranslator<'a> {
+    fn name(&self) -> &'static str {
+        "deepl"
+    }
+
+    fn available_languages(&self) -> Result<Vec<LRLanguage>, Box<dyn Error>> {
+        let res = self.send(
+            self.client
+                .get(&self.endpoint("languages"))
+                .query(&[("type", "target")]),
+        )?;
+        let languages: Vec<DLLanguage> = serde_json::from_str(&res)?;
+        Ok(languages
+            .into_iter()
+            .map(|lang| LRLanguage {
+                language_code: from_deepl_code(&lang.language),
+                display_name: lang.name,
+                support_source: false,
+                support_target: true,
+            })
+            .collect())
+    }
+
+    fn get_lang_name(&self) -> Result<String, Box<dyn Error>> {
+        // DeepL only has English language names, so ask it to translate one
+        for lang in self.available_languages()? {
+            if lang.language_code == self.language {
+                return self.translate(&lang.display_name, MimeType::Text, &None);
+            }
+        }
+        Ok("<INSERT LANGUAGE NAME HERE>".to_owned())
+    }
+
+    fn translate<'c>(
+        &self,
+        phrase: &str,
+        mime_type: MimeType,
+        glossary: &Option<GlossaryConfig<'c>>,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut translations = self.translate_batch(&[phrase], mime_type, glossary)?;
+        translations
+            .pop()
+            .ok_or_else(|| Box::from(super::errors::Errors::NoTranslations))
+    }
+
+    fn translate_batch<'c>(
+        &self,
+        phrases: &[&str],
+        mime_type: MimeType,
+        glossary: &Option<GlossaryConfig<'c>>,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        self.translate_texts(phrases, mime_type, glossary, None)
+    }
+
+    fn translate_with_context<'c>(
+        &self,
+        phrases: &[&str],
+        mime_type: MimeType,
+        glossary: &Option<GlossaryConfig<'c>>,
+        context: &str,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        self.translate_texts(phrases, mime_type, glossary, Some(context))
+    }
+
+    fn batch_limit(&self) -> Option<super::backend::BatchLimit> {
+        // DeepL takes up to 50 texts in a request of up to 128 KiB
+        Some(super::backend::BatchLimit {
+            texts: 50,
+            chars: 100_000,
+        })
+    }
+}
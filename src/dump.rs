@@ -0,0 +1,113 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+/// Where requests and responses are written, for `--dump-http`
+static DIR: OnceLock<PathBuf> = OnceLock::new();
+/// How many requests have been dumped, to number their files
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Headers whose values are secrets, which are never written
+const REDACTED_HEADERS: &[&str] = &["authorization", "x-goog-api-key", "cookie", "set-cookie"];
+
+/// Write every request sent to the backends, and their responses, into `dir`
+pub fn enable<P: AsRef<Path>>(dir: P) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir.as_ref())?;
+    // keep writing to the same place when a workspace changes directory
+    let dir = std::fs::canonicalize(dir.as_ref())?;
+    log::info!("dumping HTTP requests and responses to {}", dir.display());
+    let _ = DIR.set(dir);
+    Ok(())
+}
+
+fn write_headers(out: &mut String, headers: &HeaderMap) {
+    for (name, value) in headers.iter() {
+        let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+            "<redacted>".into()
+        } else {
+            String::from_utf8_lossy(value.as_bytes())
+        };
+        out.push_str(&format!("{}: {}\n", name, value));
+    }
+}
+
+/// Send `request` with `client`, returning the status and body of the
+/// response. With `--dump-http` the request and response are each written to
+/// a numbered file named after `backend`, with any credentials left out.
+pub fn send(
+    client: &Client,
+    backend: &str,
+    request: RequestBuilder,
+) -> Result<(StatusCode, String), Box<dyn Error>> {
+    let dir = match DIR.get() {
+        Some(dir) => dir,
+        None => {
+            let response = request.send()?;
+            let status = response.status();
+            return Ok((status, response.text()?));
+        }
+    };
+
+    let request = request.build()?;
+    let n = COUNT.fetch_add(1, Ordering::SeqCst) + 1;
+    let mut dumped = format!("{} {}\n", request.method(), request.url());
+    write_headers(&mut dumped, request.headers());
+    dumped.push('\n');
+    if let Some(body) = request.body().and_then(|body| body.as_bytes()) {
+        dumped.push_str(&String::from_utf8_lossy(body));
+    }
+    let request_file = dir.join(format!("{:04}-{}-request.txt", n, backend));
+    std::fs::write(&request_file, dumped)?;
+
+    let response = client.execute(request)?;
+    let status = response.status();
+    let mut dumped = format!("{}\n", status);
+    write_headers(&mut dumped, response.headers());
+    dumped.push('\n');
+    let body = response.text()?;
+    dumped.push_str(&body);
+    let response_file = dir.join(format!("{:04}-{}-response.txt", n, backend));
+    std::fs::write(&response_file, dumped)?;
+    log::debug!(
+        "dumped {} and {}",
+        request_file.display(),
+        response_file.display()
+    );
+    Ok((status, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_credentials() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer ya29.secret".parse().unwrap());
+        headers.insert("content-type", "application/json".parse().unwrap());
+        let mut out = String::new();
+        write_headers(&mut out, &headers);
+        assert_eq!(
+            out,
+            "authorization: <redacted>\ncontent-type: application/json\n"
+        );
+    }
+}
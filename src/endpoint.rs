@@ -0,0 +1,116 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// Where Google's translation API is, unless overridden
+pub const DEFAULT_API: &str = "https://translation.googleapis.com";
+/// Where Google's OAuth tokens come from, unless overridden
+pub const DEFAULT_TOKEN: &str = "https://www.googleapis.com/oauth2/v4/token";
+/// The region Google translates in and keeps glossaries in, unless overridden
+pub const DEFAULT_LOCATION: &str = "us-central1";
+
+/// Where Google's APIs are and which location they're used in, set from the
+/// config and command line of each run
+#[derive(Clone, Debug)]
+pub struct Endpoints {
+    /// the base URL of the translation API, without a trailing `/`
+    pub api: String,
+    /// the URL to request OAuth tokens from
+    pub token: String,
+    /// the Google Cloud location to translate in, such as `us-central1`,
+    /// `europe-west1` or `global`
+    pub location: String,
+    /// the Google Cloud location that glossaries are kept in
+    pub glossary_location: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Endpoints {
+        Endpoints::new(None, None, None, None)
+    }
+}
+
+impl Endpoints {
+    /// Send Google requests to these endpoints instead, such as for Private
+    /// Google Access, a regional endpoint or a mock server, and translate in
+    /// `location`. Glossaries are looked for where texts are translated unless
+    /// `glossary_location` says otherwise.
+    pub fn new(
+        api: Option<&str>,
+        token: Option<&str>,
+        location: Option<&str>,
+        glossary_location: Option<&str>,
+    ) -> Endpoints {
+        let location = location.unwrap_or(DEFAULT_LOCATION).to_owned();
+        Endpoints {
+            api: api.unwrap_or(DEFAULT_API).trim_end_matches('/').to_owned(),
+            token: token.unwrap_or(DEFAULT_TOKEN).to_owned(),
+            glossary_location: glossary_location
+                .map(str::to_owned)
+                .unwrap_or_else(|| location.clone()),
+            location,
+        }
+    }
+}
+
+/// Check that `location` looks like a Google Cloud location
+pub fn validate_location(location: String) -> Result<(), String> {
+    if !location.is_empty()
+        && location
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    {
+        Ok(())
+    } else {
+        Err(format!(
+            "invalid location `{}`, expected something like us-central1 or global",
+            location
+        ))
+    }
+}
+
+/// Check that `url` is something requests can be sent to
+pub fn validate(url: String) -> Result<(), String> {
+    match reqwest::Url::parse(&url) {
+        Ok(parsed) if parsed.scheme() == "http" || parsed.scheme() == "https" => Ok(()),
+        Ok(_) => Err(format!("`{}` isn't an http or https URL", url)),
+        Err(e) => Err(format!("invalid URL `{}`: {}", url, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_and_overrides() {
+        let defaults = Endpoints::default();
+        assert_eq!(defaults.api, DEFAULT_API);
+        assert_eq!(defaults.token, DEFAULT_TOKEN);
+        assert_eq!(defaults.location, DEFAULT_LOCATION);
+        assert_eq!(defaults.glossary_location, DEFAULT_LOCATION);
+
+        let regional = Endpoints::new(
+            Some("https://translate.example.com/"),
+            None,
+            Some("europe-west1"),
+            None,
+        );
+        assert_eq!(regional.api, "https://translate.example.com");
+        assert_eq!(regional.glossary_location, "europe-west1");
+
+        let global = Endpoints::new(None, None, Some("global"), Some("us-central1"));
+        assert_eq!(global.location, "global");
+        assert_eq!(global.glossary_location, "us-central1");
+    }
+}
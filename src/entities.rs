@@ -0,0 +1,134 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// Find the HTML entities (`&amp;`, `&#39;`, `&#x27;`) in `text`, along with
+/// where they start
+fn find_entities(text: &str) -> Vec<(usize, &str)> {
+    let mut found = Vec::new();
+    for (start, _) in text.match_indices('&') {
+        let rest = &text[start + 1..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => continue,
+        };
+        let name = &rest[..end];
+        let valid = if let Some(hex) = name.strip_prefix("#x").or(name.strip_prefix("#X")) {
+            !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit())
+        } else if let Some(dec) = name.strip_prefix('#') {
+            !dec.is_empty() && dec.chars().all(|c| c.is_ascii_digit())
+        } else {
+            name.chars()
+                .next()
+                .map(|c| c.is_ascii_alphabetic())
+                .unwrap_or(false)
+                && name.chars().all(|c| c.is_ascii_alphanumeric())
+        };
+        if valid {
+            found.push((start, &text[start..start + end + 2]));
+        }
+    }
+    found
+}
+
+fn count_entities(text: &str) -> BTreeMap<&str, usize> {
+    let mut counts = BTreeMap::new();
+    for (_, entity) in find_entities(text) {
+        *counts.entry(entity).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Decode the entities that a backend escaped when translating `source` as
+/// HTML. Entities that were already written in the source are left alone, so
+/// that a literal `&amp;` isn't turned into `&`.
+pub fn decode(source: &str, translated: &str) -> Result<String, Box<dyn Error>> {
+    let mut literal = count_entities(source);
+    let mut decoded = String::with_capacity(translated.len());
+    let mut last = 0;
+    for (start, entity) in find_entities(translated) {
+        decoded.push_str(&translated[last..start]);
+        match literal.get_mut(entity) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                decoded.push_str(entity);
+            }
+            _ => decoded.push_str(
+                &escaper::decode_html(entity)
+                    .map_err(|e| format!("failed to decode HTML entities: {:?}", e))?,
+            ),
+        }
+        last = start + entity.len();
+    }
+    decoded.push_str(&translated[last..]);
+    Ok(decoded)
+}
+
+/// Describe any entities that were lost or introduced between the source and its
+/// translation, which usually means something was decoded or escaped by mistake
+pub fn changes(source: &str, translated: &str) -> Vec<String> {
+    let source = count_entities(source);
+    let translated = count_entities(translated);
+    let mut changes = Vec::new();
+    for (entity, count) in source.iter() {
+        let remaining = translated.get(entity).copied().unwrap_or(0);
+        if remaining < *count {
+            changes.push(format!("`{}` was dropped", entity));
+        }
+    }
+    for (entity, count) in translated.iter() {
+        let expected = source.get(entity).copied().unwrap_or(0);
+        if *count > expected {
+            changes.push(format!("`{}` was added", entity));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_literal_entities() {
+        let source = "Tom &amp; Jerry say <em>hi</em>";
+        let translated = "Tom &amp; Jerry disent <em>salut</em>";
+        assert_eq!(decode(source, translated).unwrap(), translated);
+        assert!(changes(source, translated).is_empty());
+    }
+
+    #[test]
+    fn decodes_escaped_entities() {
+        assert_eq!(
+            decode("It's <b>a</b> < b", "C&#39;est <b>a</b> &lt; b").unwrap(),
+            "C'est <b>a</b> < b"
+        );
+        // only as many as the source had are kept
+        assert_eq!(
+            decode("&amp; <b>x</b>", "&amp; &amp; <b>x</b>").unwrap(),
+            "&amp; & <b>x</b>"
+        );
+        assert_eq!(decode("a &amp b", "a &amp b").unwrap(), "a &amp b");
+    }
+
+    #[test]
+    fn flags_entity_changes() {
+        assert_eq!(
+            changes("Tom &amp; Jerry &copy;", "Tom & Jerry &copy; &nbsp;"),
+            vec!["`&amp;` was dropped", "`&nbsp;` was added"]
+        );
+    }
+}
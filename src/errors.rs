@@ -23,6 +23,25 @@ pub enum Errors {
     InvalidShell,
     InvalidLanguage,
     NoTranslations,
+    InvalidBackend,
+    Frozen,
+    UnsupportedLanguage,
+    QuotaExceeded,
+    MissingApiKey,
+    BudgetExceeded,
+    SourceTarget,
+    OutOfDate,
+    LintFailed,
+    InvalidSignature,
+    LeftoverMarkers,
+    HookFailed,
+    LocaleExists,
+    CredentialsRejected,
+    KeyTooOld,
+    SelftestFailed,
+    Timeout,
+    InvalidTimeout,
+    InvalidCertificate,
 }
 
 impl fmt::Display for Errors {
@@ -0,0 +1,271 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::Serialize;
+use std::error::Error;
+use std::process::{Command, Stdio};
+
+use super::translate::{GlossaryConfig, LRLanguage, MimeType};
+
+/// Exit code a plugin uses to say it can't translate into the language
+const EXIT_UNSUPPORTED_LANGUAGE: i32 = 2;
+/// Exit code a plugin uses to say it has run out of quota
+const EXIT_QUOTA_EXCEEDED: i32 = 3;
+
+/// A request sent to the plugin as JSON on stdin
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct ExecRequest<'a> {
+    action: &'static str,
+    source_language: &'a str,
+    target_language: &'a str,
+    text: Option<&'a str>,
+    /// `text/plain` or `text/html`
+    mime_type: Option<&'static str>,
+    glossary: Option<&'a str>,
+    /// where the text is shown, from its `tt-context` comment
+    context: Option<&'a str>,
+}
+
+/// Sends each request to a user-provided executable, so that proprietary
+/// translation engines can be used without patching `tt`.
+///
+/// For `"action": "translate"` the plugin writes the translated text to stdout.
+/// For `"action": "languages"` it writes one supported language per line, as a
+/// language code optionally followed by a tab and the language's display name.
+/// Exiting with 2 means the language isn't supported, 3 means it is out of
+/// quota, and any other failure is reported with whatever is on stderr.
+pub struct ExecBackend<'a> {
+    path: String,
+    source_language: String,
+    throttle: super::throttle::Throttle,
+    language: &'a str,
+}
+
+impl<'a> ExecBackend<'a> {
+    pub fn new(
+        path: &str,
+        connection: &super::backend::Connection,
+        language: &'a str,
+    ) -> ExecBackend<'a> {
+        ExecBackend {
+            path: path.to_owned(),
+            source_language: connection.source_language.clone(),
+            throttle: connection.throttle.clone(),
+            language,
+        }
+    }
+
+    fn run(&self, request: &ExecRequest) -> Result<String, Box<dyn Error>> {
+        // plugins usually call an API of their own, so they're throttled too
+        self.throttle.wait();
+        let mut child = Command::new(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to run plugin `{}`: {}", self.path, e))?;
+        serde_json::to_writer(child.stdin.take().expect("stdin is piped"), request)?;
+        let output = child.wait_with_output()?;
+
+        match output.status.code() {
+            Some(0) => Ok(String::from_utf8(output.stdout)?),
+            Some(EXIT_UNSUPPORTED_LANGUAGE) => {
+                Err(Box::from(super::errors::Errors::UnsupportedLanguage))
+            }
+            Some(EXIT_QUOTA_EXCEEDED) => Err(Box::from(super::errors::Errors::QuotaExceeded)),
+            _ => {
+                log::error!(
+                    "plugin error: {}",
+                    String::from_utf8_lossy(&output.stderr).trim_end()
+                );
+                Err(Box::from(super::errors::Errors::FailedQuery))
+            }
+        }
+    }
+}
+
+impl<'a> super::backend::Backend for ExecBackend<'a> {
+    fn name(&self) -> &'static str {
+        "exec"
+    }
+
+    fn available_languages(&self) -> Result<Vec<LRLanguage>, Box<dyn Error>> {
+        let output = self.run(&ExecRequest {
+            action: "languages",
+            source_language: &self.source_language,
+            target_language: self.language,
+            text: None,
+            mime_type: None,
+            glossary: None,
+            context: None,
+        })?;
+        Ok(output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let mut parts = line.splitn(2, '\t');
+                let code = parts.next().unwrap_or_default().trim().to_owned();
+                let name = parts.next().map(|n| n.trim().to_owned());
+                LRLanguage {
+                    display_name: name.unwrap_or_else(|| code.clone()),
+                    language_code: code,
+                    support_source: false,
+                    support_target: true,
+                }
+            })
+            .collect())
+    }
+
+    fn get_lang_name(&self) -> Result<String, Box<dyn Error>> {
+        for lang in self.available_languages()? {
+            if lang.language_code == self.language {
+                return Ok(lang.display_name);
+            }
+        }
+        Ok("<INSERT LANGUAGE NAME HERE>".to_owned())
+    }
+
+    fn translate<'c>(
+        &self,
+        phrase: &str,
+        mime_type: MimeType,
+        glossary: &Option<GlossaryConfig<'c>>,
+    ) -> Result<String, Box<dyn Error>> {
+        self.translate_text(phrase, mime_type, glossary, None)
+    }
+
+    fn translate_with_context<'c>(
+        &self,
+        phrases: &[&str],
+        mime_type: MimeType,
+        glossary: &Option<GlossaryConfig<'c>>,
+        context: &str,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        phrases
+            .iter()
+            .map(|phrase| self.translate_text(phrase, mime_type, glossary, Some(context)))
+            .collect()
+    }
+}
+
+impl<'a> ExecBackend<'a> {
+    fn translate_text<'c>(
+        &self,
+        phrase: &str,
+        mime_type: MimeType,
+        glossary: &Option<GlossaryConfig<'c>>,
+        context: Option<&str>,
+    ) -> Result<String, Box<dyn Error>> {
+        // don't translate into the source language, just copy it over
+        if self.language == self.source_language {
+            return Ok(phrase.to_owned());
+        }
+
+        let translation = self.run(&ExecRequest {
+            action: "translate",
+            source_language: &self.source_language,
+            target_language: self.language,
+            text: Some(phrase),
+            mime_type: Some(mime_type.as_str()),
+            glossary: glossary.as_ref().map(|g| g.glossary),
+            context,
+        })?;
+        let translation = translation.trim_end_matches(&['\n', '\r'][..]);
+        Ok(translation.replace("\n", "\n    "))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::super::backend::Backend;
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// A plugin that runs `script` with the shell
+    fn plugin(name: &str, script: &str) -> ExecBackend<'static> {
+        let dir = std::env::temp_dir().join(format!("tt-exec-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        ExecBackend {
+            path: path.display().to_string(),
+            source_language: "en".to_owned(),
+            throttle: Default::default(),
+            language: "fr",
+        }
+    }
+
+    #[test]
+    fn sends_requests_as_json() {
+        let echo = plugin("echo", "cat");
+        let request: serde_json::Value = serde_json::from_str(
+            &echo
+                .translate_with_context(&["Hi <b>you</b>"], MimeType::Html, &None, "a greeting")
+                .unwrap()[0],
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            serde_json::json!({
+                "action": "translate",
+                "source_language": "en",
+                "target_language": "fr",
+                "text": "Hi <b>you</b>",
+                "mime_type": "text/html",
+                "glossary": null,
+                "context": "a greeting",
+            })
+        );
+
+        let translated = plugin("translate", "printf 'Bonjour\\ntout le monde\\n'");
+        assert_eq!(
+            translated
+                .translate("Hello", MimeType::Text, &None)
+                .unwrap(),
+            "Bonjour\n    tout le monde"
+        );
+
+        let languages = plugin("languages", "printf 'de\\tGerman\\n\\nfr\\n'");
+        let listed = languages.available_languages().unwrap();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].language_code, "de");
+        assert_eq!(listed[0].display_name, "German");
+        assert_eq!(listed[1].display_name, "fr");
+    }
+
+    #[test]
+    fn maps_exit_codes_to_errors() {
+        let error = |name: &str, script: &str| {
+            plugin(name, script)
+                .translate("Hello", MimeType::Text, &None)
+                .unwrap_err()
+                .downcast::<super::super::errors::Errors>()
+                .map(|error| *error)
+        };
+        assert!(matches!(
+            error("unsupported", "exit 2"),
+            Ok(super::super::errors::Errors::UnsupportedLanguage)
+        ));
+        assert!(matches!(
+            error("quota", "exit 3"),
+            Ok(super::super::errors::Errors::QuotaExceeded)
+        ));
+        assert!(matches!(
+            error("failing", "echo 'no good' >&2; exit 1"),
+            Ok(super::super::errors::Errors::FailedQuery)
+        ));
+    }
+}
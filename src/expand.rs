@@ -0,0 +1,255 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fluent_syntax::ast::{
+    Entry, Expression, InlineExpression, Message, Pattern, PatternElement, Resource, ResourceEntry,
+};
+use std::collections::BTreeMap;
+
+/// What's written in place of a reference to a term once the message has been
+/// translated with the term's value in it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Policy {
+    /// the reference is put back, so that the locale's own term is used
+    Reference,
+    /// the translation of the term's value is kept, which reads better where
+    /// the term would need to change with its grammar, marked `tt-expand: keep`
+    Keep,
+}
+
+/// The values of the terms in the source that references can be expanded
+/// into, which are those whose value is a single line of text
+#[derive(Debug, Default)]
+pub struct Expansions {
+    terms: BTreeMap<String, (String, Policy)>,
+}
+
+impl Expansions {
+    /// Find the terms of `resource` that can be expanded, where `marker` is
+    /// the comment that says what to do with their references
+    pub fn new(resource: &Resource, marker: &str) -> Expansions {
+        let terms = resource
+            .body
+            .iter()
+            .filter_map(|entry| match entry {
+                ResourceEntry::Entry(Entry::Term(term)) => {
+                    let text: Option<String> = term
+                        .value
+                        .elements
+                        .iter()
+                        .map(|element| match element {
+                            PatternElement::TextElement(text) if !text.contains('\n') => {
+                                Some(*text)
+                            }
+                            _ => None,
+                        })
+                        .collect();
+                    let policy =
+                        match super::catalog::marker_value(&term.comment, marker).as_deref() {
+                            Some("keep") => Policy::Keep,
+                            _ => Policy::Reference,
+                        };
+                    text.filter(|text| !text.trim().is_empty())
+                        .map(|text| (term.id.name.to_owned(), (text, policy)))
+                }
+                _ => None,
+            })
+            .collect();
+        Expansions { terms }
+    }
+
+    /// The value of the term that `expression` refers to, if it's a plain
+    /// reference to a term with `policy`
+    fn expansion(&self, expression: &Expression, policy: Policy) -> Option<&str> {
+        match expression {
+            Expression::InlineExpression(InlineExpression::TermReference {
+                id,
+                attribute: None,
+                arguments: None,
+            }) => match self.terms.get(id.name) {
+                Some((text, p)) if *p == policy => Some(text),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn keep_in_pattern<'a>(&'a self, pattern: Pattern<'a>) -> Pattern<'a> {
+        let elements = pattern
+            .elements
+            .into_iter()
+            .map(|element| match element {
+                PatternElement::Placeable(expression) => {
+                    match self.expansion(&expression, Policy::Keep) {
+                        Some(text) => PatternElement::TextElement(text),
+                        None => PatternElement::Placeable(match expression {
+                            Expression::SelectExpression { selector, variants } => {
+                                Expression::SelectExpression {
+                                    selector,
+                                    variants: variants
+                                        .into_iter()
+                                        .map(|mut variant| {
+                                            variant.value = self.keep_in_pattern(variant.value);
+                                            variant
+                                        })
+                                        .collect(),
+                                }
+                            }
+                            expression => expression,
+                        }),
+                    }
+                }
+                element => element,
+            })
+            .collect();
+        Pattern { elements }
+    }
+
+    /// Replace the references in the messages of `resource` to terms whose
+    /// expansion is kept with the terms' values, so that they're translated
+    /// and written as part of the message. Messages marked `no_translate` are
+    /// copied as they are, so they're left alone.
+    pub fn keep<'a>(&'a self, resource: Resource<'a>, no_translate: &str) -> Resource<'a> {
+        let body = resource
+            .body
+            .into_iter()
+            .map(|entry| match entry {
+                ResourceEntry::Entry(Entry::Message(message))
+                    if !super::catalog::has_marker(&message.comment, no_translate) =>
+                {
+                    ResourceEntry::Entry(Entry::Message(Message {
+                        value: message.value.map(|value| self.keep_in_pattern(value)),
+                        attributes: message
+                            .attributes
+                            .into_iter()
+                            .map(|mut attribute| {
+                                attribute.value = self.keep_in_pattern(attribute.value);
+                                attribute
+                            })
+                            .collect(),
+                        ..message
+                    }))
+                }
+                entry => entry,
+            })
+            .collect();
+        Resource { body }
+    }
+
+    /// Fill the markers in `text`, the `catalog::source_text` of `pattern`,
+    /// that stand in for references to terms that are put back with the terms'
+    /// values, so that the backend sees real words instead of an empty marker
+    pub fn show(&self, pattern: &Pattern, text: &str) -> String {
+        let placeables: Vec<&Expression> = pattern
+            .elements
+            .iter()
+            .filter_map(|element| match element {
+                PatternElement::Placeable(expression) => Some(expression),
+                _ => None,
+            })
+            .collect();
+        let mut shown = String::with_capacity(text.len());
+        let mut last = 0;
+        for marker in super::placeables::markers(text).into_iter() {
+            let expansion = marker
+                .id
+                .checked_sub(1)
+                .and_then(|i| placeables.get(i))
+                .and_then(|expression| self.expansion(expression, Policy::Reference));
+            if let Some(expansion) = expansion {
+                shown.push_str(&text[last..marker.range.start]);
+                shown.push_str(&super::placeables::marker_around(marker.id, expansion));
+                last = marker.range.end;
+            }
+        }
+        shown.push_str(&text[last..]);
+        shown
+    }
+
+    /// Show the term references in the texts of `segments`, which are those of
+    /// `message`
+    pub fn show_in_segments(&self, message: &Message, segments: &mut super::catalog::Segments) {
+        if let (Some(text), Some(pattern)) = (&mut segments.value, &message.value) {
+            *text = self.show(pattern, text);
+        }
+        for (text, attribute) in segments
+            .attributes
+            .iter_mut()
+            .zip(message.attributes.iter())
+        {
+            *text = self.show(&attribute.value, text);
+        }
+        let patterns = message
+            .value
+            .iter()
+            .chain(message.attributes.iter().map(|attribute| &attribute.value));
+        for (texts, pattern) in segments.variants.iter_mut().zip(patterns) {
+            let variants = super::catalog::variant_patterns(pattern);
+            for (text, variant) in texts.iter_mut().zip(variants) {
+                *text = self.show(variant, text);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_term_references() {
+        let source = fluent_syntax::parser::parse(
+            "-brand = Firefox\n-company = AT&T <b>\n# tt-expand: keep\n-device = phone\n-app = { -brand } Mobile\nhello = Welcome to { -brand } on your { -device }!\n    .title = { $count ->\n        [one] One { -device }\n       *[other] { $count } { -device }s\n    }\n# tt-no-translate\nabout = { -device }\nsupport = Call { -company }\n",
+        )
+        .unwrap();
+        let expansions = Expansions::new(&source, "tt-expand");
+        let source = expansions.keep(source, "tt-no-translate");
+        let hello = super::super::catalog::find_message(&source, "hello").unwrap();
+        let value = hello.value.as_ref().unwrap();
+        let text = super::super::catalog::source_text(value);
+        assert_eq!(
+            text,
+            format!(
+                "Welcome to {} on your phone!",
+                super::super::placeables::marker(1)
+            )
+        );
+        let shown = expansions.show(value, &text);
+        assert_eq!(
+            shown,
+            "Welcome to <span translate=\"no\" id=\"ph1\">Firefox</span> on your phone!"
+        );
+        // the reference is put back in place of the marker and its expansion
+        assert_eq!(
+            super::super::writer::fill_placeables(value, &shown),
+            "Welcome to { -brand } on your phone!"
+        );
+
+        let variants = super::super::catalog::variant_patterns(&hello.attributes[0].value);
+        assert_eq!(super::super::catalog::source_text(variants[0]), "One phone");
+        let about = super::super::catalog::find_message(&source, "about").unwrap();
+        assert_eq!(
+            super::super::catalog::source_text(about.value.as_ref().unwrap()),
+            super::super::placeables::marker(1)
+        );
+
+        // the value is escaped, so that it doesn't break the markup
+        let support = super::super::catalog::find_message(&source, "support").unwrap();
+        let value = support.value.as_ref().unwrap();
+        assert_eq!(
+            expansions.show(value, &super::super::catalog::source_text(value)),
+            "Call <span translate=\"no\" id=\"ph1\">AT&amp;T &lt;b&gt;</span>"
+        );
+    }
+}
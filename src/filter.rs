@@ -0,0 +1,167 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fluent_syntax::ast::{Comment, Entry, Resource, ResourceEntry};
+use std::collections::HashMap;
+
+/// A pattern that message ids are matched against: a glob where `*` matches
+/// any characters, or a regular expression after `re:`
+#[derive(Debug)]
+pub enum Pattern {
+    Glob(String),
+    Regex(regex::Regex),
+}
+
+impl Pattern {
+    pub fn parse(pattern: &str) -> Result<Pattern, String> {
+        match pattern.strip_prefix("re:") {
+            Some(expression) => regex::Regex::new(expression)
+                .map(Pattern::Regex)
+                .map_err(|e| format!("`{}` isn't a valid regular expression: {}", expression, e)),
+            None => Ok(Pattern::Glob(pattern.to_owned())),
+        }
+    }
+
+    /// Whether `id` matches. A regular expression can match any part of the
+    /// id, while a glob has to match all of it.
+    pub fn matches(&self, id: &str) -> bool {
+        match self {
+            Pattern::Glob(glob) => super::priority::id_matches(glob, id),
+            Pattern::Regex(regex) => regex.is_match(id),
+        }
+    }
+}
+
+/// The section that each message of `resource` is in, named by the first line
+/// of the `##` group comment above it. An empty `##` ends a section.
+pub fn sections<'s>(resource: &Resource<'s>) -> HashMap<&'s str, &'s str> {
+    let mut sections = HashMap::new();
+    let mut section = None;
+    for entry in resource.body.iter() {
+        match entry {
+            ResourceEntry::Entry(Entry::Comment(Comment::GroupComment { content })) => {
+                section = content
+                    .iter()
+                    .map(|line| line.trim())
+                    .find(|line| !line.is_empty());
+            }
+            ResourceEntry::Entry(Entry::Message(message)) => {
+                if let Some(section) = section {
+                    sections.insert(message.id.name, section);
+                }
+            }
+            _ => {}
+        }
+    }
+    sections
+}
+
+/// Check that `pattern` can be parsed, for `--only`, `--exclude` and
+/// `--section`
+pub fn validate(pattern: String) -> Result<(), String> {
+    Pattern::parse(&pattern).map(|_| ())
+}
+
+/// Which messages are translated, by their ids
+#[derive(Debug, Default)]
+pub struct IdFilter {
+    /// translate only the messages matching one of these, if there are any
+    only: Vec<Pattern>,
+    /// never translate the messages matching one of these
+    exclude: Vec<Pattern>,
+    /// translate only the messages in a section whose name matches one of
+    /// these, if there are any
+    sections: Vec<Pattern>,
+}
+
+impl IdFilter {
+    pub fn new<'a, I, E>(only: I, exclude: E) -> Result<IdFilter, String>
+    where
+        I: IntoIterator<Item = &'a str>,
+        E: IntoIterator<Item = &'a str>,
+    {
+        Ok(IdFilter {
+            only: only
+                .into_iter()
+                .map(Pattern::parse)
+                .collect::<Result<_, _>>()?,
+            exclude: exclude
+                .into_iter()
+                .map(Pattern::parse)
+                .collect::<Result<_, _>>()?,
+            sections: Vec::new(),
+        })
+    }
+
+    /// Only include the messages in sections that match one of `sections`
+    pub fn in_sections<'a, S>(mut self, sections: S) -> Result<IdFilter, String>
+    where
+        S: IntoIterator<Item = &'a str>,
+    {
+        self.sections = sections
+            .into_iter()
+            .map(Pattern::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(self)
+    }
+
+    /// Whether the message `id`, in `section` if it's in one, is translated
+    pub fn includes(&self, id: &str, section: Option<&str>) -> bool {
+        (self.only.is_empty() || self.only.iter().any(|pattern| pattern.matches(id)))
+            && !self.exclude.iter().any(|pattern| pattern.matches(id))
+            && (self.sections.is_empty()
+                || section
+                    .map(|section| self.sections.iter().any(|pattern| pattern.matches(section)))
+                    .unwrap_or(false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_ids() {
+        let filter =
+            IdFilter::new(vec!["checkout-*", "re:^cart-(add|remove)"], vec!["*-beta"]).unwrap();
+        assert!(filter.includes("checkout-pay", None));
+        assert!(filter.includes("cart-add-item", None));
+        assert!(!filter.includes("cart-empty", None));
+        assert!(!filter.includes("checkout-pay-beta", None));
+        assert!(!filter.includes("hello", None));
+
+        let filter = IdFilter::new(vec![], vec!["re:experimental"]).unwrap();
+        assert!(filter.includes("hello", None));
+        assert!(!filter.includes("new-experimental-menu", None));
+
+        let source = fluent_syntax::parser::parse(
+            "## Checkout\n\npay = Pay\n\n##\n\nhello = Hello\n\n## Cart\n## more about it\n\nadd = Add\n",
+        )
+        .unwrap();
+        let sections = sections(&source);
+        assert_eq!(sections.get("pay"), Some(&"Checkout"));
+        assert_eq!(sections.get("hello"), None);
+        assert_eq!(sections.get("add"), Some(&"Cart"));
+        let filter = IdFilter::new(vec![], vec![])
+            .unwrap()
+            .in_sections(vec!["Check*"])
+            .unwrap();
+        assert!(filter.includes("pay", Some("Checkout")));
+        assert!(!filter.includes("add", Some("Cart")));
+        assert!(!filter.includes("hello", None));
+
+        assert!(validate("re:(".to_owned()).is_err());
+        assert!(validate("(".to_owned()).is_ok());
+    }
+}
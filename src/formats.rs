@@ -0,0 +1,2256 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::de::{Deserialize, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use std::error::Error;
+use std::path::Path;
+
+use super::ids::IdMap;
+
+/// Catalog formats that messages can be converted between
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    Fluent,
+    /// a JSON object of strings, optionally nested
+    Json,
+    /// a gettext `.po` or `.pot` file
+    Po,
+    /// an Android `strings.xml` resource file
+    Android,
+    /// an Apple `Localizable.strings` file
+    Strings,
+    /// an Apple `.stringsdict` file of plurals
+    Stringsdict,
+    /// a Flutter `.arb` file of ICU MessageFormat messages
+    Arb,
+    /// a Java `.properties` resource bundle
+    Properties,
+}
+
+/// The formats accepted by `--format` when importing and exporting Fluent,
+/// along with the spreadsheet of every locale's translations and XLIFF for
+/// translating them in CAT tools
+pub const NAMES: &[&str] = &[
+    "json",
+    "po",
+    "android",
+    "strings",
+    "stringsdict",
+    "arb",
+    "properties",
+    super::sheet::FORMAT,
+    super::xliff::FORMATS[0],
+    super::xliff::FORMATS[1],
+];
+
+impl Format {
+    pub fn from_arg(name: &str) -> Option<Format> {
+        match name {
+            "json" => Some(Format::Json),
+            "po" => Some(Format::Po),
+            "android" => Some(Format::Android),
+            "strings" => Some(Format::Strings),
+            "stringsdict" => Some(Format::Stringsdict),
+            "arb" => Some(Format::Arb),
+            "properties" => Some(Format::Properties),
+            _ => None,
+        }
+    }
+
+    /// Guess the format from a file's extension
+    pub fn from_path(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()? {
+            "flt" | "ftl" => Some(Format::Fluent),
+            "json" => Some(Format::Json),
+            "po" | "pot" => Some(Format::Po),
+            "xml" => Some(Format::Android),
+            "strings" => Some(Format::Strings),
+            "stringsdict" => Some(Format::Stringsdict),
+            "arb" => Some(Format::Arb),
+            "properties" => Some(Format::Properties),
+            _ => None,
+        }
+    }
+
+    /// The extension that files in this format are written with
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Fluent => "flt",
+            Format::Json => "json",
+            Format::Po => "po",
+            Format::Android => "xml",
+            Format::Strings => "strings",
+            Format::Stringsdict => "stringsdict",
+            Format::Arb => "arb",
+            Format::Properties => "properties",
+        }
+    }
+}
+
+/// The text of a message, which may have a form for each plural category
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Text(String),
+    /// CLDR plural categories (`one`, `other` and so on) and their texts
+    Plural(Vec<(String, String)>),
+}
+
+impl Value {
+    /// The text to use where plurals can't be written: the `other` form
+    fn other(&self) -> &str {
+        match self {
+            Value::Text(text) => text,
+            Value::Plural(forms) => forms
+                .iter()
+                .find(|(category, _)| category == "other")
+                .or_else(|| forms.last())
+                .map(|(_, text)| text.as_str())
+                .unwrap_or(""),
+        }
+    }
+}
+
+/// A message in a catalog, keyed the way that the catalog's format keys it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub key: String,
+    pub value: Value,
+    pub comment: Option<String>,
+}
+
+impl Entry {
+    /// The key without the msgid_plural that gettext plurals are keyed with,
+    /// for formats that key plurals by name alone
+    fn name(&self) -> &str {
+        self.key
+            .split(PO_PLURAL_SEPARATOR)
+            .next()
+            .unwrap_or(&self.key)
+    }
+
+    /// The text to write to a format without plurals, warning if any plural
+    /// forms are lost
+    fn text(&self, format: &str) -> &str {
+        if let Value::Plural(_) = self.value {
+            log::warn!(
+                "{} doesn't support plurals, only the `other` form of `{}` is kept",
+                format,
+                self.name()
+            );
+        }
+        self.value.other()
+    }
+}
+
+fn parse(
+    format: Format,
+    name: &Path,
+    text: &str,
+    ids: &IdMap,
+) -> Result<Vec<Entry>, Box<dyn Error>> {
+    match format {
+        Format::Fluent => {
+            let resource =
+                super::catalog::continue_parsing(name, fluent_syntax::parser::parse(text));
+            Ok(from_fluent(&resource, ids))
+        }
+        Format::Json => parse_json(text),
+        Format::Po => parse_po(text),
+        Format::Android => parse_android(text),
+        Format::Strings => parse_strings(text),
+        Format::Stringsdict => parse_stringsdict(text),
+        Format::Arb => parse_arb(text),
+        Format::Properties => parse_properties(text),
+    }
+}
+
+/// Decode a catalog, which is UTF-8 unless it starts with a UTF-16 byte order
+/// mark, as Xcode used to write `.strings` files
+fn decode(bytes: Vec<u8>) -> Result<String, Box<dyn Error>> {
+    let units = |bytes: &[u8], from: fn([u8; 2]) -> u16| -> Vec<u16> {
+        bytes
+            .chunks_exact(2)
+            .map(|pair| from([pair[0], pair[1]]))
+            .collect()
+    };
+    match bytes.as_slice() {
+        [0xff, 0xfe, rest @ ..] => Ok(String::from_utf16(&units(rest, u16::from_le_bytes))?),
+        [0xfe, 0xff, rest @ ..] => Ok(String::from_utf16(&units(rest, u16::from_be_bytes))?),
+        [0xef, 0xbb, 0xbf, rest @ ..] => Ok(String::from_utf8(rest.to_vec())?),
+        _ => Ok(String::from_utf8(bytes)?),
+    }
+}
+
+/// Read the messages in a catalog. Fluent messages get back the keys they
+/// were imported with from `ids`.
+pub fn read(format: Format, path: &Path, ids: &IdMap) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let text = read_text(path)?;
+    parse(format, path, &text, ids)
+        .map_err(|e| Box::from(format!("failed to parse {}: {}", path.display(), e)))
+}
+
+/// Read the text of a catalog, in whichever encoding it's in
+pub fn read_text(path: &Path) -> Result<String, Box<dyn Error>> {
+    decode(std::fs::read(path)?)
+        .map_err(|e| Box::from(format!("failed to read {}: {}", path.display(), e)))
+}
+
+/// Parse the messages of a catalog along with how far along each of their
+/// translations is, which only gettext records
+pub fn parse_states(
+    format: Format,
+    name: &Path,
+    text: &str,
+) -> Result<Vec<(Entry, PoState)>, Box<dyn Error>> {
+    let entries = match format {
+        Format::Po => parse_po_states(text),
+        _ => parse(format, name, text, &IdMap::default()).map(|entries| {
+            entries
+                .into_iter()
+                .map(|entry| (entry, PoState::Translated))
+                .collect()
+        }),
+    };
+    entries.map_err(|e| Box::from(format!("failed to parse {}: {}", name.display(), e)))
+}
+
+/// Write the translations of a catalog for `language`, which gettext and ARB
+/// files record
+pub fn render_translations(
+    format: Format,
+    entries: &[(&Entry, PoState)],
+    language: &str,
+) -> String {
+    match format {
+        Format::Po => render_po_states(entries, Some(language)),
+        _ => {
+            let entries: Vec<Entry> = entries.iter().map(|(entry, _)| (*entry).clone()).collect();
+            match format {
+                Format::Arb => render_arb(&entries, Some(language)),
+                _ => write(format, &entries, &IdMap::default()).0,
+            }
+        }
+    }
+}
+
+/// Write messages in a format. Fluent needs valid ids, so keys that aren't
+/// are given new ones (keeping any that were given in `previous`), and the
+/// mapping from the new ids back to the keys is returned along with the file.
+pub fn write(format: Format, entries: &[Entry], previous: &IdMap) -> (String, Option<IdMap>) {
+    match format {
+        Format::Fluent => {
+            let keys: Vec<&str> = entries.iter().map(|e| e.key.as_str()).collect();
+            let (ids, map) = IdMap::assign(&keys, previous);
+            (to_fluent(entries, &ids), Some(map))
+        }
+        Format::Json => (render_json(entries), None),
+        Format::Po => (render_po(entries), None),
+        Format::Android => (render_android(entries), None),
+        Format::Strings => (render_strings(entries), None),
+        Format::Stringsdict => (render_stringsdict(entries), None),
+        Format::Arb => (render_arb(entries, None), None),
+        Format::Properties => (render_properties(entries), None),
+    }
+}
+
+/// A JSON value that keeps the keys of objects in the order they were written,
+/// which `serde_json::Value` doesn't
+enum Json {
+    String(String),
+    Object(Vec<(String, Json)>),
+    /// numbers, arrays and so on, which can't be translated
+    Other,
+}
+
+impl<'de> Deserialize<'de> for Json {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Json, D::Error> {
+        struct JsonVisitor;
+
+        impl<'de> Visitor<'de> for JsonVisitor {
+            type Value = Json;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("any JSON value")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Json, E> {
+                Ok(Json::String(value.to_owned()))
+            }
+
+            fn visit_string<E>(self, value: String) -> Result<Json, E> {
+                Ok(Json::String(value))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Json, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(Json::Object(entries))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Json, A::Error> {
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                Ok(Json::Other)
+            }
+
+            fn visit_bool<E>(self, _: bool) -> Result<Json, E> {
+                Ok(Json::Other)
+            }
+
+            fn visit_i64<E>(self, _: i64) -> Result<Json, E> {
+                Ok(Json::Other)
+            }
+
+            fn visit_u64<E>(self, _: u64) -> Result<Json, E> {
+                Ok(Json::Other)
+            }
+
+            fn visit_f64<E>(self, _: f64) -> Result<Json, E> {
+                Ok(Json::Other)
+            }
+
+            fn visit_unit<E>(self) -> Result<Json, E> {
+                Ok(Json::Other)
+            }
+        }
+
+        deserializer.deserialize_any(JsonVisitor)
+    }
+}
+
+/// Escape a key for use in a JSON pointer
+fn pointer_segment(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// The key and plural category of an i18next plural form, such as `item_one`
+fn json_plural_form(key: &str) -> Option<(&str, &str)> {
+    let (base, category) = key.rsplit_once('_')?;
+    Some((base, category)).filter(|_| !base.is_empty() && PLURAL_CATEGORIES.contains(&category))
+}
+
+/// Collect the strings in a JSON object. Nested objects are keyed by JSON
+/// pointers (`/home/title`) so that they can be nested again on export, while
+/// flat objects are keyed by their own keys. Keys with the plural suffixes of
+/// i18next, such as `item_one` and `item_other`, are collected into a plural
+/// of `item` when there's an `_other` form.
+fn parse_json(text: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let object = match serde_json::from_str(text)? {
+        Json::Object(object) => object,
+        _ => return Err(Box::from("expected a JSON object of strings")),
+    };
+    let nested = object
+        .iter()
+        .any(|(_, value)| matches!(value, Json::Object(_)));
+
+    fn collect(prefix: &str, object: Vec<(String, Json)>, nested: bool, out: &mut Vec<Entry>) {
+        let full_key = |key: &str| {
+            if nested {
+                format!("{}/{}", prefix, pointer_segment(key))
+            } else {
+                key.to_owned()
+            }
+        };
+        let plurals: Vec<String> = object
+            .iter()
+            .filter_map(|(key, value)| match (json_plural_form(key), value) {
+                (Some((base, "other")), Json::String(_)) => Some(base.to_owned()),
+                _ => None,
+            })
+            .collect();
+        let first = out.len();
+        for (key, value) in object {
+            match (json_plural_form(&key), value) {
+                (Some((base, category)), Json::String(value))
+                    if plurals.iter().any(|plural| plural == base) =>
+                {
+                    let base = full_key(base);
+                    let form = (category.to_owned(), value);
+                    let existing = out[first..].iter_mut().find(|entry| entry.key == base);
+                    match existing.map(|entry| &mut entry.value) {
+                        Some(Value::Plural(forms)) => forms.push(form),
+                        _ => out.push(Entry {
+                            key: base,
+                            value: Value::Plural(vec![form]),
+                            comment: None,
+                        }),
+                    }
+                }
+                (_, Json::String(value)) => out.push(Entry {
+                    key: full_key(&key),
+                    value: Value::Text(value),
+                    comment: None,
+                }),
+                (_, Json::Object(object)) => collect(&full_key(&key), object, nested, out),
+                (_, Json::Other) => {
+                    log::warn!("skipping `{}`, which isn't a string", full_key(&key))
+                }
+            }
+        }
+    }
+
+    let mut entries = Vec::new();
+    collect("", object, nested, &mut entries);
+    Ok(entries)
+}
+
+enum Node {
+    Leaf(String),
+    Branch(Vec<(String, Node)>),
+}
+
+fn insert_node(branch: &mut Vec<(String, Node)>, path: &[String], value: &str) {
+    let (key, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    if rest.is_empty() {
+        branch.push((key.clone(), Node::Leaf(value.to_owned())));
+        return;
+    }
+    if !branch
+        .iter()
+        .any(|(k, node)| k == key && matches!(node, Node::Branch(_)))
+    {
+        branch.push((key.clone(), Node::Branch(Vec::new())));
+    }
+    for (k, node) in branch.iter_mut() {
+        if let (true, Node::Branch(children)) = (k == key, node) {
+            insert_node(children, rest, value);
+            return;
+        }
+    }
+}
+
+fn write_nodes(out: &mut String, nodes: &[(String, Node)], indent: usize) {
+    out.push_str("{\n");
+    for (i, (key, node)) in nodes.iter().enumerate() {
+        out.push_str(&"  ".repeat(indent + 1));
+        out.push_str(&serde_json::to_string(key).expect("can serialize strings"));
+        out.push_str(": ");
+        match node {
+            Node::Leaf(value) => {
+                out.push_str(&serde_json::to_string(value).expect("can serialize strings"))
+            }
+            Node::Branch(children) => write_nodes(out, children, indent + 1),
+        }
+        if i + 1 < nodes.len() {
+            out.push(',');
+        }
+        out.push('\n');
+    }
+    out.push_str(&"  ".repeat(indent));
+    out.push('}');
+}
+
+fn render_json(entries: &[Entry]) -> String {
+    let mut nodes = Vec::new();
+    for entry in entries.iter() {
+        let path: Vec<String> = match entry.name().strip_prefix('/') {
+            Some(pointer) => pointer
+                .split('/')
+                .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+                .collect(),
+            None => vec![entry.name().to_owned()],
+        };
+        match &entry.value {
+            Value::Text(text) => insert_node(&mut nodes, &path, text),
+            // written with the plural suffixes of i18next, such as `item_one`
+            Value::Plural(forms) => {
+                for (category, text) in forms.iter() {
+                    let mut path = path.clone();
+                    if let Some(last) = path.last_mut() {
+                        last.push_str(&format!("_{}", category));
+                    }
+                    insert_node(&mut nodes, &path, text);
+                }
+            }
+        }
+    }
+    let mut out = String::new();
+    write_nodes(&mut out, &nodes, 0);
+    out.push('\n');
+    out
+}
+
+/// Separates a gettext msgctxt from its msgid in an entry's key
+const PO_CONTEXT_SEPARATOR: char = '\u{4}';
+/// Separates a gettext msgid from its msgid_plural in an entry's key, the way
+/// that `.mo` files do
+const PO_PLURAL_SEPARATOR: char = '\u{0}';
+
+/// How far along the translation of a gettext message is
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PoState {
+    /// its msgstr is empty, so the entry's value is its msgid
+    Untranslated,
+    /// flagged `fuzzy`, which gettext leaves out until someone reviews it
+    Fuzzy,
+    Translated,
+}
+
+fn po_unquote(line: &str, number: usize) -> Result<String, Box<dyn Error>> {
+    let line = line.trim();
+    let quoted = line
+        .strip_prefix('"')
+        .and_then(|l| l.strip_suffix('"'))
+        .filter(|_| line.len() >= 2)
+        .ok_or_else(|| format!("line {}: expected a quoted string", number))?;
+    let mut value = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('t') => value.push('\t'),
+            Some('r') => value.push('\r'),
+            Some(c @ '"') | Some(c @ '\\') => value.push(c),
+            other => {
+                return Err(Box::from(format!(
+                    "line {}: unknown escape `\\{}`",
+                    number,
+                    other.map(String::from).unwrap_or_default()
+                )))
+            }
+        }
+    }
+    Ok(value)
+}
+
+#[derive(Default)]
+struct PoEntry {
+    comments: Vec<String>,
+    context: Option<String>,
+    id: Option<String>,
+    plural: Option<String>,
+    translations: Vec<String>,
+    fuzzy: bool,
+}
+
+impl PoEntry {
+    fn finish(self, entries: &mut Vec<(Entry, PoState)>) {
+        let id = match self.id {
+            Some(id) => id,
+            None => return,
+        };
+        // the header is written again on export
+        if id.is_empty() && self.context.is_none() {
+            return;
+        }
+        let mut key = match &self.context {
+            Some(context) => format!("{}{}{}", context, PO_CONTEXT_SEPARATOR, id),
+            None => id.clone(),
+        };
+        let translated = self.translations.iter().any(|t| !t.is_empty());
+        let value = match self.plural {
+            None => Value::Text(match self.translations.into_iter().next() {
+                Some(translation) if translated => translation,
+                _ => id,
+            }),
+            Some(plural) => {
+                key.push(PO_PLURAL_SEPARATOR);
+                key.push_str(&plural);
+                let mut translations = self.translations.into_iter();
+                match (translated, translations.len()) {
+                    (false, _) => {
+                        Value::Plural(vec![("one".to_owned(), id), ("other".to_owned(), plural)])
+                    }
+                    (true, 1) => {
+                        Value::Plural(vec![("other".to_owned(), translations.next().unwrap())])
+                    }
+                    (true, 2) => Value::Plural(vec![
+                        ("one".to_owned(), translations.next().unwrap()),
+                        ("other".to_owned(), translations.next().unwrap()),
+                    ]),
+                    (true, n) => {
+                        // which form is which depends on the Plural-Forms formula
+                        log::warn!(
+                            "`{}` has {} plural forms, but only languages with one or two are supported, so only the first is kept",
+                            id,
+                            n
+                        );
+                        Value::Text(translations.next().unwrap())
+                    }
+                }
+            }
+        };
+        let state = match (translated, self.fuzzy) {
+            (false, _) => PoState::Untranslated,
+            (true, true) => PoState::Fuzzy,
+            (true, false) => PoState::Translated,
+        };
+        entries.push((
+            Entry {
+                key,
+                value,
+                comment: if self.comments.is_empty() {
+                    None
+                } else {
+                    Some(self.comments.join("\n"))
+                },
+            },
+            state,
+        ));
+    }
+}
+
+fn parse_po(text: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+    Ok(parse_po_states(text)?
+        .into_iter()
+        .map(|(entry, _)| entry)
+        .collect())
+}
+
+/// Read the messages of a gettext catalog along with how far along each of
+/// their translations is
+pub fn parse_po_states(text: &str) -> Result<Vec<(Entry, PoState)>, Box<dyn Error>> {
+    enum Field {
+        Context,
+        Id,
+        Plural,
+        Translation,
+    }
+
+    let mut entries = Vec::new();
+    let mut entry = PoEntry::default();
+    let mut field: Option<Field> = None;
+    for (i, line) in text.lines().enumerate() {
+        let number = i + 1;
+        let line = line.trim();
+        let starts_entry = line.starts_with("msgctxt ")
+            || (line.starts_with("msgid ") && entry.context.is_none())
+            || (line.starts_with('#') && !entry.translations.is_empty());
+        if line.is_empty() || (starts_entry && entry.id.is_some()) {
+            std::mem::take(&mut entry).finish(&mut entries);
+            field = None;
+        }
+        if line.is_empty() || line.starts_with("#~") {
+            // obsolete entries are dropped
+            continue;
+        }
+
+        if let Some(comment) = line.strip_prefix("#.") {
+            entry.comments.push(comment.trim().to_owned());
+        } else if let Some(flags) = line.strip_prefix("#,") {
+            // other flags, such as `c-format`, are regenerated by gettext
+            entry.fuzzy |= flags.split(',').any(|flag| flag.trim() == "fuzzy");
+        } else if line.starts_with("#:") || line.starts_with("#|") {
+            // references and previous msgids are regenerated by gettext
+        } else if let Some(comment) = line.strip_prefix('#') {
+            entry.comments.push(comment.trim().to_owned());
+        } else if let Some(rest) = line.strip_prefix("msgctxt ") {
+            entry.context = Some(po_unquote(rest, number)?);
+            field = Some(Field::Context);
+        } else if let Some(rest) = line.strip_prefix("msgid_plural ") {
+            entry.plural = Some(po_unquote(rest, number)?);
+            field = Some(Field::Plural);
+        } else if let Some(rest) = line.strip_prefix("msgid ") {
+            entry.id = Some(po_unquote(rest, number)?);
+            field = Some(Field::Id);
+        } else if line.starts_with("msgstr") {
+            let rest = line
+                .split_once(char::is_whitespace)
+                .map(|(_, rest)| rest)
+                .ok_or_else(|| format!("line {}: expected a quoted string", number))?;
+            entry.translations.push(po_unquote(rest, number)?);
+            field = Some(Field::Translation);
+        } else if line.starts_with('"') {
+            let continued = po_unquote(line, number)?;
+            match field {
+                Some(Field::Context) => entry.context.get_or_insert_with(String::new),
+                Some(Field::Id) => entry.id.get_or_insert_with(String::new),
+                Some(Field::Plural) => entry.plural.get_or_insert_with(String::new),
+                Some(Field::Translation) => entry
+                    .translations
+                    .last_mut()
+                    .expect("a translation was started"),
+                None => {
+                    return Err(Box::from(format!(
+                        "line {}: string without a keyword",
+                        number
+                    )))
+                }
+            }
+            .push_str(&continued);
+        } else {
+            return Err(Box::from(format!("line {}: unexpected `{}`", number, line)));
+        }
+    }
+    entry.finish(&mut entries);
+    Ok(entries)
+}
+
+fn po_quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Write a keyword and its string, splitting multi-line strings after each
+/// newline the way gettext does
+fn po_field(out: &mut String, keyword: &str, value: &str) {
+    if value.trim_end_matches('\n').contains('\n') {
+        out.push_str(keyword);
+        out.push_str(" \"\"\n");
+        for line in value.split_inclusive('\n') {
+            out.push_str(&po_quote(line));
+            out.push('\n');
+        }
+    } else {
+        out.push_str(&format!("{} {}\n", keyword, po_quote(value)));
+    }
+}
+
+fn render_po(entries: &[Entry]) -> String {
+    let entries: Vec<(&Entry, PoState)> = entries
+        .iter()
+        .map(|entry| (entry, PoState::Translated))
+        .collect();
+    render_po_states(&entries, None)
+}
+
+/// Write a gettext catalog for `language`, leaving the msgstrs of untranslated
+/// entries empty and flagging fuzzy ones
+pub fn render_po_states(entries: &[(&Entry, PoState)], language: Option<&str>) -> String {
+    let mut out = String::new();
+    out.push_str("msgid \"\"\n");
+    out.push_str("msgstr \"\"\n");
+    if let Some(language) = language {
+        out.push_str(&format!(
+            "{}\n",
+            po_quote(&format!("Language: {}\n", language))
+        ));
+    }
+    out.push_str("\"Content-Type: text/plain; charset=UTF-8\\n\"\n");
+    for (entry, state) in entries.iter() {
+        out.push('\n');
+        if let Some(comment) = &entry.comment {
+            for line in comment.lines() {
+                out.push_str(&format!("#. {}\n", line));
+            }
+        }
+        if *state == PoState::Fuzzy {
+            out.push_str("#, fuzzy\n");
+        }
+        let translation = |text: &'_ str| -> String {
+            match state {
+                PoState::Untranslated => String::new(),
+                _ => text.to_owned(),
+            }
+        };
+        let id = match entry.key.split_once(PO_CONTEXT_SEPARATOR) {
+            Some((context, id)) => {
+                po_field(&mut out, "msgctxt", context);
+                id
+            }
+            None => &entry.key,
+        };
+        let (id, plural) = match (id.split_once(PO_PLURAL_SEPARATOR), &entry.value) {
+            (Some((id, plural)), _) => (id, Some(plural)),
+            (None, Value::Plural(_)) => (id, Some(id)),
+            (None, Value::Text(_)) => (id, None),
+        };
+        po_field(&mut out, "msgid", id);
+        match (plural, &entry.value) {
+            (Some(plural), Value::Plural(forms)) => {
+                po_field(&mut out, "msgid_plural", plural);
+                for (i, (_, text)) in forms.iter().enumerate() {
+                    po_field(&mut out, &format!("msgstr[{}]", i), &translation(text));
+                }
+            }
+            (Some(plural), Value::Text(text)) => {
+                po_field(&mut out, "msgid_plural", plural);
+                po_field(&mut out, "msgstr[0]", &translation(text));
+                po_field(&mut out, "msgstr[1]", &translation(text));
+            }
+            (None, _) => po_field(&mut out, "msgstr", &translation(entry.value.other())),
+        }
+    }
+    out
+}
+
+/// Find the value of the attribute `name` in an XML start tag
+pub fn xml_attribute<'t>(tag: &'t str, name: &str) -> Option<&'t str> {
+    let mut rest = tag;
+    while let Some(start) = rest.find(name) {
+        let before = rest[..start].chars().last();
+        let after = rest[start + name.len()..].trim_start();
+        rest = &rest[start + name.len()..];
+        if !before.map(char::is_whitespace).unwrap_or(false) {
+            continue;
+        }
+        let after = match after.strip_prefix('=') {
+            Some(after) => after.trim_start(),
+            None => continue,
+        };
+        let quote = after.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            continue;
+        }
+        let value = &after[1..];
+        return value.find(quote).map(|end| &value[..end]);
+    }
+    None
+}
+
+/// Undo the backslash escapes in an Android string. Markup and XML entities
+/// are kept as they are so that they can be written back unchanged.
+fn android_unescape(text: &str) -> String {
+    let mut value = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('t') => value.push('\t'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                match u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(std::char::from_u32)
+                {
+                    Some(c) => value.push(c),
+                    None => {
+                        value.push_str("\\u");
+                        value.push_str(&hex);
+                    }
+                }
+            }
+            Some(c) => value.push(c),
+            None => value.push('\\'),
+        }
+    }
+    value
+}
+
+fn android_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for (i, c) in value.chars().enumerate() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\'' => escaped.push_str("\\'"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '@' | '?' if i == 0 => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn parse_android(text: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let mut entries = Vec::new();
+    let mut comment: Option<String> = None;
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").ok_or("unterminated XML comment")?;
+            comment = Some(rest["<!--".len()..end].trim().to_owned());
+            rest = &rest[end + "-->".len()..];
+            continue;
+        }
+
+        let tag_end = rest.find('>').ok_or("unterminated XML tag")?;
+        let tag = &rest[..=tag_end];
+        let is_string = rest.starts_with("<string")
+            && rest["<string".len()..].starts_with(|c: char| c.is_whitespace() || c == '>');
+        if rest.starts_with("<plurals") {
+            let name = xml_attribute(tag, "name")
+                .ok_or_else(|| format!("<plurals> without a name: {}", tag))?;
+            let name = escaper::decode_html(name)
+                .map_err(|e| format!("invalid name {}: {:?}", name, e))?;
+            let end = rest
+                .find("</plurals>")
+                .ok_or_else(|| format!("unterminated <plurals> `{}`", name))?;
+            let mut body = &rest[tag.len()..end];
+            rest = &rest[end + "</plurals>".len()..];
+            let mut forms = Vec::new();
+            while let Some(start) = body.find("<item") {
+                body = &body[start..];
+                let item_end = body.find('>').ok_or("unterminated XML tag")?;
+                let quantity = xml_attribute(&body[..=item_end], "quantity")
+                    .ok_or_else(|| format!("<item> without a quantity in `{}`", name))?
+                    .to_owned();
+                let close = body
+                    .find("</item>")
+                    .ok_or_else(|| format!("unterminated <item> in `{}`", name))?;
+                forms.push((quantity, android_unescape(&body[item_end + 1..close])));
+                body = &body[close + "</item>".len()..];
+            }
+            entries.push(Entry {
+                key: name,
+                value: Value::Plural(forms),
+                comment: comment.take(),
+            });
+            continue;
+        }
+        if !is_string {
+            if rest.starts_with("<string-array") {
+                let name = xml_attribute(tag, "name").unwrap_or("?");
+                log::warn!("skipping <string-array> `{}`, which isn't supported", name);
+                let end = rest
+                    .find("</string-array>")
+                    .ok_or("unterminated XML element")?;
+                rest = &rest[end..];
+            }
+            comment = None;
+            rest = &rest[rest.find('>').expect("found above") + 1..];
+            continue;
+        }
+
+        let name = xml_attribute(tag, "name")
+            .ok_or_else(|| format!("<string> without a name: {}", tag))?;
+        let name =
+            escaper::decode_html(name).map_err(|e| format!("invalid name {}: {:?}", name, e))?;
+        let (content, after) = if tag.ends_with("/>") {
+            ("", &rest[tag.len()..])
+        } else {
+            let body = &rest[tag.len()..];
+            let end = body
+                .find("</string>")
+                .ok_or_else(|| format!("unterminated <string> `{}`", name))?;
+            (&body[..end], &body[end + "</string>".len()..])
+        };
+        rest = after;
+        if xml_attribute(tag, "translatable") == Some("false") {
+            log::debug!("skipping untranslatable string `{}`", name);
+            comment = None;
+            continue;
+        }
+        entries.push(Entry {
+            key: name,
+            value: Value::Text(android_unescape(content)),
+            comment: comment.take(),
+        });
+    }
+    Ok(entries)
+}
+
+fn render_android(entries: &[Entry]) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    out.push_str("<resources>\n");
+    for entry in entries.iter() {
+        if let Some(comment) = &entry.comment {
+            out.push_str(&format!("    <!-- {} -->\n", comment.replace("--", "- -")));
+        }
+        let name = entry
+            .name()
+            .replace('&', "&amp;")
+            .replace('"', "&quot;")
+            .replace('<', "&lt;");
+        match &entry.value {
+            Value::Text(text) => out.push_str(&format!(
+                "    <string name=\"{}\">{}</string>\n",
+                name,
+                android_escape(text)
+            )),
+            Value::Plural(forms) => {
+                out.push_str(&format!("    <plurals name=\"{}\">\n", name));
+                for (quantity, text) in forms.iter() {
+                    out.push_str(&format!(
+                        "        <item quantity=\"{}\">{}</item>\n",
+                        quantity,
+                        android_escape(text)
+                    ));
+                }
+                out.push_str("    </plurals>\n");
+            }
+        }
+    }
+    out.push_str("</resources>\n");
+    out
+}
+
+/// Read a quoted `.strings` string, `rest` starting just after its opening
+/// quote, returning it and what follows its closing quote
+fn strings_unquote(rest: &str, line: usize) -> Result<(String, &str), Box<dyn Error>> {
+    let mut value = String::new();
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, &rest[i + 1..])),
+            '\\' => match chars.next().map(|(_, c)| c) {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('r') => value.push('\r'),
+                Some('U') | Some('u') => {
+                    let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                    let c = u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(std::char::from_u32)
+                        .ok_or_else(|| format!("line {}: invalid escape `\\U{}`", line, hex))?;
+                    value.push(c);
+                }
+                Some(c) => value.push(c),
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+    Err(Box::from(format!("line {}: unterminated string", line)))
+}
+
+fn strings_quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Parse an Apple `.strings` file of `"key" = "value";` pairs, keeping the
+/// comment before each one
+fn parse_strings(text: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let mut entries = Vec::new();
+    let mut comments: Vec<String> = Vec::new();
+    let mut rest = text;
+    let line = |rest: &str| text[..text.len() - rest.len()].lines().count().max(1);
+    // a key or value, which old-style property lists don't have to quote
+    let token = |rest: &str| -> Result<(String, usize), Box<dyn Error>> {
+        match rest.strip_prefix('"') {
+            Some(quoted) => {
+                let (value, after) = strings_unquote(quoted, line(rest))?;
+                Ok((value, rest.len() - after.len()))
+            }
+            None => {
+                let len = rest
+                    .find(|c: char| !(c.is_alphanumeric() || "_.-$:/".contains(c)))
+                    .unwrap_or(rest.len());
+                if len == 0 {
+                    return Err(Box::from(format!("line {}: expected a string", line(rest))));
+                }
+                Ok((rest[..len].to_owned(), len))
+            }
+        }
+    };
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        if let Some(comment) = rest.strip_prefix("/*") {
+            let end = comment
+                .find("*/")
+                .ok_or_else(|| format!("line {}: unterminated comment", line(rest)))?;
+            comments.push(comment[..end].trim().to_owned());
+            rest = &comment[end + "*/".len()..];
+            continue;
+        }
+        if let Some(comment) = rest.strip_prefix("//") {
+            let end = comment.find('\n').unwrap_or(comment.len());
+            comments.push(comment[..end].trim().to_owned());
+            rest = &comment[end..];
+            continue;
+        }
+
+        let (key, len) = token(rest)?;
+        rest = rest[len..].trim_start();
+        // `"key";` is translated as itself
+        let value = match rest.strip_prefix('=') {
+            Some(after) => {
+                let after = after.trim_start();
+                let (value, len) = token(after)?;
+                rest = after[len..].trim_start();
+                value
+            }
+            None => key.clone(),
+        };
+        rest = rest
+            .strip_prefix(';')
+            .ok_or_else(|| format!("line {}: expected `;` after `{}`", line(rest), key))?;
+        let comment = std::mem::take(&mut comments).join("\n");
+        entries.push(Entry {
+            key,
+            value: Value::Text(value),
+            comment: Some(comment).filter(|comment| !comment.is_empty()),
+        });
+    }
+    Ok(entries)
+}
+
+fn render_strings(entries: &[Entry]) -> String {
+    let mut out = String::new();
+    for entry in entries.iter() {
+        if let Some(comment) = &entry.comment {
+            out.push_str(&format!("/* {} */\n", comment.replace("*/", "* /")));
+        }
+        out.push_str(&format!(
+            "{} = {};\n\n",
+            strings_quote(entry.name()),
+            strings_quote(entry.text("Apple .strings"))
+        ));
+    }
+    out
+}
+
+/// A value in a property list, as far as `.stringsdict` files use them
+enum Plist {
+    /// with the comment before each key
+    Dict(Vec<(String, Option<String>, Plist)>),
+    String(String),
+    /// numbers, booleans, arrays and dates, which aren't needed
+    Other,
+}
+
+impl Plist {
+    fn get(&self, key: &str) -> Option<&Plist> {
+        match self {
+            Plist::Dict(entries) => entries
+                .iter()
+                .find(|(k, _, _)| k == key)
+                .map(|(_, _, value)| value),
+            _ => None,
+        }
+    }
+
+    fn text(&self) -> Option<&str> {
+        match self {
+            Plist::String(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+/// Skip whitespace and XML comments, returning the last comment
+fn plist_skip(rest: &mut &str) -> Result<Option<String>, Box<dyn Error>> {
+    let mut comment = None;
+    loop {
+        *rest = rest.trim_start();
+        match rest.strip_prefix("<!--") {
+            Some(inside) => {
+                let end = inside.find("-->").ok_or("unterminated XML comment")?;
+                comment = Some(inside[..end].trim().to_owned());
+                *rest = &inside[end + "-->".len()..];
+            }
+            None => return Ok(comment),
+        }
+    }
+}
+
+/// The text of an element whose start tag `rest` starts just after, decoding
+/// its entities, and what follows its end tag
+fn plist_text<'t>(rest: &'t str, name: &str) -> Result<(String, &'t str), Box<dyn Error>> {
+    let close = format!("</{}>", name);
+    let end = rest
+        .find(&close)
+        .ok_or_else(|| format!("unterminated <{}>", name))?;
+    let text =
+        escaper::decode_html(&rest[..end]).map_err(|e| format!("invalid <{}>: {:?}", name, e))?;
+    Ok((text, &rest[end + close.len()..]))
+}
+
+/// Parse the property list value that `rest` starts with
+fn plist_value(rest: &mut &str) -> Result<Plist, Box<dyn Error>> {
+    plist_skip(rest)?;
+    let tag_end = rest.find('>').ok_or("unterminated XML tag")?;
+    let tag = &rest[1..tag_end];
+    let empty = tag.ends_with('/');
+    let name = tag.trim_end_matches('/').trim().to_owned();
+    *rest = &rest[tag_end + 1..];
+    if empty {
+        return Ok(match name.as_str() {
+            "dict" => Plist::Dict(Vec::new()),
+            "string" => Plist::String(String::new()),
+            _ => Plist::Other,
+        });
+    }
+    match name.as_str() {
+        "dict" => {
+            let mut entries = Vec::new();
+            loop {
+                let comment = plist_skip(rest)?;
+                if let Some(after) = rest.strip_prefix("</dict>") {
+                    *rest = after;
+                    return Ok(Plist::Dict(entries));
+                }
+                let after = rest
+                    .strip_prefix("<key>")
+                    .ok_or("expected a <key> in a <dict>")?;
+                let (key, after) = plist_text(after, "key")?;
+                *rest = after;
+                let value = plist_value(rest)?;
+                entries.push((key, comment, value));
+            }
+        }
+        "string" => {
+            let (text, after) = plist_text(rest, "string")?;
+            *rest = after;
+            Ok(Plist::String(text))
+        }
+        _ => {
+            let close = format!("</{}>", name);
+            let end = rest
+                .find(&close)
+                .ok_or_else(|| format!("unterminated <{}>", name))?;
+            *rest = &rest[end + close.len()..];
+            Ok(Plist::Other)
+        }
+    }
+}
+
+/// The CLDR plural categories that a `.stringsdict` rule can have, in order
+const PLURAL_CATEGORIES: &[&str] = &["zero", "one", "two", "few", "many", "other"];
+
+/// The variables of a `.stringsdict` format, such as `files` in `%#@files@`,
+/// with where each one is in the format
+fn stringsdict_variables(format: &str) -> Vec<(std::ops::Range<usize>, &str)> {
+    let mut variables = Vec::new();
+    for (start, _) in format.match_indices('%') {
+        if let Some(len) = placeholder_len(&format[start..]) {
+            let spec = &format[start..start + len];
+            if let Some(at) = spec.find("#@") {
+                variables.push((start..start + len, &spec[at + 2..spec.len() - 1]));
+            }
+        }
+    }
+    variables
+}
+
+/// Parse an Apple `.stringsdict` file. A format with one plural variable
+/// becomes a plural with the rest of the format in each of its forms, since
+/// the other formats can't have more than one.
+fn parse_stringsdict(text: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let start = text.find("<plist").ok_or("expected a <plist>")?;
+    let mut rest = &text[start..];
+    let tag_end = rest.find('>').ok_or("unterminated XML tag")?;
+    rest = &rest[tag_end + 1..];
+    let root = match plist_value(&mut rest)? {
+        Plist::Dict(entries) => entries,
+        _ => return Err(Box::from("a .stringsdict should be a <dict>")),
+    };
+
+    let mut entries = Vec::new();
+    for (key, comment, dict) in root.iter() {
+        let format = match dict.get("NSStringLocalizedFormatKey").and_then(Plist::text) {
+            Some(format) => format,
+            None => {
+                log::warn!(
+                    "skipping `{}`, which has no NSStringLocalizedFormatKey",
+                    key
+                );
+                continue;
+            }
+        };
+        let variables = stringsdict_variables(format);
+        let forms = |variable: &str| -> Vec<(String, String)> {
+            PLURAL_CATEGORIES
+                .iter()
+                .filter_map(|category| {
+                    dict.get(variable)
+                        .and_then(|rule| rule.get(category))
+                        .and_then(Plist::text)
+                        .map(|text| ((*category).to_owned(), text.to_owned()))
+                })
+                .collect()
+        };
+        let value = match variables.as_slice() {
+            [] => Value::Text(format.to_owned()),
+            [(range, variable)] => Value::Plural(
+                forms(variable)
+                    .into_iter()
+                    .map(|(category, text)| {
+                        let text =
+                            format!("{}{}{}", &format[..range.start], text, &format[range.end..]);
+                        (category, text)
+                    })
+                    .collect(),
+            ),
+            _ => {
+                log::warn!(
+                    "`{}` has more than one plural variable, only their `other` forms are kept",
+                    key
+                );
+                let mut text = format.to_owned();
+                for (range, variable) in variables.iter().rev() {
+                    let other = forms(variable)
+                        .into_iter()
+                        .find(|(category, _)| category == "other")
+                        .map(|(_, text)| text)
+                        .unwrap_or_default();
+                    text.replace_range(range.clone(), &other);
+                }
+                Value::Text(text)
+            }
+        };
+        entries.push(Entry {
+            key: key.clone(),
+            value,
+            comment: comment.clone(),
+        });
+    }
+    Ok(entries)
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// The `NSStringFormatValueTypeKey` of a plural, such as `ld` for `%ld files`,
+/// from the first format specifier in its forms
+fn stringsdict_value_type(forms: &[(String, String)]) -> String {
+    for (_, text) in forms.iter() {
+        for (start, _) in text.match_indices('%') {
+            if let Some(len) = placeholder_len(&text[start..]) {
+                let spec = &text[start..start + len];
+                let conversion: String = spec
+                    .chars()
+                    .rev()
+                    .take_while(char::is_ascii_alphabetic)
+                    .collect::<Vec<char>>()
+                    .into_iter()
+                    .rev()
+                    .collect();
+                if !conversion.is_empty() {
+                    return conversion;
+                }
+            }
+        }
+    }
+    "d".to_owned()
+}
+
+fn render_stringsdict(entries: &[Entry]) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n");
+    out.push_str("<plist version=\"1.0\">\n<dict>\n");
+    for entry in entries.iter() {
+        if let Some(comment) = &entry.comment {
+            out.push_str(&format!("    <!-- {} -->\n", comment.replace("--", "- -")));
+        }
+        out.push_str(&format!(
+            "    <key>{}</key>\n    <dict>\n",
+            xml_escape(entry.name())
+        ));
+        out.push_str("        <key>NSStringLocalizedFormatKey</key>\n");
+        match &entry.value {
+            Value::Text(text) => {
+                out.push_str(&format!("        <string>{}</string>\n", xml_escape(text)));
+            }
+            Value::Plural(forms) => {
+                out.push_str("        <string>%#@count@</string>\n");
+                out.push_str("        <key>count</key>\n        <dict>\n");
+                out.push_str("            <key>NSStringFormatSpecTypeKey</key>\n");
+                out.push_str("            <string>NSStringPluralRuleType</string>\n");
+                out.push_str("            <key>NSStringFormatValueTypeKey</key>\n");
+                out.push_str(&format!(
+                    "            <string>{}</string>\n",
+                    stringsdict_value_type(forms)
+                ));
+                for (category, text) in forms.iter() {
+                    out.push_str(&format!(
+                        "            <key>{}</key>\n            <string>{}</string>\n",
+                        category,
+                        xml_escape(text)
+                    ));
+                }
+                out.push_str("        </dict>\n");
+            }
+        }
+        out.push_str("    </dict>\n");
+    }
+    out.push_str("</dict>\n</plist>\n");
+    out
+}
+
+/// Undo the escapes of a `.properties` key or value, including `\uXXXX`
+/// escapes of UTF-16 code units
+fn properties_unescape(text: &str, number: usize) -> Result<String, Box<dyn Error>> {
+    let mut units: Vec<u16> = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next() {
+                Some('t') => '\t',
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('f') => '\u{c}',
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let unit = u16::from_str_radix(&hex, 16)
+                        .ok()
+                        .filter(|_| hex.len() == 4)
+                        .ok_or_else(|| format!("line {}: invalid escape `\\u{}`", number, hex))?;
+                    units.push(unit);
+                    continue;
+                }
+                Some(c) => c,
+                None => break,
+            },
+            c => c,
+        };
+        units.extend_from_slice(c.encode_utf16(&mut [0; 2]));
+    }
+    String::from_utf16(&units)
+        .map_err(|_| Box::from(format!("line {}: unpaired surrogate escape", number)))
+}
+
+/// Escape a `.properties` key or value, writing everything outside of ASCII
+/// as `\uXXXX` so that the file reads the same in every encoding
+fn properties_escape(text: &str, key: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for (i, c) in text.chars().enumerate() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\u{c}' => escaped.push_str("\\f"),
+            // leading spaces would be skipped, as would any in a key
+            ' ' if key || i == 0 => escaped.push_str("\\ "),
+            '=' | ':' | '#' | '!' if key => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_ascii() && !c.is_ascii_control() => escaped.push(c),
+            c => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    escaped.push_str(&format!("\\u{:04X}", unit));
+                }
+            }
+        }
+    }
+    escaped
+}
+
+/// Parse a Java `.properties` file, keeping the comment lines right before
+/// each property
+fn parse_properties(text: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let mut entries = Vec::new();
+    let mut comments: Vec<String> = Vec::new();
+    let mut lines = text.lines().enumerate();
+    while let Some((i, line)) = lines.next() {
+        let mut line = line.trim_start().to_owned();
+        if line.is_empty() {
+            comments.clear();
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix(|c| c == '#' || c == '!') {
+            comments.push(comment.trim().to_owned());
+            continue;
+        }
+        // an odd number of backslashes continues the line
+        while (line.len() - line.trim_end_matches('\\').len()) % 2 == 1 {
+            line.pop();
+            match lines.next() {
+                Some((_, next)) => line.push_str(next.trim_start()),
+                None => break,
+            }
+        }
+        let mut end = line.len();
+        let mut escaped = false;
+        for (at, c) in line.char_indices() {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '=' || c == ':' || c.is_whitespace() {
+                end = at;
+                break;
+            }
+        }
+        let mut value = line[end..].trim_start();
+        if let Some(after) = value.strip_prefix(|c| c == '=' || c == ':') {
+            value = after.trim_start();
+        }
+        let comment = Some(comments.join("\n")).filter(|c| !c.is_empty());
+        comments.clear();
+        entries.push(Entry {
+            key: properties_unescape(&line[..end], i + 1)?,
+            value: Value::Text(properties_unescape(value, i + 1)?),
+            comment,
+        });
+    }
+    Ok(entries)
+}
+
+fn render_properties(entries: &[Entry]) -> String {
+    let mut out = String::new();
+    for entry in entries.iter() {
+        if let Some(comment) = &entry.comment {
+            for line in comment.lines() {
+                out.push_str(&format!("# {}\n", properties_escape(line, false)));
+            }
+        }
+        out.push_str(&format!(
+            "{} = {}\n",
+            properties_escape(entry.name(), true),
+            properties_escape(entry.text("Java .properties"), false)
+        ));
+    }
+    out
+}
+
+/// Collect the messages of a Flutter ARB file, with the description in each
+/// one's `@key` metadata as its comment. `@@locale` and the other metadata
+/// are left out, since only the template file needs them.
+fn parse_arb(text: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let object = match serde_json::from_str(text)? {
+        Json::Object(object) => object,
+        _ => return Err(Box::from("expected a JSON object of messages")),
+    };
+    let description = |key: &str| {
+        object
+            .iter()
+            .find(|(k, _)| k.strip_prefix('@') == Some(key))
+            .and_then(|(_, metadata)| match metadata {
+                Json::Object(metadata) => metadata.iter().find_map(|(k, v)| match v {
+                    Json::String(description) if k == "description" => Some(description.clone()),
+                    _ => None,
+                }),
+                _ => None,
+            })
+    };
+    let mut entries = Vec::new();
+    for (key, value) in object.iter() {
+        if key.starts_with('@') {
+            continue;
+        }
+        match value {
+            Json::String(value) => entries.push(Entry {
+                key: key.clone(),
+                value: Value::Text(value.clone()),
+                comment: description(key),
+            }),
+            _ => log::warn!("skipping `{}`, which isn't a string", key),
+        }
+    }
+    Ok(entries)
+}
+
+/// Write a plural as an ICU MessageFormat plural on `count`, with its `zero`
+/// form for exactly zero since English has no `zero` category
+fn icu_plural(forms: &[(String, String)]) -> String {
+    let variants: Vec<String> = forms
+        .iter()
+        .map(|(category, text)| {
+            let key = if category == "zero" { "=0" } else { category };
+            format!("{}{{{}}}", key, text)
+        })
+        .collect();
+    format!("{{count, plural, {}}}", variants.join(" "))
+}
+
+fn render_arb(entries: &[Entry], locale: Option<&str>) -> String {
+    let quote = |text: &str| serde_json::to_string(text).expect("can serialize strings");
+    let mut fields = Vec::new();
+    if let Some(locale) = locale {
+        fields.push(format!("  \"@@locale\": {}", quote(locale)));
+    }
+    for entry in entries.iter() {
+        let value = match &entry.value {
+            Value::Text(text) => text.clone(),
+            Value::Plural(forms) => icu_plural(forms),
+        };
+        fields.push(format!("  {}: {}", quote(entry.name()), quote(&value)));
+        if let Some(comment) = &entry.comment {
+            fields.push(format!(
+                "  {}: {{\n    \"description\": {}\n  }}",
+                quote(&format!("@{}", entry.name())),
+                quote(comment)
+            ));
+        }
+    }
+    if fields.is_empty() {
+        return "{}\n".to_owned();
+    }
+    format!("{{\n{}\n}}\n", fields.join(",\n"))
+}
+
+/// Quote text as a Fluent string literal placeable
+fn fluent_literal(text: &str) -> String {
+    format!(
+        "{{\"{}\"}}",
+        text.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+/// The length of the printf-style (`%s`, `%1$d`, `%(name)s`), brace-style
+/// (`{0}`, `{name}`) or i18next (`{{name}}`, `$t(key)`) placeholder that
+/// `text` starts with, if it starts with one
+fn placeholder_len(text: &str) -> Option<usize> {
+    let mut rest = text;
+    // i18next interpolation, such as `{{name}}` or `{{price, currency}}`, and
+    // nesting of other messages, such as `$t(common.save)`
+    for (open, close) in [("{{", "}}"), ("$t(", ")")].iter() {
+        if let Some(inside) = rest.strip_prefix(open) {
+            let end = inside.find(close)?;
+            return Some(open.len() + end + close.len())
+                .filter(|_| !inside[..end].contains(['\n', '{']));
+        }
+    }
+    if let Some(inside) = rest.strip_prefix('{') {
+        let name = inside.find(|c: char| "{},".contains(c) || c.is_whitespace())?;
+        let after = &inside[name..];
+        // an ICU MessageFormat argument with a type, such as `{price, number}`
+        let after = match after.strip_prefix(',') {
+            Some(format) if name > 0 => &format[format.find(|c| "{}\n".contains(c))?..],
+            _ => after,
+        };
+        return after
+            .strip_prefix('}')
+            .map(|_| text.len() - after.len() + 1);
+    }
+    rest = rest.strip_prefix('%')?;
+    if rest.starts_with('%') {
+        return Some(2);
+    }
+    // a `.stringsdict` variable, such as `%#@files@` or `%1$#@files@`
+    let positional = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let variable = match rest[positional..].strip_prefix('$') {
+        Some(after) if positional > 0 => after,
+        _ => rest,
+    };
+    if let Some(name) = variable.strip_prefix("#@") {
+        let end = name.find('@')?;
+        return Some(text.len() - name.len() + end + 1);
+    }
+    if let Some(inside) = rest.strip_prefix('(') {
+        let end = inside.find(')')?;
+        if !inside[..end]
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_')
+        {
+            return None;
+        }
+        rest = &inside[end + 1..];
+    } else {
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits > 0 && rest[digits..].starts_with('$') {
+            rest = &rest[digits + 1..];
+        }
+    }
+    // a space flag is left out, since `50% off` isn't a placeholder
+    rest = rest.trim_start_matches(|c| "-+#0".contains(c));
+    rest = rest
+        .strip_prefix('*')
+        .unwrap_or_else(|| rest.trim_start_matches(|c: char| c.is_ascii_digit()));
+    if let Some(precision) = rest.strip_prefix('.') {
+        rest = precision
+            .strip_prefix('*')
+            .unwrap_or_else(|| precision.trim_start_matches(|c: char| c.is_ascii_digit()));
+    }
+    for length in ["hh", "ll", "h", "l", "L", "q", "j", "z", "t"].iter() {
+        if let Some(after) = rest.strip_prefix(length) {
+            rest = after;
+            break;
+        }
+    }
+    let conversion = rest
+        .chars()
+        .next()
+        .filter(|c| "diouxXeEfFgGaAcspn@".contains(*c))?;
+    Some(text.len() - rest.len() + conversion.len_utf8())
+}
+
+/// The ICU MessageFormat argument that `text` starts with, such as
+/// `{count, plural,`, and whether it's a plural rather than a select
+fn icu_argument_len(text: &str) -> Option<(usize, bool)> {
+    let spaces = |s: &'_ str| -> usize { s.len() - s.trim_start_matches([' ', '\t']).len() };
+    let mut rest = text.strip_prefix('{')?;
+    rest = &rest[spaces(rest)..];
+    let name = rest.find(|c: char| !(c.is_alphanumeric() || c == '_'))?;
+    if name == 0 {
+        return None;
+    }
+    rest = &rest[name..];
+    rest = rest[spaces(rest)..].strip_prefix(',')?;
+    rest = &rest[spaces(rest)..];
+    let kind = ["selectordinal", "plural", "select"]
+        .iter()
+        .find(|kind| rest.starts_with(**kind))?;
+    rest = &rest[kind.len()..];
+    rest = rest[spaces(rest)..].strip_prefix(',')?;
+    Some((text.len() - rest.len(), *kind != "select"))
+}
+
+/// The ICU MessageFormat variant that `text` starts with, such as `one {` or
+/// `=0{`
+fn icu_variant_len(text: &str) -> Option<usize> {
+    let key = text.strip_prefix('=').unwrap_or(text);
+    let len = key.find(|c: char| !(c.is_alphanumeric() || c == '_'))?;
+    if len == 0 {
+        return None;
+    }
+    let rest = key[len..].trim_start_matches([' ', '\t']);
+    rest.strip_prefix('{').map(|after| text.len() - after.len())
+}
+
+/// The spans of `text` to keep as they are while translating: placeholders,
+/// and the syntax of ICU MessageFormat plurals and selects, such as
+/// `{count, plural, one{`, `#` and `}`, leaving the text of their variants to
+/// translate. None of them span more than a line.
+fn kept_spans(text: &str) -> Vec<std::ops::Range<usize>> {
+    enum Inside {
+        /// between the variants of a plural (`true`) or select
+        Argument(bool),
+        Variant(bool),
+    }
+    let mut spans: Vec<(std::ops::Range<usize>, bool)> = Vec::new();
+    let mut inside: Vec<Inside> = Vec::new();
+    let mut i = 0;
+    while let Some(c) = text[i..].chars().next() {
+        let rest = &text[i..];
+        let (len, syntax) = match inside.last() {
+            Some(Inside::Argument(plural)) => {
+                let plural = *plural;
+                if c.is_whitespace() {
+                    i += c.len_utf8();
+                    continue;
+                }
+                if c == '}' {
+                    inside.pop();
+                    (1, true)
+                } else if let Some(len) = icu_variant_len(rest) {
+                    inside.push(Inside::Variant(plural));
+                    (len, true)
+                } else {
+                    // not MessageFormat after all
+                    inside.clear();
+                    continue;
+                }
+            }
+            variant => {
+                let in_plural = matches!(variant, Some(Inside::Variant(true)));
+                let in_variant = variant.is_some();
+                if let Some((len, plural)) = icu_argument_len(rest) {
+                    inside.push(Inside::Argument(plural));
+                    (len, true)
+                } else if c == '}' && in_variant {
+                    inside.pop();
+                    (1, true)
+                } else if c == '#' && in_plural {
+                    (1, false)
+                } else if let Some(len) = placeholder_len(rest) {
+                    (len, false)
+                } else {
+                    i += c.len_utf8();
+                    continue;
+                }
+            }
+        };
+        // syntax that's only separated by spaces is kept together
+        match spans.last_mut() {
+            Some((last, true))
+                if syntax && text[last.end..i].chars().all(|c| c == ' ' || c == '\t') =>
+            {
+                last.end = i + len
+            }
+            _ => spans.push((i..i + len, syntax)),
+        }
+        i += len;
+    }
+    spans.into_iter().map(|(span, _)| span).collect()
+}
+
+/// Write a line of text so that Fluent reads it back exactly: braces are
+/// escaped, the `kept` spans of the line are quoted whole so that they're
+/// kept as placeables while translating, and whitespace at either end (which
+/// Fluent would trim) and characters that are special at the start of a line
+/// are quoted
+/// The Fluent variable that an i18next interpolation such as `{{name}}` stands
+/// for, if its name is one that Fluent allows
+fn i18next_variable(placeholder: &str) -> Option<String> {
+    let name = placeholder.strip_prefix("{{")?.strip_suffix("}}")?.trim();
+    let mut chars = name.chars();
+    let valid = chars.next()?.is_ascii_alphabetic()
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    Some(format!("{{ ${} }}", name)).filter(|_| valid)
+}
+
+fn fluent_line(line: &str, first: bool, kept: &[std::ops::Range<usize>]) -> String {
+    let trimmed_start = line.trim_start();
+    let leading = &line[..line.len() - trimmed_start.len()];
+    let trimmed = trimmed_start.trim_end();
+    let trailing = &trimmed_start[trimmed.len()..];
+
+    let mut out = String::new();
+    if !leading.is_empty() {
+        out.push_str(&fluent_literal(leading));
+    }
+    let mut i = 0;
+    while let Some(c) = trimmed[i..].chars().next() {
+        if let Some(span) = kept.iter().find(|span| span.start == leading.len() + i) {
+            let end = (span.end - leading.len()).min(trimmed.len());
+            let kept = &trimmed[i..end];
+            out.push_str(&i18next_variable(kept).unwrap_or_else(|| fluent_literal(kept)));
+            i = end;
+            continue;
+        }
+        match c {
+            '{' | '}' => out.push_str(&fluent_literal(&c.to_string())),
+            '[' | '*' | '.' if i == 0 && leading.is_empty() && !first => {
+                out.push_str(&fluent_literal(&c.to_string()))
+            }
+            c => out.push(c),
+        }
+        i += c.len_utf8();
+    }
+    if !trailing.is_empty() {
+        out.push_str(&fluent_literal(trailing));
+    }
+    out
+}
+
+/// Write text as a Fluent pattern, either on the same line as whatever
+/// precedes it or on lines of its own with the given indent
+fn fluent_pattern(text: &str, indent: &str) -> String {
+    let kept = kept_spans(text);
+    if text.is_empty() {
+        format!(" {}\n", fluent_literal(""))
+    } else if text.contains('\n') {
+        let mut out = "\n".to_owned();
+        let mut start = 0;
+        for line in text.split('\n') {
+            if line.is_empty() {
+                // a blank line would be dropped at the end of the pattern
+                out.push_str(&format!("{}{}\n", indent, fluent_literal("")));
+            } else {
+                let kept: Vec<std::ops::Range<usize>> = kept
+                    .iter()
+                    .filter(|span| span.start >= start && span.end <= start + line.len())
+                    .map(|span| span.start - start..span.end - start)
+                    .collect();
+                out.push_str(&format!("{}{}\n", indent, fluent_line(line, false, &kept)));
+            }
+            start += line.len() + 1;
+        }
+        out
+    } else {
+        format!(" {}\n", fluent_line(text, true, &kept))
+    }
+}
+
+/// Write plain text as a Fluent message, escaping anything that Fluent would
+/// otherwise read as syntax
+pub fn fluent_message(id: &str, text: &str) -> String {
+    format!("{} ={}", id, fluent_pattern(text, "    "))
+}
+
+/// Write imported entries as Fluent messages with the given ids. Plurals are
+/// written as a select on `$count`, with `other` as the default.
+pub fn to_fluent(entries: &[Entry], ids: &[String]) -> String {
+    let mut out = String::new();
+    for (entry, id) in entries.iter().zip(ids.iter()) {
+        if let Some(comment) = &entry.comment {
+            for line in comment.lines() {
+                out.push_str(&format!("# {}\n", line).replace("# \n", "#\n"));
+            }
+        }
+        match &entry.value {
+            Value::Text(text) => out.push_str(&fluent_message(id, text)),
+            Value::Plural(forms) => {
+                let default = forms
+                    .iter()
+                    .position(|(category, _)| category == "other")
+                    .unwrap_or(forms.len().saturating_sub(1));
+                out.push_str(&format!("{} =\n    {{ $count ->\n", id));
+                for (i, (category, text)) in forms.iter().enumerate() {
+                    out.push_str(&format!(
+                        "       {}[{}]{}",
+                        if i == default { "*" } else { " " },
+                        category,
+                        fluent_pattern(text, "            ")
+                    ));
+                }
+                out.push_str("    }\n");
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// The text of a pattern, with string literals unquoted, variables written as
+/// i18next interpolations (`{{name}}`) and any other placeables kept as Fluent
+fn pattern_text(pattern: &fluent_syntax::ast::Pattern) -> String {
+    let mut text = String::new();
+    for element in pattern.elements.iter() {
+        match element {
+            fluent_syntax::ast::PatternElement::TextElement(s) => text.push_str(s),
+            fluent_syntax::ast::PatternElement::Placeable(
+                fluent_syntax::ast::Expression::InlineExpression(
+                    fluent_syntax::ast::InlineExpression::StringLiteral { value },
+                ),
+            ) => text.push_str(&value.replace("\\\"", "\"").replace("\\\\", "\\")),
+            fluent_syntax::ast::PatternElement::Placeable(
+                fluent_syntax::ast::Expression::InlineExpression(
+                    fluent_syntax::ast::InlineExpression::VariableReference { id },
+                ),
+            ) => text.push_str(&format!("{{{{{}}}}}", id.name)),
+            fluent_syntax::ast::PatternElement::Placeable(expression) => {
+                let mut written: Vec<u8> = Vec::new();
+                super::writer::write_expression(&mut written, expression)
+                    .expect("can write expression to memory");
+                text.push_str(&String::from_utf8(written).expect("valid utf-8"));
+            }
+        }
+    }
+    text
+}
+
+/// The forms of a pattern that is nothing but a select on a variable by plural
+/// category, such as `{ $count -> [one] … *[other] … }`
+fn plural_forms(pattern: &fluent_syntax::ast::Pattern) -> Option<Vec<(String, String)>> {
+    let variants = match pattern.elements.as_slice() {
+        [fluent_syntax::ast::PatternElement::Placeable(
+            fluent_syntax::ast::Expression::SelectExpression {
+                selector: fluent_syntax::ast::InlineExpression::VariableReference { .. },
+                variants,
+            },
+        )] => variants,
+        _ => return None,
+    };
+    variants
+        .iter()
+        .map(|variant| match variant.key {
+            fluent_syntax::ast::VariantKey::Identifier { name } => {
+                Some((name.to_owned(), pattern_text(&variant.value)))
+            }
+            fluent_syntax::ast::VariantKey::NumberLiteral { .. } => None,
+        })
+        .collect()
+}
+
+/// Collect the messages in a Fluent file for exporting, restoring the keys
+/// that were changed when they were imported
+pub fn from_fluent(resource: &fluent_syntax::ast::Resource, ids: &IdMap) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for entry in resource.body.iter() {
+        if let fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Message(m)) =
+            entry
+        {
+            let value = match &m.value {
+                Some(value) => plural_forms(value)
+                    .map(Value::Plural)
+                    .unwrap_or_else(|| Value::Text(pattern_text(value))),
+                None => {
+                    log::warn!("skipping `{}`, which has no value", m.id.name);
+                    continue;
+                }
+            };
+            if !m.attributes.is_empty() {
+                log::warn!("the attributes of `{}` aren't exported", m.id.name);
+            }
+            let comment = match &m.comment {
+                Some(fluent_syntax::ast::Comment::Comment { content }) => Some(content.join("\n")),
+                _ => None,
+            };
+            entries.push(Entry {
+                key: ids.original(m.id.name).to_owned(),
+                value,
+                comment,
+            });
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: &str) -> Value {
+        Value::Text(value.to_owned())
+    }
+
+    fn round_trip(format: Format, text: &str) -> Vec<Entry> {
+        let entries = parse(format, Path::new("test"), text, &IdMap::default()).unwrap();
+        let (flt, map) = write(Format::Fluent, &entries, &IdMap::default());
+        let map = map.unwrap();
+        let exported = parse(Format::Fluent, Path::new("test.flt"), &flt, &map).unwrap();
+        assert_eq!(exported, entries, "through:\n{}", flt);
+        let (rendered, _) = write(format, &exported, &map);
+        assert_eq!(
+            parse(format, Path::new("test"), &rendered, &map).unwrap(),
+            entries
+        );
+        entries
+    }
+
+    #[test]
+    fn round_trips_json() {
+        let entries = round_trip(
+            Format::Json,
+            r#"{"home": {"title": "Welcome, {name}!", "a/b": " padded "}, "count": 3, "z": "multi\n\nline"}"#,
+        );
+        assert_eq!(entries[0].key, "/home/title");
+        assert_eq!(entries[1].key, "/home/a~1b");
+        round_trip(
+            Format::Json,
+            r#"{"home.title": "Hi", "[x]": "*star\n.dot"}"#,
+        );
+        let entries = round_trip(
+            Format::Json,
+            r#"{"cart": {"item_one": "{{count}} item", "item_other": "{{count}} items", "save_as": "$t(common.save) as"}}"#,
+        );
+        assert_eq!(entries[0].key, "/cart/item");
+        assert_eq!(
+            entries[0].value,
+            Value::Plural(vec![
+                ("one".to_owned(), "{{count}} item".to_owned()),
+                ("other".to_owned(), "{{count}} items".to_owned()),
+            ])
+        );
+        assert_eq!(entries[1].key, "/cart/save_as");
+        let (flt, _) = write(Format::Fluent, &entries, &IdMap::default());
+        assert!(flt.contains("[one] { $count } item"), "{}", flt);
+        assert!(flt.contains("{\"$t(common.save)\"} as"), "{}", flt);
+
+        // interpolations that aren't plain names stay as they're written
+        let entries = round_trip(
+            Format::Json,
+            r#"{"total": "{{price, currency}} for {{ user.name }}, {{n}}"}"#,
+        );
+        let (flt, _) = write(Format::Fluent, &entries, &IdMap::default());
+        assert!(
+            flt.contains("total = {\"{{price, currency}}\"} for {\"{{ user.name }}\"}, { $n }"),
+            "{}",
+            flt
+        );
+    }
+
+    #[test]
+    fn round_trips_po() {
+        let entries = round_trip(
+            Format::Po,
+            "msgid \"\"\nmsgstr \"\"\n\"Language: fr\\n\"\n\n#. shown on the button\n#: src/app.c:12\nmsgctxt \"menu\"\nmsgid \"Open\"\nmsgstr \"Ouvrir\"\n\nmsgid \"\"\n\"Two\\n\"\n\"lines\"\nmsgstr \"\"\n\nmsgid \"%d file\"\nmsgid_plural \"%d files\"\nmsgstr[0] \"%d fichier\"\nmsgstr[1] \"%d fichiers\"\n",
+        );
+        assert_eq!(entries[0].key, "menu\u{4}Open");
+        assert_eq!(entries[0].value, text("Ouvrir"));
+        assert_eq!(entries[0].comment.as_deref(), Some("shown on the button"));
+        assert_eq!(entries[1].value, text("Two\nlines"));
+        assert_eq!(entries[2].key, "%d file\u{0}%d files");
+        assert_eq!(
+            entries[2].value,
+            Value::Plural(vec![
+                ("one".to_owned(), "%d fichier".to_owned()),
+                ("other".to_owned(), "%d fichiers".to_owned()),
+            ])
+        );
+    }
+
+    #[test]
+    fn round_trips_strings() {
+        let entries = round_trip(
+            Format::Strings,
+            "/* Greeting on the home screen */\n\"greeting\" = \"Hello, %@!\\nWelcome \\\"back\\\"\";\n// a line comment\nok_button = \"OK\";\n\"Cancel\";\n\"snow\" = \"\\U2603\";\n",
+        );
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].value, text("Hello, %@!\nWelcome \"back\""));
+        assert_eq!(
+            entries[0].comment.as_deref(),
+            Some("Greeting on the home screen")
+        );
+        assert_eq!(entries[1].key, "ok_button");
+        assert_eq!(entries[2].value, text("Cancel"));
+        assert_eq!(entries[3].value, text("\u{2603}"));
+        assert_eq!(placeholder_len("%1$#@files@ left"), Some(11));
+    }
+
+    #[test]
+    fn round_trips_stringsdict() {
+        let entries = round_trip(
+            Format::Stringsdict,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <!-- files left to upload -->
+    <key>files</key>
+    <dict>
+        <key>NSStringLocalizedFormatKey</key>
+        <string>%#@files@ left</string>
+        <key>files</key>
+        <dict>
+            <key>NSStringFormatSpecTypeKey</key>
+            <string>NSStringPluralRuleType</string>
+            <key>NSStringFormatValueTypeKey</key>
+            <string>ld</string>
+            <key>one</key>
+            <string>%ld file</string>
+            <key>other</key>
+            <string>%ld files &amp; more</string>
+        </dict>
+    </dict>
+</dict>
+</plist>
+"#,
+        );
+        assert_eq!(entries[0].comment.as_deref(), Some("files left to upload"));
+        assert_eq!(
+            entries[0].value,
+            Value::Plural(vec![
+                ("one".to_owned(), "%ld file left".to_owned()),
+                ("other".to_owned(), "%ld files & more left".to_owned()),
+            ])
+        );
+        let (rendered, _) = write(Format::Stringsdict, &entries, &IdMap::default());
+        assert!(rendered.contains("<string>ld</string>"), "{}", rendered);
+    }
+
+    #[test]
+    fn quotes_placeholders() {
+        assert_eq!(placeholder_len("%s left"), Some(2));
+        assert_eq!(placeholder_len("%1$-5.2f"), Some(8));
+        assert_eq!(placeholder_len("%(count)d files"), Some(9));
+        assert_eq!(placeholder_len("%% off"), Some(2));
+        assert_eq!(placeholder_len("% off"), None);
+        assert_eq!(placeholder_len("{name}!"), Some(6));
+        assert_eq!(placeholder_len("{ $name }"), None);
+        assert_eq!(placeholder_len("{price, number} each"), Some(15));
+        assert_eq!(placeholder_len("{count, plural, one{x}}"), None);
+        assert_eq!(placeholder_len("{{price, currency}} off"), Some(19));
+        assert_eq!(placeholder_len("$t(common.save)!"), Some(15));
+        assert_eq!(
+            fluent_message("files", "%d files in {dir}"),
+            "files = {\"%d\"} files in {\"{dir}\"}\n"
+        );
+    }
+
+    #[test]
+    fn keeps_icu_syntax() {
+        let text = "{count, plural, =0{No items} one{# item} other{{count} items}}";
+        let kept: Vec<&str> = kept_spans(text)
+            .into_iter()
+            .map(|span| &text[span])
+            .collect();
+        assert_eq!(
+            kept,
+            vec![
+                "{count, plural, =0{",
+                "} one{",
+                "#",
+                "} other{",
+                "{count}",
+                "}}"
+            ]
+        );
+        assert_eq!(
+            fluent_message("items", text),
+            "items = {\"{count, plural, =0{\"}No items{\"} one{\"}{\"#\"} item{\"} other{\"}{\"{count}\"} items{\"}}\"}\n"
+        );
+        assert_eq!(kept_spans("# of {count}"), vec![5..12]);
+    }
+
+    #[test]
+    fn round_trips_arb() {
+        let entries = round_trip(
+            Format::Arb,
+            r#"{
+  "@@locale": "en",
+  "items": "{count, plural, =0{No items} one{# item} other{{count} items}}",
+  "@items": {
+    "description": "how many items are in the cart",
+    "placeholders": {"count": {"type": "int"}}
+  },
+  "title": "Cart"
+}
+"#,
+        );
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].comment.as_deref(),
+            Some("how many items are in the cart")
+        );
+        assert_eq!(entries[1].comment, None);
+        let plural = Entry {
+            key: "n".to_owned(),
+            value: Value::Plural(vec![
+                ("zero".to_owned(), "none".to_owned()),
+                ("other".to_owned(), "{count} songs".to_owned()),
+            ]),
+            comment: None,
+        };
+        assert_eq!(
+            render_arb(&[plural], Some("fr")),
+            "{\n  \"@@locale\": \"fr\",\n  \"n\": \"{count, plural, =0{none} other{{count} songs}}\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn round_trips_properties() {
+        let entries = round_trip(
+            Format::Properties,
+            "# Licensed under the MIT license\n\n! greeting\n# on the home screen\nhome.title = Hello, {0}!\nlong\\ key:a \\\n    continued\\n\\\\\ncaf\\u00E9=Caf\\u00e9 \\uD83D\\uDE00\nempty\n",
+        );
+        assert_eq!(entries.len(), 4);
+        assert_eq!(
+            entries[0].comment.as_deref(),
+            Some("greeting\non the home screen")
+        );
+        assert_eq!(entries[0].value, text("Hello, {0}!"));
+        assert_eq!(entries[1].key, "long key");
+        assert_eq!(entries[1].value, text("a continued\n\\"));
+        assert_eq!(entries[2].key, "café");
+        assert_eq!(entries[2].value, text("Café 😀"));
+        assert_eq!(entries[3].value, text(""));
+        let (rendered, _) = write(Format::Properties, &entries, &IdMap::default());
+        assert!(rendered.is_ascii(), "{}", rendered);
+        assert!(rendered.contains("caf\\u00E9 = Caf\\u00E9 \\uD83D\\uDE00\n"));
+    }
+
+    #[test]
+    fn round_trips_android() {
+        let entries = round_trip(
+            Format::Android,
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<resources>\n    <!-- greeting -->\n    <string name=\"app_name\">Tom\\'s <b>app</b> &amp; more</string>\n    <string name=\"key\" translatable=\"false\">x</string>\n    <plurals name=\"n\"><item quantity=\"one\">1 song</item><item quantity=\"other\">%d\nsongs</item></plurals>\n    <string name=\"empty\"/>\n</resources>\n",
+        );
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].value, text("Tom's <b>app</b> &amp; more"));
+        assert_eq!(entries[0].comment.as_deref(), Some("greeting"));
+        assert_eq!(
+            entries[1].value,
+            Value::Plural(vec![
+                ("one".to_owned(), "1 song".to_owned()),
+                ("other".to_owned(), "%d\nsongs".to_owned()),
+            ])
+        );
+    }
+}
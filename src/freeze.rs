@@ -0,0 +1,164 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::error::Error;
+use std::path::Path;
+
+/// Periods during which shipping locales must not be modified
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct FreezeConfig {
+    /// the shipping locales that are frozen; empty means every locale
+    pub locales: Vec<String>,
+    pub windows: Vec<FreezeWindow>,
+    /// freeze whenever this file exists in the working or output directory
+    pub marker_file: String,
+}
+
+impl Default for FreezeConfig {
+    fn default() -> FreezeConfig {
+        FreezeConfig {
+            locales: Vec::new(),
+            windows: Vec::new(),
+            marker_file: "FROZEN".to_owned(),
+        }
+    }
+}
+
+/// An inclusive range of dates, written as `YYYY-MM-DD`
+#[derive(Deserialize, Debug)]
+pub struct FreezeWindow {
+    pub start: String,
+    pub end: String,
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, Box<dyn Error>> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|e| Box::from(format!("invalid freeze window date `{}`: {}", date, e)))
+}
+
+impl FreezeConfig {
+    /// Check whether `locale` is frozen on `today`, returning the reason it is
+    pub fn frozen(
+        &self,
+        locale: &str,
+        today: NaiveDate,
+        out_dir: &Path,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        if !self.locales.is_empty() && !self.locales.iter().any(|l| l == locale) {
+            return Ok(None);
+        }
+
+        for window in self.windows.iter() {
+            let start = parse_date(&window.start)?;
+            let end = parse_date(&window.end)?;
+            if start <= today && today <= end {
+                return Ok(Some(format!(
+                    "freeze window {} to {}",
+                    window.start, window.end
+                )));
+            }
+        }
+
+        if !self.marker_file.is_empty() {
+            for marker in [
+                Path::new(&self.marker_file),
+                &out_dir.join(&self.marker_file),
+            ]
+            .iter()
+            {
+                if marker.exists() {
+                    return Ok(Some(format!("marker file {}", marker.display())));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> NaiveDate {
+        parse_date(text).unwrap()
+    }
+
+    fn windows(windows: &[(&str, &str)]) -> FreezeConfig {
+        FreezeConfig {
+            locales: Vec::new(),
+            windows: windows
+                .iter()
+                .map(|(start, end)| FreezeWindow {
+                    start: (*start).to_owned(),
+                    end: (*end).to_owned(),
+                })
+                .collect(),
+            marker_file: String::new(),
+        }
+    }
+
+    #[test]
+    fn freezes_inside_windows() {
+        let config = windows(&[("2020-06-01", "2020-06-14")]);
+        let frozen = |today| config.frozen("fr", date(today), Path::new(".")).unwrap();
+        assert_eq!(
+            frozen("2020-06-07"),
+            Some("freeze window 2020-06-01 to 2020-06-14".to_owned())
+        );
+        // both ends are included
+        assert!(frozen("2020-06-01").is_some());
+        assert!(frozen("2020-06-14").is_some());
+        assert_eq!(frozen("2020-05-31"), None);
+        assert_eq!(frozen("2020-06-15"), None);
+        assert_eq!(frozen("2021-06-07"), None);
+    }
+
+    #[test]
+    fn freezes_across_the_end_of_the_year() {
+        let config = windows(&[("2020-12-20", "2021-01-05"), ("2021-02-28", "2021-03-01")]);
+        let frozen = |today| config.frozen("fr", date(today), Path::new(".")).unwrap();
+        assert!(frozen("2020-12-31").is_some());
+        assert!(frozen("2021-01-01").is_some());
+        assert_eq!(frozen("2020-12-19"), None);
+        assert_eq!(frozen("2021-01-06"), None);
+        assert!(frozen("2021-02-28").is_some());
+        assert!(frozen("2021-03-01").is_some());
+    }
+
+    #[test]
+    fn only_freezes_the_listed_locales() {
+        let mut config = windows(&[("2020-06-01", "2020-06-14")]);
+        config.locales = vec!["de".to_owned()];
+        let today = date("2020-06-07");
+        assert!(config
+            .frozen("de", today, Path::new("."))
+            .unwrap()
+            .is_some());
+        assert_eq!(config.frozen("fr", today, Path::new(".")).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_invalid_dates() {
+        let config = windows(&[("2020-06-01", "2020-13-01")]);
+        let error = config
+            .frozen("fr", date("2020-06-07"), Path::new("."))
+            .unwrap_err();
+        assert!(error.to_string().contains("`2020-13-01`"), "{}", error);
+        assert!(parse_date("June 1st").is_err());
+    }
+}
@@ -0,0 +1,570 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::memory::Key;
+use super::translate::MimeType;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+/// The scope that signing in needs for the bucket, as well as translating
+pub const SCOPE: &str = "https://www.googleapis.com/auth/devstorage.read_write";
+
+const STORAGE: &str = "https://storage.googleapis.com";
+
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct GcsBatchConfig {
+    /// the bucket to send texts through with `batchTranslateText`, which
+    /// turns batch mode on
+    pub bucket: Option<String>,
+    /// the folder in the bucket that each run's files go in
+    pub prefix: String,
+    /// how many seconds to wait between checks on the operation
+    pub poll_interval_secs: u64,
+    /// how many seconds to wait for the operation before giving up on it
+    pub max_wait_secs: u64,
+}
+
+impl Default for GcsBatchConfig {
+    fn default() -> GcsBatchConfig {
+        GcsBatchConfig {
+            bucket: None,
+            prefix: "tt-batch".to_owned(),
+            poll_interval_secs: 10,
+            max_wait_secs: 3600,
+        }
+    }
+}
+
+/// A text that a locale needs translated, gathered from every locale before
+/// any of them are translated
+#[derive(Debug)]
+pub struct Wanted {
+    /// the token and project to translate with
+    pub credentials: (String, String),
+    pub language: String,
+    /// the model's resource name, if it isn't the default
+    pub model: Option<String>,
+    /// the glossary's resource name, and whether it ignores case
+    pub glossary: Option<(String, bool)>,
+    pub mime_type: MimeType,
+    pub text: String,
+    /// where the translation is remembered
+    pub key: Key,
+}
+
+/// Whether `text` can be sent in a TSV file, which can't have tabs or line
+/// breaks in its cells
+pub fn sendable(text: &str) -> bool {
+    !text.contains(&['\t', '\n', '\r'][..])
+}
+
+/// The texts that a single `batchTranslateText` operation translates into
+/// every one of its languages
+#[derive(Debug, PartialEq)]
+struct Operation {
+    credentials: (String, String),
+    model: Option<String>,
+    glossary: Option<(String, bool)>,
+    languages: BTreeSet<String>,
+    texts: BTreeMap<MimeType, Vec<String>>,
+}
+
+/// Put the wanted texts into as few operations as possible, without sending
+/// any text to a language that didn't ask for it, since every text in an
+/// operation is translated into each of its languages
+fn operations(wanted: &[Wanted]) -> Vec<Operation> {
+    type Setup = ((String, String), Option<String>, Option<(String, bool)>);
+    let mut languages: BTreeMap<(Setup, MimeType, &str), BTreeSet<String>> = BTreeMap::new();
+    for w in wanted.iter() {
+        let setup = (w.credentials.clone(), w.model.clone(), w.glossary.clone());
+        languages
+            .entry((setup, w.mime_type, w.text.as_str()))
+            .or_default()
+            .insert(w.language.clone());
+    }
+    let mut grouped: BTreeMap<(Setup, BTreeSet<String>), BTreeMap<MimeType, Vec<String>>> =
+        BTreeMap::new();
+    for ((setup, mime_type, text), languages) in languages.into_iter() {
+        grouped
+            .entry((setup, languages))
+            .or_default()
+            .entry(mime_type)
+            .or_default()
+            .push(text.to_owned());
+    }
+    grouped
+        .into_iter()
+        .map(
+            |(((credentials, model, glossary), languages), texts)| Operation {
+                credentials,
+                model,
+                glossary,
+                languages,
+                texts,
+            },
+        )
+        .collect()
+}
+
+/// The input file of `texts`, with each one's index as its id
+fn tsv(texts: &[String]) -> String {
+    texts
+        .iter()
+        .enumerate()
+        .map(|(i, text)| format!("{}\t{}\n", i, text))
+        .collect()
+}
+
+/// The translation of each text's index in an output file, whose rows are
+/// the id, the source text and its translation
+fn parse_tsv(tsv: &str) -> Vec<(usize, &str)> {
+    tsv.lines()
+        .filter_map(|line| {
+            let mut cells = line.splitn(3, '\t');
+            let id = cells.next()?.trim().parse().ok()?;
+            let _source = cells.next()?;
+            Some((id, cells.next()?))
+        })
+        .collect()
+}
+
+fn mime_name(mime_type: MimeType) -> &'static str {
+    match mime_type {
+        MimeType::Text => "plain",
+        MimeType::Html => "html",
+    }
+}
+
+/// Percent-encode an object's name to use it in a URL's path
+fn encode(name: &str) -> String {
+    name.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GcsSource {
+    input_uri: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InputConfig {
+    gcs_source: GcsSource,
+    mime_type: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GcsDestination {
+    output_uri_prefix: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OutputConfig {
+    gcs_destination: GcsDestination,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GlossaryConfig<'a> {
+    glossary: &'a str,
+    ignore_case: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BatchQuery<'a> {
+    source_language_code: String,
+    target_language_codes: Vec<&'a str>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    models: BTreeMap<&'a str, &'a str>,
+    input_configs: Vec<InputConfig>,
+    output_config: OutputConfig,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    glossaries: BTreeMap<&'a str, GlossaryConfig<'a>>,
+}
+
+#[derive(Deserialize)]
+struct Status {
+    message: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LongRunning {
+    name: String,
+    #[serde(default)]
+    done: bool,
+    error: Option<Status>,
+}
+
+#[derive(Deserialize)]
+struct Object {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Objects {
+    #[serde(default)]
+    items: Vec<Object>,
+    next_page_token: Option<String>,
+}
+
+/// Sends and fetches the files of a batch through a bucket
+struct Bucket<'a> {
+    client: Client,
+    token: &'a str,
+    name: &'a str,
+    retry: &'a super::retry::Retry,
+}
+
+impl<'a> Bucket<'a> {
+    fn send(&self, request: reqwest::blocking::RequestBuilder) -> Result<String, Box<dyn Error>> {
+        let (status, body) =
+            self.retry
+                .send(&self.client, "google", request.bearer_auth(self.token))?;
+        if !status.is_success() {
+            return Err(Box::from(format!(
+                "request failed with {}: {}",
+                status, body
+            )));
+        }
+        Ok(body)
+    }
+
+    fn upload(&self, object: &str, content: String) -> Result<(), Box<dyn Error>> {
+        self.send(
+            self.client
+                .post(&format!("{}/upload/storage/v1/b/{}/o", STORAGE, self.name))
+                .query(&[("uploadType", "media"), ("name", object)])
+                .header(reqwest::header::CONTENT_TYPE, "text/tab-separated-values")
+                .body(content),
+        )?;
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut names = Vec::new();
+        let mut page: Option<String> = None;
+        loop {
+            let mut query = vec![("prefix", prefix.to_owned())];
+            if let Some(page) = page.take() {
+                query.push(("pageToken", page));
+            }
+            let objects: Objects = serde_json::from_str(
+                &self.send(
+                    self.client
+                        .get(&format!("{}/storage/v1/b/{}/o", STORAGE, self.name))
+                        .query(&query),
+                )?,
+            )?;
+            names.extend(objects.items.into_iter().map(|object| object.name));
+            match objects.next_page_token {
+                Some(next) => page = Some(next),
+                None => return Ok(names),
+            }
+        }
+    }
+
+    fn download(&self, object: &str) -> Result<String, Box<dyn Error>> {
+        self.send(
+            self.client
+                .get(&format!(
+                    "{}/storage/v1/b/{}/o/{}",
+                    STORAGE,
+                    self.name,
+                    encode(object)
+                ))
+                .query(&[("alt", "media")]),
+        )
+    }
+
+    fn delete(&self, object: &str) {
+        let deleted = self.send(self.client.delete(&format!(
+            "{}/storage/v1/b/{}/o/{}",
+            STORAGE,
+            self.name,
+            encode(object)
+        )));
+        if let Err(e) = deleted {
+            log::warn!("failed to delete gs://{}/{}: {}", self.name, object, e);
+        }
+    }
+}
+
+/// The translations of each input file into each language, by the index of
+/// each text in the file
+type Translations = HashMap<(MimeType, String), Vec<(usize, String)>>;
+
+/// Start `operation` on the files that were uploaded for it, wait for it to
+/// finish and download its translations, by the index of each text in its
+/// input file
+fn run(
+    config: &GcsBatchConfig,
+    connection: &super::backend::Connection,
+    bucket: &Bucket,
+    folder: &str,
+    operation: &Operation,
+) -> Result<Translations, Box<dyn Error>> {
+    let (_, project_id) = &operation.credentials;
+    let location = &connection.endpoints.location;
+    let mut input_configs = Vec::new();
+    for (mime_type, texts) in operation.texts.iter() {
+        let object = format!("{}/{}.tsv", folder, mime_name(*mime_type));
+        bucket.upload(&object, tsv(texts))?;
+        input_configs.push(InputConfig {
+            gcs_source: GcsSource {
+                input_uri: format!("gs://{}/{}", bucket.name, object),
+            },
+            mime_type: mime_type.as_str(),
+        });
+    }
+    let output = format!("{}/output/", folder);
+    let languages: Vec<&str> = operation.languages.iter().map(String::as_str).collect();
+    let query = BatchQuery {
+        source_language_code: connection.source_language.clone(),
+        target_language_codes: languages.clone(),
+        models: match &operation.model {
+            Some(model) => languages.iter().map(|l| (*l, model.as_str())).collect(),
+            None => BTreeMap::new(),
+        },
+        input_configs,
+        output_config: OutputConfig {
+            gcs_destination: GcsDestination {
+                output_uri_prefix: format!("gs://{}/{}", bucket.name, output),
+            },
+        },
+        glossaries: match &operation.glossary {
+            Some((glossary, ignore_case)) => languages
+                .iter()
+                .map(|l| {
+                    (
+                        *l,
+                        GlossaryConfig {
+                            glossary,
+                            ignore_case: *ignore_case,
+                        },
+                    )
+                })
+                .collect(),
+            None => BTreeMap::new(),
+        },
+    };
+    let started: LongRunning = serde_json::from_str(
+        &bucket.send(
+            bucket
+                .client
+                .post(&format!(
+                    "{}/v3/projects/{}/locations/{}:batchTranslateText",
+                    connection.endpoints.api, project_id, location
+                ))
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(serde_json::to_string(&query)?),
+        )?,
+    )?;
+    log::info!(
+        "started {} into {} for {} text(s)",
+        started.name,
+        languages.join(", "),
+        operation.texts.values().map(Vec::len).sum::<usize>()
+    );
+
+    let start = Instant::now();
+    let mut status = started;
+    while !status.done {
+        if start.elapsed() > Duration::from_secs(config.max_wait_secs) {
+            return Err(Box::from(format!(
+                "{} didn't finish within {}s",
+                status.name, config.max_wait_secs
+            )));
+        }
+        std::thread::sleep(Duration::from_secs(config.poll_interval_secs));
+        status = serde_json::from_str(
+            &bucket.send(
+                bucket
+                    .client
+                    .get(&format!("{}/v3/{}", connection.endpoints.api, status.name)),
+            )?,
+        )?;
+    }
+    if let Some(error) = status.error {
+        return Err(Box::from(format!(
+            "{} failed: {}",
+            status.name,
+            error.message.unwrap_or_default()
+        )));
+    }
+
+    let outputs = bucket.list(&output)?;
+    let mut translations = HashMap::new();
+    for (mime_type, _) in operation.texts.iter() {
+        for language in operation.languages.iter() {
+            let stem = format!("_{}_{}_", mime_name(*mime_type), language);
+            // glossary translations are written alongside the others
+            let wanted = if operation.glossary.is_some() {
+                "glossary_translations.tsv"
+            } else {
+                "translations.tsv"
+            };
+            let file = outputs
+                .iter()
+                .find(|name| name.ends_with(&format!("{}{}", stem, wanted)));
+            match file {
+                Some(file) => {
+                    let tsv = bucket.download(file)?;
+                    let rows = parse_tsv(&tsv)
+                        .into_iter()
+                        .map(|(i, t)| (i, t.to_owned()))
+                        .collect();
+                    translations.insert((*mime_type, language.clone()), rows);
+                }
+                None => log::warn!(
+                    "{} didn't write {} translations into {}",
+                    status.name,
+                    mime_name(*mime_type),
+                    language
+                ),
+            }
+        }
+    }
+    for object in outputs.iter() {
+        bucket.delete(object);
+    }
+    for mime_type in operation.texts.keys() {
+        bucket.delete(&format!("{}/{}.tsv", folder, mime_name(*mime_type)));
+    }
+    Ok(translations)
+}
+
+/// Translate every wanted text with `batchTranslateText` through `bucket`,
+/// returning the translation for each text's key. Texts whose operation
+/// fails are left out, to be translated one locale at a time instead.
+pub fn translate(
+    config: &GcsBatchConfig,
+    connection: &super::backend::Connection,
+    bucket: &str,
+    wanted: &[Wanted],
+) -> Result<Vec<(Key, String)>, Box<dyn Error>> {
+    let client = connection.http.client()?;
+    let run_id = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|now| now.as_millis())
+        .unwrap_or_default();
+    let mut translated = Vec::new();
+    for (i, operation) in operations(wanted).iter().enumerate() {
+        let bucket = Bucket {
+            client: client.clone(),
+            token: &operation.credentials.0,
+            name: bucket,
+            retry: &connection.retry,
+        };
+        let folder = format!("{}/{}-{}", config.prefix.trim_matches('/'), run_id, i);
+        let translations = match run(config, connection, &bucket, &folder, operation) {
+            Ok(translations) => translations,
+            Err(e) => {
+                log::error!("batch translation failed, translating one by one: {}", e);
+                continue;
+            }
+        };
+        for w in wanted.iter().filter(|w| {
+            w.credentials == operation.credentials
+                && w.model == operation.model
+                && w.glossary == operation.glossary
+                && operation.languages.contains(&w.language)
+        }) {
+            let texts = match operation.texts.get(&w.mime_type) {
+                Some(texts) => texts,
+                None => continue,
+            };
+            let index = match texts.iter().position(|text| *text == w.text) {
+                Some(index) => index,
+                None => continue,
+            };
+            let rows = match translations.get(&(w.mime_type, w.language.clone())) {
+                Some(rows) => rows,
+                None => continue,
+            };
+            if let Some((_, translation)) = rows.iter().find(|(i, _)| *i == index) {
+                match super::translate::finish(&w.text, translation, w.mime_type) {
+                    Ok(translation) => translated.push((w.key.clone(), translation)),
+                    Err(e) => log::warn!("failed to decode `{}`: {}", translation, e),
+                }
+            }
+        }
+    }
+    Ok(translated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wanted(language: &str, text: &str) -> Wanted {
+        Wanted {
+            credentials: ("token".to_owned(), "proj".to_owned()),
+            language: language.to_owned(),
+            model: None,
+            glossary: None,
+            mime_type: MimeType::Text,
+            text: text.to_owned(),
+            key: Key::new(text, MimeType::Text, "en", language, "google", None),
+        }
+    }
+
+    #[test]
+    fn groups_operations() {
+        let ops = operations(&[
+            wanted("fr", "Hello"),
+            wanted("de", "Hello"),
+            wanted("fr", "Bye"),
+            wanted("de", "Bye"),
+            wanted("de", "Only German"),
+            wanted("fr", "Hello"),
+        ]);
+        assert_eq!(ops.len(), 2);
+        assert_eq!(ops[0].languages.iter().collect::<Vec<_>>(), vec!["de"]);
+        assert_eq!(ops[0].texts[&MimeType::Text], vec!["Only German"]);
+        assert_eq!(ops[1].texts[&MimeType::Text], vec!["Bye", "Hello"]);
+        assert_eq!(ops[1].languages.len(), 2);
+    }
+
+    #[test]
+    fn reads_and_writes_tsv() {
+        assert_eq!(
+            tsv(&["Hello".to_owned(), "Bye".to_owned()]),
+            "0\tHello\n1\tBye\n"
+        );
+        assert_eq!(
+            parse_tsv("0\tHello\tBonjour\n1\tBye\tAu revoir\nbad\n"),
+            vec![(0, "Bonjour"), (1, "Au revoir")]
+        );
+        assert!(!sendable("two\nlines"));
+        assert_eq!(
+            encode("tt-batch/1/output/a b.tsv"),
+            "tt-batch%2F1%2Foutput%2Fa%20b.tsv"
+        );
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::error::Error;
+use std::path::Path;
+use std::process::Command;
+
+/// The abbreviated commit that last changed `path`, followed by `+dirty` if it
+/// has changed since, or `None` if it isn't in a git repository
+pub fn revision(path: &Path) -> Option<String> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = format!("./{}", path.file_name()?.to_string_lossy());
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["log", "-1", "--format=%h", "--"])
+        .arg(&name)
+        .output()
+        .ok()?;
+    let commit = String::from_utf8(output.stdout).ok()?.trim().to_owned();
+    if !output.status.success() || commit.is_empty() {
+        return None;
+    }
+    let clean = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["diff", "--quiet", "HEAD", "--"])
+        .arg(&name)
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+    Some(if clean {
+        commit
+    } else {
+        format!("{}+dirty", commit)
+    })
+}
+
+/// Read `path` as it was at `rev` (such as `HEAD~1` or `origin/main`). A file
+/// that didn't exist at `rev` is treated as empty, so every message in it is new.
+pub fn show_file(rev: &str, path: &Path) -> Result<String, Box<dyn Error>> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("{} isn't a file", path.display()))?;
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("show")
+        // `./` makes the path relative to `dir` rather than the repository root
+        .arg(format!("{}:./{}", rev, name.to_string_lossy()))
+        .output()
+        .map_err(|e| format!("failed to run git: {}", e))?;
+    if output.status.success() {
+        return Ok(String::from_utf8(output.stdout)?);
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("does not exist in") || stderr.contains("exists on disk, but not in") {
+        log::info!(
+            "{} didn't exist at {}, translating everything",
+            path.display(),
+            rev
+        );
+        Ok(String::new())
+    } else {
+        Err(Box::from(format!(
+            "failed to read {} at {}: {}",
+            path.display(),
+            rev,
+            stderr.trim()
+        )))
+    }
+}
@@ -0,0 +1,321 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::io::prelude::*;
+use std::path::Path;
+
+use fluent_syntax::ast::{Pattern, Resource};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct GlossaryCheckConfig {
+    /// check translations against the local copy of each locale's glossary,
+    /// since backends sometimes ignore glossary entries
+    pub enabled: bool,
+    /// the glossary CSV of each locale, where `{locale}` is replaced with the
+    /// locale
+    pub file: String,
+}
+
+impl Default for GlossaryCheckConfig {
+    fn default() -> GlossaryCheckConfig {
+        GlossaryCheckConfig {
+            enabled: true,
+            file: "glossary-{locale}.csv".to_owned(),
+        }
+    }
+}
+
+/// A source text that has been translated more than one way
+#[derive(Debug, PartialEq)]
+pub struct Inconsistency {
+    pub source: String,
+    /// each distinct translation and how often it is used, most common first
+    pub renderings: Vec<(String, usize)>,
+}
+
+fn render(pattern: &Pattern) -> String {
+    let mut text: Vec<u8> = Vec::new();
+    super::writer::write_pattern(&mut text, pattern).expect("can write pattern to memory");
+    String::from_utf8(text)
+        .expect("valid utf-8")
+        .trim()
+        .to_owned()
+}
+
+/// Every value and attribute in `resource`, keyed by message id (and
+/// `id.attribute` for attributes)
+fn patterns<'ast>(resource: &'ast Resource<'ast>) -> Vec<(String, &'ast Pattern<'ast>)> {
+    let mut patterns = Vec::new();
+    for entry in resource.body.iter() {
+        if let fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Message(m)) =
+            entry
+        {
+            if let Some(value) = &m.value {
+                patterns.push((m.id.name.to_owned(), value));
+            }
+            for attribute in m.attributes.iter() {
+                patterns.push((
+                    format!("{}.{}", m.id.name, attribute.id.name),
+                    &attribute.value,
+                ));
+            }
+        }
+    }
+    patterns
+}
+
+/// Find source texts that are translated differently in different messages.
+/// Only texts without placeables are considered, since those are the ones that
+/// make sense as glossary entries.
+pub fn inconsistencies(source: &Resource, target: &Resource) -> Vec<Inconsistency> {
+    let target: BTreeMap<String, String> = patterns(target)
+        .into_iter()
+        .map(|(id, pattern)| (id, render(pattern)))
+        .collect();
+
+    // keep the source texts in the order they first appear
+    let mut order: Vec<String> = Vec::new();
+    let mut renderings: BTreeMap<String, Vec<(String, usize)>> = BTreeMap::new();
+    for (id, pattern) in patterns(source) {
+        let text = render(pattern);
+        if text.is_empty() || text.contains('{') {
+            continue;
+        }
+        let translated = match target.get(&id) {
+            Some(translated) if !translated.is_empty() => translated,
+            _ => continue,
+        };
+        let counts = renderings.entry(text.clone()).or_insert_with(|| {
+            order.push(text);
+            Vec::new()
+        });
+        match counts.iter_mut().find(|(t, _)| t == translated) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((translated.clone(), 1)),
+        }
+    }
+
+    order
+        .into_iter()
+        .filter_map(|source| {
+            let mut counts = renderings.remove(&source)?;
+            if counts.len() < 2 {
+                return None;
+            }
+            // stable, so ties keep the rendering that appeared first
+            counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+            Some(Inconsistency {
+                source,
+                renderings: counts,
+            })
+        })
+        .collect()
+}
+
+/// Ask which rendering of an inconsistent text should go in the glossary,
+/// defaulting to the most common one
+pub fn choose(inconsistency: &Inconsistency, locale: &str) -> std::io::Result<Option<String>> {
+    println!(
+        "`{}` is translated {} different ways in {}:",
+        inconsistency.source,
+        inconsistency.renderings.len(),
+        locale
+    );
+    for (i, (rendering, count)) in inconsistency.renderings.iter().enumerate() {
+        println!("  {}) {} (used {} time(s))", i + 1, rendering, count);
+    }
+    loop {
+        print!(
+            "add it to the glossary as 1-{}? [1/n] ",
+            inconsistency.renderings.len()
+        );
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+            return Ok(None);
+        }
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Ok(Some(inconsistency.renderings[0].0.clone()));
+        }
+        if answer.eq_ignore_ascii_case("n") {
+            return Ok(None);
+        }
+        match answer.parse::<usize>() {
+            Ok(i) if i >= 1 && i <= inconsistency.renderings.len() => {
+                return Ok(Some(inconsistency.renderings[i - 1].0.clone()))
+            }
+            _ => println!("please answer with a number from the list, or n to skip"),
+        }
+    }
+}
+
+/// The fields of a line of CSV, where quoted fields can hold commas and
+/// doubled quotes
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        let mut field = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            // a quoted field ends at the first quote that isn't doubled
+            while let Some(c) = chars.next() {
+                if c == '"' {
+                    if chars.peek() == Some(&'"') {
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                field.push(c);
+            }
+        }
+        for c in chars.by_ref() {
+            if c == ',' {
+                break;
+            }
+            field.push(c);
+        }
+        fields.push(field);
+        if chars.peek().is_none() {
+            return fields;
+        }
+    }
+}
+
+/// The source terms already in a glossary CSV, so they aren't suggested twice
+pub fn existing_terms<P: AsRef<Path>>(path: P) -> std::io::Result<HashSet<String>> {
+    Ok(entries(path)?
+        .into_iter()
+        .map(|(source, _)| source)
+        .collect())
+}
+
+/// The `source,target` entries of a unidirectional glossary CSV
+pub fn entries<P: AsRef<Path>>(path: P) -> std::io::Result<Vec<(String, String)>> {
+    let contents = super::catalog::read_or_empty(path)?;
+    Ok(contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = csv_fields(line).into_iter();
+            let source = fields.next().filter(|source| !source.is_empty())?;
+            Some((source, fields.next().unwrap_or_default()))
+        })
+        .collect())
+}
+
+/// Whether `term` appears in `text` as whole words, ignoring case
+fn contains_term(text: &str, term: &str) -> bool {
+    let text = text.to_lowercase();
+    let term = term.to_lowercase();
+    text.match_indices(&term).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + term.len()..].chars().next();
+        !before.map(char::is_alphanumeric).unwrap_or(false)
+            && !after.map(char::is_alphanumeric).unwrap_or(false)
+    })
+}
+
+/// Describe each glossary entry whose source term is in `source` but whose
+/// target term isn't in its `translated` text, such as a backend ignoring the
+/// glossary
+pub fn violations(entries: &[(String, String)], source: &str, translated: &str) -> Vec<String> {
+    entries
+        .iter()
+        .filter(|(term, expected)| {
+            !expected.is_empty()
+                && contains_term(source, term)
+                && !contains_term(translated, expected)
+        })
+        .map(|(term, expected)| {
+            format!(
+                "`{}` should be translated as `{}` in `{}`",
+                term, expected, translated
+            )
+        })
+        .collect()
+}
+
+/// Append entries to a unidirectional glossary CSV (`source,target` per line),
+/// ready to be uploaded with the rest of the glossary
+pub fn append_entries<P: AsRef<Path>>(
+    path: P,
+    entries: &[(String, String)],
+) -> Result<(), Box<dyn Error>> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    for (source, target) in entries.iter() {
+        writeln!(
+            file,
+            "{},{}",
+            super::compare::csv_field(source),
+            super::compare::csv_field(target)
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_inconsistent_translations() {
+        let source = fluent_syntax::parser::parse(
+            "a = Sign in\nb = Sign in\nc = Sign in\nd = Hello { $who }\ne = Hello { $who }\nf = Cancel\n    .title = Cancel\n",
+        )
+        .unwrap();
+        let target = fluent_syntax::parser::parse(
+            "a = Connexion\nb = Se connecter\nc = Se connecter\nd = Bonjour { $who }\ne = Salut { $who }\nf = Annuler\n    .title = Annuler\n",
+        )
+        .unwrap();
+        assert_eq!(
+            inconsistencies(&source, &target),
+            vec![Inconsistency {
+                source: "Sign in".to_owned(),
+                renderings: vec![("Se connecter".to_owned(), 2), ("Connexion".to_owned(), 1)],
+            }]
+        );
+    }
+
+    #[test]
+    fn finds_glossary_violations() {
+        assert_eq!(
+            csv_fields("\"Sign in, please\",\"Connexion \"\"SVP\"\"\",x"),
+            vec!["Sign in, please", "Connexion \"SVP\"", "x"]
+        );
+        let entries = vec![
+            ("Sign in".to_owned(), "Connexion".to_owned()),
+            ("cart".to_owned(), "panier".to_owned()),
+        ];
+        assert!(violations(
+            &entries,
+            "Sign in to see your cart",
+            "Connexion pour voir votre panier"
+        )
+        .is_empty());
+        assert!(violations(&entries, "Signing into ___", "Se connecter à ___").is_empty());
+        assert_eq!(
+            violations(&entries, "Your Cart", "Votre chariot"),
+            vec!["`cart` should be translated as `panier` in `Votre chariot`".to_owned()]
+        );
+    }
+}
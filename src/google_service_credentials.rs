@@ -68,6 +68,8 @@ pub struct ServiceCredentials {
     scope: String,
     credentials: ServiceAccountKey,
     token: Option<ServiceToken>,
+    http: super::http::Settings,
+    token_endpoint: String,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -164,13 +166,15 @@ impl JWT {
 }
 
 impl ServiceCredentials {
-    pub fn load<P: AsRef<std::path::Path>>(path: P, scope: &str) -> Result<ServiceCredentials, std::io::Error> {
+    pub fn load<P: AsRef<std::path::Path>>(path: P, scope: &str, connection: &super::backend::Connection) -> Result<ServiceCredentials, std::io::Error> {
         let file = std::fs::File::open(path)?;
         let credentials: ServiceAccountKey = serde_json::from_reader(&file)?;
         Ok(ServiceCredentials {
             credentials,
             scope: scope.to_owned(),
             token: None,
+            http: connection.http.clone(),
+            token_endpoint: connection.endpoints.token.clone(),
         })
     }
 
@@ -186,9 +190,10 @@ impl ServiceCredentials {
         if self.token.is_none() || self.token.as_ref().unwrap().expires_at <= now {
             // need a new token
 
+            let endpoint = self.token_endpoint.clone();
             let claims = Claims {
                 iss: self.credentials.client_email.clone(),
-                aud: "https://www.googleapis.com/oauth2/v4/token".to_owned(),
+                aud: endpoint.clone(),
                 exp: now + 3600,
                 iat: now,
                 sub: None,
@@ -198,9 +203,9 @@ impl ServiceCredentials {
             let claims_token = jwt.sign(&self.credentials.private_key)?;
 
             // request an access token from Google
-            let client = reqwest::blocking::Client::new();
+            let client = self.http.client()?;
             let res = client
-                .post("https://www.googleapis.com/oauth2/v4/token")
+                .post(&endpoint)
                 .header(
                     reqwest::header::CONTENT_TYPE,
                     "application/x-www-form-urlencoded",
@@ -241,7 +246,7 @@ mod tests {
     #[test]
     #[ignore]
     fn can_get_google_token() {
-        let mut credentials = ServiceCredentials::load("fuelgauge-221218-c727995f09a3.json", "https://www.googleapis.com/auth/datastore https://www.googleapis.com/auth/firebase.messaging")
+        let mut credentials = ServiceCredentials::load("fuelgauge-221218-c727995f09a3.json", "https://www.googleapis.com/auth/datastore https://www.googleapis.com/auth/firebase.messaging", &crate::backend::Connection::default())
             .expect("can load service credentials from file");
         let token = credentials.get_access_token().expect("can get token");
         println!("token: {}", token);
@@ -0,0 +1,188 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::lint::Reference;
+use fluent_syntax::ast::{Entry, Pattern, Resource, ResourceEntry};
+use serde::Serialize;
+use std::io::Write;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kind {
+    Message,
+    Term,
+    /// referenced, but not defined in the catalog
+    Undefined,
+}
+
+/// A message or term, with terms' ids starting with `-`
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct Node {
+    pub id: String,
+    pub kind: Kind,
+    /// how many messages and terms refer to it
+    pub used_by: usize,
+}
+
+/// One message or term referring to another, or to one of its attributes
+#[derive(Serialize, PartialEq, Debug)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attribute: Option<String>,
+}
+
+/// What refers to what in a catalog, in the order it's defined
+#[derive(Serialize, Debug, Default)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl Graph {
+    pub fn build(resource: &Resource) -> Graph {
+        let mut graph = Graph::default();
+        let mut references: Vec<(String, Reference)> = Vec::new();
+        for entry in resource.body.iter() {
+            let (id, kind, patterns): (String, Kind, Vec<&Pattern>) = match entry {
+                ResourceEntry::Entry(Entry::Message(message)) => (
+                    message.id.name.to_owned(),
+                    Kind::Message,
+                    message
+                        .value
+                        .iter()
+                        .chain(message.attributes.iter().map(|a| &a.value))
+                        .collect(),
+                ),
+                ResourceEntry::Entry(Entry::Term(term)) => (
+                    format!("-{}", term.id.name),
+                    Kind::Term,
+                    std::iter::once(&term.value)
+                        .chain(term.attributes.iter().map(|a| &a.value))
+                        .collect(),
+                ),
+                _ => continue,
+            };
+            if graph.node(&id).is_none() {
+                graph.nodes.push(Node {
+                    id: id.clone(),
+                    kind,
+                    used_by: 0,
+                });
+            }
+            for pattern in patterns.into_iter() {
+                let mut found = Vec::new();
+                super::lint::pattern_references(pattern, &mut found);
+                references.extend(found.into_iter().map(|r| (id.clone(), r)));
+            }
+        }
+
+        for (from, reference) in references.into_iter() {
+            let (to, attribute) = match reference {
+                Reference::Message(id, attribute) => (id.to_owned(), attribute),
+                Reference::Term(id, attribute) => (format!("-{}", id), attribute),
+            };
+            let edge = Edge {
+                from,
+                to,
+                attribute: attribute.map(str::to_owned),
+            };
+            if graph.edges.contains(&edge) {
+                continue;
+            }
+            // referring to several attributes of something only counts once
+            let new_user = !graph
+                .edges
+                .iter()
+                .any(|e| e.from == edge.from && e.to == edge.to);
+            match graph.nodes.iter_mut().find(|node| node.id == edge.to) {
+                Some(node) if new_user => node.used_by += 1,
+                Some(_) => {}
+                None => graph.nodes.push(Node {
+                    id: edge.to.clone(),
+                    kind: Kind::Undefined,
+                    used_by: 1,
+                }),
+            }
+            graph.edges.push(edge);
+        }
+        graph
+    }
+
+    fn node(&self, id: &str) -> Option<&Node> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    /// Write the graph in Graphviz's DOT language, with terms as boxes and
+    /// undefined references dashed
+    pub fn write_dot<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        writeln!(out, "digraph catalog {{")?;
+        for node in self.nodes.iter() {
+            let style = match node.kind {
+                Kind::Message => "shape=ellipse",
+                Kind::Term => "shape=box",
+                Kind::Undefined => "shape=ellipse, style=dashed",
+            };
+            writeln!(out, "    \"{}\" [{}];", node.id, style)?;
+        }
+        for edge in self.edges.iter() {
+            match &edge.attribute {
+                Some(attribute) => writeln!(
+                    out,
+                    "    \"{}\" -> \"{}\" [label=\"{}\"];",
+                    edge.from, edge.to, attribute
+                )?,
+                None => writeln!(out, "    \"{}\" -> \"{}\";", edge.from, edge.to)?,
+            }
+        }
+        writeln!(out, "}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_graphs() {
+        let resource = fluent_syntax::parser::parse(
+            "-brand = Firefox\nhello = Hello from { -brand }\n    .title = { -brand }\nbye = { hello.title } { -brand } { missing }\n",
+        )
+        .unwrap();
+        let graph = Graph::build(&resource);
+        let nodes: Vec<(&str, Kind, usize)> = graph
+            .nodes
+            .iter()
+            .map(|n| (n.id.as_str(), n.kind, n.used_by))
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                ("-brand", Kind::Term, 2),
+                ("hello", Kind::Message, 1),
+                ("bye", Kind::Message, 0),
+                ("missing", Kind::Undefined, 1),
+            ]
+        );
+        assert_eq!(graph.edges.len(), 4);
+
+        let mut dot: Vec<u8> = Vec::new();
+        graph.write_dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.contains("    \"-brand\" [shape=box];\n"));
+        assert!(dot.contains("    \"bye\" -> \"hello\" [label=\"title\"];\n"));
+    }
+}
@@ -0,0 +1,96 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::Deserialize;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct HooksConfig {
+    /// shell commands to run after writing each locale, where `{file}` is
+    /// replaced by the file that was written and `{locale}` by its locale
+    #[serde(alias = "post_locale")]
+    pub post_locale: Vec<String>,
+}
+
+/// Quote `text` so that the shell passes it along as a single argument
+fn quote(text: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        format!("'{}'", text.replace('\'', "'\\''"))
+    }
+}
+
+/// Fill in the `{file}` and `{locale}` of a hook's command
+pub fn command_line(template: &str, file: &Path, locale: &str) -> String {
+    template
+        .replace("{file}", &quote(&file.display().to_string()))
+        .replace("{locale}", &quote(locale))
+}
+
+/// Run `command` with the shell, describing how it failed if it did
+pub fn run(command: &str) -> Result<(), String> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let output = Command::new(shell)
+        .arg(flag)
+        .arg(command)
+        .output()
+        .map_err(|e| format!("failed to run `{}`: {}", command, e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let said = if stderr.trim().is_empty() {
+        stdout.trim()
+    } else {
+        stderr.trim()
+    };
+    let status = match output.status.code() {
+        Some(code) => format!("exited with {}", code),
+        None => "was killed".to_owned(),
+    };
+    if said.is_empty() {
+        Err(status)
+    } else {
+        Err(format!("{}: {}", status, said))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn runs_hooks() {
+        let command = command_line(
+            "test -n {file} && echo {locale}",
+            Path::new("it's here/fr.flt"),
+            "fr",
+        );
+        assert_eq!(command, "test -n 'it'\\''s here/fr.flt' && echo 'fr'");
+        assert_eq!(run(&command), Ok(()));
+        assert_eq!(
+            run("echo 'no good' >&2; exit 3"),
+            Err("exited with 3: no good".to_owned())
+        );
+    }
+}
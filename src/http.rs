@@ -0,0 +1,197 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use reqwest::blocking::Client;
+use reqwest::Certificate;
+use serde::Deserialize;
+use std::error::Error;
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct HttpConfig {
+    /// how many seconds to wait for a backend to accept a connection
+    pub connect_timeout: f64,
+    /// how many seconds to wait for a whole response
+    pub timeout: f64,
+    /// PEM files of root certificates to trust along with the system's, such
+    /// as those of a proxy that intercepts TLS
+    pub ca_certs: Vec<String>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> HttpConfig {
+        HttpConfig {
+            connect_timeout: 10.0,
+            timeout: 60.0,
+            ca_certs: Vec::new(),
+        }
+    }
+}
+
+/// The certificates in a PEM bundle, each with its own BEGIN and END lines
+fn pem_certificates(pem: &str) -> Vec<&str> {
+    const END: &str = "-----END CERTIFICATE-----";
+    let mut certificates = Vec::new();
+    let mut rest = pem;
+    while let Some(start) = rest.find("-----BEGIN CERTIFICATE-----") {
+        let end = match rest[start..].find(END) {
+            Some(end) => start + end + END.len(),
+            None => break,
+        };
+        certificates.push(&rest[start..end]);
+        rest = &rest[end..];
+    }
+    certificates
+}
+
+/// How clients are built, set from the config and command line of each run
+/// so that the projects of a workspace don't share them
+#[derive(Clone)]
+pub struct Settings {
+    connect_timeout: Duration,
+    timeout: Duration,
+    certificates: Vec<Certificate>,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            connect_timeout: Duration::from_secs(10),
+            timeout: Duration::from_secs(60),
+            certificates: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Build clients with these timeouts, in seconds
+    pub fn new(connect_timeout: f64, timeout: f64) -> Result<Settings, Box<dyn Error>> {
+        let seconds = |seconds: f64| {
+            if seconds > 0.0 && seconds.is_finite() {
+                Ok(Duration::from_secs_f64(seconds))
+            } else {
+                log::error!(
+                    "timeouts must be a positive number of seconds, not {}",
+                    seconds
+                );
+                Err(super::errors::Errors::InvalidTimeout)
+            }
+        };
+        Ok(Settings {
+            connect_timeout: seconds(connect_timeout)?,
+            timeout: seconds(timeout)?,
+            certificates: Vec::new(),
+        })
+    }
+
+    /// Trust the root certificates in the PEM file at `path` as well
+    pub fn trust(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let pem = std::fs::read_to_string(path).map_err(|e| {
+            log::error!(
+                "failed to read CA certificates from {}: {}",
+                path.display(),
+                e
+            );
+            super::errors::Errors::InvalidCertificate
+        })?;
+        let found = pem_certificates(&pem);
+        if found.is_empty() {
+            log::error!("{} doesn't have any PEM certificates in it", path.display());
+            return Err(Box::new(super::errors::Errors::InvalidCertificate));
+        }
+        let mut certificates = Vec::with_capacity(found.len());
+        for certificate in found.into_iter() {
+            certificates.push(Certificate::from_pem(certificate.as_bytes()).map_err(|e| {
+                log::error!("invalid CA certificate in {}: {}", path.display(), e);
+                super::errors::Errors::InvalidCertificate
+            })?);
+        }
+        log::debug!(
+            "trusting {} CA certificate(s) from {}",
+            certificates.len(),
+            path.display()
+        );
+        self.certificates.extend(certificates);
+        Ok(())
+    }
+
+    /// A client for talking to the backends, which gives up on connections and
+    /// responses that hang
+    pub fn client(&self) -> Result<Client, Box<dyn Error>> {
+        let mut builder = Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.timeout);
+        for certificate in self.certificates.iter() {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+        Ok(builder.build()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_pem_bundles() {
+        let pem = "first\n-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n\
+            -----BEGIN CERTIFICATE-----\nBBBB\n-----END CERTIFICATE-----\n\
+            -----BEGIN CERTIFICATE-----\ncut off";
+        assert_eq!(
+            pem_certificates(pem),
+            vec![
+                "-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----",
+                "-----BEGIN CERTIFICATE-----\nBBBB\n-----END CERTIFICATE-----",
+            ]
+        );
+        assert!(pem_certificates("").is_empty());
+    }
+
+    #[test]
+    fn each_run_trusts_only_its_own_certificates() {
+        let dir = std::env::temp_dir().join(format!("tt-http-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bundle = dir.join("proxy.pem");
+        std::fs::write(&bundle, CERTIFICATE).unwrap();
+        let empty = dir.join("empty.pem");
+        std::fs::write(&empty, "no certificates here").unwrap();
+
+        let mut first = Settings::new(5.0, 30.0).unwrap();
+        first.trust(&bundle).unwrap();
+        assert_eq!(first.certificates.len(), 1);
+        assert!(first.client().is_ok());
+        assert!(first.trust(&empty).is_err());
+        assert_eq!(first.certificates.len(), 1);
+
+        // the next run of a workspace or watch starts over
+        let second = Settings::new(5.0, 30.0).unwrap();
+        assert!(second.certificates.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// a self-signed certificate for the tests to trust
+    const CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----\n\
+MIIBezCCASGgAwIBAgIUP/01zcFqjTRLkZRxKtAnX0ciP4AwCgYIKoZIzj0EAwIw\n\
+EjEQMA4GA1UEAwwHdHQgdGVzdDAgFw0yNjEwMTQxOTA0NTRaGA8yMTI2MDkyMDE5\n\
+MDQ1NFowEjEQMA4GA1UEAwwHdHQgdGVzdDBZMBMGByqGSM49AgEGCCqGSM49AwEH\n\
+A0IABG3wNRePOdgqkcuO+Pj6IPz0TT0I4p4e1IjU0R+xxbGd/tHlsk03ZqaMgO+2\n\
+RKgZZzX8D+hsUJusYBO8CLZNVxajUzBRMB0GA1UdDgQWBBSIc7AF53Jx0SpnQ+PF\n\
+UXRcD2Mx1TAfBgNVHSMEGDAWgBSIc7AF53Jx0SpnQ+PFUXRcD2Mx1TAPBgNVHRMB\n\
+Af8EBTADAQH/MAoGCCqGSM49BAMCA0gAMEUCIQC2AWtAMVKcCFRVeFx5WHESnJH6\n\
+JeZTJXuF+GfcbwbNYgIgdu+hLWMu+/pPSBzcWgMawX5H4jA8Cb3kr6+jHolYqbQ=\n\
+-----END CERTIFICATE-----\n";
+}
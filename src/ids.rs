@@ -0,0 +1,192 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Generated ids are cut down to this many characters, since keys such as
+/// gettext msgids can be whole sentences
+const MAX_ID_LENGTH: usize = 48;
+
+/// Check whether `id` can be used as a Fluent message identifier
+pub fn is_valid(id: &str) -> bool {
+    let mut chars = id.chars();
+    chars
+        .next()
+        .map(|c| c.is_ascii_alphabetic())
+        .unwrap_or(false)
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Turn an arbitrary key into a Fluent identifier, such as `Sign in!` into
+/// `sign-in` and `homeScreen.title` into `home-screen-title`
+pub fn slugify(key: &str) -> String {
+    let mut slug = String::new();
+    let mut previous: Option<char> = None;
+    for c in key.chars() {
+        if c.is_ascii_alphanumeric() {
+            // split camelCase words
+            if c.is_ascii_uppercase()
+                && previous
+                    .map(|p| p.is_ascii_lowercase() || p.is_ascii_digit())
+                    .unwrap_or(false)
+            {
+                slug.push('-');
+            }
+            slug.push(c.to_ascii_lowercase());
+        } else if c == '_' {
+            slug.push(c);
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+        previous = Some(c);
+    }
+
+    let mut slug: String = slug
+        .trim_matches(|c| c == '-' || c == '_')
+        .chars()
+        .take(MAX_ID_LENGTH)
+        .collect();
+    while slug.ends_with('-') || slug.ends_with('_') {
+        slug.pop();
+    }
+    if !slug.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        slug.insert_str(0, if slug.is_empty() { "key" } else { "key-" });
+    }
+    slug
+}
+
+/// The original keys of messages whose ids had to be generated when they were
+/// imported, so they can be restored when exporting
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct IdMap {
+    /// original keys by message id
+    keys: BTreeMap<String, String>,
+}
+
+impl IdMap {
+    /// Load a mapping file, treating a missing file as empty
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<IdMap, Box<dyn Error>> {
+        let contents = super::catalog::read_or_empty(path.as_ref())?;
+        if contents.trim().is_empty() {
+            return Ok(IdMap::default());
+        }
+        serde_json::from_str(&contents).map_err(|e| {
+            Box::from(format!(
+                "invalid id mapping file {}: {}",
+                path.as_ref().display(),
+                e
+            ))
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let mut contents = serde_json::to_string_pretty(self)?;
+        contents.push('\n');
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// The key that the message `id` was imported from
+    pub fn original<'a>(&'a self, id: &'a str) -> &'a str {
+        self.keys.get(id).map(String::as_str).unwrap_or(id)
+    }
+
+    /// Give each key a unique, valid message id. Keys that are already valid
+    /// ids are used as they are, and keys that were mapped in `previous` keep
+    /// the id they were given then, so that importing again doesn't rename
+    /// messages.
+    pub fn assign(keys: &[&str], previous: &IdMap) -> (Vec<String>, IdMap) {
+        let reused: BTreeMap<&str, &str> = previous
+            .keys
+            .iter()
+            .map(|(id, key)| (key.as_str(), id.as_str()))
+            .collect();
+        let mut taken: HashSet<String> = keys
+            .iter()
+            .filter(|key| is_valid(key))
+            .map(|key| (*key).to_owned())
+            .collect();
+        let mut map = IdMap::default();
+        let mut ids = Vec::with_capacity(keys.len());
+        let mut seen: HashSet<&str> = HashSet::new();
+        for key in keys.iter() {
+            if is_valid(key) && seen.insert(key) {
+                ids.push((*key).to_owned());
+                continue;
+            }
+            let id = match reused.get(key) {
+                Some(id) if !taken.contains(*id) => (*id).to_owned(),
+                _ => {
+                    let slug = slugify(key);
+                    let mut id = slug.clone();
+                    let mut n = 2;
+                    while taken.contains(&id) {
+                        id = format!("{}-{}", slug, n);
+                        n += 1;
+                    }
+                    id
+                }
+            };
+            taken.insert(id.clone());
+            map.keys.insert(id.clone(), (*key).to_owned());
+            ids.push(id);
+        }
+        (ids, map)
+    }
+}
+
+/// The mapping file kept next to an imported Fluent file, such as
+/// `en.ids.json` for `en.flt`
+pub fn mapping_path(flt: &Path) -> PathBuf {
+    flt.with_extension("ids.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugifies_keys() {
+        assert_eq!(slugify("Sign in!"), "sign-in");
+        assert_eq!(slugify("homeScreen.title"), "home-screen-title");
+        assert_eq!(slugify("404_page"), "key-404_page");
+        assert_eq!(slugify("¿?"), "key");
+        assert!(slugify(&"word ".repeat(20)).len() <= MAX_ID_LENGTH);
+    }
+
+    #[test]
+    fn assigns_unique_ids() {
+        let keys = ["hello", "Sign in", "sign-in", "Sign-In", "hello"];
+        let (ids, map) = IdMap::assign(&keys, &IdMap::default());
+        assert_eq!(
+            ids,
+            vec!["hello", "sign-in-2", "sign-in", "Sign-In", "hello-2"]
+        );
+        for (id, key) in ids.iter().zip(keys.iter()) {
+            assert_eq!(map.original(id), *key);
+        }
+
+        // ids are kept when importing again, even if new keys are added
+        let keys = ["sign in", "Sign in"];
+        let (ids, _) = IdMap::assign(&keys, &map);
+        assert_eq!(ids, vec!["sign-in", "sign-in-2"]);
+    }
+}
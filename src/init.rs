@@ -0,0 +1,190 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::error::Error;
+use std::io::prelude::*;
+use std::path::Path;
+
+/// The answers that a `tt.toml` is generated from
+pub struct Answers {
+    pub source: String,
+    pub locales: Vec<String>,
+    pub output_dir: String,
+    pub layout: String,
+    pub backends: Vec<String>,
+    pub credentials_file: Option<String>,
+}
+
+/// Ask a question on the terminal, using `default` if nothing is entered
+fn ask(question: &str, default: &str) -> std::io::Result<String> {
+    print!("{} [{}]: ", question, default);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(if answer.is_empty() {
+        default.to_owned()
+    } else {
+        answer.to_owned()
+    })
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Ask for everything that goes into `tt.toml`, or take the defaults if
+/// `interactive` is false
+pub fn ask_answers(interactive: bool) -> std::io::Result<Answers> {
+    let ask = |question: &str, default: &str| {
+        if interactive {
+            ask(question, default)
+        } else {
+            Ok(default.to_owned())
+        }
+    };
+
+    let source = ask(
+        "the English translation file to take strings from",
+        "en.flt",
+    )?;
+    let locales = split_list(&ask(
+        "the locales to translate into, separated by commas",
+        "fr",
+    )?);
+    let output_dir = ask("the directory to write translations into", ".")?;
+    let mut layout = ask(
+        "where each translation is written in that directory",
+        "{locale}.flt",
+    )?;
+    while !layout.contains("{locale}") {
+        if !interactive {
+            layout = "{locale}.flt".to_owned();
+            break;
+        }
+        println!("the layout must contain {{locale}}");
+        layout = ask(
+            "where each translation is written in that directory",
+            "{locale}.flt",
+        )?;
+    }
+    let backends = split_list(&ask(
+        "the backends to translate with, separated by commas",
+        "google",
+    )?);
+    let credentials_file = if backends
+        .iter()
+        .any(|b| super::backend::needs_credentials(b))
+    {
+        Some(ask(
+            "the Google Cloud credentials file",
+            "credentials.json",
+        )?)
+    } else {
+        None
+    };
+
+    Ok(Answers {
+        source,
+        locales,
+        output_dir,
+        layout,
+        backends,
+        credentials_file,
+    })
+}
+
+/// Quote a string for TOML
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn quote_list(values: &[String]) -> String {
+    let values: Vec<String> = values.iter().map(|v| quote(v)).collect();
+    format!("[{}]", values.join(", "))
+}
+
+/// Generate the contents of `tt.toml`
+pub fn render(answers: &Answers) -> String {
+    let mut toml = String::new();
+    toml.push_str("# generated by `tt init`, see the README for everything that can go here\n");
+    toml.push_str(&format!("version = {}\n", super::migrate::CONFIG.version()));
+    toml.push_str(&format!("source = {}\n", quote(&answers.source)));
+    toml.push_str(&format!("locales = {}\n", quote_list(&answers.locales)));
+    toml.push_str(&format!("backends = {}\n", quote_list(&answers.backends)));
+    if let Some(credentials_file) = &answers.credentials_file {
+        toml.push_str(&format!("credentials-file = {}\n", quote(credentials_file)));
+    }
+    toml.push_str("\n[output]\n");
+    toml.push_str(&format!("dir = {}\n", quote(&answers.output_dir)));
+    toml.push_str(&format!("layout = {}\n", quote(&answers.layout)));
+    toml
+}
+
+/// Make sure the credentials file can be read and used to sign in
+pub fn validate_credentials(
+    path: &str,
+    connection: &super::backend::Connection,
+) -> Result<(), Box<dyn Error>> {
+    let mut credentials = super::google_service_credentials::ServiceCredentials::load(
+        path,
+        "https://www.googleapis.com/auth/cloud-translation",
+        connection,
+    )
+    .map_err(|e| format!("failed to read credentials file {}: {}", path, e))?;
+    credentials
+        .get_access_token()
+        .map_err(|e| format!("failed to sign in with {}: {}", path, e))?;
+    Ok(())
+}
+
+/// Create the directories that each locale's translation is written into
+pub fn create_output_dirs(answers: &Answers) -> std::io::Result<()> {
+    let out_dir = Path::new(&answers.output_dir);
+    for locale in answers.locales.iter() {
+        let out_path = out_dir.join(answers.layout.replace("{locale}", locale));
+        std::fs::create_dir_all(out_path.parent().unwrap_or(out_dir))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_a_loadable_config() {
+        let answers = Answers {
+            source: "i18n/en.flt".to_owned(),
+            locales: vec!["fr".to_owned(), "de".to_owned()],
+            output_dir: "i18n".to_owned(),
+            layout: "{locale}/messages.flt".to_owned(),
+            backends: vec!["google".to_owned()],
+            credentials_file: Some("C:\\keys\\\"tt\".json".to_owned()),
+        };
+        let document = crate::toml::parse(&render(&answers)).unwrap();
+        let config: crate::config::Config = serde_json::from_value(document).unwrap();
+        assert_eq!(config.source.as_deref(), Some("i18n/en.flt"));
+        assert_eq!(config.locales, vec!["fr", "de"]);
+        assert_eq!(config.output.layout, "{locale}/messages.flt");
+        assert_eq!(
+            config.credentials_file.as_deref(),
+            Some("C:\\keys\\\"tt\".json")
+        );
+    }
+}
@@ -0,0 +1,231 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::prelude::*;
+
+use super::compare::html_escape;
+use super::report::RunReport;
+
+/// A message's result, as a test case that fails once for each problem
+struct Case<'a> {
+    id: &'a str,
+    failures: Vec<(&'static str, String)>,
+}
+
+/// Add `failure` to the case for `id`, adding the case if it's new
+fn fail<'a>(cases: &mut Vec<Case<'a>>, id: &'a str, failure: Option<(&'static str, String)>) {
+    let index = match cases.iter().position(|case| case.id == id) {
+        Some(index) => index,
+        None => {
+            cases.push(Case {
+                id,
+                failures: Vec::new(),
+            });
+            cases.len() - 1
+        }
+    };
+    cases[index].failures.extend(failure);
+}
+
+fn cases(report: &RunReport) -> Vec<Case<'_>> {
+    let mut cases: Vec<Case> = Vec::new();
+    for timing in report.timings.iter() {
+        fail(&mut cases, &timing.id, None);
+    }
+    for failure in report.failures.iter() {
+        if failure.translated_by.is_none() {
+            let attempts: Vec<String> = failure
+                .attempts
+                .iter()
+                .map(|a| format!("{}: {}", a.backend, a.error))
+                .collect();
+            fail(
+                &mut cases,
+                &failure.id,
+                Some(("failed-translation", attempts.join("; "))),
+            );
+        }
+    }
+    for issue in report.issues.iter() {
+        fail(
+            &mut cases,
+            &issue.id,
+            Some((issue.kind.name(), issue.detail.clone())),
+        );
+    }
+    for id in report.skipped.iter() {
+        fail(
+            &mut cases,
+            id,
+            Some(("stale", "skipped to stay within the budget".to_owned())),
+        );
+    }
+    for hook in report.hooks.iter() {
+        fail(
+            &mut cases,
+            &hook.command,
+            hook.error.clone().map(|error| ("hook", error)),
+        );
+    }
+    for id in report.stale_hand_translated.iter() {
+        fail(
+            &mut cases,
+            id,
+            Some((
+                "stale",
+                "hand-translated from an older version of the source".to_owned(),
+            )),
+        );
+    }
+    cases
+}
+
+/// Write a test suite for each locale, with a test case for each message that
+/// was translated or still needs to be. `errors` are the locales that couldn't
+/// be translated at all, with why.
+pub fn write<W: Write>(
+    out: &mut W,
+    reports: &[RunReport],
+    errors: &[(String, String)],
+) -> std::io::Result<()> {
+    let suites: Vec<(&RunReport, Vec<Case>)> = reports
+        .iter()
+        .map(|report| (report, cases(report)))
+        .collect();
+    let tests: usize = suites.iter().map(|(_, cases)| cases.len()).sum::<usize>() + errors.len();
+    let failures: usize = suites
+        .iter()
+        .flat_map(|(_, cases)| cases.iter())
+        .filter(|case| !case.failures.is_empty())
+        .count();
+
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        out,
+        "<testsuites name=\"tt\" tests=\"{}\" failures=\"{}\" errors=\"{}\">",
+        tests,
+        failures,
+        errors.len()
+    )?;
+    for (report, cases) in suites.iter() {
+        let locale = html_escape(&report.locale);
+        writeln!(
+            out,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\">",
+            locale,
+            cases.len(),
+            cases
+                .iter()
+                .filter(|case| !case.failures.is_empty())
+                .count()
+        )?;
+        for case in cases.iter() {
+            let name = html_escape(case.id);
+            if case.failures.is_empty() {
+                writeln!(
+                    out,
+                    "    <testcase classname=\"{}\" name=\"{}\"/>",
+                    locale, name
+                )?;
+                continue;
+            }
+            writeln!(
+                out,
+                "    <testcase classname=\"{}\" name=\"{}\">",
+                locale, name
+            )?;
+            for (kind, detail) in case.failures.iter() {
+                writeln!(
+                    out,
+                    "      <failure type=\"{}\" message=\"{}\"/>",
+                    kind,
+                    html_escape(detail)
+                )?;
+            }
+            writeln!(out, "    </testcase>")?;
+        }
+        writeln!(out, "  </testsuite>")?;
+    }
+    for (locale, error) in errors.iter() {
+        let locale = html_escape(locale);
+        writeln!(
+            out,
+            "  <testsuite name=\"{}\" tests=\"1\" failures=\"0\" errors=\"1\">",
+            locale
+        )?;
+        writeln!(
+            out,
+            "    <testcase classname=\"{}\" name=\"translate\">",
+            locale
+        )?;
+        writeln!(out, "      <error message=\"{}\"/>", html_escape(error))?;
+        writeln!(out, "    </testcase>")?;
+        writeln!(out, "  </testsuite>")?;
+    }
+    writeln!(out, "</testsuites>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{IssueKind, MessageTiming};
+
+    #[test]
+    fn can_write_junit() {
+        let mut report = RunReport::new("fr");
+        for id in ["hello", "count"].iter() {
+            report.timings.push(MessageTiming {
+                id: (*id).to_owned(),
+                elapsed: std::time::Duration::from_secs(0),
+                payload_size: 0,
+                retries: 0,
+            });
+        }
+        report.record_issue(
+            "count",
+            IssueKind::PlaceableMismatch,
+            "expected 1 placeable, found 0 in \"<b>\"".to_owned(),
+        );
+        report.stale_hand_translated.push("old".to_owned());
+
+        let mut out = Vec::new();
+        write(
+            &mut out,
+            &[report],
+            &[("de".to_owned(), "no credentials".to_owned())],
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="tt" tests="4" failures="2" errors="1">
+  <testsuite name="fr" tests="3" failures="2" errors="0">
+    <testcase classname="fr" name="hello"/>
+    <testcase classname="fr" name="count">
+      <failure type="placeable-mismatch" message="expected 1 placeable, found 0 in &quot;&lt;b&gt;&quot;"/>
+    </testcase>
+    <testcase classname="fr" name="old">
+      <failure type="stale" message="hand-translated from an older version of the source"/>
+    </testcase>
+  </testsuite>
+  <testsuite name="de" tests="1" failures="0" errors="1">
+    <testcase classname="de" name="translate">
+      <error message="no credentials"/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+}
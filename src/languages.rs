@@ -0,0 +1,129 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::PathBuf;
+
+use super::paths::Paths;
+use super::translate::{LRData, LRLanguage};
+
+/// The language that source files are written in, unless `--source-locale`
+/// says otherwise
+pub const DEFAULT_SOURCE_LANGUAGE: &str = "en";
+
+/// A snapshot of Google's supported languages, used when there is no cached
+/// copy and we can't or don't want to ask the API
+const SNAPSHOT: &str = include_str!("languages.json");
+
+/// Codes that people commonly use for a language, mapped to Google's code
+const ALIASES: &[(&str, &str)] = &[
+    ("he", "iw"),
+    ("jv", "jw"),
+    ("zh", "zh-CN"),
+    ("zh-Hans", "zh-CN"),
+    ("zh-Hant", "zh-TW"),
+    ("fil", "tl"),
+    ("nb", "no"),
+];
+
+fn cache_path(paths: &Paths) -> Option<PathBuf> {
+    paths.cache_file("languages.json")
+}
+
+/// The languages Google supports, from the runtime cache if `tt languages` has
+/// written one, otherwise from the snapshot compiled into `tt`
+pub fn known_languages(paths: &Paths) -> Vec<LRLanguage> {
+    if let Some(path) = cache_path(paths) {
+        if let Ok(cached) = std::fs::read_to_string(&path) {
+            match serde_json::from_str::<LRData>(&cached) {
+                Ok(data) => return data.languages,
+                Err(e) => log::warn!("ignoring invalid cache {}: {}", path.display(), e),
+            }
+        }
+    }
+    let snapshot: LRData = serde_json::from_str(SNAPSHOT).expect("valid languages snapshot");
+    snapshot.languages
+}
+
+/// Store a freshly queried list of languages so later runs can use it offline
+pub fn update_cache(
+    paths: &Paths,
+    languages: Vec<LRLanguage>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = cache_path(paths).ok_or("no cache directory available")?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(&LRData { languages })?)?;
+    log::debug!("cached supported languages in {}", path.display());
+    Ok(())
+}
+
+/// Turn a locale such as `FR`, `pt_BR` or `he` into the code the backend knows
+/// it as, falling back to the locale's base language if the region isn't known
+pub fn normalize(locale: &str, languages: &[LRLanguage]) -> Option<String> {
+    let mut parts = locale.splitn(2, &['-', '_'][..]);
+    let language = parts.next().unwrap_or_default().to_lowercase();
+    let region = parts.next().map(|region| {
+        if region.len() == 2 {
+            region.to_uppercase()
+        } else {
+            let mut chars = region.chars();
+            chars
+                .next()
+                .map(|c| {
+                    c.to_uppercase()
+                        .chain(chars.flat_map(char::to_lowercase))
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+    });
+
+    let full = match &region {
+        Some(region) => format!("{}-{}", language, region),
+        None => language.clone(),
+    };
+    let candidates = [full, language];
+    for candidate in candidates.iter() {
+        let candidate = ALIASES
+            .iter()
+            .find(|(alias, _)| alias == candidate)
+            .map(|(_, code)| (*code).to_owned())
+            .unwrap_or_else(|| candidate.clone());
+        if let Some(lang) = languages
+            .iter()
+            .find(|lang| lang.language_code.eq_ignore_ascii_case(&candidate))
+        {
+            return Some(lang.language_code.clone());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_normalize_locales() {
+        let snapshot: LRData = serde_json::from_str(SNAPSHOT).expect("valid languages snapshot");
+        let languages = snapshot.languages;
+        assert_eq!(normalize("FR", &languages).as_deref(), Some("fr"));
+        assert_eq!(normalize("fr_CA", &languages).as_deref(), Some("fr"));
+        assert_eq!(normalize("zh_tw", &languages).as_deref(), Some("zh-TW"));
+        assert_eq!(normalize("zh-Hans", &languages).as_deref(), Some("zh-CN"));
+        assert_eq!(normalize("he", &languages).as_deref(), Some("iw"));
+        assert_eq!(normalize("xx", &languages), None);
+    }
+}
@@ -0,0 +1,209 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fluent_syntax::ast::Comment;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct LengthConfig {
+    /// how many times the length of its source a translation can be before
+    /// it's warned about
+    pub max_expansion: Option<f64>,
+    /// the `max-expansion` of each locale, for languages that run long
+    pub locales: BTreeMap<String, f64>,
+    /// sources shorter than this aren't checked, since a word or two is often
+    /// much longer in another language
+    pub min_source_length: usize,
+}
+
+impl Default for LengthConfig {
+    fn default() -> LengthConfig {
+        LengthConfig {
+            max_expansion: None,
+            locales: BTreeMap::new(),
+            min_source_length: 10,
+        }
+    }
+}
+
+impl LengthConfig {
+    pub fn max_expansion(&self, locale: &str) -> Option<f64> {
+        self.locales.get(locale).copied().or(self.max_expansion)
+    }
+
+    /// How much longer `translated` is than its `source`, if it's longer than
+    /// `locale` allows
+    pub fn too_expanded(&self, locale: &str, expansion: &Expansion) -> Option<f64> {
+        let max = self.max_expansion(locale)?;
+        let ratio = expansion.ratio();
+        if expansion.source_length >= self.min_source_length && ratio > max {
+            Some(ratio)
+        } else {
+            None
+        }
+    }
+}
+
+/// How long a translation is compared to its source, not counting placeables
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct Expansion {
+    pub id: String,
+    pub source_length: usize,
+    pub translated_length: usize,
+}
+
+impl Expansion {
+    pub fn new(id: &str, source: &str, translated: &str) -> Expansion {
+        Expansion {
+            id: id.to_owned(),
+            source_length: length(source),
+            translated_length: length(translated),
+        }
+    }
+
+    pub fn ratio(&self) -> f64 {
+        if self.source_length == 0 {
+            1.0
+        } else {
+            self.translated_length as f64 / self.source_length as f64
+        }
+    }
+}
+
+/// The expansion of every message translated into a locale, for designers to
+/// see how much room each language needs
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct LocaleExpansion {
+    /// the length of all of the translations over the length of their sources
+    pub ratio: f64,
+    pub max_expansion: Option<f64>,
+    /// the messages, most expanded first
+    pub messages: Vec<MessageExpansion>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct MessageExpansion {
+    #[serde(flatten)]
+    pub expansion: Expansion,
+    pub ratio: f64,
+}
+
+/// The length of all of `expansions` over the length of their sources
+pub fn overall_ratio(expansions: &[Expansion]) -> f64 {
+    let source: usize = expansions.iter().map(|e| e.source_length).sum();
+    let translated: usize = expansions.iter().map(|e| e.translated_length).sum();
+    if source == 0 {
+        1.0
+    } else {
+        translated as f64 / source as f64
+    }
+}
+
+impl LocaleExpansion {
+    pub fn new(expansions: &[Expansion], max_expansion: Option<f64>) -> LocaleExpansion {
+        let mut messages: Vec<MessageExpansion> = expansions
+            .iter()
+            .map(|expansion| MessageExpansion {
+                ratio: expansion.ratio(),
+                expansion: expansion.clone(),
+            })
+            .collect();
+        messages.sort_by(|a, b| b.ratio.total_cmp(&a.ratio));
+        LocaleExpansion {
+            ratio: overall_ratio(expansions),
+            max_expansion,
+            messages,
+        }
+    }
+}
+
+/// The limit given by a comment such as `# tt-max-length: 24`, where `marker`
+/// is `tt-max-length`
+pub fn limit(comment: &Option<Comment>, marker: &str) -> Result<Option<usize>, String> {
+    let content = match comment {
+        Some(Comment::Comment { content }) => content,
+        _ => return Ok(None),
+    };
+    for line in content.iter() {
+        if let Some(i) = line.find(marker) {
+            let value = line[i + marker.len()..].trim_start_matches(':').trim();
+            return value
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("`{}` isn't a number of characters", value));
+        }
+    }
+    Ok(None)
+}
+
+/// How many characters `text` is, not counting its placeables' markers, whose
+/// length isn't known until they're formatted
+pub fn length(text: &str) -> usize {
+    super::placeables::strip_markers(text, "").chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_limits() {
+        let resource = fluent_syntax::parser::parse(
+            "# A button\n# tt-max-length: 12\nsave = Save\n# tt-max-length: lots\nbad = Bad\nok = Ok\n",
+        )
+        .unwrap();
+        let comment = |id| {
+            &super::super::catalog::find_message(&resource, id)
+                .unwrap()
+                .comment
+        };
+        assert_eq!(limit(comment("save"), "tt-max-length"), Ok(Some(12)));
+        assert!(limit(comment("bad"), "tt-max-length").is_err());
+        assert_eq!(limit(comment("ok"), "tt-max-length"), Ok(None));
+        assert_eq!(
+            length(&format!(
+                "Sauvegarder {} é",
+                super::super::placeables::marker(1)
+            )),
+            14
+        );
+    }
+
+    #[test]
+    fn finds_expansion() {
+        let mut config = LengthConfig {
+            max_expansion: Some(1.5),
+            ..LengthConfig::default()
+        };
+        config.locales.insert("de".to_owned(), 1.6);
+        let long = Expansion::new("save", "Save changes", "Änderungen speichern");
+        assert_eq!(long.source_length, 12);
+        assert_eq!(long.translated_length, 20);
+        assert!(config.too_expanded("de", &long).is_some());
+        assert!(config.too_expanded("fr", &long).is_some());
+        let short = Expansion::new("ok", "OK", "Einverstanden");
+        assert!(config.too_expanded("de", &short).is_none());
+        let fine = Expansion::new("open", "Open the file", "Datei öffnen");
+        assert!(config.too_expanded("de", &fine).is_none());
+
+        let report = LocaleExpansion::new(&[fine, long], config.max_expansion("de"));
+        assert_eq!(report.ratio, 32.0 / 25.0);
+        assert_eq!(report.messages[0].expansion.id, "save");
+    }
+}
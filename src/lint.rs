@@ -0,0 +1,357 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fluent_syntax::ast::{
+    CallArguments, Entry, Expression, InlineExpression, Pattern, PatternElement, ResourceEntry,
+};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Every rule that `tt lint` knows
+pub const RULES: &[&str] = &[
+    "duplicate-id",
+    "junk",
+    "undefined-reference",
+    "unused-term",
+    "id-style",
+];
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct LintConfig {
+    /// rules turned on or off by name, where every rule is on unless it's
+    /// turned off here
+    pub rules: BTreeMap<String, bool>,
+    pub id_style: IdStyle,
+}
+
+/// How message and term ids are written
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdStyle {
+    /// `sign-in-button`
+    #[default]
+    KebabCase,
+    /// `sign_in_button`
+    SnakeCase,
+    /// `signInButton`
+    CamelCase,
+    /// anything Fluent allows
+    Any,
+}
+
+impl IdStyle {
+    fn name(&self) -> &'static str {
+        match self {
+            IdStyle::KebabCase => "kebab-case",
+            IdStyle::SnakeCase => "snake_case",
+            IdStyle::CamelCase => "camelCase",
+            IdStyle::Any => "any",
+        }
+    }
+
+    fn matches(&self, id: &str) -> bool {
+        let starts_lowercase = id.starts_with(|c: char| c.is_ascii_lowercase());
+        let words = |separator: char| {
+            starts_lowercase
+                && id.split(separator).all(|word| {
+                    !word.is_empty()
+                        && word
+                            .chars()
+                            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+                })
+        };
+        match self {
+            IdStyle::KebabCase => words('-'),
+            IdStyle::SnakeCase => words('_'),
+            IdStyle::CamelCase => starts_lowercase && id.chars().all(|c| c.is_ascii_alphanumeric()),
+            IdStyle::Any => true,
+        }
+    }
+}
+
+impl LintConfig {
+    /// The rules to run, after turning on `enable` and turning off `disable`
+    /// on top of the config
+    pub fn enabled_rules(
+        &self,
+        enable: &[&str],
+        disable: &[&str],
+    ) -> Result<Vec<&'static str>, String> {
+        for rule in self.rules.keys() {
+            if !RULES.contains(&rule.as_str()) {
+                return Err(format!(
+                    "unknown lint rule `{}`, expected one of {}",
+                    rule,
+                    RULES.join(", ")
+                ));
+            }
+        }
+        Ok(RULES
+            .iter()
+            .copied()
+            .filter(|rule| {
+                !disable.contains(rule)
+                    && (enable.contains(rule) || self.rules.get(*rule).copied().unwrap_or(true))
+            })
+            .collect())
+    }
+}
+
+/// Something wrong with a Fluent file
+#[derive(Debug, PartialEq)]
+pub struct Problem {
+    pub line: usize,
+    pub rule: &'static str,
+    pub message: String,
+}
+
+/// The line of `text` that `slice`, which was parsed out of it, starts on
+fn line_of(text: &str, slice: &str) -> usize {
+    let offset = (slice.as_ptr() as usize).wrapping_sub(text.as_ptr() as usize);
+    if offset > text.len() {
+        return 0;
+    }
+    text[..offset].matches('\n').count() + 1
+}
+
+/// A reference to another message or term, with the attribute it refers to
+pub enum Reference<'s> {
+    Message(&'s str, Option<&'s str>),
+    Term(&'s str, Option<&'s str>),
+}
+
+pub fn pattern_references<'s>(pattern: &Pattern<'s>, out: &mut Vec<Reference<'s>>) {
+    for element in pattern.elements.iter() {
+        if let PatternElement::Placeable(expression) = element {
+            expression_references(expression, out);
+        }
+    }
+}
+
+fn expression_references<'s>(expression: &Expression<'s>, out: &mut Vec<Reference<'s>>) {
+    match expression {
+        Expression::InlineExpression(inline) => inline_references(inline, out),
+        Expression::SelectExpression { selector, variants } => {
+            inline_references(selector, out);
+            for variant in variants.iter() {
+                pattern_references(&variant.value, out);
+            }
+        }
+    }
+}
+
+fn inline_references<'s>(inline: &InlineExpression<'s>, out: &mut Vec<Reference<'s>>) {
+    let arguments_references = |arguments: &Option<CallArguments<'s>>, out: &mut Vec<_>| {
+        if let Some(arguments) = arguments {
+            for argument in arguments.positional.iter() {
+                inline_references(argument, out);
+            }
+            for argument in arguments.named.iter() {
+                inline_references(&argument.value, out);
+            }
+        }
+    };
+    match inline {
+        InlineExpression::MessageReference { id, attribute } => out.push(Reference::Message(
+            id.name,
+            attribute.as_ref().map(|a| a.name),
+        )),
+        InlineExpression::TermReference {
+            id,
+            attribute,
+            arguments,
+        } => {
+            out.push(Reference::Term(id.name, attribute.as_ref().map(|a| a.name)));
+            arguments_references(arguments, out);
+        }
+        InlineExpression::FunctionReference { arguments, .. } => {
+            arguments_references(arguments, out)
+        }
+        InlineExpression::Placeable { expression } => expression_references(expression, out),
+        _ => {}
+    }
+}
+
+/// Check a Fluent file with each of `rules`, returning the problems in the
+/// order that they're found in
+pub fn lint(text: &str, rules: &[&str], id_style: IdStyle) -> Vec<Problem> {
+    let resource = match fluent_syntax::parser::parse(text) {
+        Ok(resource) => resource,
+        Err((resource, _)) => resource,
+    };
+    let enabled = |rule: &str| rules.contains(&rule);
+    let mut problems = Vec::new();
+
+    // message ids, and term ids with their `-`, mapped to their attributes
+    let mut defined: HashMap<String, (usize, HashSet<&str>)> = HashMap::new();
+    let mut references: Vec<(usize, Reference)> = Vec::new();
+    for entry in resource.body.iter() {
+        let (id, line, attributes, patterns) = match entry {
+            ResourceEntry::Junk(junk) => {
+                if enabled("junk") {
+                    problems.push(Problem {
+                        line: line_of(text, junk),
+                        rule: "junk",
+                        message: format!(
+                            "`{}` isn't valid Fluent",
+                            junk.lines().next().unwrap_or("").trim()
+                        ),
+                    });
+                }
+                continue;
+            }
+            ResourceEntry::Entry(Entry::Message(message)) => {
+                let mut patterns: Vec<&Pattern> = message.value.iter().collect();
+                patterns.extend(message.attributes.iter().map(|a| &a.value));
+                (
+                    message.id.name.to_owned(),
+                    line_of(text, message.id.name),
+                    &message.attributes,
+                    patterns,
+                )
+            }
+            ResourceEntry::Entry(Entry::Term(term)) => {
+                let mut patterns = vec![&term.value];
+                patterns.extend(term.attributes.iter().map(|a| &a.value));
+                (
+                    format!("-{}", term.id.name),
+                    line_of(text, term.id.name),
+                    &term.attributes,
+                    patterns,
+                )
+            }
+            ResourceEntry::Entry(Entry::Comment(_)) => continue,
+        };
+
+        if enabled("id-style") && !id_style.matches(id.trim_start_matches('-')) {
+            problems.push(Problem {
+                line,
+                rule: "id-style",
+                message: format!("`{}` isn't {}", id, id_style.name()),
+            });
+        }
+        let attributes: HashSet<&str> = attributes.iter().map(|a| a.id.name).collect();
+        match defined.get(&id) {
+            Some((first, _)) => {
+                if enabled("duplicate-id") {
+                    problems.push(Problem {
+                        line,
+                        rule: "duplicate-id",
+                        message: format!("`{}` is already defined on line {}", id, first),
+                    });
+                }
+            }
+            None => {
+                defined.insert(id, (line, attributes));
+            }
+        }
+        for pattern in patterns.into_iter() {
+            let mut found = Vec::new();
+            pattern_references(pattern, &mut found);
+            references.extend(found.into_iter().map(|r| (line, r)));
+        }
+    }
+
+    let mut used: HashSet<String> = HashSet::new();
+    for (line, reference) in references.iter() {
+        let (id, attribute) = match reference {
+            Reference::Message(id, attribute) => ((*id).to_owned(), attribute),
+            Reference::Term(id, attribute) => (format!("-{}", id), attribute),
+        };
+        let exists = match defined.get(&id) {
+            Some((_, attributes)) => attribute.map(|a| attributes.contains(a)).unwrap_or(true),
+            None => false,
+        };
+        if !exists && enabled("undefined-reference") {
+            let target = match attribute {
+                Some(attribute) => format!("{}.{}", id, attribute),
+                None => id.clone(),
+            };
+            problems.push(Problem {
+                line: *line,
+                rule: "undefined-reference",
+                message: format!("`{}` isn't defined", target),
+            });
+        }
+        used.insert(id);
+    }
+
+    if enabled("unused-term") {
+        let mut unused: Vec<(&String, usize)> = defined
+            .iter()
+            .filter(|(id, _)| id.starts_with('-') && !used.contains(*id))
+            .map(|(id, (line, _))| (id, *line))
+            .collect();
+        unused.sort_by_key(|(_, line)| *line);
+        for (id, line) in unused.into_iter() {
+            problems.push(Problem {
+                line,
+                rule: "unused-term",
+                message: format!("`{}` is never used", id),
+            });
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_problems() {
+        let text = "-brand = Firefox\n-unused = Nothing\n\nhello = Hello { -brand }\nhello = Again\nbye = { farewell } { hello.title }\nsignIn = Sign in\n\n= junk\n";
+        let problems: Vec<(usize, &str)> = lint(text, RULES, IdStyle::KebabCase)
+            .iter()
+            .map(|p| (p.line, p.rule))
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                (5, "duplicate-id"),
+                (7, "id-style"),
+                (9, "junk"),
+                (6, "undefined-reference"),
+                (6, "undefined-reference"),
+                (2, "unused-term"),
+            ]
+        );
+        assert!(lint(text, &["unused-term"], IdStyle::Any)
+            .iter()
+            .all(|p| p.rule == "unused-term"));
+    }
+
+    #[test]
+    fn enables_and_disables_rules() {
+        let mut config = LintConfig::default();
+        config.rules.insert("id-style".to_owned(), false);
+        config.rules.insert("junk".to_owned(), false);
+        assert_eq!(
+            config.enabled_rules(&["junk"], &["unused-term"]).unwrap(),
+            vec!["duplicate-id", "junk", "undefined-reference"]
+        );
+        config.rules.insert("typo".to_owned(), true);
+        assert!(config.enabled_rules(&[], &[]).is_err());
+    }
+
+    #[test]
+    fn checks_id_styles() {
+        assert!(IdStyle::KebabCase.matches("sign-in-2"));
+        assert!(!IdStyle::KebabCase.matches("sign_in"));
+        assert!(IdStyle::SnakeCase.matches("sign_in"));
+        assert!(IdStyle::CamelCase.matches("signIn"));
+        assert!(!IdStyle::CamelCase.matches("SignIn"));
+    }
+}
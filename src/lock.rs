@@ -0,0 +1,315 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+
+use fluent_syntax::ast::{Message, Resource};
+
+/// The lock file that is used if one isn't explicitly given
+pub const DEFAULT_LOCK_FILE: &str = "tt.lock";
+
+/// A hash of every source message as it was when each locale was last
+/// translated, so that only messages that changed since are translated again.
+/// Hand-translated messages also record a hash of their translation, so that
+/// they can be reported as stale until the translation is updated after the
+/// source changes.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Lock {
+    version: u64,
+    /// message hashes by message id, for each locale, written as
+    /// `<source hash>` or `<source hash> <translation hash>`
+    locales: BTreeMap<String, BTreeMap<String, String>>,
+    /// hashes of the value and each attribute of the messages that have
+    /// attributes, keyed the way Fluent refers to them (`login` and
+    /// `login.placeholder`), for each locale, so that only the parts of a
+    /// message that changed are translated again
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    parts: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is stable between Rust releases
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Hash a message's value and attributes
+pub fn hash_message(message: &Message) -> String {
+    let mut text: Vec<u8> = Vec::new();
+    if let Some(value) = &message.value {
+        super::writer::write_pattern(&mut text, value).expect("can write pattern to memory");
+    }
+    super::writer::write_attributes(&mut text, &message.attributes)
+        .expect("can write attributes to memory");
+    format!("{:016x}", fnv1a(&text))
+}
+
+/// Hash the value (if there is one) and each attribute of a message that has
+/// attributes, keyed the way Fluent refers to them
+fn hash_parts(message: &Message) -> Vec<(String, String)> {
+    if message.attributes.is_empty() {
+        return Vec::new();
+    }
+    let hash = |pattern| {
+        let mut text: Vec<u8> = Vec::new();
+        super::writer::write_pattern(&mut text, pattern).expect("can write pattern to memory");
+        format!("{:016x}", fnv1a(&text))
+    };
+    let id = message.id.name;
+    message
+        .value
+        .iter()
+        .map(|value| (id.to_owned(), hash(value)))
+        .chain(
+            message
+                .attributes
+                .iter()
+                .map(|a| (format!("{}.{}", id, a.id.name), hash(&a.value))),
+        )
+        .collect()
+}
+
+impl Default for Lock {
+    fn default() -> Lock {
+        Lock {
+            version: super::migrate::LOCK.version(),
+            locales: BTreeMap::new(),
+            parts: BTreeMap::new(),
+        }
+    }
+}
+
+impl Lock {
+    /// Load the lock file, treating a missing file as empty and upgrading one
+    /// from an older version of `tt`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Lock, Box<dyn Error>> {
+        let contents = super::catalog::read_or_empty(path.as_ref())?;
+        if contents.trim().is_empty() {
+            return Ok(Lock::default());
+        }
+        let invalid = |e: String| format!("invalid lock file {}: {}", path.as_ref().display(), e);
+        let mut document: serde_json::Value =
+            serde_json::from_str(&contents).map_err(|e| invalid(e.to_string()))?;
+        super::migrate::LOCK
+            .migrate(&mut document)
+            .map_err(invalid)?;
+        serde_json::from_value(document).map_err(|e| Box::from(invalid(e.to_string())))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let mut contents = serde_json::to_string_pretty(self)?;
+        contents.push('\n');
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Check whether `message` is the same as when `locale` was last
+    /// translated, or, for a hand translation, whether `existing` was
+    /// translated again since
+    pub fn unchanged(&self, locale: &str, message: &Message, existing: Option<&Message>) -> bool {
+        let entry = match self
+            .locales
+            .get(locale)
+            .and_then(|hashes| hashes.get(message.id.name))
+        {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let mut hashes = entry.split(' ');
+        if hashes.next() == Some(hash_message(message).as_str()) {
+            return true;
+        }
+        match (hashes.next(), existing) {
+            (Some(translation), Some(existing)) => translation != hash_message(existing),
+            _ => false,
+        }
+    }
+
+    /// Which of the value (if there is one) and attributes of `message` are
+    /// the same as when `locale` was last translated, if the message has
+    /// attributes and they were recorded
+    pub fn unchanged_parts(&self, locale: &str, message: &Message) -> Option<Vec<bool>> {
+        let hashes = self.parts.get(locale)?;
+        let parts = hash_parts(message);
+        if parts.is_empty() {
+            return None;
+        }
+        Some(
+            parts
+                .iter()
+                .map(|(key, hash)| hashes.get(key) == Some(hash))
+                .collect(),
+        )
+    }
+
+    /// Record the messages in `source` that `locale` is now up to date with,
+    /// forgetting any that were removed from the source or are in `skip`.
+    /// Messages that are hand-translated in `target` keep their old hashes
+    /// while they're stale.
+    pub fn update(
+        &mut self,
+        locale: &str,
+        source: &Resource,
+        target: &Resource,
+        hand_translated: &str,
+        skip: &[&str],
+    ) {
+        let mut hashes = BTreeMap::new();
+        let mut parts = BTreeMap::new();
+        for entry in source.body.iter() {
+            let m = match entry {
+                fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Message(m)) => {
+                    m
+                }
+                _ => continue,
+            };
+            if skip.contains(&m.id.name) {
+                continue;
+            }
+            let existing = super::catalog::find_message(target, m.id.name)
+                .filter(|e| super::catalog::has_marker(&e.comment, hand_translated));
+            let hash = match existing {
+                Some(existing) => {
+                    let previous = self
+                        .locales
+                        .get(locale)
+                        .and_then(|hashes| hashes.get(m.id.name));
+                    match previous {
+                        // remember the translation that's stale, so that
+                        // translating it again can be noticed
+                        Some(previous) if !self.unchanged(locale, m, Some(existing)) => {
+                            match previous.split_once(' ') {
+                                Some(_) => previous.clone(),
+                                None => format!("{} {}", previous, hash_message(existing)),
+                            }
+                        }
+                        _ => format!("{} {}", hash_message(m), hash_message(existing)),
+                    }
+                }
+                None => hash_message(m),
+            };
+            hashes.insert(m.id.name.to_owned(), hash);
+            parts.extend(hash_parts(m));
+        }
+        self.locales.insert(locale.to_owned(), hashes);
+        if parts.is_empty() {
+            self.parts.remove(locale);
+        } else {
+            self.parts.insert(locale.to_owned(), parts);
+        }
+    }
+
+    /// Start `locale` off as up to date with whatever `sibling` is, for a new
+    /// locale whose file is a copy of the sibling's
+    pub fn copy_locale(&mut self, sibling: &str, locale: &str) {
+        let hashes = self.locales.get(sibling).cloned().unwrap_or_default();
+        self.locales.insert(locale.to_owned(), hashes);
+        match self.parts.get(sibling).cloned() {
+            Some(parts) => self.parts.insert(locale.to_owned(), parts),
+            None => self.parts.remove(locale),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_locales() {
+        let source = fluent_syntax::parser::parse("a = Hello\n").unwrap();
+        let mut lock = Lock::default();
+        lock.update("pt-BR", &source, &source, "tt-hand-translated", &[]);
+        lock.copy_locale("pt-BR", "pt-PT");
+        let message = super::super::catalog::find_message(&source, "a").unwrap();
+        assert!(lock.unchanged("pt-PT", message, None));
+        lock.copy_locale("de", "de-CH");
+        assert!(!lock.unchanged("de-CH", message, None));
+    }
+
+    #[test]
+    fn tracks_changed_messages() {
+        let empty = fluent_syntax::parser::parse("").unwrap();
+        let old = fluent_syntax::parser::parse("a = Hello\nb = Bye\n    .title = Bye\nc = Gone\n")
+            .unwrap();
+        let mut lock = Lock::default();
+        lock.update("fr", &old, &empty, "tt-hand-translated", &["b"]);
+
+        let new =
+            fluent_syntax::parser::parse("a = Hello\nb = Bye\n    .title = Bye\nd = Hi\n").unwrap();
+        let message = |id| crate::catalog::find_message(&new, id).unwrap();
+        assert!(lock.unchanged("fr", message("a"), None));
+        // skipped messages aren't recorded
+        assert!(!lock.unchanged("fr", message("b"), None));
+        assert!(!lock.unchanged("fr", message("d"), None));
+        assert!(!lock.unchanged("de", message("a"), None));
+
+        let changed = fluent_syntax::parser::parse("a = Hello\n    .title = Hi\n").unwrap();
+        let a = crate::catalog::find_message(&changed, "a").unwrap();
+        assert!(!lock.unchanged("fr", a, None));
+    }
+
+    #[test]
+    fn tracks_changed_attributes() {
+        let empty = fluent_syntax::parser::parse("").unwrap();
+        let old = fluent_syntax::parser::parse(
+            "login = Log in\n    .title = Log in\n    .aria-label = Log in\nplain = Hi\n",
+        )
+        .unwrap();
+        let mut lock = Lock::default();
+        lock.update("fr", &old, &empty, "tt-hand-translated", &[]);
+
+        let new = fluent_syntax::parser::parse(
+            "login = Log in\n    .title = Sign in\n    .aria-label = Log in\n    .placeholder = Name\nplain = Hello\n",
+        )
+        .unwrap();
+        let message = |id| crate::catalog::find_message(&new, id).unwrap();
+        assert!(!lock.unchanged("fr", message("login"), None));
+        assert_eq!(
+            lock.unchanged_parts("fr", message("login")),
+            Some(vec![true, false, true, false])
+        );
+        // messages without attributes are all or nothing
+        assert_eq!(lock.unchanged_parts("fr", message("plain")), None);
+        assert_eq!(lock.unchanged_parts("de", message("login")), None);
+    }
+
+    #[test]
+    fn tracks_stale_hand_translations() {
+        let marker = "tt-hand-translated";
+        let old = fluent_syntax::parser::parse("a = Hello\n").unwrap();
+        let new = fluent_syntax::parser::parse("a = Hello!\n").unwrap();
+        let target = fluent_syntax::parser::parse("# tt-hand-translated\na = Bonjour\n").unwrap();
+        let redone = fluent_syntax::parser::parse("# tt-hand-translated\na = Bonjour !\n").unwrap();
+        let a = |resource| crate::catalog::find_message(resource, "a");
+
+        let mut lock = Lock::default();
+        lock.update("fr", &old, &target, marker, &[]);
+        assert!(lock.unchanged("fr", a(&old).unwrap(), a(&target)));
+
+        // the source changed, so the hand translation is stale until it's
+        // translated again, even after another run
+        assert!(!lock.unchanged("fr", a(&new).unwrap(), a(&target)));
+        lock.update("fr", &new, &target, marker, &[]);
+        assert!(!lock.unchanged("fr", a(&new).unwrap(), a(&target)));
+
+        assert!(lock.unchanged("fr", a(&new).unwrap(), a(&redone)));
+        lock.update("fr", &new, &redone, marker, &[]);
+        assert!(lock.unchanged("fr", a(&new).unwrap(), a(&redone)));
+    }
+}
@@ -12,428 +12,4479 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 use std::error::Error;
 use std::fs;
 use std::io::prelude::*;
 use std::io::BufWriter;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+mod adapter;
+mod audit;
+mod auth;
+mod backend;
+mod budget;
+mod casing;
+mod catalog;
 mod cli;
+mod color;
+mod compare;
+mod concurrency;
+mod config;
+mod deepl;
+mod dump;
+mod endpoint;
+mod entities;
 mod errors;
+mod expand;
+mod exec;
+mod filter;
+mod formats;
+mod freeze;
+mod gcs;
+mod git;
+mod glossary;
 mod google_service_credentials;
+mod graph;
+mod hooks;
+mod http;
+mod ids;
+mod init;
+mod junit;
+mod languages;
+mod length;
+mod lint;
+mod lock;
+mod markup;
+mod memory;
+mod merge;
+mod migrate;
+#[cfg(feature = "offline")]
+mod offline;
+mod paths;
+mod placeables;
+mod plurals;
+mod priority;
+mod progress;
+mod provenance;
+mod protect;
+mod pseudo;
+mod qa;
+mod report;
+mod retry;
+mod review;
+mod selftest;
+mod serve;
+mod sheet;
+mod spellcheck;
+mod status;
+mod throttle;
+mod toml;
 mod translate;
+mod watch;
+mod workspace;
+mod writer;
+mod xliff;
 
-/// Use the credentials file to sign in to obtain an oauth token for Google translate
+/// The scope that signing in to Google needs to translate
+const TRANSLATION_SCOPE: &str = "https://www.googleapis.com/auth/cloud-translation";
+
+/// Use the credentials file to sign in to obtain an oauth token for Google
+/// translate, and anything else in `scope`
 fn get_token_and_project_id(
-    matches: &clap::ArgMatches,
+    credentials_file: &str,
+    scope: &str,
+    connection: &backend::Connection,
 ) -> Result<(String, String), Box<dyn Error>> {
     // make sure the credentials file exists
-    let credentials_file = matches.value_of("credentials").unwrap();
     let credentials_path = PathBuf::from(credentials_file);
     if !credentials_path.exists() {
         log::error!("you must provide a credentials files!");
         return Err(Box::from(errors::Errors::MissingCredentialsFile));
     }
 
-    let mut credentials = google_service_credentials::ServiceCredentials::load(
-        credentials_path,
-        "https://www.googleapis.com/auth/cloud-translation",
-    )?;
-    let token = credentials.get_access_token()?;
-    let project_id = credentials.get_project_id();
+    let mut credentials =
+        google_service_credentials::ServiceCredentials::load(credentials_path, scope, connection)?;
+    let token = credentials.get_access_token().map_err(|e| {
+        if !auth::rejected(&e.to_string()) {
+            return e;
+        }
+        let key = match auth::KeyFile::load(credentials_file) {
+            Ok(key) => format!("key {} of {}", key.private_key_id, key.client_email),
+            Err(_) => "key".to_owned(),
+        };
+        log::error!(
+            "Google turned down the {} in {}, which usually means that it was disabled, deleted or rotated: create a new key for the service account, use it instead and check it with `tt auth status`",
+            key,
+            credentials_file
+        );
+        Box::from(errors::Errors::CredentialsRejected)
+    })?;
+    let project_id = credentials.get_project_id();
+
+    Ok((token, project_id))
+}
+
+/// Sign in with the credentials that `locale` is routed through. An explicit
+/// `--credentials` wins over the config's credential groups. Each credentials
+/// file is only signed in with once, no matter how many locales use it.
+fn get_locale_token_and_project_id(
+    matches: &clap::ArgMatches,
+    config: &config::Config,
+    connection: &backend::Connection,
+    locale: &str,
+    cache: &mut HashMap<String, (String, String)>,
+) -> Result<(String, String), Box<dyn Error>> {
+    let group = if matches.occurrences_of("credentials") > 0 {
+        None
+    } else {
+        config.credentials_for(locale)
+    };
+    let file = match group {
+        Some(group) => {
+            log::debug!("using credentials {} for {}", group.file, locale);
+            &group.file
+        }
+        None => arg_or_config(matches, "credentials", config.credentials_file.as_deref()).unwrap(),
+    };
+    // relative paths are the same file in every project of a workspace
+    let key = fs::canonicalize(file)
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| file.to_owned());
+    if !cache.contains_key(&key) {
+        let scope = match gcs_bucket(matches, config) {
+            Some(_) => format!("{} {}", TRANSLATION_SCOPE, gcs::SCOPE),
+            None => TRANSLATION_SCOPE.to_owned(),
+        };
+        cache.insert(key.clone(), get_token_and_project_id(file, &scope, connection)?);
+        if config.auth.max_key_age_days.is_some() || config.auth.expiry_warning_days.is_some() {
+            match auth::KeyFile::load(file).and_then(|key| Ok((key.validity(&connection.http)?, key))) {
+                Ok((validity, key)) => {
+                    for problem in config
+                        .auth
+                        .problems(&validity, chrono::Utc::now().naive_utc())
+                    {
+                        log::warn!("key {} in {} {}", key.private_key_id, file, problem);
+                    }
+                }
+                Err(e) => log::warn!("couldn't check how old the key in {} is: {}", file, e),
+            }
+        }
+    }
+    let (token, project_id) = cache[&key].clone();
+    let project_id = group
+        .and_then(|group| group.project_id.clone())
+        .unwrap_or(project_id);
+    Ok((token, project_id))
+}
+
+/// Translate the built-in sample catalog into a locale in a scratch directory,
+/// running the whole pipeline the way a real run does, and report how each
+/// stage went
+fn selftest(
+    matches: &clap::ArgMatches,
+    submatches: &clap::ArgMatches,
+    config: &config::Config,
+    connection: &backend::Connection,
+    backend_names: &[&str],
+) -> Result<(), Box<dyn Error>> {
+    let locale = submatches.value_of("test-locale").unwrap();
+    let palette = palette(matches);
+    let report = |stage: &str, result: &Result<String, String>| match result {
+        Ok(detail) => println!("{} {}: {}", palette.added("pass"), stage, detail),
+        Err(e) => println!("{} {}: {}", palette.failed("FAIL"), stage, e),
+    };
+    let needs_credentials = backend_names
+        .iter()
+        .any(|name| backend::needs_credentials(name));
+
+    let credentials = if needs_credentials {
+        match get_locale_token_and_project_id(matches, config, connection, locale, &mut HashMap::new()) {
+            Ok(credentials) => {
+                report("sign in", &Ok(format!("signed in to {}", credentials.1)));
+                Some(credentials)
+            }
+            Err(e) => {
+                report("sign in", &Err(e.to_string()));
+                return Err(Box::from(errors::Errors::SelftestFailed));
+            }
+        }
+    } else {
+        report("sign in", &Ok("no credentials are needed".to_owned()));
+        None
+    };
+
+    let languages = backend::Chain::create(backend_names, credentials.as_ref(), connection, None, "en")
+        .and_then(|chain| chain.available_languages())
+        .map_err(|e| e.to_string())
+        .and_then(|available| match languages::normalize(locale, &available) {
+            Some(language) => Ok(format!(
+                "{} can translate into `{}`",
+                backend_names.join(", "),
+                language
+            )),
+            None => Err(format!(
+                "none of the backends can translate into `{}`",
+                locale
+            )),
+        });
+    report("languages", &languages);
+    if languages.is_err() {
+        return Err(Box::from(errors::Errors::SelftestFailed));
+    }
+
+    let dir = env::temp_dir().join(format!("tt-selftest-{}", std::process::id()));
+    let translated = (|| -> Result<String, String> {
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let from_file = dir.join("en.flt");
+        fs::write(&from_file, selftest::SAMPLE).map_err(|e| e.to_string())?;
+        // an empty config, so that the project's hooks and locales don't
+        // apply, and without the translation memory so the backend is used
+        fs::write(
+            dir.join(config::DEFAULT_CONFIG_FILE),
+            "[memory]\nenabled = false\n",
+        )
+        .map_err(|e| e.to_string())?;
+
+        // the scratch directory is the working directory, so relative paths
+        // have to be made absolute
+        let absolute = |path: &str| {
+            fs::canonicalize(path)
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(|_| path.to_owned())
+        };
+        let backends: Vec<String> = backend_names
+            .iter()
+            .map(|name| match name.strip_prefix("exec:") {
+                Some(path) => format!("exec:{}", absolute(path)),
+                None => (*name).to_owned(),
+            })
+            .collect();
+        let mut command =
+            std::process::Command::new(env::current_exe().map_err(|e| e.to_string())?);
+        command
+            .current_dir(&dir)
+            .arg("--config")
+            .arg(config::DEFAULT_CONFIG_FILE)
+            .arg("--from")
+            .arg("en.flt")
+            .arg("--outpath")
+            .arg(".")
+            .arg("--locale")
+            .arg(locale)
+            .arg("--backend")
+            .arg(backends.join(","))
+            .arg("--color")
+            .arg("never");
+        if needs_credentials {
+            let file = match config.credentials_for(locale) {
+                Some(group) if matches.occurrences_of("credentials") == 0 => group.file.as_str(),
+                _ => arg_or_config(matches, "credentials", config.credentials_file.as_deref())
+                    .unwrap(),
+            };
+            command.arg("--credentials").arg(absolute(file));
+        }
+        let output = command
+            .output()
+            .map_err(|e| format!("failed to run tt: {}", e))?;
+        if !output.status.success() {
+            let said = String::from_utf8_lossy(&output.stderr);
+            let said: Vec<&str> = said
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .collect();
+            return Err(format!(
+                "translating failed: {}",
+                said.last().copied().unwrap_or("for no reason given")
+            ));
+        }
+        Ok(format!("translated the sample into `{}`", locale))
+    })();
+    report("translate", &translated);
+
+    let read_back = match &translated {
+        Ok(_) => {
+            let out_path = config::OutputConfig::default()
+                .layout
+                .replace("{locale}", locale);
+            fs::read_to_string(dir.join(out_path))
+                .map_err(|e| format!("failed to read the translation: {}", e))
+                .and_then(|written| selftest::check_translation(&written))
+                .map(|text| format!("`{}`", text))
+        }
+        Err(_) => Err("nothing was translated".to_owned()),
+    };
+    if translated.is_ok() {
+        report("read back", &read_back);
+    }
+
+    let cleaned = if dir.exists() {
+        fs::remove_dir_all(&dir)
+            .map(|_| format!("removed {}", dir.display()))
+            .map_err(|e| format!("failed to remove {}: {}", dir.display(), e))
+    } else {
+        Ok("nothing to remove".to_owned())
+    };
+    report("clean up", &cleaned);
+
+    if translated.is_ok() && read_back.is_ok() && cleaned.is_ok() {
+        Ok(())
+    } else {
+        Err(Box::from(errors::Errors::SelftestFailed))
+    }
+}
+
+/// Show which key each credentials file has, how old it is, when it expires and
+/// whether it can still sign in, failing if any of them can't or are too old
+fn auth_status(
+    matches: &clap::ArgMatches,
+    config: &config::Config,
+    connection: &backend::Connection,
+) -> Result<(), Box<dyn Error>> {
+    let mut files: Vec<&str> = Vec::new();
+    if matches.occurrences_of("credentials") > 0 || config.credentials.is_empty() {
+        files.push(
+            arg_or_config(matches, "credentials", config.credentials_file.as_deref()).unwrap(),
+        );
+    }
+    for group in config.credentials.iter() {
+        if !files.contains(&group.file.as_str()) {
+            files.push(&group.file);
+        }
+    }
+
+    let palette = palette(matches);
+    let now = chrono::Utc::now().naive_utc();
+    let mut first_error = None;
+    for file in files.into_iter() {
+        println!("{}", file);
+        let key = match auth::KeyFile::load(file) {
+            Ok(key) => key,
+            Err(e) => {
+                println!("  {}", palette.failed(&e.to_string()));
+                first_error.get_or_insert(errors::Errors::MissingCredentialsFile);
+                continue;
+            }
+        };
+        println!("  account: {}", key.client_email);
+        println!("  project: {}", key.project_id);
+        println!("  key:     {}", key.private_key_id);
+        match key.validity(&connection.http) {
+            Ok(validity) => {
+                let age = (now - validity.created).num_days();
+                println!("  created: {} ({} days ago)", validity.created.date(), age);
+                if validity.never_expires() {
+                    println!("  expires: never");
+                } else {
+                    println!("  expires: {}", validity.expires.date());
+                }
+                for problem in config.auth.problems(&validity, now) {
+                    println!("  {}", palette.stale(&format!("the key {}", problem)));
+                    first_error.get_or_insert(errors::Errors::KeyTooOld);
+                }
+            }
+            Err(e) => println!("  created: {}", palette.stale(&format!("unknown, {}", e))),
+        }
+        match get_token_and_project_id(file, TRANSLATION_SCOPE, connection) {
+            Ok(_) => println!("  sign-in: {}", palette.added("ok")),
+            Err(e) => {
+                println!("  sign-in: {}", palette.failed(&e.to_string()));
+                first_error.get_or_insert(errors::Errors::CredentialsRejected);
+            }
+        }
+    }
+    match first_error {
+        Some(e) => Err(Box::from(e)),
+        None => Ok(()),
+    }
+}
+
+/// The bucket to send texts through with `batchTranslateText`, if batch mode
+/// is on
+fn gcs_bucket<'a>(matches: &'a clap::ArgMatches, config: &'a config::Config) -> Option<&'a str> {
+    matches
+        .value_of("gcs-bucket")
+        .or(config.gcs_batch.bucket.as_deref())
+}
+
+/// Where the translation memory is kept
+fn memory_file(matches: &clap::ArgMatches, config: &config::Config) -> Option<PathBuf> {
+    match &config.memory.file {
+        Some(file) => Some(PathBuf::from(file)),
+        None => load_paths(matches, config).cache_file("translation-memory.json"),
+    }
+}
+
+/// Translate the texts that every locale wants with `batchTranslateText`,
+/// remembering their translations for the locales to find
+fn translate_batches(
+    matches: &clap::ArgMatches,
+    config: &config::Config,
+    connection: &backend::Connection,
+    bucket: &str,
+    wanted: &[gcs::Wanted],
+) -> Result<(), Box<dyn Error>> {
+    if wanted.is_empty() {
+        return Ok(());
+    }
+    let translated = gcs::translate(&config.gcs_batch, connection, bucket, wanted)?;
+    log::info!(
+        "translated {} of {} text(s) in batches",
+        translated.len(),
+        wanted.len()
+    );
+    let mut memory = memory::Memory::load(memory_file(matches, config));
+    for (key, translation) in translated.into_iter() {
+        memory.insert(key, translation);
+    }
+    memory.save()
+}
+
+/// The value of an argument, taken from the config unless it was given on the
+/// command line
+fn arg_or_config<'a>(
+    matches: &'a clap::ArgMatches,
+    name: &str,
+    config: Option<&'a str>,
+) -> Option<&'a str> {
+    if matches.occurrences_of(name) > 0 {
+        matches.value_of(name)
+    } else {
+        config.or_else(|| matches.value_of(name))
+    }
+}
+
+/// The directory translations are written into, and the file that `locale`
+/// is written to inside it. Java resource bundles are named for locales like
+/// `pt_BR`, so `.properties` layouts are given those.
+fn output_paths(
+    matches: &clap::ArgMatches,
+    config: &config::Config,
+    locale: &str,
+) -> (PathBuf, PathBuf) {
+    let out_dir =
+        PathBuf::from(arg_or_config(matches, "outpath", config.output.dir.as_deref()).unwrap());
+    let locale = match adapter::format_of(Path::new(&config.output.layout)) {
+        Some(formats::Format::Properties) => locale.replace('-', "_"),
+        _ => locale.to_owned(),
+    };
+    let out_path = out_dir.join(config.output.layout.replace("{locale}", &locale));
+    (out_dir, out_path)
+}
+
+/// Propose glossary entries for texts that are translated inconsistently, and
+/// append the accepted ones to each locale's glossary CSV
+fn suggest_glossary(
+    matches: &clap::ArgMatches,
+    submatches: &clap::ArgMatches,
+    config: &config::Config,
+    locales: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let from_file = arg_or_config(matches, "from", config.source.as_deref()).unwrap();
+    let source = fs::read_to_string(from_file)?;
+    let source = catalog::continue_parsing(from_file, fluent_syntax::parser::parse(&source));
+
+    for locale in locales.iter() {
+        let (_, out_path) = output_paths(matches, config, locale);
+        let target = catalog::read_or_empty(&out_path)?;
+        let target = catalog::continue_parsing(&out_path, fluent_syntax::parser::parse(&target));
+        let glossary_file = submatches
+            .value_of("file")
+            .unwrap()
+            .replace("{locale}", locale);
+        let existing = glossary::existing_terms(&glossary_file)?;
+
+        let mut entries = Vec::new();
+        for inconsistency in glossary::inconsistencies(&source, &target) {
+            if existing.contains(&inconsistency.source) {
+                log::debug!("`{}` is already in {}", inconsistency.source, glossary_file);
+                continue;
+            }
+            let chosen = if submatches.is_present("yes") {
+                Some(inconsistency.renderings[0].0.clone())
+            } else {
+                glossary::choose(&inconsistency, locale)?
+            };
+            if let Some(chosen) = chosen {
+                entries.push((inconsistency.source, chosen));
+            }
+        }
+
+        if entries.is_empty() {
+            log::info!("{}: no glossary entries to add", locale);
+        } else {
+            glossary::append_entries(&glossary_file, &entries)?;
+            log::info!(
+                "{}: added {} entries to {}, upload it to update the glossary",
+                locale,
+                entries.len(),
+                glossary_file
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Generate a `tt.toml` for a new project
+fn init_project(
+    matches: &clap::ArgMatches,
+    submatches: &clap::ArgMatches,
+) -> Result<(), Box<dyn Error>> {
+    let path = matches
+        .value_of("config")
+        .unwrap_or(config::DEFAULT_CONFIG_FILE);
+    if Path::new(path).exists() && !submatches.is_present("force") {
+        log::error!("{} already exists, pass --force to overwrite it", path);
+        return Err(Box::from(format!("{} already exists", path)));
+    }
+
+    let answers = init::ask_answers(!submatches.is_present("yes"))?;
+    let paths = paths::Paths::new(matches.value_of("cache-dir"), matches.value_of("state-dir"));
+    let known = languages::known_languages(&paths);
+    for locale in answers.locales.iter() {
+        if languages::normalize(locale, &known).is_none() {
+            log::warn!("`{}` isn't a language that Google supports", locale);
+        }
+    }
+    for name in answers.backends.iter() {
+        if let Err(e) = backend::validate_name(name.clone()) {
+            log::warn!("{}", e);
+        }
+    }
+    if !Path::new(&answers.source).exists() {
+        log::warn!("{} doesn't exist yet", answers.source);
+    }
+    if let Some(credentials_file) = &answers.credentials_file {
+        // there's no config yet to take endpoints or certificates from
+        match init::validate_credentials(credentials_file, &backend::Connection::default()) {
+            Ok(()) => log::info!("signed in with {}", credentials_file),
+            Err(e) => log::warn!("{}", e),
+        }
+    }
+
+    fs::write(path, init::render(&answers))?;
+    init::create_output_dirs(&answers)?;
+    log::info!("wrote {}, run `tt` to translate", path);
+    Ok(())
+}
+
+/// The format of a catalog, given by `--format` or guessed from its path
+fn catalog_format(format: Option<&str>, path: &Path) -> Result<formats::Format, Box<dyn Error>> {
+    format
+        .and_then(formats::Format::from_arg)
+        .or_else(|| formats::Format::from_path(path))
+        .ok_or_else(|| {
+            Box::from(format!(
+                "can't tell the format of {}, pass --format",
+                path.display()
+            ))
+        })
+}
+
+/// Convert a catalog from one format to another, returning how many messages
+/// were converted. The original keys of any messages whose keys aren't valid
+/// Fluent ids are kept in a mapping file next to the Fluent side, so that
+/// they can be restored when converting back.
+fn convert(
+    input: &Path,
+    in_format: formats::Format,
+    output: &Path,
+    out_format: formats::Format,
+    ids_path: Option<&str>,
+) -> Result<usize, Box<dyn Error>> {
+    let ids_path = ids_path.map(PathBuf::from).unwrap_or_else(|| {
+        if out_format == formats::Format::Fluent {
+            ids::mapping_path(output)
+        } else {
+            ids::mapping_path(input)
+        }
+    });
+    let previous = ids::IdMap::load(&ids_path)?;
+    let entries = formats::read(in_format, input, &previous)?;
+    let (contents, map) = formats::write(out_format, &entries, &previous);
+    fs::write(output, contents)?;
+    if let Some(map) = map {
+        if !map.is_empty() || ids_path.exists() {
+            map.save(&ids_path)?;
+        }
+        if !map.is_empty() {
+            log::info!(
+                "keys that aren't valid message ids were renamed, their original keys are in {}",
+                ids_path.display()
+            );
+        }
+    }
+    Ok(entries.len())
+}
+
+/// Convert a foreign catalog into a Fluent file
+fn import_catalog(submatches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let input = Path::new(submatches.value_of("input").unwrap());
+    let format = catalog_format(submatches.value_of("format"), input)?;
+    let output = submatches
+        .value_of("output")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| input.with_extension("flt"));
+    let count = convert(
+        input,
+        format,
+        &output,
+        formats::Format::Fluent,
+        submatches.value_of("ids"),
+    )?;
+    log::info!("imported {} message(s) into {}", count, output.display());
+    Ok(())
+}
+
+/// Convert a Fluent file back into a foreign catalog
+fn export_catalog(submatches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let input = Path::new(
+        submatches
+            .value_of("input")
+            .ok_or("pass the Fluent file to export")?,
+    );
+    let output = submatches.value_of("output").map(PathBuf::from);
+    let format = submatches
+        .value_of("format")
+        .and_then(formats::Format::from_arg)
+        .or_else(|| output.as_deref().and_then(formats::Format::from_path))
+        .ok_or("can't tell which format to export to, pass --format")?;
+    let output = output.unwrap_or_else(|| input.with_extension(format.extension()));
+    let count = convert(
+        input,
+        formats::Format::Fluent,
+        &output,
+        format,
+        submatches.value_of("ids"),
+    )?;
+    log::info!("exported {} message(s) into {}", count, output.display());
+    Ok(())
+}
+
+/// Convert between any two catalog formats, guessing both from their paths
+fn convert_catalog(submatches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let input = Path::new(submatches.value_of("in").unwrap());
+    let output = Path::new(submatches.value_of("out").unwrap());
+    let count = convert(
+        input,
+        catalog_format(None, input)?,
+        output,
+        catalog_format(None, output)?,
+        submatches.value_of("ids"),
+    )?;
+    log::info!(
+        "converted {} message(s) from {} into {}",
+        count,
+        input.display(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// How to color output, from `--color`
+fn palette(matches: &clap::ArgMatches) -> color::Palette {
+    color::Palette::new(color::When::from_arg(matches.value_of("color").unwrap()))
+}
+
+/// Load the project configuration, falling back to the defaults if no config
+/// file was given and `tt.toml` doesn't exist
+fn load_config(matches: &clap::ArgMatches) -> Result<config::Config, Box<dyn Error>> {
+    match matches.value_of("config") {
+        Some(path) => config::Config::load(path),
+        None if Path::new(config::DEFAULT_CONFIG_FILE).exists() => {
+            config::Config::load(config::DEFAULT_CONFIG_FILE)
+        }
+        None => Ok(config::Config::default()),
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let matches = cli::build_cli().get_matches();
+    let log_config = simplelog::ConfigBuilder::new()
+        .add_filter_allow_str("tt")
+        .build();
+    let logger: Box<dyn simplelog::SharedLogger> = if palette(&matches).enabled() {
+        simplelog::TermLogger::new(
+            simplelog::LevelFilter::Debug,
+            log_config,
+            simplelog::TerminalMode::Mixed,
+        )
+        .expect("can init termlogger")
+    } else {
+        simplelog::SimpleLogger::new(simplelog::LevelFilter::Debug, log_config)
+    };
+    simplelog::CombinedLogger::init(vec![logger]).expect("can initiate logging");
+    if let Some(dir) = matches.value_of("dump-http") {
+        dump::enable(dir)?;
+    }
+    if let Some(submatches) = matches.subcommand_matches("init") {
+        return init_project(&matches, submatches);
+    }
+    if let Some(submatches) = matches.subcommand_matches("import") {
+        let input = Path::new(submatches.value_of("input").unwrap());
+        let format = submatches.value_of("format");
+        if !sheet::is_sheet(format, input) && !xliff::is_xliff(format, input) {
+            return import_catalog(submatches);
+        }
+    }
+    if let Some(submatches) = matches.subcommand_matches("export") {
+        let format = submatches.value_of("format");
+        if format != Some(sheet::FORMAT) && format.and_then(xliff::Version::from_arg).is_none() {
+            return export_catalog(submatches);
+        }
+    }
+    if let Some(submatches) = matches.subcommand_matches("convert") {
+        return convert_catalog(submatches);
+    }
+    if let Some(submatches) = matches.subcommand_matches("watch") {
+        return watch_source(&matches, submatches);
+    }
+    if matches.is_present("workspace") {
+        return run_workspace(&matches);
+    }
+    run(&matches, None, &mut HashMap::new())
+}
+
+/// Run every project under the working directory, each from its own
+/// directory, signing in with each credentials file only once
+fn run_workspace(matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let root = env::current_dir()?;
+    let configs = workspace::discover(&root)?;
+    if configs.is_empty() {
+        return Err(Box::from(format!(
+            "no {} found under {}",
+            config::DEFAULT_CONFIG_FILE,
+            root.display()
+        )));
+    }
+
+    let mut credentials_cache = HashMap::new();
+    let mut results = Vec::new();
+    for config in configs.iter() {
+        let name = config.strip_prefix(&root).unwrap_or(config).display();
+        log::info!("running {}", name);
+        env::set_current_dir(config.parent().unwrap_or(&root))?;
+        let result = run(matches, None, &mut credentials_cache);
+        env::set_current_dir(&root)?;
+        if let Err(e) = &result {
+            log::error!("{} failed: {}", name, e);
+        }
+        results.push((name, result));
+    }
+
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    log::info!(
+        "workspace: {} project(s), {} succeeded, {} failed",
+        results.len(),
+        results.len() - failed,
+        failed
+    );
+    for (name, result) in results.iter() {
+        match result {
+            Ok(()) => log::info!("    {}: ok", name),
+            Err(e) => log::info!("    {}: {}", name, e),
+        }
+    }
+    if failed > 0 {
+        return Err(Box::from(format!("{} project(s) failed", failed)));
+    }
+    Ok(())
+}
+
+fn load_paths(matches: &clap::ArgMatches, config: &config::Config) -> paths::Paths {
+    paths::Paths::new(
+        arg_or_config(matches, "cache-dir", config.cache_dir.as_deref()),
+        arg_or_config(matches, "state-dir", config.state_dir.as_deref()),
+    )
+}
+
+/// Translate whenever the source file or the config changes, using the
+/// version of the source from the last successful run as the diff so that only
+/// the messages that changed are translated again
+fn watch_source(
+    matches: &clap::ArgMatches,
+    submatches: &clap::ArgMatches,
+) -> Result<(), Box<dyn Error>> {
+    let debounce = Duration::from_millis(submatches.value_of("debounce").unwrap().parse()?);
+    let config_path = PathBuf::from(
+        matches
+            .value_of("config")
+            .unwrap_or(config::DEFAULT_CONFIG_FILE),
+    );
+    let mut baseline: Option<PathBuf> = None;
+    loop {
+        let config = match load_config(matches) {
+            Ok(config) => config,
+            Err(e) => {
+                log::error!("{}", e);
+                watch::wait_for_change(std::slice::from_ref(&config_path), debounce);
+                continue;
+            }
+        };
+        let source =
+            PathBuf::from(arg_or_config(matches, "from", config.source.as_deref()).unwrap());
+        let snapshot = load_paths(matches, &config)
+            .state_file(&watch::baseline_name(&source))
+            .unwrap_or_else(|| std::env::temp_dir().join(watch::baseline_name(&source)));
+
+        match run(matches, baseline.as_deref(), &mut HashMap::new()) {
+            Ok(()) => {
+                if let Some(parent) = snapshot.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(&source, &snapshot)?;
+                baseline = Some(snapshot);
+            }
+            Err(e) => log::error!("failed to translate: {}", e),
+        }
+
+        log::info!("watching {} for changes", source.display());
+        watch::wait_for_change(&[source, config_path.clone()], debounce);
+    }
+}
+
+/// How this run's backends reach their services, from the config and the
+/// command line
+fn connection(
+    matches: &clap::ArgMatches,
+    config: &config::Config,
+) -> Result<backend::Connection, Box<dyn Error>> {
+    let mut http = http::Settings::new(
+        match matches.value_of("connect-timeout") {
+            Some(seconds) => seconds.parse()?,
+            None => config.http.connect_timeout,
+        },
+        match matches.value_of("timeout") {
+            Some(seconds) => seconds.parse()?,
+            None => config.http.timeout,
+        },
+    )?;
+    for path in config
+        .http
+        .ca_certs
+        .iter()
+        .map(String::as_str)
+        .chain(matches.values_of("ca-cert").into_iter().flatten())
+    {
+        http.trust(Path::new(path))?;
+    }
+    let endpoints = endpoint::Endpoints::new(
+        matches
+            .value_of("api-endpoint")
+            .or(config.api_endpoint.as_deref()),
+        matches
+            .value_of("token-endpoint")
+            .or(config.token_endpoint.as_deref()),
+        matches.value_of("location").or(config.location.as_deref()),
+        matches
+            .value_of("glossary-location")
+            .or(config.glossary_location.as_deref()),
+    );
+    let source_language = matches
+        .value_of("source-locale")
+        .or(config.source_locale.as_deref())
+        .unwrap_or(languages::DEFAULT_SOURCE_LANGUAGE)
+        .to_owned();
+    for (key, value) in config.labels.iter() {
+        translate::validate_label(format!("{}={}", key, value))?;
+    }
+    let mut labels = config.labels.clone();
+    for label in matches.values_of("label").into_iter().flatten() {
+        let (key, value) = label.split_once('=').expect("labels are validated");
+        labels.insert(key.to_owned(), value.to_owned());
+    }
+    let throttle = throttle::Throttle::new(match matches.value_of("qps") {
+        Some(qps) => Some(qps.parse()?),
+        None => config.qps,
+    });
+    Ok(backend::Connection {
+        http,
+        endpoints,
+        source_language,
+        labels,
+        retry: retry::Retry::new(&config.retry, throttle.clone()),
+        throttle,
+    })
+}
+
+/// Load the config and do whatever the command line asks for. `baseline`
+/// replaces the diff file, so that watch mode only translates what changed.
+/// Tokens are kept in `credentials_cache` by credentials file.
+fn run(
+    matches: &clap::ArgMatches,
+    baseline: Option<&Path>,
+    credentials_cache: &mut HashMap<String, (String, String)>,
+) -> Result<(), Box<dyn Error>> {
+    let config = load_config(matches)?;
+    let paths = load_paths(matches, &config);
+    let connection = connection(matches, &config)?;
+
+    if let Some(submatches) = matches.subcommand_matches("dirs") {
+        let show = |dir: &Option<PathBuf>| match dir {
+            Some(dir) => dir.display().to_string(),
+            None => "<none>".to_owned(),
+        };
+        println!("cache: {}", show(&paths.cache));
+        println!("state: {}", show(&paths.state));
+        if submatches.is_present("clear-cache") {
+            if let Some(cache) = paths.cache.as_ref().filter(|dir| dir.exists()) {
+                fs::remove_dir_all(cache)?;
+                log::info!("cleared {}", cache.display());
+            }
+        }
+        return Ok(());
+    }
+    if let Some(submatches) = matches.subcommand_matches("review") {
+        return review_translations(matches, submatches, &config);
+    }
+    if let Some(submatches) = matches.subcommand_matches("serve") {
+        return serve_reviews(matches, submatches, &config);
+    }
+    if let Some(submatches) = matches.subcommand_matches("import") {
+        let input = Path::new(submatches.value_of("input").unwrap());
+        if xliff::is_xliff(submatches.value_of("format"), input) {
+            return import_xliff(matches, submatches, &config);
+        }
+        return import_sheet(matches, submatches, &config);
+    }
+    if matches
+        .subcommand_matches("auth")
+        .and_then(|submatches| submatches.subcommand_matches("status"))
+        .is_some()
+    {
+        return auth_status(matches, &config, &connection);
+    }
+    if let Some(submatches) = matches.subcommand_matches("verify-signature") {
+        return verify_signature(submatches, &config);
+    }
+    if let Some(submatches) = matches.subcommand_matches("lint") {
+        return lint_files(matches, submatches, &config);
+    }
+    if let Some(submatches) = matches.subcommand_matches("graph") {
+        return graph_catalog(matches, submatches, &config);
+    }
+    if matches.subcommand_matches("migrate").is_some() {
+        return migrate_files(matches, &config);
+    }
+
+    let comparing = matches.subcommand_matches("compare");
+
+    // the command line overrides any backends in the config
+    let backend_names: Vec<&str> = if let Some(submatches) = comparing {
+        submatches.values_of("backends").unwrap().collect()
+    } else if matches.occurrences_of("backend") > 0 || config.backends.is_empty() {
+        matches.values_of("backend").unwrap().collect()
+    } else {
+        config.backends.iter().map(String::as_str).collect()
+    };
+    let needs_credentials = backend_names
+        .iter()
+        .any(|name| backend::needs_credentials(name));
+
+    if let Some(submatches) = matches.subcommand_matches("selftest") {
+        return selftest(matches, submatches, &config, &connection, &backend_names);
+    }
+
+    if let Some(submatches) = matches.subcommand_matches("languages") {
+        let available_languages = if submatches.is_present("offline") {
+            languages::known_languages(&paths)
+        } else {
+            let credentials = if needs_credentials {
+                Some(
+                    get_token_and_project_id(
+                        arg_or_config(matches, "credentials", config.credentials_file.as_deref())
+                            .unwrap(),
+                        TRANSLATION_SCOPE,
+                        &connection,
+                    )
+                    .map_err(|e| {
+                        log::error!(
+                            "failed to get token and project id from credentials file: {:?}",
+                            e
+                        );
+                        e
+                    })?,
+                )
+            } else {
+                None
+            };
+            let translator = backend::Chain::create(
+                &backend_names,
+                credentials.as_ref(),
+                &connection,
+                None,
+                "en",
+            )?;
+            let available_languages = translator.available_languages().inspect_err(|_| {
+                log::error!("failed to list available languages from translator!");
+            })?;
+
+            // keep the list around so validation can work offline next time
+            if translator.names() == ["google"] {
+                if let Err(e) = languages::update_cache(&paths, available_languages.clone()) {
+                    log::warn!("failed to cache supported languages: {}", e);
+                }
+            }
+            available_languages
+        };
+
+        let available_languages: Vec<String> = available_languages
+            .into_iter()
+            .map(|lang| format!("{} => '{}'", lang.display_name, lang.language_code))
+            .collect();
+
+        println!("Accepted languages:");
+        println!("{}", available_languages.join("\n"));
+        return Ok(());
+    } else if let Some(submatches) = matches.subcommand_matches("gen-completions") {
+        let shell = submatches.value_of("shell").unwrap_or("bash");
+
+        cli::build_cli().gen_completions_to(
+            env!("CARGO_PKG_NAME"),
+            match shell {
+                "bash" => clap::Shell::Bash,
+                "zsh" => clap::Shell::Zsh,
+                "fish" => clap::Shell::Fish,
+                "powershell" => clap::Shell::PowerShell,
+                "elvish" => clap::Shell::Elvish,
+                _ => return Err(Box::from(errors::Errors::InvalidShell)),
+            },
+            &mut std::io::stdout(),
+        );
+        return Ok(());
+    }
+
+    let run = Run {
+        matches,
+        config: &config,
+        connection: &connection,
+        backend_names: &backend_names,
+        diff: match (baseline, matches.value_of("diff-git")) {
+            (Some(baseline), _) => Some(catalog::DiffSource::File(baseline.to_path_buf())),
+            (None, Some(rev)) => Some(catalog::DiffSource::Git(rev.to_owned())),
+            (None, None) if matches.occurrences_of("diff") > 0 => matches
+                .value_of("diff")
+                .map(|path| catalog::DiffSource::File(PathBuf::from(path))),
+            (None, None) => match (&config.diff_git, &config.diff) {
+                (Some(rev), _) => Some(catalog::DiffSource::Git(rev.clone())),
+                (None, Some(path)) => Some(catalog::DiffSource::File(PathBuf::from(path))),
+                (None, None) => None,
+            },
+        },
+        protector: protect::Protector::new(&config.protect)
+            .with_terms(protect::load_terms(&config.protect.terms_file)?),
+        mime_type: translate::MimeType::from_arg(
+            arg_or_config(matches, "mime-type", config.mime_type.as_deref()).unwrap(),
+        ),
+        progress_format: progress::Format::from_arg(matches.value_of("progress-format").unwrap()),
+        deadline: Duration::from_secs_f64(matches.value_of("deadline").unwrap().parse()?),
+        concurrency: concurrency::Concurrency::from_arg(matches.value_of("concurrency").unwrap()),
+        dry_run: matches.is_present("dry-run"),
+        interactive: matches.is_present("interactive"),
+        dry_run_totals: std::cell::Cell::new((0, 0)),
+        budget: budget::Budget::new(
+            matches.value_of("max-chars").map(str::parse).transpose()?,
+            matches.value_of("max-cost").map(str::parse).transpose()?,
+            config
+                .cost_per_million_chars
+                .unwrap_or(budget::DEFAULT_COST_PER_MILLION_CHARS),
+        ),
+        collecting: std::cell::RefCell::new(None),
+        comparing,
+        reports: std::cell::RefCell::new(Vec::new()),
+        palette: palette(matches),
+        source_revision: if config.provenance.annotate || signing_key(matches, &config).is_some() {
+            arg_or_config(matches, "from", config.source.as_deref())
+                .map(provenance::source_revision)
+        } else {
+            None
+        },
+    };
+    let mut credentials_for = |locale: &str| -> Result<_, Box<dyn Error>> {
+        if needs_credentials && !run.dry_run {
+            Ok(Some(get_locale_token_and_project_id(
+                matches,
+                &config,
+                &connection,
+                locale,
+                credentials_cache,
+            )?))
+        } else {
+            Ok(None)
+        }
+    };
+
+    // find the languages once for every locale, from the list of Google's
+    // languages we already have if possible before asking the backends
+    let snapshot = if backend_names == ["google"] || run.dry_run {
+        Some(languages::known_languages(&paths))
+    } else {
+        None
+    };
+    let mut queried: Option<Vec<translate::LRLanguage>> = None;
+    let mut added = None;
+    if let Some(submatches) = matches.subcommand_matches("add-locale") {
+        let locale = submatches.value_of("new-locale").unwrap();
+        let credentials = credentials_for(locale)?;
+        add_locale(
+            &run,
+            submatches,
+            &backend_names,
+            snapshot.as_deref(),
+            credentials.as_ref(),
+        )?;
+        if !submatches.is_present("translate") {
+            return Ok(());
+        }
+        added = Some(locale.to_owned());
+    }
+    let locales: Vec<String> = match matches.values_of("locale") {
+        _ if added.is_some() => added.into_iter().collect(),
+        Some(locales) if locales.clone().any(|locale| locale == "all") => {
+            let available = match &snapshot {
+                Some(snapshot) => snapshot.clone(),
+                None => {
+                    let credentials = credentials_for("en")?;
+                    let probe = backend::Chain::create(
+                        &backend_names,
+                        credentials.as_ref(),
+                        &connection,
+                        None,
+                        "en",
+                    )?;
+                    probe.available_languages()?
+                }
+            };
+            let mut locales: Vec<String> = available
+                .into_iter()
+                .filter(|lang| {
+                    lang.support_target && lang.language_code != connection.source_language
+                })
+                .map(|lang| lang.language_code)
+                .collect();
+            locales.sort();
+            locales.dedup();
+            locales
+        }
+        Some(locales) => locales.map(str::to_owned).collect(),
+        None if !config.locales.is_empty() => config.locales.clone(),
+        None => return Err(Box::from(errors::Errors::MissingLanguage)),
+    };
+    if let Some(submatches) = matches
+        .subcommand_matches("glossary")
+        .and_then(|submatches| submatches.subcommand_matches("suggest"))
+    {
+        return suggest_glossary(matches, submatches, &config, &locales);
+    }
+    if let Some(submatches) = matches.subcommand_matches("status") {
+        return print_status(&run, submatches, &locales);
+    }
+    if let Some(submatches) = matches.subcommand_matches("export") {
+        if let Some(version) = submatches
+            .value_of("format")
+            .and_then(xliff::Version::from_arg)
+        {
+            return export_xliff(&run, submatches, &locales, version);
+        }
+        return export_sheet(&run, submatches, &locales);
+    }
+    if matches.subcommand_matches("check").is_some() {
+        return check_locales(&run, &locales);
+    }
+    if comparing.is_some() && locales.len() > 1 {
+        log::error!("only one locale can be compared at a time");
+        return Err(Box::from(errors::Errors::MissingLanguage));
+    }
+
+    let batching = match gcs_bucket(matches, &config) {
+        Some(_) if run.dry_run || comparing.is_some() => None,
+        Some(_) if backend_names != ["google"] => {
+            log::warn!("--gcs-bucket only works with the google backend on its own, ignoring");
+            None
+        }
+        Some(_) if !config.memory.enabled => {
+            log::warn!("--gcs-bucket needs the translation memory, ignoring");
+            None
+        }
+        Some(_) if run.budget.limited() => {
+            log::warn!("budgets can't be kept to with --gcs-bucket, ignoring");
+            None
+        }
+        Some(_) if connection.endpoints.location == "global" => {
+            log::warn!("batches can't be translated in the global location, ignoring --gcs-bucket");
+            None
+        }
+        bucket => bucket,
+    };
+    // when batching, every locale's texts are gathered and translated at
+    // once, so that the locales find their translations in the memory
+    let passes: &[bool] = if batching.is_some() {
+        &[true, false]
+    } else {
+        &[false]
+    };
+    let mut first_error = None;
+    let mut locale_errors = Vec::new();
+    for collecting in passes.iter().copied() {
+        if collecting {
+            *run.collecting.borrow_mut() = Some(Vec::new());
+        }
+        for locale in locales.iter() {
+            let result = credentials_for(locale).and_then(|credentials| {
+                // validate the locale and find the code the backends know it as
+                let known = snapshot
+                    .as_ref()
+                    .and_then(|snapshot| languages::normalize(locale, snapshot));
+                let language = match known {
+                    Some(language) => language,
+                    // a dry run doesn't ask the backends, so has to trust the locale
+                    None if run.dry_run => {
+                        log::warn!("`{}` isn't a language that Google supports", locale);
+                        locale.clone()
+                    }
+                    None => {
+                        if queried.is_none() {
+                            queried = Some(
+                                backend::Chain::create(
+                                    &backend_names,
+                                    credentials.as_ref(),
+                                    &connection,
+                                    None,
+                                    "en",
+                                )?
+                                .available_languages()?,
+                            );
+                        }
+                        languages::normalize(locale, queried.as_ref().unwrap()).ok_or_else(
+                            || {
+                                log::error!("none of the backends can translate into `{}`", locale);
+                                errors::Errors::InvalidLanguage
+                            },
+                        )?
+                    }
+                };
+                if &language != locale {
+                    log::info!("translating `{}` as `{}`", locale, language);
+                }
+                translate_locale(&run, locale, &language, credentials.as_ref())
+            });
+            if collecting {
+                if let Err(e) = result {
+                    log::debug!("not batching `{}`: {}", locale, e);
+                }
+                continue;
+            }
+            if let Err(e) = result {
+                if locales.len() > 1 {
+                    log::error!("failed to translate `{}`: {}", locale, e);
+                }
+                progress::error(run.progress_format, locale, &e.to_string());
+                locale_errors.push((locale.clone(), e.to_string()));
+                first_error.get_or_insert(e);
+            }
+        }
+        if let (Some(bucket), Some(wanted)) = (batching, run.collecting.borrow_mut().take()) {
+            translate_batches(matches, &config, &connection, bucket, &wanted)?;
+        }
+    }
+
+    if run
+        .reports
+        .borrow()
+        .iter()
+        .any(report::RunReport::hooks_failed)
+    {
+        first_error.get_or_insert(Box::from(errors::Errors::HookFailed));
+    }
+
+    if run.dry_run && locales.len() > 1 {
+        let (messages, characters) = run.dry_run_totals.get();
+        println!(
+            "total: {} message(s), {} character(s) across {} locales",
+            messages,
+            characters,
+            locales.len()
+        );
+    }
+
+    if let Some(path) = matches.value_of("report-expansion") {
+        if !run.dry_run && comparing.is_none() {
+            let reports = run.reports.borrow();
+            let report: BTreeMap<&str, length::LocaleExpansion> = reports
+                .iter()
+                .map(|report| {
+                    (
+                        report.locale.as_str(),
+                        length::LocaleExpansion::new(
+                            &report.expansions,
+                            config.length.max_expansion(&report.locale),
+                        ),
+                    )
+                })
+                .collect();
+            let mut contents = serde_json::to_string_pretty(&report)?;
+            contents.push('\n');
+            fs::write(path, contents)?;
+            log::info!("wrote the expansion report to {}", path);
+        }
+    }
+
+    if let Some(path) = matches.value_of("report-junit") {
+        if !run.dry_run && comparing.is_none() {
+            let mut out = BufWriter::new(fs::File::create(path)?);
+            junit::write(&mut out, &run.reports.borrow(), &locale_errors)?;
+            log::info!("wrote the JUnit report to {}", path);
+        }
+    }
+
+    if let Some(key) = signing_key(matches, &config).filter(|_| !run.dry_run && comparing.is_none())
+    {
+        if first_error.is_some() || run.budget.exhausted() {
+            log::warn!("not signing a run that didn't finish");
+        } else {
+            sign_run(&run, key)?;
+        }
+    }
+
+    if run.budget.exhausted() {
+        log::error!(
+            "stopped after {} character(s), the rest of the messages were skipped to stay within the budget",
+            run.budget.used()
+        );
+        first_error.get_or_insert(Box::from(errors::Errors::BudgetExceeded));
+    }
+
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// The key to sign the run with, if it's to be signed
+fn signing_key<'a>(matches: &'a clap::ArgMatches, config: &'a config::Config) -> Option<&'a str> {
+    matches
+        .value_of("sign")
+        .or(config.provenance.signing_key.as_deref())
+}
+
+/// Write the provenance manifest of a run, listing the source and each
+/// translation it wrote along with their hashes, signed with `key`
+fn sign_run(run: &Run, key: &str) -> Result<(), Box<dyn Error>> {
+    let key = provenance::load_private_key(key)?;
+    let from_file = arg_or_config(run.matches, "from", run.config.source.as_deref()).unwrap();
+    let reports = run.reports.borrow();
+    let mut manifest = provenance::Manifest {
+        created: chrono::Local::now().to_rfc3339(),
+        user: audit::current_user(),
+        tool: format!("tt {}", env!("CARGO_PKG_VERSION")),
+        source: provenance::FileHash::of(from_file)?,
+        files: reports
+            .iter()
+            .map(|report| output_paths(run.matches, run.config, &report.locale).1)
+            .filter(|path| path.exists())
+            .map(provenance::FileHash::of)
+            .collect::<Result<_, _>>()?,
+        translated: reports
+            .iter()
+            .map(|report| (report.locale.clone(), report.translated_count()))
+            .collect(),
+        sources: reports
+            .iter()
+            .filter(|report| !report.sources.is_empty())
+            .map(|report| (report.locale.clone(), report.sources.clone()))
+            .collect(),
+        signature: None,
+    };
+    manifest.sign(&key);
+    let mut contents = serde_json::to_string_pretty(&manifest)?;
+    contents.push('\n');
+    fs::write(&run.config.provenance.file, contents)?;
+    log::info!(
+        "signed {} file(s) in {}",
+        manifest.files.len() + 1,
+        run.config.provenance.file
+    );
+    Ok(())
+}
+
+/// Check that a provenance manifest was signed by the team's key, and that
+/// none of the files it lists have changed since
+fn verify_signature(
+    submatches: &clap::ArgMatches,
+    config: &config::Config,
+) -> Result<(), Box<dyn Error>> {
+    let path = submatches
+        .value_of("manifest")
+        .unwrap_or(&config.provenance.file);
+    let manifest: provenance::Manifest = serde_json::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| format!("invalid provenance manifest {}: {}", path, e))?;
+    let public_key = provenance::load_public_key(submatches.value_of("key").unwrap())?;
+    if let Err(e) = manifest.verify(&public_key) {
+        log::error!("{}: {}", path, e);
+        return Err(Box::from(errors::Errors::InvalidSignature));
+    }
+    let changed = manifest.changed_files();
+    for file in changed.iter() {
+        println!("{} changed since it was signed", file);
+    }
+    if !changed.is_empty() {
+        log::error!("{} file(s) don't match the signed run", changed.len());
+        return Err(Box::from(errors::Errors::InvalidSignature));
+    }
+    log::info!(
+        "{} file(s) match the run signed by {} at {}",
+        manifest.files.len() + 1,
+        manifest.user,
+        manifest.created
+    );
+    Ok(())
+}
+
+/// Work out how much of the source each locale covers
+fn locale_coverage(run: &Run, locales: &[String]) -> Result<Vec<status::Coverage>, Box<dyn Error>> {
+    let Run {
+        matches,
+        config,
+        diff,
+        ..
+    } = run;
+    let from_file = arg_or_config(matches, "from", config.source.as_deref()).unwrap();
+    let source = fs::read_to_string(from_file)?;
+    let source = catalog::continue_parsing(from_file, fluent_syntax::parser::parse(&source));
+    let source_outdated = match diff {
+        Some(diff) => Some(diff.read(Path::new(from_file))?),
+        None => None,
+    };
+    let source_outdated = source_outdated.as_ref().map(|(outdated, name)| {
+        catalog::continue_parsing(name, fluent_syntax::parser::parse(outdated))
+    });
+    let lock_file = arg_or_config(matches, "lock-file", config.lock_file.as_deref())
+        .unwrap_or(lock::DEFAULT_LOCK_FILE);
+    let lock = lock::Lock::load(lock_file)?;
+
+    let mut coverages = Vec::new();
+    for locale in locales.iter() {
+        let (_, out_path) = output_paths(matches, config, locale);
+        let target = catalog::read_or_empty(&out_path)?;
+        let target = catalog::continue_parsing(&out_path, fluent_syntax::parser::parse(&target));
+        coverages.push(match &source_outdated {
+            Some(source_outdated) => status::coverage(
+                locale,
+                &source,
+                |message| catalog::unchanged_since(source_outdated, message),
+                &target,
+                &config.markers,
+            ),
+            None => status::coverage(
+                locale,
+                &source,
+                |message| {
+                    lock.unchanged(
+                        locale,
+                        message,
+                        catalog::find_message(&target, message.id.name),
+                    )
+                },
+                &target,
+                &config.markers,
+            ),
+        });
+    }
+    Ok(coverages)
+}
+
+/// Write a spreadsheet with a row for each message of each locale, for
+/// translators who work outside of Fluent
+fn export_sheet(
+    run: &Run,
+    submatches: &clap::ArgMatches,
+    locales: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let Run {
+        matches, config, ..
+    } = run;
+    if submatches.is_present("input") {
+        return Err(Box::from(
+            "csv exports the source and the translation of each locale, so it doesn't take a file",
+        ));
+    }
+    let from_file = arg_or_config(matches, "from", config.source.as_deref()).unwrap();
+    let source = fs::read_to_string(from_file)?;
+    let source = catalog::continue_parsing(from_file, fluent_syntax::parser::parse(&source));
+    let output = submatches
+        .value_of("output")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(from_file).with_extension(sheet::FORMAT));
+
+    let mut rows = Vec::new();
+    for coverage in locale_coverage(run, locales)?.into_iter() {
+        let locale = coverage.locale.as_str();
+        let (_, out_path) = output_paths(matches, config, locale);
+        let target = catalog::read_or_empty(&out_path)?;
+        let target = catalog::continue_parsing(&out_path, fluent_syntax::parser::parse(&target));
+        for entry in source.body.iter() {
+            let message = match entry {
+                fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Message(
+                    message,
+                )) if !catalog::has_marker(&message.comment, &config.markers.no_translate) => {
+                    message
+                }
+                _ => continue,
+            };
+            let id = message.id.name;
+            let translation = catalog::find_message(&target, id);
+            let status = match coverage
+                .outdated
+                .iter()
+                .find(|(outdated, _)| outdated == id)
+            {
+                Some((_, state)) => state.name(),
+                None => match translation {
+                    Some(translation)
+                        if catalog::has_marker(
+                            &translation.comment,
+                            &config.markers.hand_translated,
+                        ) =>
+                    {
+                        catalog::State::HandTranslated.name()
+                    }
+                    Some(translation)
+                        if catalog::has_marker(
+                            &translation.comment,
+                            &config.markers.needs_review,
+                        ) =>
+                    {
+                        "needs-review"
+                    }
+                    _ => catalog::State::Translated.name(),
+                },
+            };
+            rows.push(sheet::Row {
+                locale: locale.to_owned(),
+                id: id.to_owned(),
+                comment: sheet::comment_text(&message.comment),
+                source: sheet::message_text(message),
+                translation: translation.map(sheet::message_text).unwrap_or_default(),
+                status: status.to_owned(),
+            });
+        }
+    }
+    let mut out = BufWriter::new(fs::File::create(&output)?);
+    sheet::write_csv(&mut out, &rows)?;
+    out.flush()?;
+    log::info!(
+        "exported {} message(s) of {} locale(s) into {}",
+        rows.len(),
+        locales.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// A hand translation to merge into a locale, as a Fluent message, with where
+/// it came from to report any problems with it, such as `row 4`
+struct HandTranslation {
+    place: String,
+    id: String,
+    entry: String,
+}
+
+/// Merge hand translations into the file of `locale`, marked as
+/// hand-translated, leaving out the ones that it already has and the ones that
+/// aren't valid Fluent or don't have the source's placeables. Returns how
+/// many were merged and how many were left out.
+fn merge_hand_translations(
+    matches: &clap::ArgMatches,
+    config: &config::Config,
+    source: &fluent_syntax::ast::Resource,
+    locale: &str,
+    translations: &[HandTranslation],
+) -> Result<(usize, usize), Box<dyn Error>> {
+    let entry_id = |entry: &fluent_syntax::ast::ResourceEntry| match entry {
+        fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Message(m)) => {
+            Some(m.id.name.to_owned())
+        }
+        fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Term(t)) => {
+            Some(format!("-{}", t.id.name))
+        }
+        _ => None,
+    };
+    let (_, out_path) = output_paths(matches, config, locale);
+    let existing_text = catalog::read_or_empty(&out_path)?;
+    let existing =
+        catalog::continue_parsing(&out_path, fluent_syntax::parser::parse(&existing_text));
+
+    let (mut merged, mut rejected) = (0, 0);
+    let mut replaced: Vec<(&str, String)> = Vec::new();
+    let mut added: HashMap<&str, String> = HashMap::new();
+    for hand in translations.iter() {
+        let id = hand.id.as_str();
+        let translation = catalog::find_message(&existing, id);
+        let parsed = fluent_syntax::parser::parse(hand.entry.as_str());
+        let imported = parsed
+            .as_ref()
+            .ok()
+            .and_then(|parsed| catalog::find_message(parsed, id));
+        if let (Some(translation), Some(imported)) = (translation, imported) {
+            if sheet::message_text(translation) == sheet::message_text(imported) {
+                continue;
+            }
+        }
+        let problems = match (catalog::find_message(source, id), &parsed, imported) {
+            (None, _, _) => vec![format!("`{}` isn't in the source", id)],
+            (Some(_), Err((_, errors)), _) => vec![format!("it isn't valid Fluent: {:?}", errors)],
+            (Some(message), Ok(_), Some(imported)) => hand_translation_problems(message, imported),
+            (Some(_), Ok(_), None) => vec!["it isn't a message".to_owned()],
+        };
+        if !problems.is_empty() {
+            for problem in problems.iter() {
+                log::error!("{} ({}, `{}`): {}", hand.place, locale, id, problem);
+            }
+            rejected += 1;
+            continue;
+        }
+
+        // it's no longer the backend's translation, if it ever was
+        let mut text = String::new();
+        let comment = translation.and_then(|message| message.comment.as_ref());
+        if let Some(fluent_syntax::ast::Comment::Comment { content }) = comment {
+            for line in content.iter().filter(|line| {
+                !line.contains(&config.markers.needs_review)
+                    && !line.contains(&config.markers.machine_translated)
+                    && !line.contains(&config.markers.hand_translated)
+            }) {
+                text.push_str(format!("# {}\n", line).replace("# \n", "#\n").as_str());
+            }
+        }
+        text.push_str(&format!("# {}\n", config.markers.hand_translated));
+        text.push_str(&hand.entry);
+        if translation.is_some() {
+            replaced.push((id, text));
+        } else {
+            added.insert(id, text.trim_end().to_owned());
+        }
+        merged += 1;
+    }
+
+    if !added.is_empty() {
+        // keep whatever the locale has that the source doesn't
+        let mut ids: Vec<String> = source.body.iter().filter_map(entry_id).collect();
+        for id in existing.body.iter().filter_map(entry_id) {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        fs::write(&out_path, merge::merge(&existing_text, &ids, &added, None))?;
+    }
+    for (id, text) in replaced.iter() {
+        replace_entry(&out_path, id, Some(text))?;
+    }
+    Ok((merged, rejected))
+}
+
+/// Merge the translations of a filled-in spreadsheet into each locale's file
+/// as hand-translated, leaving out the rows whose translations aren't valid
+/// Fluent or don't have the source's placeables
+fn import_sheet(
+    matches: &clap::ArgMatches,
+    submatches: &clap::ArgMatches,
+    config: &config::Config,
+) -> Result<(), Box<dyn Error>> {
+    let input = Path::new(submatches.value_of("input").unwrap());
+    let rows = sheet::read_csv(&fs::read_to_string(input)?)?;
+    let from_file = arg_or_config(matches, "from", config.source.as_deref()).unwrap();
+    let source = fs::read_to_string(from_file)?;
+    let source = catalog::continue_parsing(from_file, fluent_syntax::parser::parse(&source));
+    let only: Vec<&str> = matches
+        .values_of("locale")
+        .map(|locales| locales.filter(|locale| *locale != "all").collect())
+        .unwrap_or_default();
+
+    let mut by_locale: BTreeMap<&str, Vec<HandTranslation>> = BTreeMap::new();
+    for row in rows.iter() {
+        by_locale
+            .entry(row.locale.as_str())
+            .or_default()
+            .push(HandTranslation {
+                place: format!("row {}", row.row),
+                id: row.id.clone(),
+                entry: sheet::message_entry(&row.id, &row.translation),
+            });
+    }
+    let (mut imported, mut rejected) = (0, 0);
+    for (locale, translations) in by_locale.into_iter() {
+        if !only.is_empty() && !only.contains(&locale) {
+            continue;
+        }
+        let (merged, left_out) =
+            merge_hand_translations(matches, config, &source, locale, &translations)?;
+        imported += merged;
+        rejected += left_out;
+    }
+    log::info!(
+        "imported {} translation(s) from {}",
+        imported,
+        input.display()
+    );
+    if rejected > 0 {
+        return Err(Box::from(format!(
+            "{} row(s) of {} weren't imported",
+            rejected,
+            input.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Write the messages that each locale needs translated, or that need review,
+/// as XLIFF for CAT tools, with a unit for each value and attribute
+fn export_xliff(
+    run: &Run,
+    submatches: &clap::ArgMatches,
+    locales: &[String],
+    version: xliff::Version,
+) -> Result<(), Box<dyn Error>> {
+    let Run {
+        matches, config, ..
+    } = run;
+    if submatches.is_present("input") {
+        return Err(Box::from(
+            "XLIFF exports the messages that each locale needs from the source, so it doesn't take a file",
+        ));
+    }
+    let from_file = arg_or_config(matches, "from", config.source.as_deref()).unwrap();
+    let source = fs::read_to_string(from_file)?;
+    let source = catalog::continue_parsing(from_file, fluent_syntax::parser::parse(&source));
+    let template = submatches
+        .value_of("output")
+        .map(str::to_owned)
+        .unwrap_or_else(|| {
+            format!(
+                "{}.{{locale}}.xlf",
+                Path::new(from_file).with_extension("").display()
+            )
+        });
+    if locales.len() > 1 && !template.contains("{locale}") {
+        return Err(Box::from(
+            "XLIFF has a file for each locale, so --output needs a {locale} in it",
+        ));
+    }
+    let unit = |id: String, pattern: &fluent_syntax::ast::Pattern, note: &str| {
+        let text = catalog::source_text(pattern);
+        let placeables = (1..=placeables::markers(&text).len())
+            .map(|n| writer::fill_placeables(pattern, &placeables::marker(n)))
+            .collect();
+        xliff::Unit {
+            id,
+            source: text,
+            placeables,
+            note: Some(note.to_owned()).filter(|note| !note.is_empty()),
+        }
+    };
+
+    for coverage in locale_coverage(run, locales)?.into_iter() {
+        let locale = coverage.locale.as_str();
+        let (_, out_path) = output_paths(matches, config, locale);
+        let target = catalog::read_or_empty(&out_path)?;
+        let target = catalog::continue_parsing(&out_path, fluent_syntax::parser::parse(&target));
+        let mut units = Vec::new();
+        let mut count = 0;
+        for entry in source.body.iter() {
+            let message = match entry {
+                fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Message(
+                    message,
+                )) if !catalog::has_marker(&message.comment, &config.markers.no_translate)
+                    && !catalog::has_marker(&message.comment, &config.markers.lang_name) =>
+                {
+                    message
+                }
+                _ => continue,
+            };
+            let id = message.id.name;
+            let outdated = coverage.outdated.iter().any(|(outdated, _)| outdated == id);
+            let needs_review = catalog::find_message(&target, id)
+                .map(|translation| {
+                    catalog::has_marker(&translation.comment, &config.markers.needs_review)
+                })
+                .unwrap_or(false);
+            if !outdated && !needs_review {
+                continue;
+            }
+            let note = sheet::comment_text(&message.comment);
+            if let Some(value) = &message.value {
+                units.push(unit(id.to_owned(), value, &note));
+            }
+            for attribute in message.attributes.iter() {
+                units.push(unit(
+                    format!("{}.{}", id, attribute.id.name),
+                    &attribute.value,
+                    &note,
+                ));
+            }
+            count += 1;
+        }
+
+        let output = PathBuf::from(template.replace("{locale}", locale));
+        let mut out = BufWriter::new(fs::File::create(&output)?);
+        xliff::write(
+            &mut out,
+            version,
+            from_file,
+            &run.connection.source_language,
+            locale,
+            &units,
+        )?;
+        out.flush()?;
+        log::info!(
+            "{}: exported {} message(s) into {}",
+            locale,
+            count,
+            output.display()
+        );
+    }
+    Ok(())
+}
+
+/// Merge the translations of a completed XLIFF file into its locale as
+/// hand-translated, leaving out the messages whose translations aren't valid
+/// Fluent or don't have the source's placeables
+fn import_xliff(
+    matches: &clap::ArgMatches,
+    submatches: &clap::ArgMatches,
+    config: &config::Config,
+) -> Result<(), Box<dyn Error>> {
+    let input = Path::new(submatches.value_of("input").unwrap());
+    let translated = xliff::read(&fs::read_to_string(input)?)?;
+    // CAT tools often want a region, such as `fr-FR` for `fr`, so `--locale`
+    // can say which file it goes into
+    let locale = match matches
+        .values_of("locale")
+        .map(|locales| locales.collect::<Vec<_>>())
+    {
+        Some(locales) if locales.len() == 1 && locales[0] != "all" => locales[0].to_owned(),
+        Some(_) => return Err(Box::from("give one --locale to import the XLIFF into")),
+        None => translated.locale.clone(),
+    };
+    let from_file = arg_or_config(matches, "from", config.source.as_deref()).unwrap();
+    let source = fs::read_to_string(from_file)?;
+    let source = catalog::continue_parsing(from_file, fluent_syntax::parser::parse(&source));
+
+    // the units of each message, in the order they're first seen
+    let mut order: Vec<&str> = Vec::new();
+    let mut units: HashMap<&str, HashMap<Option<&str>, &str>> = HashMap::new();
+    for (id, text) in translated.units.iter() {
+        let (message, attribute) = match id.split_once('.') {
+            Some((message, attribute)) => (message, Some(attribute)),
+            None => (id.as_str(), None),
+        };
+        if !units.contains_key(message) {
+            order.push(message);
+        }
+        units
+            .entry(message)
+            .or_default()
+            .insert(attribute, text.as_str());
+    }
+
+    let mut rejected = 0;
+    let mut translations = Vec::new();
+    for id in order.into_iter() {
+        let texts = &units[id];
+        let message = match catalog::find_message(&source, id) {
+            Some(message) => message,
+            None => {
+                log::error!("unit `{}` ({}): `{}` isn't in the source", id, locale, id);
+                rejected += 1;
+                continue;
+            }
+        };
+        let mut missing = Vec::new();
+        let value = match &message.value {
+            Some(_) => match texts.get(&None) {
+                Some(text) => Some((*text).to_owned()),
+                None => {
+                    missing.push("its value".to_owned());
+                    None
+                }
+            },
+            None => None,
+        };
+        let mut attributes = Vec::new();
+        for attribute in message.attributes.iter() {
+            match texts.get(&Some(attribute.id.name)) {
+                Some(text) => attributes.push((*text).to_owned()),
+                None => missing.push(format!("`.{}`", attribute.id.name)),
+            }
+        }
+        if !missing.is_empty() {
+            log::error!(
+                "unit `{}` ({}): {} has no translation",
+                id,
+                locale,
+                missing.join(", ")
+            );
+            rejected += 1;
+            continue;
+        }
+        let mut entry: Vec<u8> = Vec::new();
+        writer::write_translated_message(
+            &mut entry,
+            message,
+            &catalog::Segments {
+                value,
+                attributes,
+                comment: None,
+                kept: Vec::new(),
+                variants: Vec::new(),
+                plurals: None,
+            },
+        )?;
+        writeln!(entry)?;
+        let entry = String::from_utf8(entry)?;
+        if !placeables::markers(&entry).is_empty() {
+            log::error!(
+                "unit `{}` ({}): it has a placeholder that isn't in the source",
+                id,
+                locale
+            );
+            rejected += 1;
+            continue;
+        }
+        translations.push(HandTranslation {
+            place: format!("unit `{}`", id),
+            id: id.to_owned(),
+            entry,
+        });
+    }
+    let (imported, left_out) =
+        merge_hand_translations(matches, config, &source, &locale, &translations)?;
+    rejected += left_out;
+    log::info!(
+        "{}: imported {} translation(s) from {}",
+        locale,
+        imported,
+        input.display()
+    );
+    if rejected > 0 {
+        return Err(Box::from(format!(
+            "{} message(s) of {} weren't imported",
+            rejected,
+            input.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Describe how a hand translation differs from the source `message` in its
+/// placeables and attributes
+fn hand_translation_problems(
+    message: &fluent_syntax::ast::Message,
+    translation: &fluent_syntax::ast::Message,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    match (&message.value, &translation.value) {
+        (Some(pattern), Some(translated)) => {
+            problems.extend(placeables::differences(pattern, translated))
+        }
+        (Some(_), None) => problems.push("it has no value".to_owned()),
+        (None, Some(_)) => problems.push("the source has no value".to_owned()),
+        (None, None) => {}
+    }
+    for attribute in message.attributes.iter() {
+        match translation
+            .attributes
+            .iter()
+            .find(|a| a.id.name == attribute.id.name)
+        {
+            Some(translated) => problems.extend(
+                placeables::differences(&attribute.value, &translated.value)
+                    .into_iter()
+                    .map(|problem| format!("{} in `.{}`", problem, attribute.id.name)),
+            ),
+            None => problems.push(format!("`.{}` is missing", attribute.id.name)),
+        }
+    }
+    for attribute in translation.attributes.iter() {
+        if !message
+            .attributes
+            .iter()
+            .any(|a| a.id.name == attribute.id.name)
+        {
+            problems.push(format!("`.{}` isn't in the source", attribute.id.name));
+        }
+    }
+    problems
+}
+
+/// Print how much of the source each locale covers
+fn print_status(
+    run: &Run,
+    submatches: &clap::ArgMatches,
+    locales: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let coverages = locale_coverage(run, locales)?;
+    if submatches.is_present("json") {
+        println!("{}", serde_json::to_string_pretty(&coverages)?);
+    } else {
+        status::print_table(&coverages, run.palette);
+    }
+    Ok(())
+}
+
+/// Set up a new locale: check that the backends can translate into it, create
+/// its file (from a copy of `--from-locale` if given), add it to the config's
+/// locales and show its status
+fn add_locale(
+    run: &Run,
+    submatches: &clap::ArgMatches,
+    backend_names: &[&str],
+    snapshot: Option<&[translate::LRLanguage]>,
+    credentials: Option<&(String, String)>,
+) -> Result<(), Box<dyn Error>> {
+    let Run {
+        matches,
+        config,
+        connection,
+        ..
+    } = run;
+    let locale = submatches.value_of("new-locale").unwrap();
+    let queried;
+    let available = match snapshot {
+        Some(snapshot) if languages::normalize(locale, snapshot).is_some() => snapshot,
+        _ => {
+            queried = backend::Chain::create(backend_names, credentials, connection, None, "en")?
+                .available_languages()?;
+            &queried[..]
+        }
+    };
+    let language = languages::normalize(locale, available).ok_or_else(|| {
+        log::error!("none of the backends can translate into `{}`", locale);
+        errors::Errors::InvalidLanguage
+    })?;
+    let display_name = available
+        .iter()
+        .find(|lang| lang.language_code == language)
+        .map(|lang| lang.display_name.clone())
+        .unwrap_or_else(|| language.clone());
+
+    let (out_dir, out_path) = output_paths(matches, config, locale);
+    let listed = config.locales.iter().any(|l| l == locale);
+    if listed && out_path.exists() {
+        log::error!(
+            "`{}` is already in the config's locales, and {} exists",
+            locale,
+            out_path.display()
+        );
+        return Err(Box::from(errors::Errors::LocaleExists));
+    }
+
+    if out_path.exists() {
+        log::info!("{} already exists, leaving it as it is", out_path.display());
+    } else {
+        fs::create_dir_all(out_path.parent().unwrap_or(&out_dir))?;
+        match submatches.value_of("from-locale") {
+            Some(sibling) => {
+                let (_, sibling_path) = output_paths(matches, config, sibling);
+                fs::copy(&sibling_path, &out_path)
+                    .map_err(|e| format!("failed to copy {}: {}", sibling_path.display(), e))?;
+                let lock_file = arg_or_config(matches, "lock-file", config.lock_file.as_deref())
+                    .unwrap_or(lock::DEFAULT_LOCK_FILE);
+                let mut lock = lock::Lock::load(lock_file)?;
+                lock.copy_locale(sibling, locale);
+                lock.save(lock_file)?;
+                log::info!(
+                    "created {} from the `{}` translations",
+                    out_path.display(),
+                    sibling
+                );
+            }
+            None => {
+                let from_file = arg_or_config(matches, "from", config.source.as_deref()).unwrap();
+                fs::write(
+                    &out_path,
+                    format!(
+                        "### {} ({}) translation of {}, made with tt\n\n",
+                        display_name, locale, from_file
+                    ),
+                )?;
+                log::info!("created {}", out_path.display());
+            }
+        }
+    }
+
+    if !listed {
+        let config_path = matches
+            .value_of("config")
+            .unwrap_or(config::DEFAULT_CONFIG_FILE);
+        let text = catalog::read_or_empty(config_path)?;
+        let mut locales = config.locales.clone();
+        locales.push(locale.to_owned());
+        fs::write(
+            config_path,
+            toml::set_top_level(
+                &text,
+                "locales",
+                &toml::write_value(&serde_json::json!(locales)),
+            ),
+        )?;
+        log::info!("added `{}` to the locales in {}", locale, config_path);
+    }
+
+    status::print_table(&locale_coverage(run, &[locale.to_owned()])?, run.palette);
+    Ok(())
+}
+
+/// Fail if any locale has messages that are missing, stale, or hand-translated
+/// from an older version of the source, that are the same as the source or that
+/// have a placeable's marker left in them, listing each of them
+fn check_locales(run: &Run, locales: &[String]) -> Result<(), Box<dyn Error>> {
+    let coverages = locale_coverage(run, locales)?;
+    let mut problems = 0;
+    for coverage in coverages.iter() {
+        for (id, state) in coverage.outdated.iter() {
+            let problem = match state {
+                catalog::State::Missing => run.palette.failed("missing"),
+                catalog::State::Stale => run.palette.stale("stale"),
+                catalog::State::StaleHandTranslated => {
+                    run.palette.stale("hand-translated from an older source")
+                }
+                catalog::State::HandTranslated | catalog::State::Translated => continue,
+            };
+            println!("{}: {} is {}", coverage.locale, id, problem);
+            problems += 1;
+        }
+    }
+
+    let from_file = arg_or_config(run.matches, "from", run.config.source.as_deref()).unwrap();
+    let source = fs::read_to_string(from_file)?;
+    let source = catalog::continue_parsing(from_file, fluent_syntax::parser::parse(&source));
+    for locale in locales.iter() {
+        let (_, out_path) = output_paths(run.matches, run.config, locale);
+        let target = catalog::read_or_empty(&out_path)?;
+        let target = catalog::continue_parsing(&out_path, fluent_syntax::parser::parse(&target));
+        let untranslated =
+            run.config
+                .qa
+                .untranslated_messages(&source, &target, &run.config.markers);
+        for id in untranslated.into_iter() {
+            println!(
+                "{}: {} is {}",
+                locale,
+                id,
+                run.palette.failed("untranslated")
+            );
+            problems += 1;
+        }
+        for id in qa::leftover_markers(&source, &target).into_iter() {
+            println!(
+                "{}: {} has {}",
+                locale,
+                id,
+                run.palette.failed("a leftover placeable marker")
+            );
+            problems += 1;
+        }
+    }
+    if problems > 0 {
+        log::error!("{} message(s) are out of date or broken", problems);
+        return Err(Box::from(errors::Errors::OutOfDate));
+    }
+    log::info!("{} locale(s) are up to date", coverages.len());
+    Ok(())
+}
+
+/// Everything that stays the same while translating each of the locales
+struct Run<'a> {
+    matches: &'a clap::ArgMatches<'a>,
+    config: &'a config::Config,
+    /// the timeouts, certificates and endpoints the backends connect with
+    connection: &'a backend::Connection,
+    backend_names: &'a [&'a str],
+    /// the outdated English translation file to diff against
+    diff: Option<catalog::DiffSource>,
+    protector: protect::Protector,
+    mime_type: Option<translate::MimeType>,
+    progress_format: progress::Format,
+    /// how long a message can take before it's reported as slow
+    deadline: Duration,
+    /// how many requests can be sent to the backends at once
+    concurrency: concurrency::Concurrency,
+    /// only list what would be translated
+    dry_run: bool,
+    /// ask whether to write each translation as it comes back
+    interactive: bool,
+    /// the messages and characters that would be translated, summed over the
+    /// locales, for `--dry-run`
+    dry_run_totals: std::cell::Cell<(usize, usize)>,
+    /// the characters that can be sent to the backends, over all the locales
+    budget: budget::Budget,
+    /// the texts that each locale needs translated, while they're being
+    /// gathered to send all at once for `--gcs-bucket`
+    collecting: std::cell::RefCell<Option<Vec<gcs::Wanted>>>,
+    /// the `compare` subcommand's arguments, if comparing backends
+    comparing: Option<&'a clap::ArgMatches<'a>>,
+    /// the report of each locale that was translated, for `--report-junit`
+    reports: std::cell::RefCell<Vec<report::RunReport>>,
+    palette: color::Palette,
+    /// the revision of the source file, when it's recorded in the provenance
+    /// manifest or above translations
+    source_revision: Option<String>,
+}
+
+/// The texts of a message as they're sent to the backends: in sentence case if
+/// they're short, since backends tend to keep the casing of English, and with
+/// their protected spans taken out. Returns the casing of each text to put back
+/// afterwards, the texts, and the spans taken out of each.
+fn prepare_texts(
+    config: &config::Config,
+    protector: &protect::Protector,
+    source_language: &str,
+    values: &[&str],
+) -> (Vec<casing::Style>, Vec<String>, Vec<Vec<String>>) {
+    let styles: Vec<casing::Style> = values
+        .iter()
+        .map(|value| config.casing.detect(value))
+        .collect();
+    let (protected, spans) = values
+        .iter()
+        .zip(styles.iter())
+        .map(|(value, style)| {
+            // protected before casing, so that terms are found as they're written
+            let (protected, spans) = protector.protect(value);
+            (config.casing.prepare(&protected, *style, source_language), spans)
+        })
+        .unzip();
+    (styles, protected, spans)
+}
+
+/// Translate a message as HTML if any of its texts has markup in it
+fn detect_mime_type(protected: &[&str]) -> translate::MimeType {
+    if protected
+        .iter()
+        .any(|p| translate::MimeType::detect(p) == translate::MimeType::Html)
+    {
+        translate::MimeType::Html
+    } else {
+        translate::MimeType::Text
+    }
+}
+
+/// The MIME type that `message` is marked to be translated as, which wins
+/// over `--mime-type`
+fn marked_mime_type(
+    config: &config::Config,
+    message: Option<&fluent_syntax::ast::Message>,
+) -> Option<translate::MimeType> {
+    match message {
+        Some(message) if catalog::has_marker(&message.comment, &config.markers.plain) => {
+            Some(translate::MimeType::Text)
+        }
+        _ => None,
+    }
+}
+
+/// A message's id, its texts, the MIME type it's marked to be sent as and its
+/// `tt-context`
+type Sendable<'m> = (
+    &'m str,
+    Vec<String>,
+    Option<translate::MimeType>,
+    Option<String>,
+);
+
+/// What came back for each message that was sent ahead, and how long it took
+/// if it was sent on its own
+type Ahead<'m> = HashMap<&'m str, (backend::Outcome, Option<Duration>)>;
+
+/// A message to send ahead, and its texts as they're sent
+type Queued<'m> = (&'m str, Vec<String>);
+
+/// How a request is sent: as a MIME type, and in a `tt-context` if it has one
+type SentAs<'m> = (translate::MimeType, Option<&'m str>);
+
+/// Send the texts of `messages` to the backends in as few requests as they
+/// allow, up to `--concurrency` of them at once, ahead of going through the
+/// messages one at a time. Messages whose translation can be reused, or that
+/// repeat another's texts, aren't sent. Returns what came back for each
+/// message that was sent, and every message that was paid for; the messages
+/// of a request for several of them that failed are left to be translated on
+/// their own.
+fn translate_ahead<'m>(
+    run: &Run,
+    messages: &[Sendable<'m>],
+    translator: &backend::Chain,
+    glossary: &Option<translate::GlossaryConfig>,
+    memory: &mut memory::Memory,
+    lookup: &memory::Lookup,
+    progress: &mut progress::Progress,
+) -> (Ahead<'m>, HashSet<&'m str>) {
+    let mut translated = HashMap::new();
+    let mut paid = HashSet::new();
+    let limit = match translator.batch_limit() {
+        Some(limit) => limit,
+        // one message per request, but several requests at once
+        None if run.concurrency.most() > 1 => backend::BatchLimit {
+            texts: 1,
+            chars: usize::MAX,
+        },
+        None => return (translated, paid),
+    };
+    let names = translator.names();
+
+    let mut queued: HashSet<(Vec<String>, translate::MimeType, Option<&str>)> = HashSet::new();
+    // the messages to send as each MIME type, and in each context
+    let mut requests: BTreeMap<SentAs, Vec<Queued>> = BTreeMap::new();
+    for (id, values, marked, context) in messages.iter() {
+        let values: Vec<&str> = values.iter().map(String::as_str).collect();
+        let (_, protected, _) = prepare_texts(
+            run.config,
+            &run.protector,
+            &run.connection.source_language,
+            &values,
+        );
+        let texts: Vec<&str> = protected.iter().map(String::as_str).collect();
+        let context = context.as_deref();
+        let mime_type = marked
+            .or(run.mime_type)
+            .unwrap_or_else(|| detect_mime_type(&texts));
+        if memory
+            .recall(&lookup.for_message(mime_type, context), &names, &texts)
+            .is_some()
+        {
+            continue;
+        }
+        // repeats are given the translation of the first
+        if !queued.insert((protected.clone(), mime_type, context)) {
+            continue;
+        }
+        if !run
+            .budget
+            .spend(values.iter().map(|value| value.chars().count()).sum())
+        {
+            continue;
+        }
+        paid.insert(*id);
+        requests
+            .entry((mime_type, context))
+            .or_default()
+            .push((*id, protected));
+    }
+
+    let mut batches: Vec<(SentAs, &[Queued])> = Vec::new();
+    for (sent_as, queued) in requests.iter() {
+        let sizes: Vec<(usize, usize)> = queued
+            .iter()
+            .map(|(_, texts)| (texts.len(), texts.iter().map(|t| t.chars().count()).sum()))
+            .collect();
+        for batch in limit.batches(&sizes) {
+            batches.push((*sent_as, &queued[batch]));
+        }
+    }
+
+    let next = std::sync::atomic::AtomicUsize::new(0);
+    // the workers take turns from the limiter, which with `auto` lets more of
+    // them send at once until the backend pushes back
+    let limiter = concurrency::Limiter::new(run.concurrency);
+    std::thread::scope(|scope| {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        for _ in 0..run.concurrency.most().min(batches.len()) {
+            let sender = sender.clone();
+            let (next, batches, limiter) = (&next, &batches, &limiter);
+            scope.spawn(move || loop {
+                let turn = limiter.acquire();
+                let i = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                let ((mime_type, context), batch) = match batches.get(i) {
+                    Some(batch) => batch,
+                    None => {
+                        limiter.release(turn, None);
+                        break;
+                    }
+                };
+                let texts: Vec<&str> = batch
+                    .iter()
+                    .flat_map(|(_, texts)| texts.iter().map(String::as_str))
+                    .collect();
+                let started = std::time::Instant::now();
+                let outcome = translator.translate(&texts, *mime_type, glossary, *context);
+                let elapsed = started.elapsed();
+                limiter.release(turn, Some(elapsed));
+                if sender.send((i, outcome, elapsed)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        // the results are handled here as they come back, so that the
+        // progress bar keeps moving
+        for (i, outcome, elapsed) in receiver {
+            let ((mime_type, context), batch) = batches[i];
+            let lookup = lookup.for_message(mime_type, context);
+            if let [(id, texts)] = batch {
+                if let Some((translated, backend)) = &outcome.translation {
+                    let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+                    memory.remember(&lookup, backend, &texts, translated);
+                }
+                progress.message_translated(
+                    id,
+                    outcome.translation.as_ref().map(|(_, backend)| *backend),
+                );
+                translated.insert(*id, (outcome, Some(elapsed)));
+                continue;
+            }
+            let texts: usize = batch.iter().map(|(_, texts)| texts.len()).sum();
+            let (all, backend) = match outcome.translation {
+                Some((all, backend)) if all.len() == texts => (all, backend),
+                _ => {
+                    log::debug!(
+                        "translating the {} message(s) of a failed request one at a time",
+                        batch.len()
+                    );
+                    continue;
+                }
+            };
+            log::debug!(
+                "translated {} message(s) in one request to {} in {:.1}s",
+                batch.len(),
+                backend,
+                elapsed.as_secs_f64()
+            );
+            let mut all = all.into_iter();
+            for (id, texts) in batch.iter() {
+                let message: Vec<String> = all.by_ref().take(texts.len()).collect();
+                let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+                memory.remember(&lookup, backend, &texts, &message);
+                progress.message_translated(id, Some(backend));
+                let outcome = backend::Outcome {
+                    translation: Some((message, backend)),
+                    failures: Vec::new(),
+                };
+                translated.insert(*id, (outcome, None));
+            }
+        }
+    });
+    (translated, paid)
+}
+
+/// Translate the source file into a single locale, which the backends know as
+/// `language`
+fn translate_locale(
+    run: &Run,
+    locale: &str,
+    language: &str,
+    credentials: Option<&(String, String)>,
+) -> Result<(), Box<dyn Error>> {
+    let Run {
+        matches,
+        config,
+        connection,
+        backend_names,
+        diff,
+        protector,
+        mime_type,
+        progress_format,
+        deadline,
+        concurrency: _,
+        dry_run,
+        interactive,
+        dry_run_totals,
+        budget,
+        collecting,
+        comparing,
+        reports,
+        palette,
+        source_revision,
+    } = run;
+    let from_file = arg_or_config(matches, "from", config.source.as_deref()).unwrap();
+    let (out_dir, out_path) = output_paths(matches, config, locale);
+    let out_dir = out_dir.as_path();
+
+    // translating into the source language would only reformat the source,
+    // which is rarely what was meant
+    let same_file = match (fs::canonicalize(from_file), fs::canonicalize(&out_path)) {
+        (Ok(from), Ok(out)) => from == out,
+        _ => false,
+    };
+    if (language == connection.source_language || same_file)
+        && comparing.is_none()
+        && !dry_run
+        && !matches.is_present("allow-source-target")
+    {
+        if same_file {
+            log::error!(
+                "{} is the source file, pass --allow-source-target to overwrite it anyway",
+                out_path.display()
+            );
+        } else {
+            log::error!(
+                "`{}` is the source locale, pass --allow-source-target to write {} anyway",
+                locale,
+                out_path.display()
+            );
+        }
+        return Err(Box::from(errors::Errors::SourceTarget));
+    }
+
+    if !dry_run {
+        fs::create_dir_all(out_path.parent().unwrap_or(out_dir))?;
+    }
+
+    // don't touch frozen locales unless explicitly told to, comparing and dry
+    // runs don't write anything so they're always allowed
+    let today = chrono::Local::today().naive_local();
+    if let Some(freeze) = config
+        .freeze
+        .frozen(locale, today, out_dir)?
+        .filter(|_| comparing.is_none() && !dry_run)
+    {
+        match matches.value_of("override-freeze") {
+            // the override is recorded when the locale is translated
+            Some(_) if collecting.borrow().is_some() => {}
+            Some(reason) => {
+                log::warn!("overriding {} for locale `{}`: {}", freeze, locale, reason);
+                audit::record(
+                    &config.audit.file,
+                    "override-freeze",
+                    locale,
+                    &format!("{} (overrode {})", reason, freeze),
+                )?;
+            }
+            None => {
+                log::error!(
+                    "locale `{}` is frozen by {}, pass --override-freeze <REASON> to modify it anyway",
+                    locale,
+                    freeze
+                );
+                return Err(Box::from(errors::Errors::Frozen));
+            }
+        }
+    }
+
+    let glossary = match (
+        arg_or_config(matches, "glossary", config.glossary_for(locale)),
+        credentials,
+    ) {
+        (Some(_), Some(_)) if connection.endpoints.glossary_location == "global" => {
+            log::warn!("glossaries can't be used in the global location, ignoring");
+            None
+        }
+        (Some(glossary), Some((_, project_id))) => Some(format!(
+            "projects/{}/locations/{}/glossaries/{}",
+            project_id,
+            connection.endpoints.glossary_location,
+            glossary
+        )),
+        (Some(_), None) => {
+            log::warn!("glossaries are only supported by the google backend, ignoring");
+            None
+        }
+        (None, _) => None,
+    };
+    let glossary = glossary.as_ref().map(|glossary| translate::GlossaryConfig {
+        glossary,
+        ignore_case: Some(matches.is_present("ignore-case") || config.ignore_case),
+    });
+    let model = arg_or_config(matches, "model", config.model_for(locale));
+    let glossary_entries = if config.glossary_check.enabled {
+        glossary::entries(config.glossary_check.file.replace("{locale}", locale))?
+    } else {
+        Vec::new()
+    };
+
+    // catalogs in other formats are translated as Fluent, in a working file
+    // that's written back to the catalog once the locale is translated
+    let source_format = adapter::format_of(Path::new(from_file));
+    let (source, catalog_ids) = match source_format {
+        Some(format) => {
+            adapter::read_source(Path::new(from_file), format, &config.markers.context)?
+        }
+        None => (std::fs::read_to_string(from_file)?, ids::IdMap::default()),
+    };
+    let source_outdated = match (diff, source_format) {
+        (Some(diff), Some(format)) => {
+            let (outdated, name) = diff.read(Path::new(from_file))?;
+            let (outdated, _) = adapter::source_fluent(
+                &outdated,
+                &name,
+                format,
+                &catalog_ids,
+                &config.markers.context,
+            )?;
+            Some((outdated, name))
+        }
+        (Some(diff), None) => Some(diff.read(Path::new(from_file))?),
+        (None, _) => None,
+    };
+    let catalog_path = adapter::format_of(&out_path).map(|format| (out_path.clone(), format));
+    let (out_path, target_existing) = match &catalog_path {
+        Some((path, format)) => (
+            adapter::working_path(locale),
+            adapter::read_translations(
+                path,
+                *format,
+                &catalog_ids,
+                &config.markers.needs_review,
+            )?,
+        ),
+        None => {
+            let target_existing = catalog::read_or_empty(&out_path)?;
+            (out_path, target_existing)
+        }
+    };
+    // the file that's written, rather than the working file
+    let written_path = match &catalog_path {
+        Some((path, _)) => path.clone(),
+        None => out_path.clone(),
+    };
+
+    let source = catalog::continue_parsing(from_file, fluent_syntax::parser::parse(&source));
+    let source_outdated = source_outdated.as_ref().map(|(outdated, name)| {
+        catalog::continue_parsing(name, fluent_syntax::parser::parse(outdated))
+    });
+    let target_existing =
+        catalog::continue_parsing(&out_path, fluent_syntax::parser::parse(&target_existing));
+    // with `--expand-terms`, the backend sees the values of the terms that
+    // messages refer to
+    let expansions = if matches.is_present("expand-terms") {
+        expand::Expansions::new(&source, &config.markers.expand)
+    } else {
+        expand::Expansions::default()
+    };
+    // with `--translate-terms`, terms are translated as messages with ids like
+    // `-tagline`, which are written back as terms
+    let term_ids = if matches.is_present("translate-terms") {
+        catalog::translated_term_ids(&source, &config.markers.no_translate)
+    } else {
+        Vec::new()
+    };
+    let source = catalog::terms_as_messages(source, &term_ids);
+    let source = expansions.keep(source, &config.markers.no_translate);
+    let source_outdated = source_outdated.map(|outdated| {
+        let outdated = catalog::terms_as_messages(outdated, &term_ids);
+        expansions.keep(outdated, &config.markers.no_translate)
+    });
+    let target_existing = catalog::terms_as_messages(target_existing, &term_ids);
+
+    // an explicit diff takes precedence over the lock file
+    let lock_file = arg_or_config(matches, "lock-file", config.lock_file.as_deref())
+        .unwrap_or(lock::DEFAULT_LOCK_FILE);
+    let mut lock = lock::Lock::load(lock_file)?;
+    let unchanged = |message: &fluent_syntax::ast::Message| match &source_outdated {
+        Some(source_outdated) => catalog::unchanged_since(source_outdated, message),
+        None => lock.unchanged(
+            locale,
+            message,
+            catalog::find_message(&target_existing, message.id.name),
+        ),
+    };
+    let forced = |id: &str| {
+        matches.is_present("force")
+            || matches
+                .values_of("force-ids")
+                .map(|mut patterns| patterns.any(|pattern| priority::id_matches(pattern, id)))
+                .unwrap_or(false)
+    };
+    let unchanged_parts = |message: &fluent_syntax::ast::Message| match &source_outdated {
+        Some(source_outdated) => catalog::unchanged_parts(source_outdated, message),
+        None => lock.unchanged_parts(locale, message),
+    };
+    let mut pending = catalog::collect_pending(
+        &source,
+        unchanged,
+        unchanged_parts,
+        forced,
+        &target_existing,
+        &config.markers,
+    );
+    for (id, pending) in pending.iter_mut() {
+        if let catalog::Pending::Text(segments) = pending {
+            if let Some(message) = catalog::find_message(&source, id) {
+                expansions.show_in_segments(message, segments);
+            }
+        }
+    }
+    // messages left out by `--only`, `--exclude` and `--section` stay pending
+    // for next time
+    let id_filter = filter::IdFilter::new(
+        matches.values_of("only").into_iter().flatten(),
+        matches.values_of("exclude").into_iter().flatten(),
+    )?
+    .in_sections(matches.values_of("section").into_iter().flatten())?;
+    let sections = filter::sections(&source);
+    let (pending, filtered_out): (Vec<_>, Vec<_>) = pending
+        .into_iter()
+        .partition(|(id, _)| id_filter.includes(id, sections.get(id).copied()));
+    let filtered_out: Vec<&str> = filtered_out.into_iter().map(|(id, _)| id).collect();
+    if !filtered_out.is_empty() {
+        log::info!(
+            "{}: leaving out {} message(s) that --only, --exclude or --section filter out",
+            locale,
+            filtered_out.len()
+        );
+    }
+    let stale_hand_translated: Vec<String> = status::coverage(
+        locale,
+        &source,
+        unchanged,
+        &target_existing,
+        &config.markers,
+    )
+    .outdated
+    .into_iter()
+    .filter(|(_, state)| *state == catalog::State::StaleHandTranslated)
+    .map(|(id, _)| id)
+    .collect();
+    let (pending, high_priority) =
+        priority::prioritize(pending, &source, &config.markers.priority, &config.priority);
+
+    if *dry_run {
+        let mut messages = 0;
+        let mut characters = 0;
+        // repeated texts are only sent once
+        let mut first_with: HashMap<Vec<&str>, &str> = HashMap::new();
+        for (id, pending) in pending.iter() {
+            // new messages are added, and existing translations are replaced
+            let shown = if catalog::find_message(&target_existing, id).is_some() {
+                palette.stale(id)
+            } else {
+                palette.added(id)
+            };
+            match pending {
+                catalog::Pending::Text(segments) => {
+                    let texts = segments.texts();
+                    if let Some(first) = first_with.get(&texts) {
+                        println!("{}: {} (the same as {})", locale, shown, first);
+                        messages += 1;
+                        continue;
+                    }
+                    let count: usize = texts.iter().map(|t| t.chars().count()).sum();
+                    println!("{}: {} ({} characters)", locale, shown, count);
+                    messages += 1;
+                    characters += count;
+                    first_with.insert(texts, id);
+                }
+                catalog::Pending::LanguageName => {
+                    println!("{}: {} (language name)", locale, shown);
+                    messages += 1;
+                }
+                catalog::Pending::Empty => {}
+            }
+        }
+        println!(
+            "{}: {} message(s), {} character(s)",
+            locale, messages, characters
+        );
+        let (total_messages, total_characters) = dry_run_totals.get();
+        dry_run_totals.set((total_messages + messages, total_characters + characters));
+        return Ok(());
+    }
+
+    if let Some(submatches) = comparing {
+        let backends = backend_names
+            .iter()
+            .map(|name| backend::create(name, credentials, connection, model, language))
+            .collect::<Result<Vec<_>, _>>()?;
+        // compare the value and each attribute on their own rows
+        let mut texts: Vec<(String, &fluent_syntax::ast::Pattern, String, _, _)> = Vec::new();
+        for (id, pending) in pending {
+            if let catalog::Pending::Text(segments) = pending {
+                let message = catalog::find_message(&source, id).unwrap();
+                let mime_type = marked_mime_type(config, Some(message)).or(*mime_type);
+                let context = catalog::marker_value(&message.comment, &config.markers.context);
+                if let (Some(pattern), Some(text)) = (&message.value, segments.value) {
+                    texts.push((id.to_owned(), pattern, text, mime_type, context.clone()));
+                }
+                for (attribute, text) in message.attributes.iter().zip(segments.attributes) {
+                    texts.push((
+                        format!("{}.{}", id, attribute.id.name),
+                        &attribute.value,
+                        text,
+                        mime_type,
+                        context.clone(),
+                    ));
+                }
+            }
+        }
+
+        let mut progress =
+            progress::Progress::start(*progress_format, locale, texts.len() * backends.len());
+        let rows: Vec<compare::Row> = texts
+            .into_iter()
+            .map(|(id, pattern, text, mime_type, context)| {
+                let translations = backends
+                    .iter()
+                    .map(|backend| {
+                        let translated = compare::translate_cell(
+                            backend.as_ref(),
+                            protector,
+                            mime_type,
+                            &glossary,
+                            context.as_deref(),
+                            &text,
+                        );
+                        progress.message_translated(&id, Some(backend.name()));
+                        writer::fill_placeables(pattern, &translated)
+                    })
+                    .collect();
+                compare::Row {
+                    id,
+                    source: writer::fill_placeables(pattern, &text),
+                    translations,
+                }
+            })
+            .collect();
+        progress.finish(rows.len(), 0);
+
+        let mut out: Box<dyn Write> = match submatches.value_of("output") {
+            Some(path) => Box::new(BufWriter::new(fs::File::create(path)?)),
+            None => Box::new(std::io::stdout()),
+        };
+        match submatches.value_of("format") {
+            Some("html") => compare::write_html(&mut out, locale, backend_names, &rows)?,
+            _ => compare::write_csv(&mut out, backend_names, &rows)?,
+        }
+        return Ok(());
+    }
+
+    let translator = backend::Chain::create(backend_names, credentials, connection, model, language)?;
+    log::debug!("translating with {}", translator.names().join(", "));
+    let mut memory = if config.memory.enabled {
+        memory::Memory::load(memory_file(matches, config))
+    } else {
+        // still kept for the run, so that repeated texts are translated once
+        memory::Memory::default()
+    };
+    let lookup = memory::Lookup {
+        backends: backend_names,
+        source_language: &connection.source_language,
+        target_language: language,
+        glossary: glossary.as_ref().map(|glossary| glossary.glossary),
+        model,
+        fuzzy_threshold: config
+            .memory
+            .fuzzy_threshold
+            .filter(|_| config.memory.enabled),
+        // set for each message as it's looked up
+        mime_type: translate::MimeType::Text,
+        context: None,
+    };
+
+    if let Some(wanted) = collecting.borrow_mut().as_mut() {
+        let credentials = match credentials {
+            Some(credentials) => credentials,
+            None => return Ok(()),
+        };
+        let names = translator.names();
+        for (id, pending) in pending.iter() {
+            let segments = match pending {
+                catalog::Pending::Text(segments) => segments,
+                _ => continue,
+            };
+            let values = segments.texts();
+            let (_, protected, _) =
+                prepare_texts(config, protector, &connection.source_language, &values);
+            let texts: Vec<&str> = protected.iter().map(String::as_str).collect();
+            let mime_type = marked_mime_type(config, catalog::find_message(&source, id))
+                .or(*mime_type)
+                .unwrap_or_else(|| detect_mime_type(&texts));
+            let lookup = lookup.for_message(mime_type, None);
+            if memory.recall(&lookup, &names, &texts).is_some()
+                || !texts.iter().all(|text| gcs::sendable(text))
+            {
+                continue;
+            }
+            for text in texts.into_iter() {
+                wanted.push(gcs::Wanted {
+                    credentials: credentials.clone(),
+                    language: language.to_owned(),
+                    model: model.map(|model| {
+                        translate::model_name(&credentials.1, &connection.endpoints.location, model)
+                    }),
+                    glossary: glossary
+                        .as_ref()
+                        .map(|g| (g.glossary.to_owned(), g.ignore_case.unwrap_or_default())),
+                    mime_type,
+                    text: text.to_owned(),
+                    key: lookup.key(text, "google"),
+                });
+            }
+        }
+        return Ok(());
+    }
+
+    let mut pending_translations: Vec<(&str, Option<catalog::Segments>)> = Vec::new();
+    for (id, pending) in pending {
+        let value = match pending {
+            catalog::Pending::LanguageName => Some(catalog::Segments {
+                value: Some(match translator.get_lang_name() {
+                    Ok(t) => t,
+                    Err(e) => {
+                        log::warn!("failed to get language name: {:?}", e);
+                        "<INSERT LANGUAGE NAME HERE>".to_owned()
+                    }
+                }),
+                attributes: Vec::new(),
+                comment: None,
+                kept: Vec::new(),
+                variants: Vec::new(),
+                plurals: None,
+            }),
+            catalog::Pending::Text(segments) => Some(segments),
+            catalog::Pending::Empty => None,
+        };
+        pending_translations.push((id, value));
+    }
+
+    log::debug!("pending translations: {:?}", pending_translations);
+
+    let mut progress =
+        progress::Progress::start(*progress_format, locale, pending_translations.len());
+    if *interactive {
+        // the bar would be drawn over the questions
+        progress.hide();
+    }
+
+    let mut report = report::RunReport::new(locale);
+    // the translated texts to spellcheck once they're all back
+    let mut spellchecked: Vec<(&str, String)> = Vec::new();
+    let protected_terms = protector.spans();
+    report.stale_hand_translated = stale_hand_translated;
+    let order: Vec<&str> = pending_translations.iter().map(|(id, _)| *id).collect();
+    // messages are sent to the backends in as few requests as they allow,
+    // the high-priority ones first so that they can be written before the rest
+    let mut sendable: Vec<Vec<Sendable>> = vec![Vec::new(), Vec::new()];
+    for (i, (id, segments)) in pending_translations.iter().enumerate() {
+        if let Some(segments) = segments {
+            let texts = segments.texts().into_iter().map(str::to_owned).collect();
+            let marked = marked_mime_type(config, catalog::find_message(&source, id));
+            let context = catalog::find_message(&source, id).and_then(|message| {
+                catalog::marker_value(&message.comment, &config.markers.context)
+            });
+            sendable[usize::from(i >= high_priority)].push((*id, texts, marked, context));
+        }
+    }
+    let (mut ahead, mut paid) = translate_ahead(
+        run,
+        &sendable[usize::from(high_priority == 0)],
+        &translator,
+        &glossary,
+        &mut memory,
+        &lookup,
+        &mut progress,
+    );
+    let mut translations: HashMap<&str, Option<catalog::Segments>> = HashMap::new();
+    // the translations that were skipped with `--interactive`
+    let mut skipped: Vec<&str> = Vec::new();
+    for id in filtered_out.iter() {
+        // keep any existing translation, however outdated
+        if catalog::find_message(&target_existing, id).is_none() {
+            translations.insert(id, None);
+        }
+    }
+    for (i, (id, segments)) in pending_translations.into_iter().enumerate() {
+        // write the high-priority messages out before starting on the rest,
+        // so they're there even if the run doesn't finish
+        if i == high_priority && i > 0 {
+            let remaining = &order[high_priority..];
+            let mut partial = translations.clone();
+            for id in remaining.iter() {
+                // leave any existing translation until its new one is ready
+                if catalog::find_message(&target_existing, id).is_none() {
+                    partial.insert(id, None);
+                }
+            }
+            save_translations(
+                matches,
+                config,
+                &out_path,
+                &source,
+                &target_existing,
+                &partial,
+            )?;
+            if let Some((path, format)) = &catalog_path {
+                let partial = fs::read_to_string(&out_path)?;
+                let partial =
+                    catalog::continue_parsing(&out_path, fluent_syntax::parser::parse(&partial));
+                adapter::write_translations(
+                    path,
+                    *format,
+                    locale,
+                    &source,
+                    &partial,
+                    &catalog_ids,
+                    &config.markers.needs_review,
+                )?;
+            }
+            let mut skip = unfinished_ids(&report);
+            skip.extend_from_slice(remaining);
+            skip.extend_from_slice(&filtered_out);
+            skip.extend_from_slice(&skipped);
+            lock.update(
+                locale,
+                &source,
+                &target_existing,
+                &config.markers.hand_translated,
+                &skip,
+            );
+            lock.save(lock_file)?;
+            log::info!(
+                "wrote {} high-priority message(s) to {}",
+                high_priority,
+                written_path.display()
+            );
+            let (more, more_paid) = translate_ahead(
+                run,
+                &sendable[1],
+                &translator,
+                &glossary,
+                &mut memory,
+                &lookup,
+                &mut progress,
+            );
+            ahead.extend(more);
+            paid.extend(more_paid);
+        }
+
+        let translation = if let Some(segments) = segments {
+            // the value and attributes are sent together so the backend
+            // can keep their terminology consistent
+            let values = segments.texts();
+            let chars = values.iter().map(|value| value.chars().count()).sum();
+            let (styles, protected, spans) =
+                prepare_texts(config, protector, &connection.source_language, &values);
+            let protected: Vec<&str> = protected.iter().map(String::as_str).collect();
+            // texts that were sent ahead with other messages, or that a
+            // backend in the chain has translated before, don't need to be
+            // paid for again, and nor do ones close enough to them for a
+            // reviewer to fix up
+            let context = catalog::find_message(&source, id).and_then(|message| {
+                catalog::marker_value(&message.comment, &config.markers.context)
+            });
+            let mime_type = marked_mime_type(config, catalog::find_message(&source, id))
+                .or(*mime_type)
+                .unwrap_or_else(|| detect_mime_type(&protected));
+            let lookup = lookup.for_message(mime_type, context.as_deref());
+            let mut sent = ahead.remove(id);
+            let mut ready = match sent {
+                Some(_) => None,
+                None => memory.recall(&lookup, &translator.names(), &protected),
+            };
+            if sent.is_none() && ready.is_none() && !paid.contains(id) && !budget.spend(chars) {
+                report.skipped.push(id.to_owned());
+                progress.message_translated(id, None);
+                // keep any existing translation, however outdated
+                if catalog::find_message(&target_existing, id).is_none() {
+                    translations.insert(id, None);
+                }
+                continue;
+            }
+            // the patterns that the texts came from, to check the placeables of
+            // the translations against
+            let patterns: Vec<&fluent_syntax::ast::Pattern> = catalog::find_message(&source, id)
+                .map(|message| {
+                    let parts = segments.sent(
+                        message
+                            .value
+                            .iter()
+                            .chain(message.attributes.iter().map(|a| &a.value)),
+                    );
+                    let variants: Vec<&fluent_syntax::ast::Pattern> = parts
+                        .iter()
+                        .flat_map(|pattern| catalog::variant_patterns(pattern))
+                        .collect();
+                    parts.into_iter().chain(variants).collect()
+                })
+                .unwrap_or_default();
+            // language names are already in the language, so can come back as they are
+            let is_language_name = catalog::find_message(&source, id)
+                .map(|message| catalog::has_marker(&message.comment, &config.markers.lang_name))
+                .unwrap_or(false);
+            let max_length = match catalog::find_message(&source, id)
+                .map(|message| length::limit(&message.comment, &config.markers.max_length))
+            {
+                Some(Ok(limit)) => limit,
+                Some(Err(e)) => {
+                    log::warn!("ignoring the length limit of `{}`: {}", id, e);
+                    None
+                }
+                None => None,
+            };
+            let mut retried = false;
+            let mut fuzzy = false;
+            let (translated, backend, mismatches) = loop {
+                let outcome = match ready.take() {
+                    Some((translated, backend, recall)) => {
+                        match recall {
+                            memory::Recall::Repeated => report.repeated += 1,
+                            memory::Recall::Remembered => report.remembered += 1,
+                            memory::Recall::Fuzzy => {
+                                log::debug!("reusing a close match's translation for `{}`", id);
+                                report.remembered += 1;
+                                report.fuzzy += 1;
+                                fuzzy = true;
+                            }
+                        }
+                        backend::Outcome {
+                            translation: Some((translated, backend)),
+                            failures: Vec::new(),
+                        }
+                    }
+                    None => {
+                        let (outcome, elapsed) = match sent.take() {
+                            Some(sent) => sent,
+                            None => {
+                                let started = std::time::Instant::now();
+                                let outcome = translator.translate(
+                                    &protected,
+                                    mime_type,
+                                    &glossary,
+                                    context.as_deref(),
+                                );
+                                if let Some((translated, backend)) = &outcome.translation {
+                                    memory.remember(&lookup, backend, &protected, translated);
+                                }
+                                (outcome, Some(started.elapsed()))
+                            }
+                        };
+                        // messages sent together don't have a time of their own
+                        if let Some(elapsed) = elapsed {
+                            report.record_timing(
+                                report::MessageTiming {
+                                    id: id.to_owned(),
+                                    elapsed,
+                                    payload_size: protected.iter().map(|p| p.len()).sum(),
+                                    retries: outcome.failures.len(),
+                                },
+                                *deadline,
+                            );
+                        }
+                        outcome
+                    }
+                };
+                let backend = outcome.translation.as_ref().map(|(_, backend)| *backend);
+                if !outcome.failures.is_empty() {
+                    report.record_failures(id, outcome.failures, backend);
+                }
+                let translated: Vec<String> = match outcome.translation {
+                    Some((t, backend)) => {
+                        report.record_translation(backend);
+                        t.iter()
+                            .zip(spans.iter())
+                            .zip(styles.iter())
+                            .map(|((t, spans), style)| {
+                                config
+                                    .casing
+                                    .apply(&protector.restore(t, spans), *style, locale)
+                            })
+                            .collect()
+                    }
+                    None => values.iter().map(|value| (*value).to_owned()).collect(),
+                };
+                let translated: Vec<String> = patterns
+                    .iter()
+                    .zip(translated)
+                    .map(|(pattern, translated)| match placeables::order(pattern, &translated) {
+                        placeables::Order::Moved => {
+                            log::debug!("the placeables of `{}` were moved around", id);
+                            translated
+                        }
+                        placeables::Order::Repaired(repaired) => {
+                            log::info!(
+                                "the placeables of `{}` were renumbered in `{}`, putting them back in order",
+                                id,
+                                translated
+                            );
+                            repaired
+                        }
+                        placeables::Order::Same | placeables::Order::Broken => translated,
+                    })
+                    .collect();
+                let mismatches: Vec<String> = patterns
+                    .iter()
+                    .zip(translated.iter())
+                    .flat_map(|(pattern, translated)| placeables::mismatches(pattern, translated))
+                    .collect();
+                if mismatches.is_empty()
+                    || backend.is_none()
+                    || retried
+                    || config.placeables.on_mismatch != placeables::OnMismatch::Retry
+                    || !budget.spend(chars)
+                {
+                    break (translated, backend, mismatches);
+                }
+                log::info!("translating `{}` again since its placeables changed", id);
+                retried = true;
+            };
+            for detail in mismatches.iter() {
+                log::warn!("placeables changed translating `{}`: {}", id, detail);
+                report.record_issue(id, report::IssueKind::PlaceableMismatch, detail.clone());
+            }
+            for (value, translated) in values.iter().zip(translated.iter()) {
+                for span in protector.missing(value, translated) {
+                    log::warn!("protected span `{}` was lost translating `{}`", span, id);
+                    report.record_issue(
+                        id,
+                        report::IssueKind::LostSpan,
+                        format!("`{}` is missing from `{}`", span, translated),
+                    );
+                }
+                for change in entities::changes(value, translated) {
+                    log::warn!("suspicious entity change translating `{}`: {}", id, change);
+                    report.record_issue(id, report::IssueKind::EntityChange, change);
+                }
+                for change in markup::changes(value, translated) {
+                    log::warn!("markup changed translating `{}`: {}", id, change);
+                    report.record_issue(id, report::IssueKind::MarkupChange, change);
+                }
+                if backend.is_some() {
+                    for violation in glossary::violations(&glossary_entries, value, translated) {
+                        log::warn!("glossary ignored translating `{}`: {}", id, violation);
+                        report.record_issue(id, report::IssueKind::GlossaryViolation, violation);
+                    }
+                }
+                if let Some(limit) = max_length {
+                    let length = length::length(translated);
+                    if length > limit {
+                        report.record_issue(
+                            id,
+                            report::IssueKind::TooLong,
+                            format!(
+                                "`{}` is {} characters, over the limit of {}",
+                                translated, length, limit
+                            ),
+                        );
+                    }
+                }
+                if backend.is_some() {
+                    let expansion = length::Expansion::new(id, value, translated);
+                    if let Some(ratio) = config.length.too_expanded(locale, &expansion) {
+                        log::warn!(
+                            "`{}` is {:.2}x the length of the source after translating it",
+                            id,
+                            ratio
+                        );
+                        report.record_issue(
+                            id,
+                            report::IssueKind::Expanded,
+                            format!(
+                                "`{}` is {:.2}x the length of the source, over {:.2}x",
+                                translated,
+                                ratio,
+                                config.length.max_expansion(locale).unwrap_or_default()
+                            ),
+                        );
+                    }
+                    report.expansions.push(expansion);
+                    if config.spellcheck.enabled {
+                        spellchecked
+                            .push((id, spellcheck::checked_text(translated, protected_terms)));
+                    }
+                }
+                if backend.is_some()
+                    && !is_language_name
+                    && config.qa.untranslated(id, value, translated)
+                {
+                    log::warn!("`{}` is the same as the source after translating it", id);
+                    report.record_issue(
+                        id,
+                        report::IssueKind::Untranslated,
+                        format!("`{}` is the same as the source", translated),
+                    );
+                }
+            }
+            let leftover: Vec<String> = patterns
+                .iter()
+                .zip(translated.iter())
+                .filter_map(|(pattern, translated)| {
+                    qa::leftover_in_translation(pattern, translated)
+                })
+                .collect();
+            let broken = patterns
+                .iter()
+                .zip(translated.iter())
+                .any(|(pattern, translated)| {
+                    placeables::order(pattern, translated) == placeables::Order::Broken
+                });
+            let invalid: Vec<&String> = patterns
+                .iter()
+                .zip(translated.iter())
+                .filter(|(pattern, translated)| !placeables::valid(pattern, translated))
+                .map(|(_, translated)| translated)
+                .collect();
+            progress.message_translated(id, backend);
+            if backend.is_none() {
+                // no backend could translate it, so rather than writing the
+                // English, keep what was there and try again next run
+                if catalog::find_message(&target_existing, id).is_none() {
+                    translations.insert(id, None);
+                }
+                continue;
+            }
+            if !leftover.is_empty() {
+                // never write a broken message, keep what was there instead
+                for filled in leftover.into_iter() {
+                    log::error!(
+                        "{}: `{}` still has a placeable's marker in `{}`, so it isn't written",
+                        locale,
+                        id,
+                        filled
+                    );
+                    report.record_issue(
+                        id,
+                        report::IssueKind::LeftoverMarker,
+                        format!("a placeable's marker is left in `{}`", filled),
+                    );
+                }
+                if catalog::find_message(&target_existing, id).is_none() {
+                    translations.insert(id, None);
+                }
+                continue;
+            }
+            if !invalid.is_empty() {
+                for translated in invalid.into_iter() {
+                    log::error!(
+                        "{}: `{}` isn't valid Fluent once its placeables are back in `{}`, so it isn't written",
+                        locale,
+                        id,
+                        translated
+                    );
+                    report.record_issue(
+                        id,
+                        report::IssueKind::InvalidFluent,
+                        format!("`{}` isn't valid Fluent", translated),
+                    );
+                }
+                if catalog::find_message(&target_existing, id).is_none() {
+                    translations.insert(id, None);
+                }
+                continue;
+            }
+            let mut segments = segments.with_texts(translated);
+            // gettext numbers plural forms by its own rules rather than by
+            // CLDR categories, so they're left as they are
+            segments.plurals = plurals::categories(locale)
+                .filter(|_| !matches!(catalog_path, Some((_, formats::Format::Po))));
+            let mut comments = Vec::new();
+            // a hand translation is only replaced when forced to, so someone
+            // should check that the new one is as good
+            let was_hand_translated = catalog::find_message(&target_existing, id)
+                .map(|existing| {
+                    catalog::has_marker(&existing.comment, &config.markers.hand_translated)
+                })
+                .unwrap_or(false);
+            if was_hand_translated {
+                log::warn!(
+                    "{}: `{}` was hand-translated, marking its new translation as needing review",
+                    locale,
+                    id
+                );
+            }
+            // the same translations that are queued for review
+            let queued = report.failures.iter().any(|f| f.id == id)
+                || report.issues.iter().any(|i| i.id == id)
+                || catalog::find_message(&source, id)
+                    .map(|message| catalog::has_marker(&message.comment, &config.markers.review))
+                    .unwrap_or(false);
+            if fuzzy
+                || broken
+                || was_hand_translated
+                || (queued && config.review.mark)
+                || (!mismatches.is_empty()
+                    && config.placeables.on_mismatch == placeables::OnMismatch::Review)
+            {
+                comments.push(config.markers.needs_review.clone());
+            }
+            if let (Some(revision), Some(message)) =
+                (source_revision, catalog::find_message(&source, id))
+            {
+                let version = provenance::SourceVersion {
+                    revision: revision.clone(),
+                    hash: lock::hash_message(message),
+                };
+                if config.provenance.annotate {
+                    comments.push(format!("{}: {}", config.markers.source, version));
+                }
+                report.sources.insert(id.to_owned(), version);
+            }
+            if let (true, Some(backend)) = (config.provenance.mark_machine_translated, backend) {
+                comments.push(format!(
+                    "{} ({}, {})",
+                    config.markers.machine_translated,
+                    backend,
+                    chrono::Local::today().format("%Y-%m-%d")
+                ));
+            }
+            if !comments.is_empty() {
+                segments.comment = Some(comments.join("\n"));
+            }
+            match catalog::find_message(&source, id) {
+                Some(message) if *interactive => {
+                    match ask_translation(config, locale, message, segments)? {
+                        Some(segments) => Some(segments),
+                        None => {
+                            skipped.push(id);
+                            // keep any existing translation, however outdated
+                            if catalog::find_message(&target_existing, id).is_some() {
+                                continue;
+                            }
+                            None
+                        }
+                    }
+                }
+                _ => Some(segments),
+            }
+        } else {
+            progress.message_translated(id, None);
+            None
+        };
+        translations.insert(id, translation);
+    }
+    if !spellchecked.is_empty() {
+        let texts: Vec<String> = spellchecked.iter().map(|(_, text)| text.clone()).collect();
+        match config.spellcheck.misspellings(locale, &texts) {
+            Ok(misspelled) => {
+                for ((id, _), words) in spellchecked.iter().zip(misspelled) {
+                    for word in words.into_iter() {
+                        log::warn!("{}: `{}` may be misspelled in `{}`", locale, word, id);
+                        report.record_issue(
+                            id,
+                            report::IssueKind::Misspelling,
+                            format!("`{}` may be misspelled", word),
+                        );
+                    }
+                }
+            }
+            Err(e) => log::warn!("{}: not spellchecking: {}", locale, e),
+        }
+    }
+    progress.finish(report.translated_count(), report.failed_count());
+    if let Err(e) = memory.save() {
+        log::warn!("failed to save the translation memory: {}", e);
+    }
+    report.print();
+
+    save_translations(
+        matches,
+        config,
+        &out_path,
+        &source,
+        &target_existing,
+        &translations,
+    )?;
+    queue_reviews(config, locale, &source, &order, &translations, &report)?;
+    if let Some(text) = &config.machine_translated_notice.text {
+        let id = config.machine_translated_notice.id.as_str();
+        if catalog::find_message(&source, id).is_some() {
+            log::warn!(
+                "the source has its own `{}` message, so no notice is added",
+                id
+            );
+        } else {
+            let notice = machine_translated_notice(
+                config,
+                &source,
+                &target_existing,
+                &translations,
+                text,
+                |text| {
+                    if !budget.spend(text.chars().count()) {
+                        return None;
+                    }
+                    let outcome =
+                        translator.translate(&[text], translate::MimeType::Text, &glossary, None);
+                    outcome.translation.map(|(mut t, _)| t.remove(0))
+                },
+            );
+            replace_entry(&out_path, id, notice.as_deref())?;
+        }
+    }
+    let written = catalog::read_or_empty(&out_path)?;
+    let written = catalog::continue_parsing(&out_path, fluent_syntax::parser::parse(&written));
+    if let Some((path, format)) = &catalog_path {
+        adapter::write_translations(
+            path,
+            *format,
+            locale,
+            &source,
+            &written,
+            &catalog_ids,
+            &config.markers.needs_review,
+        )?;
+        fs::remove_file(&out_path)?;
+    }
+    let leftover = qa::leftover_markers(&source, &written);
+    if !leftover.is_empty() {
+        log::error!(
+            "{}: {} still has placeable markers in {}",
+            locale,
+            leftover.join(", "),
+            written_path.display()
+        );
+        return Err(Box::from(errors::Errors::LeftoverMarkers));
+    }
+    let mut skip = unfinished_ids(&report);
+    skip.extend_from_slice(&filtered_out);
+    skip.extend_from_slice(&skipped);
+    lock.update(
+        locale,
+        &source,
+        &target_existing,
+        &config.markers.hand_translated,
+        &skip,
+    );
+    lock.save(lock_file)?;
+
+    for template in config.hooks.post_locale.iter() {
+        let command = hooks::command_line(template, &written_path, locale);
+        log::debug!("running {}", command);
+        let result = hooks::run(&command);
+        if let Err(e) = &result {
+            log::error!("{}: `{}` failed: {}", locale, command, e);
+        }
+        report.hooks.push(report::HookRun {
+            command,
+            error: result.err(),
+        });
+    }
+    reports.borrow_mut().push(report);
 
-    Ok((token, project_id))
+    Ok(())
 }
 
-fn continue_parsing<'ast, P: AsRef<Path>>(
-    path: P,
-    r: Result<
-        fluent_syntax::ast::Resource<'ast>,
-        (
-            fluent_syntax::ast::Resource<'ast>,
-            Vec<fluent_syntax::parser::ParserError>,
-        ),
-    >,
-) -> fluent_syntax::ast::Resource<'ast> {
-    match r {
-        Ok(r) => r,
-        Err((r, errs)) => {
-            for err in errs {
-                log::warn!("parse error in {}: {:?}", path.as_ref().display(), err);
-            }
-            r
+/// The messages that no backend could translate, that were over the budget or
+/// that had a placeable's marker left in them or weren't valid Fluent, which are left for the next run
+fn unfinished_ids(report: &report::RunReport) -> Vec<&str> {
+    report
+        .failures
+        .iter()
+        .filter(|f| f.translated_by.is_none())
+        .map(|f| f.id.as_str())
+        .chain(report.skipped.iter().map(String::as_str))
+        .chain(
+            report
+                .issues
+                .iter()
+                .filter(|i| {
+                    i.kind == report::IssueKind::LeftoverMarker
+                        || i.kind == report::IssueKind::InvalidFluent
+                })
+                .map(|i| i.id.as_str()),
+        )
+        .collect()
+}
+
+/// The machine translation notice to add to a locale, unless every message is
+/// hand-translated. The notice is only translated again when other messages
+/// were, otherwise the existing one is kept.
+fn machine_translated_notice<F: FnOnce(&str) -> Option<String>>(
+    config: &config::Config,
+    source: &fluent_syntax::ast::Resource,
+    target_existing: &fluent_syntax::ast::Resource,
+    translations: &HashMap<&str, Option<catalog::Segments>>,
+    text: &str,
+    translate: F,
+) -> Option<String> {
+    let id = config.machine_translated_notice.id.as_str();
+    let hand_translated = source.body.iter().all(|entry| match entry {
+        fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Message(m)) => {
+            !translations.contains_key(m.id.name)
+                && catalog::find_message(target_existing, m.id.name)
+                    .map(|e| catalog::has_marker(&e.comment, &config.markers.hand_translated))
+                    .unwrap_or(false)
+        }
+        _ => true,
+    });
+    if hand_translated {
+        return None;
+    }
+
+    let existing = catalog::find_message(target_existing, id).and_then(|m| m.value.as_ref());
+    if let Some(existing) = existing {
+        if translations.values().all(Option::is_none) {
+            let mut written: Vec<u8> = format!("{} = ", id).into_bytes();
+            writer::write_pattern(&mut written, existing).expect("can write pattern to memory");
+            written.push(b'\n');
+            return Some(String::from_utf8(written).expect("valid utf-8"));
+        }
+    }
+    let translated = translate(text).unwrap_or_else(|| {
+        log::warn!("failed to translate the notice, writing it in English");
+        text.to_owned()
+    });
+    Some(formats::fluent_message(id, &translated))
+}
+
+/// Replace the entry `id` in a Fluent file with `entry` where it is, adding
+/// it to the end if it's new, or remove it
+fn replace_entry(path: &Path, id: &str, entry: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let existing = fs::read_to_string(path)?;
+    let mut text = String::new();
+    let mut replaced = false;
+    for chunk in merge::split(&existing) {
+        if chunk.id != Some(id) {
+            text.push_str(chunk.text);
+        } else if let (Some(entry), false) = (entry, replaced) {
+            // keep the blank lines after the entry
+            let blank = &chunk.text[chunk.text.trim_end().len()..];
+            text.push_str(entry.trim_end());
+            text.push_str(if blank.is_empty() { "\n" } else { blank });
+            replaced = true;
+        }
+    }
+    if let (Some(entry), false) = (entry, replaced) {
+        while !text.is_empty() && !text.ends_with("\n\n") {
+            text.push('\n');
         }
+        text.push_str(entry);
+    }
+    fs::write(path, text)?;
+    Ok(())
+}
+
+/// Queue the translations that no backend could translate, that fell back to
+/// another backend or have QA issues, and that are flagged in the source
+fn queue_reviews(
+    config: &config::Config,
+    locale: &str,
+    source: &fluent_syntax::ast::Resource,
+    order: &[&str],
+    translations: &HashMap<&str, Option<catalog::Segments>>,
+    report: &report::RunReport,
+) -> Result<(), Box<dyn Error>> {
+    let mut queue = review::Queue::load(&config.review.file)?;
+    let mut queued = 0;
+    for id in order.iter() {
+        let (value, variants, plurals) = match translations.get(id) {
+            Some(Some(segments)) => (
+                segments.value.as_ref(),
+                segments.variants.first(),
+                segments.plurals,
+            ),
+            _ => (None, None, None),
+        };
+        let message = catalog::find_message(source, id);
+        let (message, pattern, value) = match (message, value) {
+            (Some(message), Some(value)) => match &message.value {
+                Some(pattern) => (message, pattern, value),
+                None => continue,
+            },
+            _ => continue,
+        };
+        let failures = report.failures.iter().filter(|f| f.id == *id);
+        let reason = if failures.clone().any(|f| f.translated_by.is_none()) {
+            review::Reason::Failed
+        } else if failures.count() > 0 || report.issues.iter().any(|i| i.id == *id) {
+            review::Reason::LowConfidence
+        } else if catalog::has_marker(&message.comment, &config.markers.review) {
+            review::Reason::Flagged
+        } else {
+            continue;
+        };
+        let mut source_text: Vec<u8> = Vec::new();
+        writer::write_pattern(&mut source_text, pattern)?;
+        queue.add(review::Item {
+            locale: locale.to_owned(),
+            id: (*id).to_owned(),
+            reason,
+            source: String::from_utf8(source_text)?.trim().to_owned(),
+            translation: writer::fill_variants(
+                pattern,
+                value,
+                &mut variants.into_iter().flatten(),
+                plurals,
+            ),
+            state: review::State::Pending,
+            edit: None,
+        });
+        queued += 1;
+    }
+    if queued > 0 {
+        queue.save(&config.review.file)?;
+        log::info!(
+            "{}: queued {} translation(s) for review, see `tt review`",
+            locale,
+            queued
+        );
     }
+    Ok(())
 }
 
-fn find_message<'ast>(
-    resource: &'ast fluent_syntax::ast::Resource<'ast>,
+/// Replace the comments saying that the message `id` needs review or was
+/// machine-translated with the hand-translated marker, keeping its
+/// translation, returning whether it needed review
+fn approve_entry(
+    config: &config::Config,
+    out_path: &Path,
+    locale: &str,
     id: &str,
-) -> Option<&'ast fluent_syntax::ast::Message<'ast>> {
-    for entry in resource.body.iter() {
-        if let fluent_syntax::ast::ResourceEntry::Entry(entry) = entry {
-            if let fluent_syntax::ast::Entry::Message(message) = &entry {
-                if message.id.name == id {
-                    return Some(message);
-                }
-            }
+) -> Result<bool, Box<dyn Error>> {
+    let existing = catalog::read_or_empty(out_path)?;
+    let existing = catalog::continue_parsing(out_path, fluent_syntax::parser::parse(&existing));
+    let message = match catalog::find_message(&existing, id) {
+        Some(message) if catalog::has_marker(&message.comment, &config.markers.needs_review) => {
+            message
         }
+        _ => return Ok(false),
+    };
+
+    let mut entry: Vec<u8> = Vec::new();
+    if let Some(fluent_syntax::ast::Comment::Comment { content }) = &message.comment {
+        for line in content.iter().filter(|line| {
+            !line.contains(&config.markers.needs_review)
+                && !line.contains(&config.markers.machine_translated)
+        }) {
+            write!(entry, "{}", format!("# {}\n", line).replace("# \n", "#\n"))?;
+        }
+    }
+    if !catalog::has_marker(&message.comment, &config.markers.hand_translated) {
+        writeln!(entry, "# {}", config.markers.hand_translated)?;
     }
-    None
+    write!(entry, "{} =", id)?;
+    if let Some(value) = &message.value {
+        write!(entry, " ")?;
+        writer::write_pattern(&mut entry, value)?;
+    }
+    writer::write_attributes(&mut entry, &message.attributes)?;
+    writeln!(entry)?;
+    let entry = String::from_utf8(entry)?;
+    if let Err((_, errors)) = fluent_syntax::parser::parse(entry.as_str()) {
+        return Err(Box::from(format!(
+            "{}: `{}` wouldn't be valid Fluent once approved: {:?}",
+            locale, id, errors
+        )));
+    }
+    replace_entry(out_path, id, Some(&entry))?;
+    Ok(true)
 }
 
-fn write_comment<'ast, W: Write>(
-    wtr: &mut W,
-    comment: Option<&fluent_syntax::ast::Comment<'ast>>,
-) -> std::io::Result<()> {
-    if let Some(comment) = comment {
-        match comment {
-            fluent_syntax::ast::Comment::Comment { content } => {
-                for c in content {
-                    writeln!(wtr, "# {}", c)?;
+/// Serve a page listing the translations that need review in each locale,
+/// from both the queue and the files, where each can be edited and saved
+fn serve_reviews(
+    matches: &clap::ArgMatches,
+    submatches: &clap::ArgMatches,
+    config: &config::Config,
+) -> Result<(), Box<dyn Error>> {
+    let locales: Vec<String> = match matches.values_of("locale") {
+        Some(locales) => locales
+            .filter(|locale| *locale != "all")
+            .map(str::to_owned)
+            .collect(),
+        None => config.locales.clone(),
+    };
+    let from_file = arg_or_config(matches, "from", config.source.as_deref()).unwrap();
+    let pattern_text = |message: &fluent_syntax::ast::Message| -> Result<String, Box<dyn Error>> {
+        let mut text: Vec<u8> = Vec::new();
+        if let Some(value) = &message.value {
+            writer::write_pattern(&mut text, value)?;
+        }
+        Ok(String::from_utf8(text)?.trim().to_owned())
+    };
+
+    let list = || -> Result<Vec<serve::Pending>, Box<dyn Error>> {
+        let source = fs::read_to_string(from_file)?;
+        let source = catalog::continue_parsing(from_file, fluent_syntax::parser::parse(&source));
+        let queue = review::Queue::load(&config.review.file)?;
+        let mut pending = Vec::new();
+        for locale in locales.iter() {
+            let (_, out_path) = output_paths(matches, config, locale);
+            let target = catalog::read_or_empty(&out_path)?;
+            let target =
+                catalog::continue_parsing(&out_path, fluent_syntax::parser::parse(&target));
+            let mut listed = HashSet::new();
+            for entry in target.body.iter() {
+                if let fluent_syntax::ast::ResourceEntry::Entry(
+                    fluent_syntax::ast::Entry::Message(message),
+                ) = entry
+                {
+                    if !catalog::has_marker(&message.comment, &config.markers.needs_review) {
+                        continue;
+                    }
+                    let source_text = match catalog::find_message(&source, message.id.name) {
+                        Some(source_message) => pattern_text(source_message)?,
+                        None => String::new(),
+                    };
+                    pending.push(serve::Pending {
+                        locale: locale.clone(),
+                        id: message.id.name.to_owned(),
+                        reason: config.markers.needs_review.clone(),
+                        source: source_text,
+                        translation: pattern_text(message)?,
+                    });
+                    listed.insert(message.id.name);
                 }
             }
-            fluent_syntax::ast::Comment::GroupComment { content } => {
-                for c in content {
-                    writeln!(wtr, "## {}", c)?;
+            for i in queue.pending(std::slice::from_ref(locale)).into_iter() {
+                let item = &queue.items[i];
+                if !listed.contains(item.id.as_str()) {
+                    pending.push(serve::Pending {
+                        locale: item.locale.clone(),
+                        id: item.id.clone(),
+                        reason: item.reason.name().to_owned(),
+                        source: item.source.clone(),
+                        translation: item.translation.clone(),
+                    });
                 }
             }
-            fluent_syntax::ast::Comment::ResourceComment { content } => {
-                for c in content {
-                    writeln!(wtr, "### {}", c)?;
-                }
+        }
+        Ok(pending)
+    };
+
+    let save = |locale: &str, id: &str, translation: &str| -> Result<(), Box<dyn Error>> {
+        if !locales.iter().any(|l| l == locale) {
+            return Err(Box::from(format!("`{}` isn't one of the locales", locale)));
+        }
+        let (_, out_path) = output_paths(matches, config, locale);
+        let existing = catalog::read_or_empty(&out_path)?;
+        let existing =
+            catalog::continue_parsing(&out_path, fluent_syntax::parser::parse(&existing));
+        let unchanged = match catalog::find_message(&existing, id) {
+            Some(message) => pattern_text(message)? == translation,
+            None => false,
+        };
+        let mut item = review::Item {
+            locale: locale.to_owned(),
+            id: id.to_owned(),
+            reason: review::Reason::Flagged,
+            source: String::new(),
+            translation: String::new(),
+            state: review::State::Accepted,
+            edit: None,
+        };
+        if !unchanged {
+            item.state = review::State::Edited;
+            item.edit = Some(translation.to_owned());
+        }
+        apply_review(matches, config, &item)?;
+
+        let mut queue = review::Queue::load(&config.review.file)?;
+        for queued in queue.items.iter_mut() {
+            if queued.locale == locale && queued.id == id && queued.state == review::State::Pending
+            {
+                queued.state = item.state;
+                queued.edit = item.edit.clone();
             }
         }
-    }
-    Ok(())
+        queue.save(&config.review.file)?;
+        log::info!("{}: saved `{}`", locale, id);
+        Ok(())
+    };
+
+    let port: u16 = submatches.value_of("port").unwrap().parse()?;
+    let address = submatches.value_of("bind").unwrap();
+    let listener = std::net::TcpListener::bind((address, port))?;
+    log::info!(
+        "serving the translations to review on http://{}:{}/",
+        address,
+        port
+    );
+    serve::serve(listener, list, save)
 }
 
-fn write_expression<'ast, W: Write>(
-    wtr: &mut W,
-    expression: &fluent_syntax::ast::Expression<'ast>,
-) -> std::io::Result<()> {
-    match expression {
-        fluent_syntax::ast::Expression::InlineExpression(ie) => match ie {
-            fluent_syntax::ast::InlineExpression::StringLiteral { value } => {
-                write!(wtr, "{{ {} }}", *value)?;
-            }
-            fluent_syntax::ast::InlineExpression::NumberLiteral { value } => {
-                write!(wtr, "{{ {} }}", *value)?;
-            }
-            fluent_syntax::ast::InlineExpression::FunctionReference { .. } => {
-                write!(wtr, "___")?;
-            }
-            fluent_syntax::ast::InlineExpression::MessageReference { id, .. } => {
-                write!(wtr, "{{ {} }}", id.name)?;
-            }
-            fluent_syntax::ast::InlineExpression::TermReference { id, .. } => {
-                write!(wtr, "{{ -{} }}", id.name)?;
-            }
-            fluent_syntax::ast::InlineExpression::VariableReference { id } => {
-                write!(wtr, "{{ ${} }}", id.name)?;
-            }
-            fluent_syntax::ast::InlineExpression::Placeable { .. } => {
-                write!(wtr, "___")?;
+/// List the messages marked as needing review in each locale's file, one
+/// `locale: id` per line
+fn list_reviews(
+    matches: &clap::ArgMatches,
+    config: &config::Config,
+    locales: &[String],
+) -> Result<(), Box<dyn Error>> {
+    for locale in locales.iter() {
+        let (_, out_path) = output_paths(matches, config, locale);
+        let target = catalog::read_or_empty(&out_path)?;
+        let target = catalog::continue_parsing(&out_path, fluent_syntax::parser::parse(&target));
+        for entry in target.body.iter() {
+            if let fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Message(
+                message,
+            )) = entry
+            {
+                if catalog::has_marker(&message.comment, &config.markers.needs_review) {
+                    println!("{}: {}", locale, message.id.name);
+                }
             }
-        },
-        fluent_syntax::ast::Expression::SelectExpression { .. } => {
-            write!(wtr, "___")?;
         }
     }
     Ok(())
 }
 
-fn write_pattern<'ast, W: Write>(
-    wtr: &mut W,
-    pattern: &fluent_syntax::ast::Pattern<'ast>,
-) -> std::io::Result<()> {
-    for element in &pattern.elements {
-        match element {
-            fluent_syntax::ast::PatternElement::TextElement(s) => {
-                wtr.write_all((*s).as_bytes())?;
-            }
-            fluent_syntax::ast::PatternElement::Placeable(e) => {
-                write_expression(wtr, e)?;
+/// Mark the message `id` as hand-translated in each locale where it needs
+/// review, keeping its translation but dropping the comments that say it
+/// needs review or was machine-translated
+fn approve_review(
+    matches: &clap::ArgMatches,
+    config: &config::Config,
+    locales: &[String],
+    id: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut queue = review::Queue::load(&config.review.file)?;
+    let mut approved = 0;
+    for locale in locales.iter() {
+        let (_, out_path) = output_paths(matches, config, locale);
+        if !approve_entry(config, &out_path, locale, id)? {
+            continue;
+        }
+        for item in queue.items.iter_mut() {
+            if item.locale == *locale && item.id == id && item.state == review::State::Pending {
+                item.state = review::State::Accepted;
             }
         }
+        log::info!("{}: approved `{}`", locale, id);
+        approved += 1;
     }
+    if approved == 0 {
+        return Err(Box::from(format!(
+            "`{}` doesn't need review in any of the locales",
+            id
+        )));
+    }
+    queue.save(&config.review.file)?;
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    simplelog::CombinedLogger::init(vec![simplelog::TermLogger::new(
-        simplelog::LevelFilter::Debug,
-        simplelog::ConfigBuilder::new()
-            .add_filter_allow_str("tt")
-            .build(),
-        simplelog::TerminalMode::Mixed,
-    )
-    .expect("can init termlogger")])
-    .expect("can initiate logging");
-    let matches = cli::build_cli().get_matches();
-
-    if let Some(_submatches) = matches.subcommand_matches("languages") {
-        let (token, project_id) = get_token_and_project_id(&matches).map_err(|e| {
-            log::error!(
-                "failed to get token and project id from credentials file: {:?}",
-                e
-            );
-            e
-        })?;
-        let translator = translate::Translator::new(&token, &project_id, "en");
-        let available_languages = translator.available_languages().map_err(|e| {
-            log::error!("failed to list available languages from translator!");
-            e
-        })?;
-
-        let available_languages: Vec<String> = available_languages
-            .into_iter()
-            .map(|lang| format!("{} => '{}'", lang.display_name, lang.language_code))
-            .collect();
+/// Review the queued translations one at a time, saving after each so that a
+/// review can be stopped and picked up again, or move the queue to and from
+/// CSV for reviewers outside of `tt`
+fn review_translations(
+    matches: &clap::ArgMatches,
+    submatches: &clap::ArgMatches,
+    config: &config::Config,
+) -> Result<(), Box<dyn Error>> {
+    if submatches.subcommand_name().is_some() {
+        let locales: Vec<String> = match matches.values_of("locale") {
+            Some(locales) => locales
+                .filter(|locale| *locale != "all")
+                .map(str::to_owned)
+                .collect(),
+            None => config.locales.clone(),
+        };
+        if let Some(approving) = submatches.subcommand_matches("approve") {
+            return approve_review(matches, config, &locales, approving.value_of("id").unwrap());
+        }
+        return list_reviews(matches, config, &locales);
+    }
 
-        println!("Accepted languages:");
-        println!("{}", available_languages.join("\n"));
+    let mut queue = review::Queue::load(&config.review.file)?;
+    if let Some(path) = submatches.value_of("export") {
+        let mut out = BufWriter::new(fs::File::create(path)?);
+        queue.write_csv(&mut out)?;
+        log::info!("exported {} translation(s) to {}", queue.items.len(), path);
         return Ok(());
-    } else if let Some(submatches) = matches.subcommand_matches("gen-completions") {
-        let shell = submatches.value_of("shell").unwrap_or("bash");
+    }
+    if let Some(path) = submatches.value_of("import") {
+        let changed = queue.read_csv(&fs::read_to_string(path)?)?;
+        for i in changed.iter() {
+            apply_review(matches, config, &queue.items[*i])?;
+        }
+        queue.save(&config.review.file)?;
+        log::info!("imported {} review(s) from {}", changed.len(), path);
+        return Ok(());
+    }
 
-        cli::build_cli().gen_completions_to(
-            env!("CARGO_PKG_NAME"),
-            match shell {
-                "bash" => clap::Shell::Bash,
-                "zsh" => clap::Shell::Zsh,
-                "fish" => clap::Shell::Fish,
-                "powershell" => clap::Shell::PowerShell,
-                "elvish" => clap::Shell::Elvish,
-                _ => return Err(Box::from(errors::Errors::InvalidShell)),
-            },
-            &mut std::io::stdout(),
-        );
+    let locales: Vec<String> = matches
+        .values_of("locale")
+        .map(|locales| {
+            locales
+                .filter(|locale| *locale != "all")
+                .map(str::to_owned)
+                .collect()
+        })
+        .unwrap_or_default();
+    let pending = queue.pending(&locales);
+    if pending.is_empty() {
+        log::info!("there's nothing to review");
         return Ok(());
     }
+    for (n, i) in pending.iter().enumerate() {
+        let state = match review::ask(&queue.items[*i], n + 1, pending.len())? {
+            Some(review::State::Pending) => continue,
+            Some(state) => state,
+            None => break,
+        };
+        if state == review::State::Edited {
+            match review::ask_edit()? {
+                Some(edit) => queue.items[*i].edit = Some(edit),
+                None => continue,
+            }
+        }
+        queue.items[*i].state = state;
+        if let Err(e) = apply_review(matches, config, &queue.items[*i]) {
+            log::error!("{}", e);
+            queue.items[*i].state = review::State::Pending;
+            queue.items[*i].edit = None;
+            continue;
+        }
+        queue.save(&config.review.file)?;
+    }
+    let remaining = queue.pending(&locales).len();
+    if remaining > 0 {
+        log::info!("{} translation(s) are left to review", remaining);
+    }
+    Ok(())
+}
 
-    let (token, project_id) = get_token_and_project_id(&matches)?;
-    let from_file = matches.value_of("from").unwrap();
-    let diff_path: Option<PathBuf> = matches.value_of("diff").map(PathBuf::from);
-    let locale = matches
-        .value_of("locale")
-        .ok_or(errors::Errors::MissingLanguage)?;
-    let out_path = Path::new(matches.value_of("outpath").unwrap());
-    fs::create_dir_all(out_path)?;
-    let out_path = out_path.join(format!("{}.flt", locale));
-
-    let glossary = matches.value_of("glossary").map(|glossary| {
-        format!(
-            "projects/{}/locations/us-central1/glossaries/{}",
-            project_id, glossary
-        )
-    });
-    let glossary = glossary.as_ref().map(|glossary| translate::GlossaryConfig {
-        glossary,
-        ignore_case: Some(matches.is_present("ignore-case")),
-    });
+/// Print what refers to what in the source, or the file given
+fn graph_catalog(
+    matches: &clap::ArgMatches,
+    submatches: &clap::ArgMatches,
+    config: &config::Config,
+) -> Result<(), Box<dyn Error>> {
+    let file = submatches
+        .value_of("file")
+        .or_else(|| arg_or_config(matches, "from", config.source.as_deref()))
+        .unwrap();
+    let text = fs::read_to_string(file).map_err(|e| format!("failed to read {}: {}", file, e))?;
+    let resource = catalog::continue_parsing(file, fluent_syntax::parser::parse(&text));
+    let graph = graph::Graph::build(&resource);
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    match submatches.value_of("format") {
+        Some("json") => writeln!(out, "{}", serde_json::to_string_pretty(&graph)?)?,
+        _ => graph.write_dot(&mut out)?,
+    }
+    Ok(())
+}
 
-    let translator = translate::Translator::new(&token, &project_id, locale);
-    let available_languages = translator.available_languages()?;
-    available_languages
-        .iter()
-        .find(|lang| lang.language_code == locale)
-        .ok_or(errors::Errors::InvalidLanguage)?;
+/// Lint the given Fluent files, or the source and the translation of each
+/// locale if none are given, failing if there are any problems
+fn lint_files(
+    matches: &clap::ArgMatches,
+    submatches: &clap::ArgMatches,
+    config: &config::Config,
+) -> Result<(), Box<dyn Error>> {
+    let palette = palette(matches);
+    let rule_list = |name: &str| -> Vec<&str> {
+        submatches
+            .values_of(name)
+            .map(|rules| rules.collect())
+            .unwrap_or_default()
+    };
+    let rules = config
+        .lint
+        .enabled_rules(&rule_list("enable"), &rule_list("disable"))?;
 
-    let source = std::fs::read_to_string(from_file)?;
-    let source_outdated = if let Some(diff_path) = &diff_path {
-        if diff_path.exists() {
-            std::fs::read_to_string(diff_path)?
-        } else {
-            String::new()
+    let files: Vec<PathBuf> = match submatches.values_of("files") {
+        Some(files) => files.map(PathBuf::from).collect(),
+        None => {
+            let from_file = arg_or_config(matches, "from", config.source.as_deref()).unwrap();
+            let locales: Vec<String> = match matches.values_of("locale") {
+                Some(locales) => locales
+                    .filter(|locale| *locale != "all")
+                    .map(str::to_owned)
+                    .collect(),
+                None => config.locales.clone(),
+            };
+            std::iter::once(PathBuf::from(from_file))
+                .chain(
+                    locales
+                        .iter()
+                        .map(|locale| output_paths(matches, config, locale).1)
+                        .filter(|path| path.exists()),
+                )
+                .collect()
         }
-    } else {
-        String::new()
     };
-    let target_existing = if out_path.exists() {
-        std::fs::read_to_string(&out_path)?
-    } else {
-        String::new()
-    };
-
-    let source = continue_parsing(&from_file, fluent_syntax::parser::parse(&source));
-    let source_outdated = continue_parsing(
-        &diff_path.unwrap_or_default(),
-        fluent_syntax::parser::parse(&source_outdated),
-    );
-    let target_existing =
-        continue_parsing(&out_path, fluent_syntax::parser::parse(&target_existing));
 
-    //let mut translations: HashMap<&str, Option<String>> = HashMap::new();
-    let mut pending_translations: HashMap<&str, Option<String>> = HashMap::new();
+    let mut problems = 0;
+    for file in files.iter() {
+        let text = fs::read_to_string(file)
+            .map_err(|e| format!("failed to read {}: {}", file.display(), e))?;
+        for problem in lint::lint(&text, &rules, config.lint.id_style) {
+            println!(
+                "{}:{}: {}: {}",
+                file.display(),
+                problem.line,
+                palette.failed(problem.rule),
+                problem.message
+            );
+            problems += 1;
+        }
+    }
+    if problems > 0 {
+        log::error!("found {} problem(s)", problems);
+        return Err(Box::from(errors::Errors::LintFailed));
+    }
+    log::info!("{} file(s) have no problems", files.len());
+    Ok(())
+}
 
-    for entry in source.body.iter() {
-        if let fluent_syntax::ast::ResourceEntry::Entry(entry) = entry {
-            if let fluent_syntax::ast::Entry::Message(message) = &entry {
-                // check if we need to translate based on diffs
-                let needs_translation: bool =
-                    if let Some(outdated) = find_message(&source_outdated, message.id.name) {
-                        log::debug!("found existing term `{}` in diff", message.id.name);
-                        log::debug!("message.value = {:?}", message.value);
-                        log::debug!("outdated.value = {:?}", outdated.value);
-                        log::debug!(
-                            "message.value != outdated.value => {}",
-                            message.value != outdated.value
-                        );
-                        message.value != outdated.value
-                    } else {
-                        true
-                    };
-                log::debug!(
-                    "term `{}` needs translation from diff: {}",
-                    message.id.name,
-                    needs_translation
-                );
+/// Upgrade the config, lock file and review queue to the versions that this
+/// `tt` writes. A file that changes beyond its version is rewritten from
+/// scratch, so the original is kept next to it with a `.bak` extension.
+fn migrate_files(
+    matches: &clap::ArgMatches,
+    config: &config::Config,
+) -> Result<(), Box<dyn Error>> {
+    let back_up = |path: &str| -> Result<(), Box<dyn Error>> {
+        let backup = format!("{}.bak", path);
+        fs::copy(path, &backup)?;
+        log::info!("kept the original {} as {}", path, backup);
+        Ok(())
+    };
 
-                // disable translation if we have a hand-translated one
-                let needs_translation =
-                    if let Some(existing) = find_message(&target_existing, message.id.name) {
-                        if let Some(comment) = &existing.comment {
-                            if let fluent_syntax::ast::Comment::Comment { content } = comment {
-                                !content.iter().any(|c| c.contains("tt-hand-translated"))
-                            } else {
-                                needs_translation
-                            }
-                        } else {
-                            needs_translation
-                        }
-                    } else {
-                        // we always need translation if we don't have the message in the existing file
-                        true
-                    };
-                log::debug!(
-                    "term `{}` needs translation after checking hand-translated: {}",
-                    message.id.name,
-                    needs_translation
-                );
+    let config_path = matches
+        .value_of("config")
+        .or_else(|| Some(config::DEFAULT_CONFIG_FILE).filter(|path| Path::new(path).exists()));
+    if let Some(path) = config_path {
+        let text = fs::read_to_string(path)?;
+        let mut document = toml::parse(&text)?;
+        let migrated = migrate::CONFIG.migrate(&mut document)?;
+        let version = migrate::CONFIG.version();
+        if migrated.from == version {
+            log::info!("{} is up to date", path);
+        } else {
+            if migrated.changed {
+                back_up(path)?;
+                fs::write(path, toml::write(&document))?;
+            } else {
+                // comments and formatting can be kept when only the version changes
+                fs::write(
+                    path,
+                    toml::set_top_level(&text, "version", &version.to_string()),
+                )?;
+            }
+            log::info!(
+                "upgraded {} from version {} to {}",
+                path,
+                migrated.from,
+                version
+            );
+        }
+    }
 
-                if needs_translation {
-                    // deal with language names
-                    let is_lang_name = if let Some(comment) = &message.comment {
-                        if let fluent_syntax::ast::Comment::Comment { content } = comment {
-                            content.iter().any(|c| c.contains("tt-lang-name"))
-                        } else {
-                            false
-                        }
-                    } else {
-                        false
-                    };
+    let lock_file = arg_or_config(matches, "lock-file", config.lock_file.as_deref())
+        .unwrap_or(lock::DEFAULT_LOCK_FILE);
+    for (path, schema) in [
+        (lock_file, &migrate::LOCK),
+        (config.review.file.as_str(), &migrate::REVIEW),
+    ]
+    .iter()
+    {
+        if !Path::new(path).exists() {
+            continue;
+        }
+        let mut document: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let migrated = schema.migrate(&mut document)?;
+        if migrated.from == schema.version() {
+            log::info!("{} is up to date", path);
+            continue;
+        }
+        if migrated.changed {
+            back_up(path)?;
+        }
+        let mut contents = serde_json::to_string_pretty(&document)?;
+        contents.push('\n');
+        fs::write(path, contents)?;
+        log::info!(
+            "upgraded {} from version {} to {}",
+            path,
+            migrated.from,
+            schema.version()
+        );
+    }
+    Ok(())
+}
 
-                    if is_lang_name {
-                        pending_translations.insert(
-                            message.id.name,
-                            Some(match translator.get_lang_name() {
-                                Ok(t) => t,
-                                Err(e) => {
-                                    log::warn!("failed to get language name: {:?}", e);
-                                    "<INSERT LANGUAGE NAME HERE>".to_owned()
-                                }
-                            }),
-                        );
-                    } else if let Some(pattern) = &message.value {
-                        // prepare the pattern for translating by stripping placeables
-                        let source_formatted: String = pattern
-                            .elements
-                            .iter()
-                            .map(|pe| match pe {
-                                fluent_syntax::ast::PatternElement::TextElement(s) => s,
-                                fluent_syntax::ast::PatternElement::Placeable(_) => "___",
-                            })
-                            .collect();
+/// Write a review into the locale's translation file. Edits are marked as
+/// hand-translated so that they aren't translated over, and rejected
+/// translations are removed so that they're translated again.
+fn apply_review(
+    matches: &clap::ArgMatches,
+    config: &config::Config,
+    item: &review::Item,
+) -> Result<(), Box<dyn Error>> {
+    let (_, out_path) = output_paths(matches, config, &item.locale);
+    match item.state {
+        review::State::Edited => {
+            let edit = item.edit.as_deref().unwrap_or("");
+            let existing = catalog::read_or_empty(&out_path)?;
+            let existing =
+                catalog::continue_parsing(&out_path, fluent_syntax::parser::parse(&existing));
+            let message = catalog::find_message(&existing, &item.id);
 
-                        pending_translations.insert(message.id.name, Some(source_formatted));
-                    } else {
-                        pending_translations.insert(message.id.name, None);
-                    }
+            let mut entry = String::new();
+            let comment = message.and_then(|message| message.comment.as_ref());
+            if let Some(fluent_syntax::ast::Comment::Comment { content }) = comment {
+                // it's no longer the backend's translation
+                for line in content.iter().filter(|line| {
+                    !line.contains(&config.markers.needs_review)
+                        && !line.contains(&config.markers.machine_translated)
+                }) {
+                    entry.push_str(format!("# {}\n", line).replace("# \n", "#\n").as_str());
                 }
             }
+            if !message
+                .map(|message| {
+                    catalog::has_marker(&message.comment, &config.markers.hand_translated)
+                })
+                .unwrap_or(false)
+            {
+                entry.push_str(&format!("# {}\n", config.markers.hand_translated));
+            }
+            entry.push_str(&format!(
+                "{} = {}",
+                item.id,
+                edit.lines().collect::<Vec<&str>>().join("\n    ")
+            ));
+            if let Some(message) = message {
+                let mut attributes: Vec<u8> = Vec::new();
+                writer::write_attributes(&mut attributes, &message.attributes)?;
+                entry.push_str(&String::from_utf8(attributes)?);
+            }
+            entry.push('\n');
+            if let Err((_, errors)) = fluent_syntax::parser::parse(entry.as_str()) {
+                return Err(Box::from(format!(
+                    "the edit of `{}` isn't valid Fluent: {:?}",
+                    item.id, errors
+                )));
+            }
+            replace_entry(&out_path, &item.id, Some(&entry))?;
+        }
+        review::State::Accepted if out_path.exists() => {
+            approve_entry(config, &out_path, &item.locale, &item.id)?;
         }
+        review::State::Rejected if out_path.exists() => {
+            replace_entry(&out_path, &item.id, None)?;
+        }
+        _ => {}
     }
+    Ok(())
+}
 
-    log::debug!("pending translations: {:?}", pending_translations);
+/// Show the translation of `message` and ask whether to write it as it is,
+/// write a hand translation of its value instead, or skip it, returning the
+/// translation to write
+fn ask_translation(
+    config: &config::Config,
+    locale: &str,
+    message: &fluent_syntax::ast::Message,
+    mut segments: catalog::Segments,
+) -> Result<Option<catalog::Segments>, Box<dyn Error>> {
+    let id = message.id.name;
+    let mut source: Vec<u8> = Vec::new();
+    if let Some(value) = &message.value {
+        writer::write_pattern(&mut source, value)?;
+    }
+    writer::write_attributes(&mut source, &message.attributes)?;
+    let mut variants = segments.variants.iter();
+    let mut translation = match (&message.value, &segments.value) {
+        (Some(pattern), Some(value)) => {
+            writer::fill_variants(
+                pattern,
+                value,
+                &mut variants.next().into_iter().flatten(),
+                segments.plurals,
+            )
+        }
+        _ => String::new(),
+    };
+    for (attribute, value) in message.attributes.iter().zip(segments.attributes.iter()) {
+        translation.push_str(&format!(
+            "\n    .{} = {}",
+            attribute.id.name,
+            writer::fill_variants(
+                &attribute.value,
+                value,
+                &mut variants.next().into_iter().flatten(),
+                segments.plurals
+            )
+        ));
+    }
+    loop {
+        match review::ask_translation(
+            locale,
+            id,
+            String::from_utf8_lossy(&source).trim(),
+            translation.trim(),
+        )? {
+            review::State::Accepted => return Ok(Some(segments)),
+            review::State::Edited if message.value.is_none() => {
+                println!(
+                    "only a message's value can be edited, and `{}` has none",
+                    id
+                );
+            }
+            review::State::Edited => {
+                let edit = match review::ask_edit()? {
+                    Some(edit) => edit.lines().collect::<Vec<&str>>().join("\n    "),
+                    None => continue,
+                };
+                if let Err((_, errors)) =
+                    fluent_syntax::parser::parse(format!("{} = {}\n", id, edit).as_str())
+                {
+                    println!("that isn't valid Fluent: {:?}", errors);
+                    continue;
+                }
+                // it's no longer the backend's translation
+                let mut comments: Vec<String> = segments
+                    .comment
+                    .iter()
+                    .flat_map(|comment| comment.lines())
+                    .filter(|line| {
+                        !line.contains(&config.markers.needs_review)
+                            && !line.contains(&config.markers.machine_translated)
+                    })
+                    .map(str::to_owned)
+                    .collect();
+                comments.push(config.markers.hand_translated.clone());
+                segments.value = Some(edit);
+                segments.comment = Some(comments.join("\n"));
+                return Ok(Some(segments));
+            }
+            _ => return Ok(None),
+        }
+    }
+}
 
-    let pb = indicatif::ProgressBar::new(pending_translations.len() as u64);
-    pb.set_style(
-        indicatif::ProgressStyle::default_bar()
-            .template("{prefix} {spinner} [{elapsed_precise}] [{wide_bar}] {pos}/{len} ({eta})"),
-    );
-    pb.set_prefix(locale);
+/// Write the translations, merging them into the existing file with `--merge`
+fn save_translations(
+    matches: &clap::ArgMatches,
+    config: &config::Config,
+    out_path: &Path,
+    source: &fluent_syntax::ast::Resource,
+    target_existing: &fluent_syntax::ast::Resource,
+    translations: &HashMap<&str, Option<catalog::Segments>>,
+) -> Result<(), Box<dyn Error>> {
+    if matches.is_present("merge") && out_path.exists() {
+        merge_translations(
+            matches,
+            config,
+            out_path,
+            source,
+            target_existing,
+            translations,
+        )
+    } else {
+        write_translations(config, out_path, source, target_existing, translations)
+    }
+}
 
-    let translations: HashMap<&str, Option<String>> = pending_translations
-        .into_iter()
-        .map(|(id, value)| {
-            pb.inc(1);
-            if let Some(value) = value {
-                (
-                    id,
-                    Some(match translator.translate(&value, &glossary) {
-                        Ok(t) => t,
-                        Err(e) => {
-                            log::warn!("failed to translate term `{}`: {:?}", id, e);
-                            value
-                        }
-                    }),
-                )
-            } else {
-                (id, None)
+/// Update the existing translation file in place, leaving the messages that
+/// weren't translated again exactly as they are
+fn merge_translations(
+    matches: &clap::ArgMatches,
+    config: &config::Config,
+    out_path: &Path,
+    source: &fluent_syntax::ast::Resource,
+    target_existing: &fluent_syntax::ast::Resource,
+    translations: &HashMap<&str, Option<catalog::Segments>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut source_ids: Vec<String> = Vec::new();
+    let mut rewrite: HashMap<String, String> = HashMap::new();
+    for entry in source.body.iter() {
+        match entry {
+            fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Term(t)) => {
+                let id = format!("-{}", t.id.name);
+                let unchanged = catalog::find_term(target_existing, t.id.name)
+                    .map(|existing| {
+                        existing.value == t.value && existing.attributes == t.attributes
+                    })
+                    .unwrap_or(false);
+                if !unchanged {
+                    let mut text: Vec<u8> = Vec::new();
+                    writer::write_term(&mut text, t)?;
+                    rewrite.insert(id.clone(), String::from_utf8(text)?);
+                }
+                source_ids.push(id);
             }
-        })
+            fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Message(m))
+                if catalog::has_marker(&m.comment, &config.markers.no_translate) =>
+            {
+                let unchanged = catalog::find_message(target_existing, m.id.name)
+                    .map(|existing| existing == m)
+                    .unwrap_or(false);
+                if !unchanged {
+                    let mut text: Vec<u8> = Vec::new();
+                    writer::write_message(&mut text, m)?;
+                    rewrite.insert(m.id.name.to_owned(), String::from_utf8(text)?);
+                }
+                source_ids.push(m.id.name.to_owned());
+            }
+            fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Message(m)) => {
+                if let Some(Some(segments)) = translations.get(m.id.name) {
+                    let mut text: Vec<u8> = Vec::new();
+                    writer::write_translated_message(&mut text, m, segments)?;
+                    rewrite.insert(m.id.name.to_owned(), String::from_utf8(text)?);
+                }
+                source_ids.push(m.id.name.to_owned());
+            }
+            _ => {}
+        }
+    }
+
+    let source_ids: Vec<&str> = source_ids.iter().map(String::as_str).collect();
+    let rewrite: HashMap<&str, String> = rewrite
+        .iter()
+        .map(|(id, text)| (id.as_str(), text.clone()))
         .collect();
-    pb.finish();
+    let deprecated = if matches.is_present("keep-removed") {
+        Some(config.markers.deprecated.as_str())
+    } else {
+        None
+    };
+    let existing = fs::read_to_string(out_path)?;
+    fs::write(
+        out_path,
+        merge::merge(&existing, &source_ids, &rewrite, deprecated),
+    )?;
+    Ok(())
+}
 
+/// Write a translation file from scratch, in the same order as the source
+fn write_translations(
+    config: &config::Config,
+    out_path: &Path,
+    source: &fluent_syntax::ast::Resource,
+    target_existing: &fluent_syntax::ast::Resource,
+    translations: &HashMap<&str, Option<catalog::Segments>>,
+) -> Result<(), Box<dyn Error>> {
     // now we have all the translations we need, time to reconstruct a translated .flt file
-    let f = fs::File::create(&out_path)?;
+    let f = fs::File::create(out_path)?;
     let mut file = BufWriter::new(&f);
 
+    // keep the target's own header, such as the one `tt add-locale` writes
+    for entry in target_existing.body.iter() {
+        if let fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Comment(
+            comment @ fluent_syntax::ast::Comment::ResourceComment { .. },
+        )) = entry
+        {
+            let in_source = source.body.iter().any(|entry| match entry {
+                fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Comment(c)) => {
+                    c == comment
+                }
+                _ => false,
+            });
+            if !in_source {
+                writer::write_comment(&mut file, Some(comment))?;
+                writeln!(&mut file)?;
+            }
+        }
+    }
+
     for entry in source.body.iter() {
         if let fluent_syntax::ast::ResourceEntry::Entry(entry) = entry {
             match entry {
                 fluent_syntax::ast::Entry::Term(t) => {
-                    write_comment(&mut file, t.comment.as_ref())?;
-                    write!(&mut file, "-{} = ", t.id.name)?;
-                    write_pattern(&mut file, &t.value)?;
-                    // TODO: write attributes
+                    writer::write_term(&mut file, t)?;
                     writeln!(&mut file, "")?;
                     writeln!(&mut file, "")?;
                 }
+                fluent_syntax::ast::Entry::Message(m)
+                    if catalog::has_marker(&m.comment, &config.markers.no_translate) =>
+                {
+                    writer::write_message(&mut file, m)?;
+                    writeln!(&mut file)?;
+                    writeln!(&mut file)?;
+                }
                 fluent_syntax::ast::Entry::Message(m) => {
+                    // leave out messages that have nothing to write yet
+                    if let Some(None) = translations.get(m.id.name) {
+                        continue;
+                    }
                     // see if we have a new translation for the message
                     if translations.contains_key(m.id.name) {
-                        if let Some(msg) = translations.get(m.id.name).unwrap() {
-                            // convert each of the placeables
-                            let placeables: Vec<String> = if let Some(v) = &m.value {
-                                v.elements
-                                    .iter()
-                                    .filter_map(|e| match e {
-                                        fluent_syntax::ast::PatternElement::Placeable(e) => {
-                                            let mut text: Vec<u8> = Vec::default();
-                                            write_expression(&mut text, e)
-                                                .expect("can write_expression on placeable");
-                                            let text =
-                                                String::from_utf8(text).expect("valid utf-8");
-                                            Some(text)
-                                        }
-                                        _ => None,
-                                    })
-                                    .collect()
-                            } else {
-                                Vec::new()
-                            };
-
-                            let mut msg: String = msg.clone();
-                            for placeable in placeables.into_iter() {
-                                msg = msg.replacen("___", &placeable, 1);
-                            }
-                            write!(&mut file, "{} = ", m.id.name)?;
-                            file.write_all(msg.as_bytes())?;
-                            // TODO: write attributes
+                        if let Some(segments) = translations.get(m.id.name).unwrap() {
+                            writer::write_translated_message(&mut file, m, segments)?;
                         }
                     }
                     // see if there's already a hand-translated message
@@ -441,12 +4492,14 @@ fn main() -> Result<(), Box<dyn Error>> {
                         // TODO: fix the hand-translated comments
                         log::debug!("checking hand-translated for {}", m.id.name);
                         let message = if let Some(existing) =
-                            find_message(&target_existing, m.id.name)
+                            catalog::find_message(target_existing, m.id.name)
                         {
                             log::debug!("found message in existing");
                             let hand_translated = if let Some(comment) = &existing.comment {
                                 if let fluent_syntax::ast::Comment::Comment { content } = comment {
-                                    content.iter().any(|c| c.contains("tt-hand-translated"))
+                                    content
+                                        .iter()
+                                        .any(|c| c.contains(&config.markers.hand_translated))
                                 } else {
                                     false
                                 }
@@ -459,24 +4512,109 @@ fn main() -> Result<(), Box<dyn Error>> {
                             m
                         };
 
-                        write_comment(&mut file, message.comment.as_ref())?;
-                        write!(&mut file, "{} = ", m.id.name)?;
+                        writer::write_comment(&mut file, message.comment.as_ref())?;
+                        write!(&mut file, "{} =", m.id.name)?;
                         if let Some(value) = &message.value {
-                            write_pattern(&mut file, value)?;
+                            write!(&mut file, " ")?;
+                            writer::write_pattern(&mut file, value)?;
                         }
-                        // TODO: write attributes
+                        writer::write_attributes(&mut file, &message.attributes)?;
                     }
 
                     writeln!(&mut file, "")?;
                     writeln!(&mut file, "")?;
                 }
                 fluent_syntax::ast::Entry::Comment(c) => {
-                    write_comment(&mut file, Some(c))?;
+                    writer::write_comment(&mut file, Some(c))?;
                     writeln!(&mut file, "")?;
                 }
             }
         }
     }
+    file.flush()?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_the_source_locale_for_each_run() {
+        let matches = cli::build_cli().get_matches_from(vec!["tt"]);
+        let french = config::Config {
+            source_locale: Some("fr".to_owned()),
+            ..config::Config::default()
+        };
+        let unset = config::Config::default();
+        let translate = |connection: &backend::Connection| {
+            backend::create("pseudo", None, connection, None, "fr")
+                .unwrap()
+                .translate("Hello", translate::MimeType::Text, &None)
+                .unwrap()
+        };
+
+        // run one after the other, as the projects of a workspace are
+        let first = connection(&matches, &french).unwrap();
+        assert_eq!(first.source_language, "fr");
+        assert_eq!(translate(&first), "Hello");
+        let second = connection(&matches, &unset).unwrap();
+        assert_eq!(second.source_language, languages::DEFAULT_SOURCE_LANGUAGE);
+        assert_ne!(translate(&second), "Hello");
+
+        let matches = cli::build_cli().get_matches_from(vec!["tt", "--source-locale", "de"]);
+        assert_eq!(connection(&matches, &french).unwrap().source_language, "de");
+    }
+
+    #[test]
+    fn merges_labels_for_each_run() {
+        let mut labelled = config::Config::default();
+        labelled.labels.insert("team".to_owned(), "web".to_owned());
+        labelled.labels.insert("app".to_owned(), "shop".to_owned());
+        let matches = cli::build_cli().get_matches_from(vec!["tt", "--label", "team=mobile"]);
+        let labels = connection(&matches, &labelled).unwrap().labels;
+        assert_eq!(labels["team"], "mobile");
+        assert_eq!(labels["app"], "shop");
+        // the next project's requests aren't billed under this one's labels
+        let matches = cli::build_cli().get_matches_from(vec!["tt"]);
+        let unlabelled = connection(&matches, &config::Config::default()).unwrap();
+        assert!(unlabelled.labels.is_empty());
+
+        labelled.labels.insert("Team".to_owned(), "web".to_owned());
+        assert!(connection(&matches, &labelled).is_err());
+    }
+
+    #[test]
+    fn retries_as_each_run_says() {
+        let matches = cli::build_cli().get_matches_from(vec!["tt"]);
+        let mut patient = config::Config::default();
+        patient.retry.max_attempts = 10;
+        let first = connection(&matches, &patient).unwrap();
+        assert_eq!(
+            first.retry,
+            retry::Retry::new(&patient.retry, throttle::Throttle::default())
+        );
+        // the next project retries as its own config says
+        let second = connection(&matches, &config::Config::default()).unwrap();
+        assert_eq!(second.retry, retry::Retry::default());
+        assert_ne!(first.retry, second.retry);
+    }
+
+    #[test]
+    fn throttles_as_each_run_says() {
+        let limited = config::Config {
+            qps: Some(5.0),
+            ..config::Config::default()
+        };
+        let matches = cli::build_cli().get_matches_from(vec!["tt"]);
+        let first = connection(&matches, &limited).unwrap();
+        assert_eq!(first.throttle, throttle::Throttle::new(Some(5.0)));
+        let second = connection(&matches, &config::Config::default()).unwrap();
+        assert_eq!(second.throttle, throttle::Throttle::new(None));
+
+        let matches = cli::build_cli().get_matches_from(vec!["tt", "--qps", "2"]);
+        let overridden = connection(&matches, &limited).unwrap();
+        assert_eq!(overridden.throttle, throttle::Throttle::new(Some(2.0)));
+    }
+}
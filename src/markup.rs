@@ -0,0 +1,172 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+/// Elements that never have a closing tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum Kind {
+    Open,
+    Close,
+    /// `<br/>`, or any void element
+    Empty,
+}
+
+/// An HTML tag, by its lowercased element name
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+struct Tag {
+    name: String,
+    kind: Kind,
+}
+
+impl std::fmt::Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.kind {
+            Kind::Open => write!(f, "<{}>", self.name),
+            Kind::Close => write!(f, "</{}>", self.name),
+            Kind::Empty => write!(f, "<{}/>", self.name),
+        }
+    }
+}
+
+/// Find the tags in `text`, ignoring `<`s that don't start one, like `a < b`
+fn find_tags(text: &str) -> Vec<Tag> {
+    let mut tags = Vec::new();
+    for (start, _) in text.match_indices('<') {
+        let rest = &text[start + 1..];
+        let inside = match rest.find('>') {
+            Some(end) => &rest[..end],
+            None => continue,
+        };
+        let (closing, inside) = match inside.strip_prefix('/') {
+            Some(inside) => (true, inside),
+            None => (false, inside),
+        };
+        let name: String = inside
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            continue;
+        }
+        let kind = if closing {
+            Kind::Close
+        } else if inside.ends_with('/') || VOID_ELEMENTS.contains(&name.as_str()) {
+            Kind::Empty
+        } else {
+            Kind::Open
+        };
+        tags.push(Tag { name, kind });
+    }
+    tags
+}
+
+/// Describe how the tags in `text` don't pair up
+fn unbalanced(tags: &[Tag]) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut open: Vec<&Tag> = Vec::new();
+    for tag in tags.iter() {
+        match tag.kind {
+            Kind::Open => open.push(tag),
+            Kind::Close => match open.last() {
+                Some(last) if last.name == tag.name => {
+                    open.pop();
+                }
+                Some(last) if open.iter().any(|o| o.name == tag.name) => {
+                    problems.push(format!("`{}` closes `{}` out of order", tag, last));
+                    let i = open.iter().rposition(|o| o.name == tag.name).unwrap();
+                    open.truncate(i);
+                }
+                _ => problems.push(format!("`{}` doesn't close anything", tag)),
+            },
+            Kind::Empty => {}
+        }
+    }
+    for tag in open.into_iter() {
+        problems.push(format!("`{}` is never closed", tag));
+    }
+    problems
+}
+
+/// Describe how the tags of `translated` are unbalanced or differ from those
+/// of its `source`, such as a backend dropping or mangling markup
+pub fn changes(source: &str, translated: &str) -> Vec<String> {
+    let source_tags = find_tags(source);
+    let translated_tags = find_tags(translated);
+    // markup that was already broken in the source isn't the backend's fault
+    let mut problems = if unbalanced(&source_tags).is_empty() {
+        unbalanced(&translated_tags)
+    } else {
+        Vec::new()
+    };
+
+    let mut counts: BTreeMap<Tag, isize> = BTreeMap::new();
+    for tag in source_tags.into_iter() {
+        *counts.entry(tag).or_default() += 1;
+    }
+    for tag in translated_tags.into_iter() {
+        *counts.entry(tag).or_default() -= 1;
+    }
+    for (tag, count) in counts.into_iter() {
+        if count > 0 {
+            problems.push(format!("`{}` is missing from `{}`", tag, translated));
+        } else if count < 0 {
+            problems.push(format!("`{}` was added in `{}`", tag, translated));
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_markup_changes() {
+        let source = "I am <em>very</em> glad to<br> see <a href=\"/you\">you</a>!";
+        assert!(changes(
+            source,
+            "Je suis <EM>très</EM> content de<br/> te <a href=\"/you\">voir</a> !"
+        )
+        .is_empty());
+        assert_eq!(
+            changes("<b>Bold</b> text", "<b>Gras texte"),
+            vec![
+                "`<b>` is never closed".to_owned(),
+                "`</b>` is missing from `<b>Gras texte`".to_owned(),
+            ]
+        );
+        assert_eq!(
+            changes("<b><i>both</i></b>", "<b><i>les deux</b></i>"),
+            vec![
+                "`</b>` closes `<i>` out of order".to_owned(),
+                "`</i>` doesn't close anything".to_owned(),
+            ]
+        );
+        assert_eq!(
+            changes("1 < 2", "1 < 2 <span>"),
+            vec![
+                "`<span>` is never closed".to_owned(),
+                "`<span>` was added in `1 < 2 <span>`".to_owned(),
+            ]
+        );
+        assert!(changes("<b>broken", "<b>cassé").is_empty());
+    }
+}
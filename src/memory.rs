@@ -0,0 +1,762 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::translate::MimeType;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct MemoryConfig {
+    /// serve texts that have been translated before from the translation
+    /// memory instead of sending them to the backend again
+    pub enabled: bool,
+    /// where the translation memory is kept, instead of the cache directory
+    pub file: Option<String>,
+    /// how alike a remembered text has to be, from 0 to 1, for its
+    /// translation to be reused for review instead of asking the backend
+    pub fuzzy_threshold: Option<f64>,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> MemoryConfig {
+        MemoryConfig {
+            enabled: true,
+            file: None,
+            fuzzy_threshold: None,
+        }
+    }
+}
+
+/// What a translation depends on, so that it's only reused when the same
+/// text is sent the same way
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct Key {
+    pub source: String,
+    /// whether the text was sent as HTML, which is translated and escaped
+    /// differently
+    pub mime_type: MimeType,
+    pub source_language: String,
+    pub target_language: String,
+    pub backend: String,
+    pub glossary: Option<String>,
+    /// the message's `tt-context`, for backends that take it into account
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+}
+
+impl Key {
+    pub fn new(
+        source: &str,
+        mime_type: MimeType,
+        source_language: &str,
+        target_language: &str,
+        backend: &str,
+        glossary: Option<&str>,
+    ) -> Key {
+        Key {
+            source: source.to_owned(),
+            mime_type,
+            source_language: source_language.to_owned(),
+            target_language: target_language.to_owned(),
+            backend: backend.to_owned(),
+            glossary: glossary.map(str::to_owned),
+            context: None,
+        }
+    }
+}
+
+/// The backends that translate differently given a message's context
+const USE_CONTEXT: &[&str] = &["deepl", "exec"];
+
+/// How a locale's texts are looked up in the memory
+#[derive(Clone, Copy)]
+pub struct Lookup<'a> {
+    /// the backends as they were given, so that different `exec:` plugins
+    /// don't share translations
+    pub backends: &'a [&'a str],
+    pub source_language: &'a str,
+    pub target_language: &'a str,
+    pub glossary: Option<&'a str>,
+    /// the Google model, since other models translate differently
+    pub model: Option<&'a str>,
+    pub fuzzy_threshold: Option<f64>,
+    /// how the message being looked up is sent
+    pub mime_type: MimeType,
+    /// the `tt-context` of the message being looked up
+    pub context: Option<&'a str>,
+}
+
+impl<'a> Lookup<'a> {
+    /// Look up the texts of a message sent as `mime_type` with `context`
+    pub fn for_message(&self, mime_type: MimeType, context: Option<&'a str>) -> Lookup<'a> {
+        Lookup {
+            mime_type,
+            context,
+            ..*self
+        }
+    }
+
+    pub fn key(&self, text: &str, backend: &str) -> Key {
+        let mut key = self.key_without_context(text, backend);
+        if USE_CONTEXT.contains(&backend) {
+            key.context = self.context.map(str::to_owned);
+        }
+        key
+    }
+
+    fn key_without_context(&self, text: &str, backend: &str) -> Key {
+        let backend = self
+            .backends
+            .iter()
+            .find(|spec| spec.split(':').next() == Some(backend))
+            .copied()
+            .unwrap_or(backend);
+        match self.model {
+            Some(model) if backend == "google" => Key::new(
+                text,
+                self.mime_type,
+                self.source_language,
+                self.target_language,
+                &format!("google:{}", model),
+                self.glossary,
+            ),
+            _ => Key::new(
+                text,
+                self.mime_type,
+                self.source_language,
+                self.target_language,
+                backend,
+                self.glossary,
+            ),
+        }
+    }
+}
+
+/// Why a message's translation could be reused
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Recall {
+    /// it was translated in an earlier run
+    Remembered,
+    /// another message with the same text was translated earlier in the run
+    Repeated,
+    /// a text close to it was translated, so it needs reviewing
+    Fuzzy,
+}
+
+/// How alike two texts are, from 0 for nothing in common to 1 for the same,
+/// by how many characters have to change to turn one into the other
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    // the Levenshtein distance, a row at a time
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    1.0 - row[b.len()] as f64 / longest as f64
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Entry {
+    #[serde(flatten)]
+    key: Key,
+    translation: String,
+}
+
+impl Entry {
+    /// Read an entry, and whether it's from before the memory kept mime types,
+    /// in which case it's taken to have been sent as `--mime-type auto` would
+    fn parse(mut value: serde_json::Value) -> Result<(Entry, bool), serde_json::Error> {
+        let upgraded = match value.as_object_mut() {
+            Some(object) if !object.contains_key("mime-type") => {
+                let source = object.get("source").and_then(|s| s.as_str());
+                let mime_type = MimeType::detect(source.unwrap_or_default());
+                object.insert("mime-type".to_owned(), serde_json::to_value(mime_type)?);
+                true
+            }
+            _ => false,
+        };
+        Ok((serde_json::from_value(value)?, upgraded))
+    }
+}
+
+/// The languages and backend of a translation. Texts are only compared for
+/// fuzzy matches with the others on their shelf.
+type Shelf = (String, String, String);
+
+fn shelf(key: &Key) -> Shelf {
+    (
+        key.source_language.clone(),
+        key.target_language.clone(),
+        key.backend.clone(),
+    )
+}
+
+/// The backends' translations of every text sent to them, kept between runs
+/// and shared by the locales, so that a text is only paid for once. The file
+/// has an entry per line, and each run only appends the texts it translated.
+#[derive(Debug, Default)]
+pub struct Memory {
+    path: Option<PathBuf>,
+    entries: HashMap<Shelf, HashMap<Key, String>>,
+    /// the texts that were translated since the memory was loaded
+    added: HashSet<Key>,
+    /// how many entries the file had, counting those that later lines replace
+    lines: usize,
+    /// whether the file has to be written again whole, because it's in the
+    /// old format or has lines that couldn't be read
+    rewrite: bool,
+}
+
+impl Memory {
+    /// Load the translation memory at `path`, starting over if it can't be
+    /// read since it's only a cache
+    pub fn load(path: Option<PathBuf>) -> Memory {
+        let mut memory = Memory {
+            path,
+            ..Memory::default()
+        };
+        let path = match memory.path.clone() {
+            Some(path) => path,
+            None => return memory,
+        };
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return memory,
+            Err(e) => {
+                log::warn!("can't read translation memory {}: {}", path.display(), e);
+                return memory;
+            }
+        };
+        // memories from before it was kept a line at a time are one array
+        if contents.trim_start().starts_with('[') {
+            let stored =
+                serde_json::from_str::<Vec<serde_json::Value>>(&contents).and_then(|stored| {
+                    stored
+                        .into_iter()
+                        .map(Entry::parse)
+                        .collect::<Result<Vec<_>, _>>()
+                });
+            match stored {
+                Ok(stored) => {
+                    for (entry, _) in stored {
+                        memory.store(entry.key, entry.translation);
+                    }
+                }
+                Err(e) => log::warn!(
+                    "ignoring invalid translation memory {}: {}",
+                    path.display(),
+                    e
+                ),
+            }
+            memory.rewrite = true;
+            return memory;
+        }
+        for (number, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(line).and_then(Entry::parse) {
+                Ok((entry, upgraded)) => {
+                    memory.store(entry.key, entry.translation);
+                    memory.lines += 1;
+                    memory.rewrite |= upgraded;
+                }
+                Err(e) => {
+                    // such as the end of a run that was cut off while saving
+                    log::warn!(
+                        "ignoring line {} of translation memory {}: {}",
+                        number + 1,
+                        path.display(),
+                        e
+                    );
+                    memory.rewrite = true;
+                }
+            }
+        }
+        memory
+    }
+
+    pub fn get(&self, key: &Key) -> Option<&str> {
+        self.entries.get(&shelf(key))?.get(key).map(String::as_str)
+    }
+
+    /// How many texts are remembered
+    fn len(&self) -> usize {
+        self.entries.values().map(HashMap::len).sum()
+    }
+
+    /// Whether `key` was translated since the memory was loaded, rather than
+    /// in an earlier run
+    pub fn added_now(&self, key: &Key) -> bool {
+        self.added.contains(key)
+    }
+
+    /// The translation of the remembered text most like that of `key` that
+    /// is at least `threshold` alike, translated the same way and with the
+    /// same number of placeables
+    pub fn closest(&self, key: &Key, threshold: f64) -> Option<&str> {
+        let placeables = super::placeables::markers(&key.source).len();
+        // a placeable's marker is much longer than the words around it, so it
+        // only counts as a short word when comparing texts
+        let compared = |source: &str| super::placeables::strip_markers(source, "___");
+        let source = compared(&key.source);
+        let longest = source.chars().count();
+        self.entries
+            .get(&shelf(key))?
+            .iter()
+            .filter(|(other, _)| {
+                other.mime_type == key.mime_type
+                    && other.glossary == key.glossary
+                    && other.context == key.context
+                    && super::placeables::markers(&other.source).len() == placeables
+            })
+            .map(|(other, translation)| (compared(&other.source), other, translation))
+            .filter(|(other_source, _, _)| {
+                // texts of very different lengths can't be alike enough
+                let length = other_source.chars().count();
+                let difference = length.max(longest) - length.min(longest);
+                difference as f64 <= (1.0 - threshold) * length.max(longest) as f64
+            })
+            .map(|(other_source, other, translation)| {
+                (similarity(&source, &other_source), other, translation)
+            })
+            .filter(|(similarity, _, _)| *similarity >= threshold)
+            .max_by(|(a, a_key, _), (b, b_key, _)| {
+                a.partial_cmp(b)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| b_key.source.cmp(&a_key.source))
+            })
+            .map(|(_, _, translation)| translation.as_str())
+    }
+
+    /// The translations of a message's `texts` by the first of `backends`
+    /// that has translated them all, or ones close enough to them
+    pub fn recall(
+        &self,
+        lookup: &Lookup,
+        backends: &[&'static str],
+        texts: &[&str],
+    ) -> Option<(Vec<String>, &'static str, Recall)> {
+        backends.iter().find_map(|backend| {
+            let mut fuzzy = false;
+            let translated = texts
+                .iter()
+                .map(|text| {
+                    let key = lookup.key(text, backend);
+                    self.get(&key)
+                        .or_else(|| {
+                            let closest = self.closest(&key, lookup.fuzzy_threshold?);
+                            fuzzy |= closest.is_some();
+                            closest
+                        })
+                        .map(str::to_owned)
+                })
+                .collect::<Option<Vec<String>>>()?;
+            let recall = if fuzzy {
+                Recall::Fuzzy
+            } else if texts
+                .iter()
+                .all(|text| self.added_now(&lookup.key(text, backend)))
+            {
+                Recall::Repeated
+            } else {
+                Recall::Remembered
+            };
+            Some((translated, *backend, recall))
+        })
+    }
+
+    /// Remember what `backend` translated a message's `texts` as
+    pub fn remember(
+        &mut self,
+        lookup: &Lookup,
+        backend: &str,
+        texts: &[&str],
+        translated: &[String],
+    ) {
+        for (text, translated) in texts.iter().zip(translated.iter()) {
+            self.insert(lookup.key(text, backend), translated.clone());
+        }
+    }
+
+    pub fn insert(&mut self, key: Key, translation: String) {
+        if self.get(&key) != Some(translation.as_str()) {
+            self.store(key.clone(), translation);
+            self.added.insert(key);
+        }
+    }
+
+    fn store(&mut self, key: Key, translation: String) {
+        self.entries
+            .entry(shelf(&key))
+            .or_default()
+            .insert(key, translation);
+    }
+
+    /// Append what was added to the translation memory to its file, or write
+    /// it again whole once half of its lines have been replaced by later ones
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = match &self.path {
+            Some(path) if !self.added.is_empty() || self.rewrite => path,
+            _ => return Ok(()),
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        if self.rewrite || self.lines + self.added.len() >= 2 * self.len() {
+            let keys = self.entries.values().flat_map(HashMap::keys);
+            let written = self.write(path, keys, false)?;
+            log::debug!("saved {} translation(s) to {}", written, path.display());
+        } else {
+            let written = self.write(path, self.added.iter(), true)?;
+            log::debug!("added {} translation(s) to {}", written, path.display());
+        }
+        Ok(())
+    }
+
+    /// Write the entries of `keys` to `path` a line at a time, sorted so that
+    /// the file doesn't churn between runs
+    fn write<'k>(
+        &self,
+        path: &Path,
+        keys: impl Iterator<Item = &'k Key>,
+        append: bool,
+    ) -> Result<usize, Box<dyn Error>> {
+        let mut keys: Vec<&Key> = keys.collect();
+        keys.sort_by(|a, b| {
+            (&a.target_language, &a.source, &a.backend).cmp(&(
+                &b.target_language,
+                &b.source,
+                &b.backend,
+            ))
+        });
+        let mut lines = String::new();
+        for key in keys.iter() {
+            let entry = Entry {
+                key: (*key).clone(),
+                translation: self.entries[&shelf(key)][*key].clone(),
+            };
+            lines.push_str(&serde_json::to_string(&entry)?);
+            lines.push('\n');
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)?;
+        // a single write, so that runs saving at the same time don't
+        // interleave their lines
+        file.write_all(lines.as_bytes())?;
+        Ok(keys.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_similarity() {
+        assert_eq!(similarity("", ""), 1.0);
+        assert_eq!(similarity("kitten", "sitting"), 1.0 - 3.0 / 7.0);
+        assert_eq!(similarity("Save", "Save"), 1.0);
+        assert_eq!(similarity("abc", "xyz"), 0.0);
+    }
+
+    #[test]
+    fn remembers_translations() {
+        let mut memory = Memory::default();
+        memory.insert(
+            Key::new("Hello", MimeType::Text, "en", "fr", "google", None),
+            "Bonjour".to_owned(),
+        );
+        assert_eq!(
+            memory.get(&Key::new(
+                "Hello",
+                MimeType::Text,
+                "en",
+                "fr",
+                "google",
+                None
+            )),
+            Some("Bonjour")
+        );
+        assert_eq!(
+            memory.get(&Key::new(
+                "Hello",
+                MimeType::Text,
+                "en",
+                "fr",
+                "deepl",
+                None
+            )),
+            None
+        );
+        assert_eq!(
+            memory.get(&Key::new(
+                "Hello",
+                MimeType::Text,
+                "en",
+                "fr",
+                "google",
+                Some("terms")
+            )),
+            None
+        );
+        assert!(memory.added_now(&Key::new(
+            "Hello",
+            MimeType::Text,
+            "en",
+            "fr",
+            "google",
+            None
+        )));
+
+        let name = super::super::placeables::marker(1);
+        memory.insert(
+            Key::new(
+                &format!("Hello {}!", name),
+                MimeType::Text,
+                "en",
+                "fr",
+                "google",
+                None,
+            ),
+            format!("Bonjour {} !", name),
+        );
+        assert_eq!(
+            memory.closest(
+                &Key::new(
+                    &format!("Hello {}.", name),
+                    MimeType::Text,
+                    "en",
+                    "fr",
+                    "google",
+                    None
+                ),
+                0.85
+            ),
+            Some(format!("Bonjour {} !", name).as_str())
+        );
+        assert_eq!(
+            memory.closest(
+                &Key::new(
+                    &format!("Hello {}.", name),
+                    MimeType::Text,
+                    "en",
+                    "fr",
+                    "google",
+                    None
+                ),
+                0.95
+            ),
+            None
+        );
+        assert_eq!(
+            memory.closest(
+                &Key::new(
+                    &format!("Hello {} {}!", name, name),
+                    MimeType::Text,
+                    "en",
+                    "fr",
+                    "google",
+                    None
+                ),
+                0.5
+            ),
+            None
+        );
+        assert_eq!(
+            memory.closest(
+                &Key::new(
+                    &format!("Hello {}.", name),
+                    MimeType::Text,
+                    "en",
+                    "de",
+                    "google",
+                    None
+                ),
+                0.5
+            ),
+            None
+        );
+
+        // entries from before mime types were kept are told apart by looking
+        let (stored, upgraded) = Entry::parse(serde_json::json!({"source": "Hello", "source-language": "en", "target-language": "de", "backend": "deepl", "glossary": null, "translation": "Hallo"})).unwrap();
+        assert!(upgraded);
+        assert_eq!(
+            stored.key,
+            Key::new("Hello", MimeType::Text, "en", "de", "deepl", None)
+        );
+        let (stored, _) = Entry::parse(serde_json::json!({"source": "<b>Hello</b>", "source-language": "en", "target-language": "de", "backend": "deepl", "glossary": null, "translation": "<b>Hallo</b>"})).unwrap();
+        assert_eq!(stored.key.mime_type, MimeType::Html);
+        let line = serde_json::to_value(&stored).unwrap();
+        assert_eq!(line["mime-type"], "text/html");
+        assert!(!Entry::parse(line).unwrap().1);
+    }
+
+    #[test]
+    fn appends_to_the_file() {
+        let dir = std::env::temp_dir().join(format!("tt-memory-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("translation-memory.json");
+        let hello = Key::new("Hello", MimeType::Text, "en", "fr", "google", None);
+        let save = Key::new("Save", MimeType::Text, "en", "fr", "google", None);
+
+        let mut memory = Memory::load(Some(path.clone()));
+        memory.insert(hello.clone(), "Bonjour".to_owned());
+        memory.save().unwrap();
+        let first = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(first.lines().count(), 1);
+
+        // only the new translation is written, after what was there
+        let mut memory = Memory::load(Some(path.clone()));
+        assert_eq!(memory.get(&hello), Some("Bonjour"));
+        assert!(!memory.added_now(&hello));
+        memory.insert(save.clone(), "Enregistrer".to_owned());
+        memory.save().unwrap();
+        let second = std::fs::read_to_string(&path).unwrap();
+        assert!(second.starts_with(&first));
+        assert_eq!(second.lines().count(), 2);
+
+        // later lines replace earlier ones, until the file is written again
+        let mut memory = Memory::load(Some(path.clone()));
+        memory.insert(hello.clone(), "Salut".to_owned());
+        memory.save().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+        let mut memory = Memory::load(Some(path.clone()));
+        assert_eq!(memory.get(&hello), Some("Salut"));
+        memory.insert(hello.clone(), "Coucou".to_owned());
+        memory.save().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+        assert_eq!(Memory::load(Some(path.clone())).get(&hello), Some("Coucou"));
+
+        // a line cut off while saving is skipped, and the file is mended
+        let mut contents = std::fs::read_to_string(&path).unwrap();
+        contents.push_str("{\"source\": \"Can");
+        std::fs::write(&path, contents).unwrap();
+        let memory = Memory::load(Some(path.clone()));
+        assert_eq!(memory.get(&save), Some("Enregistrer"));
+        memory.save().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+        // and memories kept as a single array are read and rewritten
+        std::fs::write(
+            &path,
+            r#"[{"source": "Hello", "source-language": "en", "target-language": "fr", "backend": "google", "glossary": null, "translation": "Bonjour"}]"#,
+        )
+        .unwrap();
+        let memory = Memory::load(Some(path.clone()));
+        assert_eq!(memory.get(&hello), Some("Bonjour"));
+        memory.save().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 1);
+        assert_eq!(Memory::load(Some(path)).get(&hello), Some("Bonjour"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recalls_messages() {
+        let lookup = Lookup {
+            backends: &["exec:./mt.py", "google"],
+            source_language: "en",
+            target_language: "fr",
+            glossary: None,
+            model: None,
+            fuzzy_threshold: Some(0.8),
+            mime_type: MimeType::Text,
+            context: None,
+        };
+        let mut memory = Memory::load(None);
+        memory.store(
+            Key::new("Cancel", MimeType::Text, "en", "fr", "exec:./mt.py", None),
+            "Annuler".to_owned(),
+        );
+        assert_eq!(
+            memory.recall(&lookup, &["exec", "google"], &["Cancel"]),
+            Some((vec!["Annuler".to_owned()], "exec", Recall::Remembered))
+        );
+        memory.remember(
+            &lookup,
+            "google",
+            &["Save", "Save it"],
+            &["Enregistrer".to_owned(), "Enregistrez-le".to_owned()],
+        );
+        assert_eq!(
+            memory.recall(&lookup, &["exec", "google"], &["Save", "Save it"]),
+            Some((
+                vec!["Enregistrer".to_owned(), "Enregistrez-le".to_owned()],
+                "google",
+                Recall::Repeated
+            ))
+        );
+        assert_eq!(
+            memory
+                .recall(&lookup, &["exec", "google"], &["Save", "Save it!"])
+                .map(|r| r.2),
+            Some(Recall::Fuzzy)
+        );
+        assert_eq!(memory.recall(&lookup, &["google"], &["Cancel"]), None);
+        let base = Lookup {
+            model: Some("general/base"),
+            ..lookup
+        };
+        assert_eq!(memory.recall(&base, &["google"], &["Save"]), None);
+        // Google doesn't take the context into account, but plugins might
+        let checkout = lookup.for_message(MimeType::Text, Some("on the checkout button"));
+        assert_eq!(memory.recall(&checkout, &["exec"], &["Cancel"]), None);
+        assert_eq!(
+            memory
+                .recall(&checkout, &["google"], &["Save", "Save it"])
+                .map(|r| r.1),
+            Some("google")
+        );
+
+        // HTML is escaped differently, so neither is reused for the other
+        let html = lookup.for_message(MimeType::Html, None);
+        assert_eq!(memory.recall(&html, &["google"], &["Save"]), None);
+        assert_eq!(memory.recall(&html, &["google"], &["Save it!"]), None);
+        memory.remember(
+            &html,
+            "google",
+            &["Save"],
+            &["Enregistrer&nbsp;".to_owned()],
+        );
+        assert_eq!(
+            memory.recall(&html, &["google"], &["Save"]).map(|r| r.0),
+            Some(vec!["Enregistrer&nbsp;".to_owned()])
+        );
+        assert_eq!(
+            memory.recall(&lookup, &["google"], &["Save"]).map(|r| r.0),
+            Some(vec!["Enregistrer".to_owned()])
+        );
+    }
+}
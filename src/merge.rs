@@ -0,0 +1,185 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+/// A piece of a Fluent file, exactly as it was written
+#[derive(Debug, PartialEq)]
+pub struct Chunk<'s> {
+    /// the message id (or `-id` for terms), or `None` for standalone comments
+    /// and anything else that isn't an entry
+    pub id: Option<&'s str>,
+    /// the entry along with its comment and the blank lines that follow it
+    pub text: &'s str,
+}
+
+/// Split a Fluent file into its entries without parsing their contents, so
+/// that each one can be kept byte for byte
+pub fn split(text: &str) -> Vec<Chunk<'_>> {
+    // where each chunk starts, and which entry it is
+    let mut starts: Vec<(usize, Option<&str>)> = Vec::new();
+    // the chunk holding the `#` comments directly above the current line
+    let mut comment: Option<usize> = None;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let trimmed = line.trim_end();
+        if trimmed.starts_with("##") {
+            // group and resource comments never belong to an entry
+            starts.push((start, None));
+            comment = None;
+        } else if trimmed.starts_with('#') {
+            if comment.is_none() {
+                comment = Some(starts.len());
+                starts.push((start, None));
+            }
+        } else if trimmed.is_empty() || line.starts_with(char::is_whitespace) {
+            // blank lines and indented lines belong to the chunk above
+            comment = None;
+        } else {
+            let id = trimmed.split('=').next().unwrap_or(trimmed).trim();
+            match comment.take() {
+                Some(i) => starts[i].1 = Some(id),
+                None => starts.push((start, Some(id))),
+            }
+        }
+    }
+    if starts
+        .first()
+        .map(|(start, _)| *start > 0)
+        .unwrap_or(!text.is_empty())
+    {
+        starts.insert(0, (0, None));
+    }
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, (start, id))| {
+            let end = starts.get(i + 1).map(|(end, _)| *end).unwrap_or(text.len());
+            Chunk {
+                id: *id,
+                text: &text[*start..end],
+            }
+        })
+        .collect()
+}
+
+/// Split a chunk into its leading comment lines, its entry, and the blank
+/// lines after it
+fn parts(text: &str) -> (&str, &str, &str) {
+    let body_start = text
+        .split_inclusive('\n')
+        .take_while(|line| line.starts_with('#'))
+        .map(str::len)
+        .sum();
+    let body_end = text.trim_end().len().max(body_start);
+    (
+        &text[..body_start],
+        &text[body_start..body_end],
+        &text[body_end..],
+    )
+}
+
+/// Update the `existing` Fluent file in place. Entries in `rewrite` (rendered
+/// without a trailing newline) replace the existing ones but keep their
+/// comments, new entries are inserted after the entry that precedes them in
+/// `source_ids`, and everything else keeps its exact bytes. Entries that
+/// aren't in `source_ids` are removed, unless `deprecated` gives the marker to
+/// keep them with.
+pub fn merge(
+    existing: &str,
+    source_ids: &[&str],
+    rewrite: &HashMap<&str, String>,
+    deprecated: Option<&str>,
+) -> String {
+    let mut chunks: Vec<(Option<&str>, String)> = Vec::new();
+    for chunk in split(existing) {
+        let text = match chunk.id {
+            Some(id) if rewrite.contains_key(id) => {
+                let (comment, _, blank) = parts(chunk.text);
+                format!("{}{}{}", comment, rewrite[id], blank)
+            }
+            Some(id) if source_ids.contains(&id) => chunk.text.to_owned(),
+            Some(_) => match deprecated {
+                Some(marker) if chunk.text.contains(marker) => chunk.text.to_owned(),
+                Some(marker) => format!("# {}\n{}", marker, chunk.text),
+                None => continue,
+            },
+            None => chunk.text.to_owned(),
+        };
+        chunks.push((chunk.id, text));
+    }
+
+    // insert new entries after the one that comes before them in the source,
+    // or before the first entry if they come first
+    let mut cursor = chunks
+        .iter()
+        .position(|(id, _)| id.is_some())
+        .unwrap_or(chunks.len());
+    for id in source_ids.iter() {
+        if let Some(position) = chunks.iter().position(|(i, _)| *i == Some(*id)) {
+            cursor = position + 1;
+        } else if let Some(text) = rewrite.get(id) {
+            if let Some((_, previous)) = cursor.checked_sub(1).and_then(|i| chunks.get_mut(i)) {
+                if !previous.ends_with('\n') {
+                    previous.push('\n');
+                }
+            }
+            chunks.insert(cursor, (Some(*id), format!("{}\n\n", text)));
+            cursor += 1;
+        }
+    }
+
+    chunks.into_iter().map(|(_, text)| text).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_entries_with_their_comments() {
+        let text = "### resource\n\n# about a\na = A\n    .title = T\n\n## group\nb = B\n\n\nc =\n    multi\n\n    line\n";
+        let chunks: Vec<(Option<&str>, &str)> =
+            split(text).into_iter().map(|c| (c.id, c.text)).collect();
+        assert_eq!(
+            chunks,
+            vec![
+                (None, "### resource\n\n"),
+                (Some("a"), "# about a\na = A\n    .title = T\n\n"),
+                (None, "## group\n"),
+                (Some("b"), "b = B\n\n\n"),
+                (Some("c"), "c =\n    multi\n\n    line\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn merges_in_place() {
+        let existing = "# keep me\na   =   Un\n\n# translator note\nb = Deux\n\nold = Vieux\n";
+        let mut rewrite = HashMap::new();
+        rewrite.insert("b", "b = Deux!".to_owned());
+        rewrite.insert("new", "new = Nouveau".to_owned());
+        assert_eq!(
+            merge(existing, &["a", "b", "new"], &rewrite, None),
+            "# keep me\na   =   Un\n\n# translator note\nb = Deux!\n\nnew = Nouveau\n\n"
+        );
+        assert_eq!(
+            merge(existing, &["new", "a", "b"], &rewrite, Some("tt-deprecated")),
+            "new = Nouveau\n\n# keep me\na   =   Un\n\n# translator note\nb = Deux!\n\n# tt-deprecated\nold = Vieux\n"
+        );
+    }
+}
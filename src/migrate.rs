@@ -0,0 +1,155 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde_json::Value;
+
+/// Upgrades a document from the version before it
+type Step = fn(&mut Value) -> Result<(), String>;
+
+/// A file format that's kept between runs, along with how to upgrade each
+/// older version of it. The version is stored in the file's `version` key.
+pub struct Schema {
+    pub name: &'static str,
+    /// `steps[n]` upgrades version `n` to version `n + 1`, where version 0 is
+    /// a file written before versions were recorded
+    steps: &'static [Step],
+}
+
+/// What upgrading a document did
+#[derive(Debug, PartialEq)]
+pub struct Migrated {
+    /// the version the document was in
+    pub from: u64,
+    /// whether anything other than the version changed
+    pub changed: bool,
+}
+
+impl Schema {
+    /// The version that this `tt` writes
+    pub fn version(&self) -> u64 {
+        self.steps.len() as u64
+    }
+
+    /// Upgrade `document` to the current version. Documents written by a
+    /// newer `tt` are refused, rather than read wrongly or written over.
+    pub fn migrate(&self, document: &mut Value) -> Result<Migrated, String> {
+        let from = match document.get("version") {
+            None => 0,
+            Some(version) => version
+                .as_u64()
+                .ok_or_else(|| format!("the {} version must be a whole number", self.name))?,
+        };
+        if from > self.version() {
+            return Err(format!(
+                "the {} is version {}, but this tt only understands up to version {}, upgrade tt to use it",
+                self.name,
+                from,
+                self.version()
+            ));
+        }
+
+        let original = document.clone();
+        for step in self.steps[from as usize..].iter() {
+            step(document)?;
+        }
+        let changed = without_version(document) != without_version(&original);
+        if let Value::Object(object) = document {
+            object.insert("version".to_owned(), Value::from(self.version()));
+        }
+        Ok(Migrated { from, changed })
+    }
+}
+
+fn without_version(document: &Value) -> Value {
+    let mut document = document.clone();
+    if let Value::Object(object) = &mut document {
+        object.remove("version");
+    }
+    document
+}
+
+/// Files from before versions were recorded are the same as version 1
+fn add_version(_: &mut Value) -> Result<(), String> {
+    Ok(())
+}
+
+/// `tt.toml`
+pub const CONFIG: Schema = Schema {
+    name: "config",
+    steps: &[add_version],
+};
+
+/// `tt.lock`
+pub const LOCK: Schema = Schema {
+    name: "lock file",
+    steps: &[add_version],
+};
+
+/// `tt-review.json`
+pub const REVIEW: Schema = Schema {
+    name: "review queue",
+    steps: &[add_version],
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rename_backend(document: &mut Value) -> Result<(), String> {
+        if let Value::Object(object) = document {
+            if let Some(backend) = object.remove("backend") {
+                object.insert("backends".to_owned(), json!([backend]));
+            }
+        }
+        Ok(())
+    }
+
+    const SCHEMA: Schema = Schema {
+        name: "config",
+        steps: &[add_version, rename_backend],
+    };
+
+    #[test]
+    fn migrates_older_versions() {
+        let mut document = json!({ "backend": "deepl" });
+        assert_eq!(
+            SCHEMA.migrate(&mut document),
+            Ok(Migrated {
+                from: 0,
+                changed: true
+            })
+        );
+        assert_eq!(document, json!({ "backends": ["deepl"], "version": 2 }));
+
+        let mut document = json!({ "version": 2, "backends": ["google"] });
+        assert_eq!(
+            SCHEMA.migrate(&mut document),
+            Ok(Migrated {
+                from: 2,
+                changed: false
+            })
+        );
+
+        let mut document = json!({ "locales": ["fr"] });
+        assert!(!CONFIG.migrate(&mut document).unwrap().changed);
+        assert_eq!(document["version"], 1);
+    }
+
+    #[test]
+    fn refuses_newer_versions() {
+        assert!(SCHEMA.migrate(&mut json!({ "version": 3 })).is_err());
+        assert!(SCHEMA.migrate(&mut json!({ "version": "two" })).is_err());
+    }
+}
@@ -0,0 +1,244 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::error::Error;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use super::translate::{GlossaryConfig, LRLanguage, MimeType};
+
+/// The Bergamot `translateLocally` executable, overridable with the
+/// `TT_TRANSLATE_LOCALLY` environment variable
+fn binary() -> String {
+    std::env::var("TT_TRANSLATE_LOCALLY").unwrap_or_else(|_| "translateLocally".to_owned())
+}
+
+/// A model installed for `translateLocally`, identified as `<from>-<to>-<type>`
+struct Model {
+    id: String,
+    from: String,
+    to: String,
+}
+
+/// List the models installed for `translateLocally`
+fn installed_models() -> Result<Vec<Model>, Box<dyn Error>> {
+    list_models(&binary())
+}
+
+/// List the models installed for the `translateLocally` at `binary`
+fn list_models(binary: &str) -> Result<Vec<Model>, Box<dyn Error>> {
+    let output = Command::new(binary).arg("-l").output().map_err(|e| {
+        format!(
+            "failed to run `{}`, is translateLocally installed? {}",
+            binary, e
+        )
+    })?;
+    if !output.status.success() {
+        return Err(Box::from(format!(
+            "failed to list translateLocally models: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    Ok(parse_models(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// The models in the output of `translateLocally -l`, each listed on a line
+/// ending with `To invoke do -m <model>`
+fn parse_models(stdout: &str) -> Vec<Model> {
+    stdout
+        .lines()
+        .filter_map(|line| line.split("-m ").nth(1))
+        .filter_map(|id| {
+            let id = id.trim();
+            let mut parts = id.splitn(3, '-');
+            let from = parts.next()?;
+            let to = parts.next()?;
+            Some(Model {
+                id: id.to_owned(),
+                from: from.to_owned(),
+                to: to.to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// The languages that `models` can translate `source` into
+fn target_languages(models: Vec<Model>, source: &str) -> Vec<LRLanguage> {
+    models
+        .into_iter()
+        .filter(|m| m.from == source)
+        .map(|m| LRLanguage {
+            display_name: m.to.clone(),
+            language_code: m.to,
+            support_source: false,
+            support_target: true,
+        })
+        .collect()
+}
+
+/// Translates using local Bergamot models so that no network access is needed
+pub struct OfflineTranslator<'a> {
+    source_language: String,
+    language: &'a str,
+    model: Option<String>,
+}
+
+impl<'a> OfflineTranslator<'a> {
+    pub fn new(source: &str, language: &'a str) -> Result<OfflineTranslator<'a>, Box<dyn Error>> {
+        // translating into the source language doesn't need a model, it's just
+        // copied over
+        let model = if language == source {
+            None
+        } else {
+            let model = installed_models()?
+                .into_iter()
+                .find(|m| m.from == source && m.to == language)
+                .ok_or_else(|| {
+                    log::error!(
+                        "no offline model is installed for {} -> {}",
+                        source,
+                        language
+                    );
+                    super::errors::Errors::InvalidLanguage
+                })?;
+            Some(model.id)
+        };
+        Ok(OfflineTranslator {
+            source_language: source.to_owned(),
+            language,
+            model,
+        })
+    }
+}
+
+impl<'a> super::backend::Backend for OfflineTranslator<'a> {
+    fn name(&self) -> &'static str {
+        "offline"
+    }
+
+    fn available_languages(&self) -> Result<Vec<LRLanguage>, Box<dyn Error>> {
+        Ok(target_languages(installed_models()?, &self.source_language))
+    }
+
+    fn get_lang_name(&self) -> Result<String, Box<dyn Error>> {
+        // models don't carry language names
+        Ok("<INSERT LANGUAGE NAME HERE>".to_owned())
+    }
+
+    fn translate<'c>(
+        &self,
+        phrase: &str,
+        mime_type: MimeType,
+        glossary: &Option<GlossaryConfig<'c>>,
+    ) -> Result<String, Box<dyn Error>> {
+        let model = match &self.model {
+            Some(model) => model,
+            None => return Ok(phrase.to_owned()),
+        };
+        if glossary.is_some() {
+            log::debug!(
+                "glossaries aren't supported offline, ignoring for {}",
+                self.language
+            );
+        }
+
+        // the placeables' markers and protected spans are markup, which
+        // translateLocally only leaves alone when it translates HTML
+        let html = mime_type == MimeType::Html || MimeType::detect(phrase) == MimeType::Html;
+        let mut command = Command::new(binary());
+        command.arg("-m").arg(model);
+        if html {
+            command.arg("--html");
+        }
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(phrase.as_bytes())?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            log::error!(
+                "translateLocally failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            return Err(Box::from(super::errors::Errors::FailedQuery));
+        }
+
+        let translation = String::from_utf8(output.stdout)?;
+        let translation = if html {
+            super::entities::decode(phrase, translation.trim_end())?
+        } else {
+            translation.trim_end().to_owned()
+        };
+        Ok(translation.replace("\n", "\n    "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Backend;
+
+    const LISTING: &str = "\
+Czech-English type: tiny version: 1; To invoke do -m cs-en-tiny
+English-German type: base version: 2; To invoke do -m en-de-base
+English-French type: tiny version: 1; To invoke do -m en-fr-tiny
+not a model
+";
+
+    #[test]
+    fn parses_installed_models() {
+        let models = parse_models(LISTING);
+        let ids: Vec<&str> = models.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["cs-en-tiny", "en-de-base", "en-fr-tiny"]);
+        assert_eq!(models[1].from, "en");
+        assert_eq!(models[1].to, "de");
+        assert!(parse_models("").is_empty());
+    }
+
+    #[test]
+    fn lists_languages_from_the_source() {
+        let languages = target_languages(parse_models(LISTING), "en");
+        let codes: Vec<&str> = languages.iter().map(|l| l.language_code.as_str()).collect();
+        assert_eq!(codes, ["de", "fr"]);
+        assert!(languages
+            .iter()
+            .all(|l| l.support_target && !l.support_source));
+        assert_eq!(
+            target_languages(parse_models(LISTING), "cs")[0].language_code,
+            "en"
+        );
+    }
+
+    #[test]
+    fn explains_a_missing_binary() {
+        let error = list_models("/nonexistent/translateLocally")
+            .err()
+            .expect("there's no binary to run");
+        assert!(error.to_string().contains("is translateLocally installed?"));
+        // the source language is copied over without any model
+        let translator = OfflineTranslator::new("en", "en").unwrap();
+        assert_eq!(
+            translator
+                .translate("Hello", MimeType::Text, &None)
+                .unwrap(),
+            "Hello"
+        );
+    }
+}
@@ -0,0 +1,59 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::PathBuf;
+
+/// The platform's state directory: `$XDG_STATE_HOME` (or `~/.local/state`) on
+/// Linux, and the local data directory elsewhere
+fn state_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "linux") {
+        std::env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| dirs::home_dir().map(|home| home.join(".local").join("state")))
+    } else {
+        dirs::data_local_dir()
+    }
+}
+
+/// Where `tt` keeps files between runs. Caches can be deleted at any time,
+/// while state is worth keeping.
+#[derive(Debug)]
+pub struct Paths {
+    pub cache: Option<PathBuf>,
+    pub state: Option<PathBuf>,
+}
+
+impl Paths {
+    /// Use the given directories, falling back to `tt` directories inside the
+    /// platform's cache and state directories
+    pub fn new(cache: Option<&str>, state: Option<&str>) -> Paths {
+        Paths {
+            cache: cache
+                .map(PathBuf::from)
+                .or_else(|| dirs::cache_dir().map(|dir| dir.join("tt"))),
+            state: state
+                .map(PathBuf::from)
+                .or_else(|| state_dir().map(|dir| dir.join("tt"))),
+        }
+    }
+
+    pub fn cache_file(&self, name: &str) -> Option<PathBuf> {
+        self.cache.as_ref().map(|dir| dir.join(name))
+    }
+
+    pub fn state_file(&self, name: &str) -> Option<PathBuf> {
+        self.state.as_ref().map(|dir| dir.join(name))
+    }
+}
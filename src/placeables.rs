@@ -0,0 +1,452 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fluent_syntax::ast::{
+    CallArguments, Entry, Expression, InlineExpression, Pattern, PatternElement, ResourceEntry,
+};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct PlaceablesConfig {
+    pub on_mismatch: OnMismatch,
+}
+
+/// What to do with a translation whose placeables don't match the source's
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnMismatch {
+    /// warn about it and queue it for review
+    #[default]
+    Warn,
+    /// ask the backend for it again, once, before warning
+    Retry,
+    /// warn, and mark the message as needing review in the output
+    Review,
+}
+
+/// The marker that stands in for the placeable numbered `id`, from 1, in the
+/// text that's sent to be translated. Backends leave `translate="no"` spans
+/// alone, and the id says which placeable it was even if it's moved.
+pub fn marker(id: usize) -> String {
+    format!("<span translate=\"no\" id=\"ph{}\"></span>", id)
+}
+
+/// The marker of the placeable numbered `id` with `text` inside of it, such as
+/// the value of a term it refers to, for the backend to see but not translate.
+/// `text` is escaped so that characters like `&` and `<` don't break the markup.
+pub fn marker_around(id: usize, text: &str) -> String {
+    format!(
+        "<span translate=\"no\" id=\"ph{}\">{}</span>",
+        id,
+        super::compare::html_escape(text)
+    )
+}
+
+/// A placeable's marker found in a translation
+#[derive(Clone, PartialEq, Debug)]
+pub struct Marker {
+    /// where the marker is, in bytes
+    pub range: std::ops::Range<usize>,
+    pub id: usize,
+}
+
+fn starts_with_ignoring_case(text: &str, prefix: &str) -> bool {
+    text.get(..prefix.len())
+        .map(|start| start.eq_ignore_ascii_case(prefix))
+        .unwrap_or(false)
+}
+
+/// The id in the `id="phN"` of a tag's insides
+fn marker_id(inside: &str) -> Option<usize> {
+    let lowercase = inside.to_ascii_lowercase();
+    let start = lowercase.find("id=")? + "id=".len();
+    let rest = lowercase[start..].trim_start_matches(['"', '\'']);
+    let digits: String = rest
+        .strip_prefix("ph")?
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse().ok()
+}
+
+/// Find the placeables' markers in `text`, in order, forgiving backends that
+/// change their case or quotes, or put something inside of them
+pub fn markers(text: &str) -> Vec<Marker> {
+    let mut found = Vec::new();
+    let mut searched = 0;
+    while let Some(start) = text[searched..].find('<').map(|i| searched + i) {
+        searched = start + 1;
+        if !starts_with_ignoring_case(&text[start..], "<span") {
+            continue;
+        }
+        let open_end = match text[start..].find('>') {
+            Some(end) => start + end + 1,
+            None => break,
+        };
+        let id = match marker_id(&text[start..open_end]) {
+            Some(id) => id,
+            None => continue,
+        };
+        let mut end = open_end;
+        if let Some(inner) = text[open_end..].find('<') {
+            let close = open_end + inner;
+            if starts_with_ignoring_case(&text[close..], "</span") {
+                if let Some(close_end) = text[close..].find('>') {
+                    end = close + close_end + 1;
+                }
+            }
+        }
+        found.push(Marker {
+            range: start..end,
+            id,
+        });
+        searched = end;
+    }
+    found
+}
+
+/// `text` with its placeables' markers replaced by `with`
+pub fn strip_markers(text: &str, with: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut last = 0;
+    for marker in markers(text).into_iter() {
+        stripped.push_str(&text[last..marker.range.start]);
+        stripped.push_str(with);
+        last = marker.range.end;
+    }
+    stripped.push_str(&text[last..]);
+    stripped
+}
+
+/// How the markers in a translation compare to its source's placeables
+#[derive(Clone, PartialEq, Debug)]
+pub enum Order {
+    /// each placeable's marker is there once, in the same order as the source
+    Same,
+    /// each placeable's marker is there once, but moved around, which the
+    /// target language's word order may need
+    Moved,
+    /// there's a marker for each placeable, but their ids were changed or
+    /// repeated, so they're renumbered in the order that they're in
+    Repaired(String),
+    /// there are more or fewer markers than placeables
+    Broken,
+}
+
+fn placeable_count(pattern: &Pattern) -> usize {
+    pattern
+        .elements
+        .iter()
+        .filter(|element| matches!(element, PatternElement::Placeable(_)))
+        .count()
+}
+
+/// Compare the markers in the translation of `pattern` to its placeables,
+/// renumbering them by their position if their ids can't be trusted
+pub fn order(pattern: &Pattern, translated: &str) -> Order {
+    let expected = placeable_count(pattern);
+    let found = markers(translated);
+    if found.len() != expected {
+        return Order::Broken;
+    }
+    let mut ids: Vec<usize> = found.iter().map(|marker| marker.id).collect();
+    if ids.iter().enumerate().all(|(i, id)| *id == i + 1) {
+        return Order::Same;
+    }
+    ids.sort_unstable();
+    if ids.iter().enumerate().all(|(i, id)| *id == i + 1) {
+        return Order::Moved;
+    }
+    let mut repaired = String::with_capacity(translated.len());
+    let mut last = 0;
+    for (i, found) in found.into_iter().enumerate() {
+        repaired.push_str(&translated[last..found.range.start]);
+        repaired.push_str(&marker(i + 1));
+        last = found.range.end;
+    }
+    repaired.push_str(&translated[last..]);
+    Order::Repaired(repaired)
+}
+
+/// The variables, references and literals that `pattern` uses, sorted
+pub fn of(pattern: &Pattern) -> Vec<String> {
+    let mut found = Vec::new();
+    pattern_placeables(pattern, &mut found);
+    found.sort();
+    found
+}
+
+fn pattern_placeables(pattern: &Pattern, out: &mut Vec<String>) {
+    for element in pattern.elements.iter() {
+        if let PatternElement::Placeable(expression) = element {
+            expression_placeables(expression, out);
+        }
+    }
+}
+
+fn expression_placeables(expression: &Expression, out: &mut Vec<String>) {
+    match expression {
+        Expression::InlineExpression(inline) => inline_placeables(inline, out),
+        Expression::SelectExpression { selector, variants } => {
+            inline_placeables(selector, out);
+            for variant in variants.iter() {
+                pattern_placeables(&variant.value, out);
+            }
+        }
+    }
+}
+
+fn inline_placeables(inline: &InlineExpression, out: &mut Vec<String>) {
+    let arguments_placeables = |arguments: &Option<CallArguments>, out: &mut Vec<String>| {
+        if let Some(arguments) = arguments {
+            for argument in arguments.positional.iter() {
+                inline_placeables(argument, out);
+            }
+            for argument in arguments.named.iter() {
+                inline_placeables(&argument.value, out);
+            }
+        }
+    };
+    let with_attribute =
+        |id: String, attribute: &Option<fluent_syntax::ast::Identifier>| match attribute {
+            Some(attribute) => format!("{}.{}", id, attribute.name),
+            None => id,
+        };
+    match inline {
+        InlineExpression::StringLiteral { value } => out.push(format!("\"{}\"", value)),
+        InlineExpression::NumberLiteral { value } => out.push((*value).to_owned()),
+        InlineExpression::VariableReference { id } => out.push(format!("${}", id.name)),
+        InlineExpression::MessageReference { id, attribute } => {
+            out.push(with_attribute(id.name.to_owned(), attribute))
+        }
+        InlineExpression::TermReference {
+            id,
+            attribute,
+            arguments,
+        } => {
+            out.push(with_attribute(format!("-{}", id.name), attribute));
+            arguments_placeables(arguments, out);
+        }
+        InlineExpression::FunctionReference { id, arguments } => {
+            out.push(format!("{}()", id.name));
+            arguments_placeables(arguments, out);
+        }
+        InlineExpression::Placeable { expression } => expression_placeables(expression, out),
+    }
+}
+
+/// Check the translation of `pattern`, with its placeables' markers, against
+/// the source once its placeables are put back, describing anything that
+/// went missing or turned up
+pub fn mismatches(pattern: &Pattern, translated: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    let expected = placeable_count(pattern);
+    let markers = markers(translated)
+        .into_iter()
+        .filter(|marker| marker.id >= 1 && marker.id <= expected)
+        .count();
+    if markers != expected {
+        problems.push(format!(
+            "expected {} placeable(s), found {}",
+            expected, markers
+        ));
+    }
+
+    let filled = super::writer::fill_placeables(pattern, translated);
+    let found = match filled_placeables(&filled) {
+        Some(found) => found,
+        None => {
+            problems.push(format!("`{}` isn't valid Fluent", filled));
+            return problems;
+        }
+    };
+
+    let mut counts: BTreeMap<String, isize> = BTreeMap::new();
+    for placeable in of(pattern).into_iter() {
+        *counts.entry(placeable).or_default() += 1;
+    }
+    for placeable in found.into_iter() {
+        *counts.entry(placeable).or_default() -= 1;
+    }
+    for (placeable, count) in counts.into_iter() {
+        if count > 0 {
+            problems.push(format!("`{}` is missing from `{}`", placeable, filled));
+        } else if count < 0 {
+            problems.push(format!("`{}` was added in `{}`", placeable, filled));
+        }
+    }
+    problems
+}
+
+/// Check a hand translation of `pattern` against it, describing the
+/// variables, references and literals that went missing or turned up. Each
+/// only has to be used once, since a translation may need a select where the
+/// source has none.
+pub fn differences(pattern: &Pattern, translated: &Pattern) -> Vec<String> {
+    let expected: BTreeSet<String> = of(pattern).into_iter().collect();
+    let found: BTreeSet<String> = of(translated).into_iter().collect();
+    let missing = expected
+        .difference(&found)
+        .map(|placeable| format!("`{}` is missing", placeable));
+    let added = found
+        .difference(&expected)
+        .map(|placeable| format!("`{}` was added", placeable));
+    missing.chain(added).collect()
+}
+
+/// The placeables of a translation once they're put back, if it's valid Fluent
+fn filled_placeables(filled: &str) -> Option<Vec<String>> {
+    let entry = format!(
+        "placeables = {}\n",
+        filled.lines().collect::<Vec<&str>>().join("\n    ")
+    );
+    match fluent_syntax::parser::parse(entry.as_str()) {
+        Ok(resource) => match resource.body.first() {
+            Some(ResourceEntry::Entry(Entry::Message(message))) => message.value.as_ref().map(of),
+            _ => None,
+        },
+        Err(_) => None,
+    }
+}
+
+/// Whether the translation of `pattern` is valid Fluent once its placeables
+/// are put back, so that writing it can't break the file
+pub fn valid(pattern: &Pattern, translated: &str) -> bool {
+    filled_placeables(&super::writer::fill_placeables(pattern, translated)).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value<'s>(resource: &'s fluent_syntax::ast::Resource<'s>) -> &'s Pattern<'s> {
+        match &resource.body[0] {
+            ResourceEntry::Entry(Entry::Message(message)) => message.value.as_ref().unwrap(),
+            _ => panic!("expected a message"),
+        }
+    }
+
+    #[test]
+    fn lists_placeables() {
+        let resource = fluent_syntax::parser::parse(
+            "hello = { $name } on { -brand(case: \"short\") } { NUMBER($count, style: \"percent\") } { 5 }\n",
+        )
+        .unwrap();
+        assert_eq!(
+            of(value(&resource)),
+            vec![
+                "\"percent\"",
+                "\"short\"",
+                "$count",
+                "$name",
+                "-brand",
+                "5",
+                "NUMBER()"
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_mismatches() {
+        let resource =
+            fluent_syntax::parser::parse("hello = Hello { $name }, welcome to { -brand }\n")
+                .unwrap();
+        let pattern = value(&resource);
+        let (name, brand) = (marker(1), marker(2));
+        assert!(mismatches(
+            pattern,
+            &format!("Bonjour {}, bienvenue sur {}", name, brand)
+        )
+        .is_empty());
+        assert!(mismatches(pattern, &format!("Sur {}, bienvenue {}", brand, name)).is_empty());
+        assert_eq!(
+            mismatches(pattern, &format!("Bonjour {}, bienvenue", name)),
+            vec![
+                "expected 2 placeable(s), found 1".to_owned(),
+                "`-brand` is missing from `Bonjour { $name }, bienvenue`".to_owned(),
+            ]
+        );
+        assert_eq!(
+            mismatches(pattern, &format!("Bonjour {}, {} {}", name, brand, brand)),
+            vec![
+                "expected 2 placeable(s), found 3".to_owned(),
+                "`-brand` was added in `Bonjour { $name }, { -brand } { -brand }`".to_owned(),
+            ]
+        );
+        assert_eq!(
+            mismatches(pattern, &format!("Bonjour {{ $nom }} {} {}", name, brand)).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn repairs_markers() {
+        let resource =
+            fluent_syntax::parser::parse("hello = Hello { $name }, welcome to { -brand }\n")
+                .unwrap();
+        let pattern = value(&resource);
+        let (name, brand) = (marker(1), marker(2));
+        assert_eq!(order(pattern, &format!("{} {}", name, brand)), Order::Same);
+        assert_eq!(order(pattern, &format!("{} {}", brand, name)), Order::Moved);
+        assert_eq!(
+            order(pattern, &format!("{} {}", name, name)),
+            Order::Repaired(format!("{} {}", name, brand))
+        );
+        assert_eq!(
+            order(pattern, &format!("{} <span id=\"ph9\"></span>!", name)),
+            Order::Repaired(format!("{} {}!", name, brand))
+        );
+        assert_eq!(order(pattern, &name), Order::Broken);
+        assert!(valid(pattern, &format!("{} {}", brand, name)));
+        assert!(!valid(pattern, &format!("{} {{ {}", brand, name)));
+    }
+
+    #[test]
+    fn finds_markers() {
+        let text = format!("a {} b <b>c</b> {}", marker(12), marker(3));
+        let found = markers(&text);
+        assert_eq!(found.iter().map(|m| m.id).collect::<Vec<_>>(), vec![12, 3]);
+        assert_eq!(&text[found[0].range.clone()], marker(12));
+        assert_eq!(
+            markers("<SPAN TRANSLATE='NO' ID='PH2'>x</SPAN> <span id=\"ph\">")
+                .into_iter()
+                .map(|m| (m.range, m.id))
+                .collect::<Vec<_>>(),
+            vec![(0..38, 2)]
+        );
+        assert_eq!(strip_markers(&text, "_"), "a _ b <b>c</b> _");
+    }
+
+    #[test]
+    fn finds_differences_in_hand_translations() {
+        let source =
+            fluent_syntax::parser::parse("photos = { $count } photos of { $name }\n").unwrap();
+        let plural = fluent_syntax::parser::parse(
+            "photos = { $count ->\n    [one] une photo de { $name }\n   *[other] { $count } photos de { $name }\n}\n",
+        )
+        .unwrap();
+        assert!(differences(value(&source), value(&plural)).is_empty());
+        let wrong =
+            fluent_syntax::parser::parse("photos = { $num } photos de { $name }\n").unwrap();
+        assert_eq!(
+            differences(value(&source), value(&wrong)),
+            vec![
+                "`$count` is missing".to_owned(),
+                "`$num` was added".to_owned()
+            ]
+        );
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The CLDR plural categories that each language uses for cardinal numbers,
+//! so that the variants of plural selects can be fitted to the locale
+
+/// Every CLDR plural category, in the order they're written in
+pub const CATEGORIES: &[&str] = &["zero", "one", "two", "few", "many", "other"];
+
+/// Languages by the categories they use, where a language that isn't listed
+/// isn't adjusted at all
+const LANGUAGES: &[(&[&str], &[&str])] = &[
+    (
+        &["other"],
+        &[
+            "bo", "dz", "id", "ig", "ii", "in", "ja", "jbo", "jv", "jw", "kde", "kea", "km", "ko",
+            "lkt", "lo", "ms", "my", "nqo", "osa", "sah", "ses", "sg", "su", "th", "to", "tpi",
+            "vi", "wo", "yo", "yue", "zh",
+        ],
+    ),
+    (
+        &["one", "other"],
+        &[
+            "af", "am", "as", "ast", "az", "bg", "bn", "ce", "da", "de", "el", "en", "eo", "et",
+            "eu", "fa", "fi", "fil", "fo", "fy", "gl", "gu", "ha", "hi", "hu", "hy", "ia", "is",
+            "ka", "kk", "kn", "ku", "ky", "lb", "mk", "ml", "mn", "mr", "nb", "ne", "nl", "nn",
+            "no", "or", "pa", "ps", "sd", "si", "so", "sq", "sv", "sw", "ta", "te", "tk", "tl",
+            "tr", "ug", "ur", "uz", "xh", "yi", "zu",
+        ],
+    ),
+    (&["one", "many", "other"], &["ca", "es", "fr", "it", "pt"]),
+    (&["zero", "one", "other"], &["ksh", "lag", "lv", "prg"]),
+    (&["one", "two", "other"], &["he", "iw", "iu", "se", "smn"]),
+    (
+        &["one", "few", "other"],
+        &["bs", "hr", "mo", "ro", "sh", "sr"],
+    ),
+    (&["one", "two", "few", "other"], &["dsb", "gd", "hsb", "sl"]),
+    (
+        &["one", "few", "many", "other"],
+        &["be", "cs", "lt", "pl", "ru", "sk", "uk"],
+    ),
+    (
+        &["one", "two", "few", "many", "other"],
+        &["br", "ga", "gv", "mt"],
+    ),
+    (
+        &["zero", "one", "two", "few", "many", "other"],
+        &["ar", "ars", "cy", "kw"],
+    ),
+];
+
+/// The plural categories of `locale`'s language, such as `one`, `few`,
+/// `many` and `other` for `ru`, if it's known
+pub fn categories(locale: &str) -> Option<&'static [&'static str]> {
+    let language = locale
+        .split(['-', '_'])
+        .next()
+        .unwrap_or(locale)
+        .to_ascii_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|(_, languages)| languages.contains(&language.as_str()))
+        .map(|(categories, _)| *categories)
+}
+
+/// A select expression's variant as it's written: its key, whether it's the
+/// default, and its text
+pub type Variant = (String, bool, String);
+
+/// Fit the variants of a plural select to `categories`, adding the ones it's
+/// missing with the text of its `other` (or default) variant and leaving out
+/// the ones the language doesn't use. Variants for exact numbers such as
+/// `[0]` are kept, and `other` becomes the default. Selects whose keys aren't
+/// all plural categories are left as they are.
+pub fn fit(variants: Vec<Variant>, categories: &[&str]) -> Vec<Variant> {
+    let is_number = |key: &str| key.parse::<f64>().is_ok();
+    let plural = variants
+        .iter()
+        .all(|(key, _, _)| is_number(key) || CATEGORIES.contains(&key.as_str()))
+        && variants
+            .iter()
+            .any(|(key, _, _)| key != "other" && CATEGORIES.contains(&key.as_str()));
+    if !plural {
+        return variants;
+    }
+    let seed = variants
+        .iter()
+        .find(|(key, _, _)| key == "other")
+        .or_else(|| variants.iter().find(|(_, default, _)| *default))
+        .map(|(_, _, text)| text.clone())
+        .unwrap_or_default();
+    let mut fitted: Vec<Variant> = variants
+        .iter()
+        .filter(|(key, _, _)| is_number(key))
+        .map(|(key, _, text)| (key.clone(), false, text.clone()))
+        .collect();
+    for category in categories.iter() {
+        let text = variants
+            .iter()
+            .find(|(key, _, _)| key == category)
+            .map(|(_, _, text)| text.clone())
+            .unwrap_or_else(|| seed.clone());
+        fitted.push(((*category).to_owned(), *category == "other", text));
+    }
+    fitted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(key: &str, default: bool, text: &str) -> Variant {
+        (key.to_owned(), default, text.to_owned())
+    }
+
+    #[test]
+    fn fits_plurals_to_locales() {
+        assert_eq!(categories("pt-BR"), Some(&["one", "many", "other"][..]));
+        assert_eq!(categories("ru"), Some(&["one", "few", "many", "other"][..]));
+        assert_eq!(categories("tlh"), None);
+
+        let english = vec![
+            variant("0", false, "no files"),
+            variant("one", false, "one file"),
+            variant("other", true, "files"),
+        ];
+        assert_eq!(
+            fit(english.clone(), categories("ru").unwrap()),
+            vec![
+                variant("0", false, "no files"),
+                variant("one", false, "one file"),
+                variant("few", false, "files"),
+                variant("many", false, "files"),
+                variant("other", true, "files"),
+            ]
+        );
+        assert_eq!(
+            fit(english, categories("ja").unwrap()),
+            vec![
+                variant("0", false, "no files"),
+                variant("other", true, "files")
+            ]
+        );
+        let gender = vec![
+            variant("male", false, "his"),
+            variant("other", true, "their"),
+        ];
+        assert_eq!(fit(gender.clone(), &["other"]), gender);
+    }
+}
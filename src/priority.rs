@@ -0,0 +1,91 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::catalog::Pending;
+
+/// Check whether a message id matches a pattern, where `*` matches any run of
+/// characters
+pub fn id_matches(pattern: &str, id: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let mut rest = match id.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts: Vec<&str> = parts.collect();
+    let (last, middle) = match parts.split_last() {
+        Some(split) => split,
+        // no `*` at all, so the pattern has to be the whole id
+        None => return rest.is_empty(),
+    };
+    for part in middle.iter() {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Move the high-priority messages to the front, keeping the order of the
+/// source within each group, and return how many of them there are. Messages
+/// are high-priority if their comment contains `marker` or their id matches
+/// one of `patterns`.
+pub fn prioritize<'ast>(
+    pending: Vec<(&'ast str, Pending)>,
+    source: &fluent_syntax::ast::Resource,
+    marker: &str,
+    patterns: &[String],
+) -> (Vec<(&'ast str, Pending)>, usize) {
+    let (high, low): (Vec<_>, Vec<_>) = pending.into_iter().partition(|(id, _)| {
+        patterns.iter().any(|pattern| id_matches(pattern, id))
+            || super::catalog::find_message(source, id)
+                .map(|message| super::catalog::has_marker(&message.comment, marker))
+                .unwrap_or(false)
+    });
+    let count = high.len();
+    (high.into_iter().chain(low).collect(), count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_patterns() {
+        assert!(id_matches("nav-*", "nav-home"));
+        assert!(id_matches("*-button", "save-button"));
+        assert!(id_matches("a*b*c", "a-b-c"));
+        assert!(id_matches("title", "title"));
+        assert!(!id_matches("title", "title-2"));
+        assert!(!id_matches("a*b*c", "a-c-b"));
+        assert!(!id_matches("ab*ba", "aba"));
+    }
+
+    #[test]
+    fn moves_high_priority_messages_first() {
+        let source =
+            fluent_syntax::parser::parse("a = A\n# tt-priority: high\nb = B\nnav-c = C\nd = D\n")
+                .unwrap();
+        let pending = ["a", "b", "nav-c", "d"]
+            .iter()
+            .map(|id| (*id, Pending::Empty))
+            .collect();
+        let (pending, high) =
+            prioritize(pending, &source, "tt-priority: high", &["nav-*".to_owned()]);
+        let ids: Vec<&str> = pending.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec!["b", "nav-c", "a", "d"]);
+        assert_eq!(high, 2);
+    }
+}
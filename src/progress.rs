@@ -0,0 +1,147 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// How progress is shown while translating
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    /// a progress bar for people
+    Bar,
+    /// newline-delimited JSON events on stderr for programs wrapping `tt`
+    Json,
+}
+
+impl Format {
+    pub fn from_arg(arg: &str) -> Format {
+        match arg {
+            "json" => Format::Json,
+            _ => Format::Bar,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+enum Event<'a> {
+    Started {
+        locale: &'a str,
+        total: usize,
+    },
+    MessageTranslated {
+        locale: &'a str,
+        id: &'a str,
+        /// the backend that translated the message, if any could
+        backend: Option<&'a str>,
+        done: usize,
+        total: usize,
+    },
+    LocaleComplete {
+        locale: &'a str,
+        translated: usize,
+        failed: usize,
+    },
+    Error {
+        locale: &'a str,
+        message: &'a str,
+    },
+}
+
+fn emit(event: &Event) {
+    eprintln!(
+        "{}",
+        serde_json::to_string(event).expect("can serialize progress events")
+    );
+}
+
+/// Report that translating into `locale` failed
+pub fn error(format: Format, locale: &str, message: &str) {
+    if format == Format::Json {
+        emit(&Event::Error { locale, message });
+    }
+}
+
+/// Progress through the messages of one locale
+pub struct Progress<'a> {
+    locale: &'a str,
+    bar: Option<indicatif::ProgressBar>,
+    done: usize,
+    total: usize,
+    /// the messages that have been counted, since messages sent ahead are
+    /// counted as soon as they're back
+    counted: HashSet<String>,
+}
+
+impl<'a> Progress<'a> {
+    pub fn start(format: Format, locale: &'a str, total: usize) -> Progress<'a> {
+        let bar = match format {
+            Format::Bar => {
+                let bar = indicatif::ProgressBar::new(total as u64);
+                bar.set_style(indicatif::ProgressStyle::default_bar().template(
+                    "{prefix} {spinner} [{elapsed_precise}] [{wide_bar}] {pos}/{len} ({eta})",
+                ));
+                bar.set_prefix(locale);
+                Some(bar)
+            }
+            Format::Json => {
+                emit(&Event::Started { locale, total });
+                None
+            }
+        };
+        Progress {
+            locale,
+            bar,
+            done: 0,
+            total,
+            counted: HashSet::new(),
+        }
+    }
+
+    /// Count the message `id` as done, unless it already was
+    pub fn message_translated(&mut self, id: &str, backend: Option<&str>) {
+        if !self.counted.insert(id.to_owned()) {
+            return;
+        }
+        self.done += 1;
+        match &self.bar {
+            Some(bar) => bar.inc(1),
+            None => emit(&Event::MessageTranslated {
+                locale: self.locale,
+                id,
+                backend,
+                done: self.done,
+                total: self.total,
+            }),
+        }
+    }
+
+    /// Stop drawing the bar, such as while asking questions
+    pub fn hide(&self) {
+        if let Some(bar) = &self.bar {
+            bar.set_draw_target(indicatif::ProgressDrawTarget::hidden());
+        }
+    }
+
+    pub fn finish(&self, translated: usize, failed: usize) {
+        match &self.bar {
+            Some(bar) => bar.finish(),
+            None => emit(&Event::LocaleComplete {
+                locale: self.locale,
+                translated,
+                failed,
+            }),
+        }
+    }
+}
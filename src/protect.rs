@@ -0,0 +1,215 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::config::ProtectConfig;
+use std::collections::HashSet;
+use std::path::Path;
+
+const OPEN: &str = "<span translate=\"no\">";
+const CLOSE: &str = "</span>";
+
+/// Keeps trademark symbols and legal strings intact through translation by
+/// wrapping them in `translate="no"` spans, then restoring the original text
+/// from the wrapped spans after translation.
+pub struct Protector {
+    /// sorted longest-first so that sentences win over the symbols they contain
+    spans: Vec<String>,
+    /// the spans that are only protected as whole words, like brand names
+    terms: HashSet<String>,
+}
+
+/// The do-not-translate terms listed in `path`, one per line, skipping blank
+/// lines and `#` comments. A missing file lists no terms.
+pub fn load_terms<P: AsRef<Path>>(path: P) -> std::io::Result<Vec<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_owned)
+            .collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn is_word(c: Option<char>) -> bool {
+    c.map(|c| c.is_alphanumeric() || c == '_').unwrap_or(false)
+}
+
+impl Protector {
+    pub fn new(config: &ProtectConfig) -> Protector {
+        let mut spans: Vec<String> = config
+            .symbols
+            .iter()
+            .chain(config.spans.iter())
+            .filter(|s| !s.is_empty())
+            .cloned()
+            .collect();
+        spans.sort();
+        spans.dedup();
+        spans.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        Protector {
+            spans,
+            terms: HashSet::new(),
+        }
+    }
+
+    /// Protect `terms` too, wherever they're a whole word
+    pub fn with_terms(mut self, terms: Vec<String>) -> Protector {
+        for term in terms.into_iter().filter(|t| !t.is_empty()) {
+            if !self.spans.contains(&term) {
+                self.spans.push(term.clone());
+                self.terms.insert(term);
+            }
+        }
+        self.spans.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        self
+    }
+
+    /// Everything that's protected, longest first
+    pub fn spans(&self) -> &[String] {
+        &self.spans
+    }
+
+    /// Wrap every protected span in `text`, returning the wrapped text and the
+    /// spans that were wrapped, in order
+    pub fn protect(&self, text: &str) -> (String, Vec<String>) {
+        let mut wrapped = String::with_capacity(text.len());
+        let mut found = Vec::new();
+        let mut rest = text;
+        let mut previous = None;
+        while let Some(c) = rest.chars().next() {
+            let fits = |span: &String| {
+                rest.starts_with(span.as_str())
+                    && (!self.terms.contains(span)
+                        || !(is_word(previous) || is_word(rest[span.len()..].chars().next())))
+            };
+            if let Some(span) = self.spans.iter().find(|s| fits(s)) {
+                wrapped.push_str(OPEN);
+                wrapped.push_str(span);
+                wrapped.push_str(CLOSE);
+                found.push(span.clone());
+                rest = &rest[span.len()..];
+                previous = span.chars().last();
+            } else {
+                wrapped.push(c);
+                rest = &rest[c.len_utf8()..];
+                previous = Some(c);
+            }
+        }
+        (wrapped, found)
+    }
+
+    /// Unwrap the protected spans in a translation, putting back the original
+    /// text of each span in case the translator touched it anyway
+    pub fn restore(&self, translated: &str, spans: &[String]) -> String {
+        let mut restored = String::with_capacity(translated.len());
+        let mut spans = spans.iter();
+        let mut rest = translated;
+        while let Some(start) = rest.find(OPEN) {
+            let inner_start = start + OPEN.len();
+            let inner_end = match rest[inner_start..].find(CLOSE) {
+                Some(end) => inner_start + end,
+                None => break,
+            };
+            restored.push_str(&rest[..start]);
+            match spans.next() {
+                Some(span) => restored.push_str(span),
+                None => restored.push_str(&rest[inner_start..inner_end]),
+            }
+            rest = &rest[inner_end + CLOSE.len()..];
+        }
+        restored.push_str(rest);
+        restored
+    }
+
+    /// List the protected spans that occur fewer times in `translated` than in
+    /// `source`
+    pub fn missing(&self, source: &str, translated: &str) -> Vec<&str> {
+        let mut missing = Vec::new();
+        for span in self.spans.iter() {
+            // count on text without longer spans so that a sentence containing
+            // a symbol doesn't get the symbol counted twice
+            let expected = count_outside_longer(&self.spans, source, span);
+            let actual = count_outside_longer(&self.spans, translated, span);
+            if actual < expected {
+                missing.push(span.as_str());
+            }
+        }
+        missing
+    }
+}
+
+fn count_outside_longer(spans: &[String], text: &str, span: &str) -> usize {
+    let mut text = text.to_owned();
+    for longer in spans
+        .iter()
+        .filter(|s| s.len() > span.len() && s.contains(span))
+    {
+        text = text.replace(longer.as_str(), "");
+    }
+    text.matches(span).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn protector() -> Protector {
+        Protector::new(&ProtectConfig {
+            spans: vec!["Acme™ is a trademark of Acme Corp.".to_owned()],
+            ..ProtectConfig::default()
+        })
+    }
+
+    #[test]
+    fn round_trips_protected_spans() {
+        let p = protector();
+        let (wrapped, spans) = p.protect("Try Acme™! Acme™ is a trademark of Acme Corp.");
+        assert_eq!(spans, vec!["™", "Acme™ is a trademark of Acme Corp."]);
+        assert_eq!(
+            wrapped,
+            "Try Acme<span translate=\"no\">™</span>! <span translate=\"no\">Acme™ is a trademark of Acme Corp.</span>"
+        );
+
+        let translated = "Essayez Acme<span translate=\"no\">(TM)</span> ! <span translate=\"no\">Acme™ est une marque</span>";
+        let restored = p.restore(translated, &spans);
+        assert_eq!(
+            restored,
+            "Essayez Acme™ ! Acme™ is a trademark of Acme Corp."
+        );
+        assert!(p
+            .missing("Try Acme™! Acme™ is a trademark of Acme Corp.", &restored)
+            .is_empty());
+    }
+
+    #[test]
+    fn protects_whole_terms() {
+        let p = Protector::new(&ProtectConfig::default())
+            .with_terms(vec!["Go".to_owned(), "Acme Cloud".to_owned()]);
+        let (wrapped, spans) = p.protect("Go to Acme Cloud™, Good");
+        assert_eq!(spans, vec!["Go", "Acme Cloud", "™"]);
+        assert_eq!(
+            wrapped,
+            "<span translate=\"no\">Go</span> to <span translate=\"no\">Acme Cloud</span><span translate=\"no\">™</span>, Good"
+        );
+    }
+
+    #[test]
+    fn reports_lost_spans() {
+        let p = protector();
+        assert_eq!(p.missing("Acme® and Acme™", "Acme et Acme™"), vec!["®"]);
+    }
+}
@@ -0,0 +1,157 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::error::Error;
+
+use super::translate::{GlossaryConfig, LRLanguage, MimeType};
+
+/// The pseudo-locale that the backend translates into
+pub const LOCALE: &str = "en-XA";
+
+/// How much longer pseudo-translations are than their source, since most
+/// languages take more room than English
+const EXPANSION: f64 = 0.3;
+
+/// Words that pad out pseudo-translations, so that whatever is cut off by a
+/// layout that's too small is easy to see
+const PADDING: &[&str] = &[
+    "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+];
+
+/// The accented version of an ASCII letter
+fn accent(c: char) -> char {
+    const PLAIN: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    const ACCENTED: &str = "åƀçðéƒĝĥîĵķļɱñöþǫŕšţûṽŵẋýžÅƁÇÐÉƑĜĤÎĴĶĻṀÑÖÞǪŔŠŢÛṼŴẊÝŽ";
+    match PLAIN.find(c) {
+        Some(i) if c.is_ascii() => ACCENTED.chars().nth(i).unwrap_or(c),
+        _ => c,
+    }
+}
+
+/// Pseudo-translate `text`: accent its letters, pad it out and put it in
+/// brackets, leaving its placeables' markers, tags and entities as they are
+pub fn pseudo_translate(text: &str) -> String {
+    let mut translated = String::with_capacity(text.len() * 2);
+    let mut letters = 0;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let untouched = match c {
+            '<' => rest.find('>').map(|end| end + 1),
+            '&' => rest
+                .find(';')
+                .filter(|end| {
+                    rest[1..*end]
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '#')
+                })
+                .map(|end| end + 1),
+            _ => None,
+        };
+        match untouched {
+            Some(len) => {
+                translated.push_str(&rest[..len]);
+                rest = &rest[len..];
+            }
+            None => {
+                if c.is_alphabetic() {
+                    letters += 1;
+                }
+                translated.push(accent(c));
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    let wanted = (letters as f64 * EXPANSION).ceil() as usize;
+    let mut padding: Vec<&str> = Vec::new();
+    let mut padded = 0;
+    for word in PADDING.iter().cycle() {
+        if padded >= wanted {
+            break;
+        }
+        padding.push(word);
+        padded += word.len();
+    }
+    if padding.is_empty() {
+        format!("[{}]", translated)
+    } else {
+        format!("[{} {}]", translated, padding.join(" "))
+    }
+}
+
+/// Pseudo-localizes messages without any network access, so that layouts can
+/// be tested against longer text, and strings that aren't in the catalog stand
+/// out as the ones without accents
+pub struct PseudoTranslator<'a> {
+    source_language: String,
+    language: &'a str,
+}
+
+impl<'a> PseudoTranslator<'a> {
+    pub fn new(source_language: &str, language: &'a str) -> PseudoTranslator<'a> {
+        PseudoTranslator {
+            source_language: source_language.to_owned(),
+            language,
+        }
+    }
+}
+
+impl<'a> super::backend::Backend for PseudoTranslator<'a> {
+    fn name(&self) -> &'static str {
+        "pseudo"
+    }
+
+    fn available_languages(&self) -> Result<Vec<LRLanguage>, Box<dyn Error>> {
+        Ok(vec![LRLanguage {
+            language_code: LOCALE.to_owned(),
+            display_name: "Pseudo-accented English".to_owned(),
+            support_source: false,
+            support_target: true,
+        }])
+    }
+
+    fn get_lang_name(&self) -> Result<String, Box<dyn Error>> {
+        // it's pseudo-translated along with everything else
+        Ok("English".to_owned())
+    }
+
+    fn translate<'c>(
+        &self,
+        phrase: &str,
+        _mime_type: MimeType,
+        _glossary: &Option<GlossaryConfig<'c>>,
+    ) -> Result<String, Box<dyn Error>> {
+        // don't translate into the source language, just copy it over
+        if self.language == self.source_language {
+            return Ok(phrase.to_owned());
+        }
+        Ok(pseudo_translate(phrase).replace("\n", "\n    "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pseudo_translates() {
+        assert_eq!(pseudo_translate("Hello"), "[Ĥéļļö one]");
+        assert_eq!(
+            pseudo_translate("Hi <span translate=\"no\" id=\"ph1\"></span>, see <a href=\"x\">this</a> &amp; more"),
+            "[Ĥî <span translate=\"no\" id=\"ph1\"></span>, šéé <a href=\"x\">ţĥîš</a> &amp; ɱöŕé one two]"
+        );
+        assert_eq!(pseudo_translate("___"), "[___]");
+        assert_eq!(pseudo_translate("a & b;"), "[å & ƀ; one]");
+    }
+}
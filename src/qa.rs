@@ -0,0 +1,199 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fluent_syntax::ast::{Entry, Message, Pattern, Resource, ResourceEntry};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct QaConfig {
+    /// the ids of messages that can be translated as they are in the source,
+    /// such as brand names, where `*` matches any characters
+    pub allow_identical: Vec<String>,
+}
+
+/// The value and attributes of `message`, as they're written
+fn message_text(message: &Message) -> String {
+    let mut text: Vec<u8> = Vec::new();
+    if let Some(value) = &message.value {
+        super::writer::write_pattern(&mut text, value).expect("can write to a Vec");
+    }
+    super::writer::write_attributes(&mut text, &message.attributes).expect("can write to a Vec");
+    String::from_utf8(text).expect("valid utf-8")
+}
+
+fn messages<'r, 's>(resource: &'r Resource<'s>) -> impl Iterator<Item = &'r Message<'s>> {
+    resource.body.iter().filter_map(|entry| match entry {
+        ResourceEntry::Entry(Entry::Message(message)) => Some(message),
+        _ => None,
+    })
+}
+
+/// Whether `written` still has a placeable's marker in it that wasn't
+/// replaced by a placeable, and that isn't in its `source`
+pub fn leftover_marker(source: &str, written: &str) -> bool {
+    !super::placeables::markers(written).is_empty() && super::placeables::markers(source).is_empty()
+}
+
+/// The translation of `pattern` with its placeables put back, if it still has
+/// placeable's marker left in it
+pub fn leftover_in_translation(pattern: &Pattern, translated: &str) -> Option<String> {
+    let mut source: Vec<u8> = Vec::new();
+    super::writer::write_pattern(&mut source, pattern).expect("can write to a Vec");
+    let filled = super::writer::fill_placeables(pattern, translated);
+    if leftover_marker(&String::from_utf8_lossy(&source), &filled) {
+        Some(filled)
+    } else {
+        None
+    }
+}
+
+/// The ids of the messages in `target` that have a placeable's marker left in
+/// them
+pub fn leftover_markers<'s>(source: &Resource<'s>, target: &Resource) -> Vec<&'s str> {
+    messages(source)
+        .filter(
+            |message| match super::catalog::find_message(target, message.id.name) {
+                Some(translation) => {
+                    leftover_marker(&message_text(message), &message_text(translation))
+                }
+                None => false,
+            },
+        )
+        .map(|message| message.id.name)
+        .collect()
+}
+
+/// Whether `text` has nothing in it to translate, such as a number or nothing
+/// but placeables
+fn language_neutral(text: &str) -> bool {
+    !super::placeables::strip_markers(text, "")
+        .chars()
+        .any(char::is_alphabetic)
+}
+
+impl QaConfig {
+    fn allowed(&self, id: &str) -> bool {
+        self.allow_identical
+            .iter()
+            .any(|pattern| super::priority::id_matches(pattern, id))
+    }
+
+    /// Whether the translation of the message `id` came back the same as its
+    /// source, with its placeables as markers, which usually means that the
+    /// backend didn't translate it
+    pub fn untranslated(&self, id: &str, source: &str, translated: &str) -> bool {
+        source == translated && !language_neutral(source) && !self.allowed(id)
+    }
+
+    /// The ids of the messages in `target` that are the same as they are in
+    /// `source`, leaving out hand-translated ones and those that aren't to be
+    /// translated
+    pub fn untranslated_messages<'s>(
+        &self,
+        source: &Resource<'s>,
+        target: &Resource,
+        markers: &super::config::Markers,
+    ) -> Vec<&'s str> {
+        let markers_text = |message: &Message| -> String {
+            message
+                .value
+                .iter()
+                .chain(message.attributes.iter().map(|a| &a.value))
+                .map(super::catalog::source_text)
+                .collect()
+        };
+        messages(source)
+            .filter(
+                |message| match super::catalog::find_message(target, message.id.name) {
+                    Some(translation) => {
+                        !super::catalog::has_marker(&translation.comment, &markers.hand_translated)
+                            && !super::catalog::has_marker(&message.comment, &markers.no_translate)
+                            && message_text(message) == message_text(translation)
+                            && !language_neutral(&markers_text(message))
+                            && !self.allowed(message.id.name)
+                    }
+                    None => false,
+                },
+            )
+            .map(|message| message.id.name)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_untranslated_messages() {
+        let config = QaConfig {
+            allow_identical: vec!["brand-*".to_owned()],
+        };
+        let name = super::super::placeables::marker(1);
+        let hello = format!("Hello {}", name);
+        assert!(config.untranslated("hello", &hello, &hello));
+        assert!(!config.untranslated("hello", &hello, &format!("Bonjour {}", name)));
+        let count = format!("{} / 100", name);
+        assert!(!config.untranslated("count", &count, &count));
+        assert!(!config.untranslated("brand-name", "Firefox", "Firefox"));
+
+        let source = fluent_syntax::parser::parse(
+            "hello = Hello\nbye = Goodbye\n    .title = Bye\nbrand-name = Firefox\nmissing = Missing\nname = { $name }\n# tt-no-translate\nversion = Version 2\n",
+        )
+        .unwrap();
+        let target = fluent_syntax::parser::parse(
+            "# tt-hand-translated\nhello = Hello\nbye = Goodbye\n    .title = Bye\nbrand-name = Firefox\nname = { $name }\n# tt-no-translate\nversion = Version 2\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.untranslated_messages(
+                &source,
+                &target,
+                &super::super::config::Markers::default()
+            ),
+            vec!["bye"]
+        );
+    }
+
+    #[test]
+    fn finds_leftover_markers() {
+        let marker = super::super::placeables::marker(2);
+        assert!(leftover_marker(
+            "Hello { $name }",
+            &format!("Bonjour {}", marker)
+        ));
+        assert!(!leftover_marker(
+            &format!("Call it {}", marker),
+            &format!("Appelle-le {}", marker)
+        ));
+        assert!(!leftover_marker("Call it ___", "Appelle-le ___"));
+
+        let source = fluent_syntax::parser::parse(
+            "hello = Hello { $name }
+bye = Goodbye
+    .title = Bye
+",
+        )
+        .unwrap();
+        let target = fluent_syntax::parser::parse(
+            "hello = Bonjour { $name }
+bye = Au revoir
+    .title = <span translate=\"no\" id=\"ph1\"></span> Salut
+",
+        )
+        .unwrap();
+        assert_eq!(leftover_markers(&source, &target), vec!["bye"]);
+    }
+}
@@ -0,0 +1,316 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use super::backend::Failure;
+
+/// A message that one or more backends failed to translate
+pub struct MessageFailure {
+    pub id: String,
+    pub attempts: Vec<Failure>,
+    /// the backend that eventually translated the message, if any did
+    pub translated_by: Option<&'static str>,
+}
+
+/// How long a message took to translate
+pub struct MessageTiming {
+    pub id: String,
+    pub elapsed: Duration,
+    /// the size of the text sent to the backends, in bytes
+    pub payload_size: usize,
+    /// how many backends failed before one translated the message
+    pub retries: usize,
+}
+
+/// A problem found in a translation, which is still written out
+pub struct Issue {
+    pub id: String,
+    pub kind: IssueKind,
+    pub detail: String,
+}
+
+/// A `post-locale` hook that was run on the locale's file
+pub struct HookRun {
+    pub command: String,
+    /// how the hook failed, if it did
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum IssueKind {
+    /// the translation has more or fewer placeables than the source
+    PlaceableMismatch,
+    /// a protected span is missing from the translation
+    LostSpan,
+    /// an entity or escape in the source changed in the translation
+    EntityChange,
+    /// the translation's HTML tags are unbalanced or differ from the source's
+    MarkupChange,
+    /// the translation is the same as the source
+    Untranslated,
+    /// a placeable's marker was left in the translation after putting its
+    /// placeables back, so it wasn't written
+    LeftoverMarker,
+    /// the translation isn't valid Fluent after putting its placeables back,
+    /// so it wasn't written
+    InvalidFluent,
+    /// the translation is longer than its `tt-max-length`
+    TooLong,
+    /// the translation is more times the length of its source than the
+    /// locale's `max-expansion`
+    Expanded,
+    /// hunspell doesn't know a word of the translation
+    Misspelling,
+    /// a glossary term in the source wasn't translated as the glossary says
+    GlossaryViolation,
+}
+
+impl IssueKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IssueKind::PlaceableMismatch => "placeable-mismatch",
+            IssueKind::LostSpan => "lost-span",
+            IssueKind::EntityChange => "entity-change",
+            IssueKind::MarkupChange => "markup-change",
+            IssueKind::Untranslated => "untranslated",
+            IssueKind::LeftoverMarker => "leftover-marker",
+            IssueKind::InvalidFluent => "invalid-fluent",
+            IssueKind::TooLong => "too-long",
+            IssueKind::Expanded => "expanded",
+            IssueKind::Misspelling => "misspelling",
+            IssueKind::GlossaryViolation => "glossary-violation",
+        }
+    }
+}
+
+/// How many of the slowest messages are listed in the report
+const SLOWEST_MESSAGES: usize = 5;
+
+/// A summary of a translation run, printed once it's done
+pub struct RunReport {
+    pub locale: String,
+    /// how many messages each backend translated
+    pub translated: BTreeMap<&'static str, usize>,
+    pub failures: Vec<MessageFailure>,
+    pub timings: Vec<MessageTiming>,
+    /// messages that were left untranslated because the budget ran out
+    pub skipped: Vec<String>,
+    pub issues: Vec<Issue>,
+    /// hand translations of an older version of the source, which are left
+    /// as they are
+    pub stale_hand_translated: Vec<String>,
+    pub hooks: Vec<HookRun>,
+    /// how long each translation is compared to its source
+    pub expansions: Vec<super::length::Expansion>,
+    /// the version of the source that each message was translated from
+    pub sources: BTreeMap<String, super::provenance::SourceVersion>,
+    /// how many of the translated messages came from the translation memory
+    pub remembered: usize,
+    /// how many of those were close matches rather than the same text
+    pub fuzzy: usize,
+    /// how many of the translated messages were the same as one translated
+    /// earlier in the run, so weren't sent again
+    pub repeated: usize,
+}
+
+impl RunReport {
+    pub fn new(locale: &str) -> RunReport {
+        RunReport {
+            locale: locale.to_owned(),
+            translated: BTreeMap::new(),
+            failures: Vec::new(),
+            timings: Vec::new(),
+            skipped: Vec::new(),
+            issues: Vec::new(),
+            stale_hand_translated: Vec::new(),
+            hooks: Vec::new(),
+            expansions: Vec::new(),
+            sources: BTreeMap::new(),
+            remembered: 0,
+            fuzzy: 0,
+            repeated: 0,
+        }
+    }
+
+    /// Whether any of the hooks failed
+    pub fn hooks_failed(&self) -> bool {
+        self.hooks.iter().any(|hook| hook.error.is_some())
+    }
+
+    pub fn record_translation(&mut self, backend: &'static str) {
+        *self.translated.entry(backend).or_insert(0) += 1;
+    }
+
+    pub fn record_failures(
+        &mut self,
+        id: &str,
+        attempts: Vec<Failure>,
+        translated_by: Option<&'static str>,
+    ) {
+        self.failures.push(MessageFailure {
+            id: id.to_owned(),
+            attempts,
+            translated_by,
+        });
+    }
+
+    pub fn record_issue(&mut self, id: &str, kind: IssueKind, detail: String) {
+        self.issues.push(Issue {
+            id: id.to_owned(),
+            kind,
+            detail,
+        });
+    }
+
+    /// Record how long a message took, logging the details if it took longer
+    /// than `deadline`
+    pub fn record_timing(&mut self, timing: MessageTiming, deadline: Duration) {
+        if timing.elapsed > deadline {
+            log::warn!(
+                "{}: `{}` took {:.1}s to translate ({} bytes, {} retries)",
+                self.locale,
+                timing.id,
+                timing.elapsed.as_secs_f64(),
+                timing.payload_size,
+                timing.retries
+            );
+        }
+        self.timings.push(timing);
+    }
+
+    /// How many messages were translated, by any backend
+    pub fn translated_count(&self) -> usize {
+        self.translated.values().sum()
+    }
+
+    /// How many messages no backend could translate
+    pub fn failed_count(&self) -> usize {
+        self.failures
+            .iter()
+            .filter(|f| f.translated_by.is_none())
+            .count()
+    }
+
+    pub fn print(&self) {
+        let translated: Vec<String> = self
+            .translated
+            .iter()
+            .map(|(backend, count)| format!("{} with {}", count, backend))
+            .collect();
+        log::info!(
+            "{}: translated {} message(s) ({}), {} failed",
+            self.locale,
+            self.translated_count(),
+            if translated.is_empty() {
+                "none".to_owned()
+            } else {
+                translated.join(", ")
+            },
+            self.failed_count()
+        );
+        if self.repeated > 0 {
+            log::info!(
+                "{}: {} message(s) repeated texts that were already translated, so weren't sent again",
+                self.locale,
+                self.repeated
+            );
+        }
+        if self.remembered > 0 {
+            log::info!(
+                "{}: {} of them came from the translation memory",
+                self.locale,
+                self.remembered
+            );
+        }
+        if self.fuzzy > 0 {
+            log::info!(
+                "{}: reused the translations of close matches for {} message(s), which are marked as needing review",
+                self.locale,
+                self.fuzzy
+            );
+        }
+
+        for failure in self.failures.iter() {
+            let attempts: Vec<String> = failure
+                .attempts
+                .iter()
+                .map(|a| format!("{}: {}", a.backend, a.error))
+                .collect();
+            match failure.translated_by {
+                Some(backend) => log::warn!(
+                    "{}: fell back to {} for `{}` ({})",
+                    self.locale,
+                    backend,
+                    failure.id,
+                    attempts.join("; ")
+                ),
+                None => log::warn!(
+                    "{}: failed to translate `{}` ({})",
+                    self.locale,
+                    failure.id,
+                    attempts.join("; ")
+                ),
+            }
+        }
+
+        if !self.skipped.is_empty() {
+            log::warn!(
+                "{}: skipped {} message(s) over the budget: {}",
+                self.locale,
+                self.skipped.len(),
+                self.skipped.join(", ")
+            );
+        }
+
+        let too_long: Vec<&str> = self
+            .issues
+            .iter()
+            .filter(|issue| issue.kind == IssueKind::TooLong)
+            .map(|issue| issue.id.as_str())
+            .collect();
+        if !too_long.is_empty() {
+            log::warn!(
+                "{}: {} message(s) are over their length limit: {}",
+                self.locale,
+                too_long.len(),
+                too_long.join(", ")
+            );
+        }
+
+        if !self.expansions.is_empty() {
+            log::info!(
+                "{}: translations are {:.2}x the length of the source",
+                self.locale,
+                super::length::overall_ratio(&self.expansions)
+            );
+        }
+
+        let mut slowest: Vec<&MessageTiming> = self.timings.iter().collect();
+        slowest.sort_by_key(|timing| std::cmp::Reverse(timing.elapsed));
+        if !slowest.is_empty() {
+            log::info!("{}: slowest messages:", self.locale);
+        }
+        for timing in slowest.into_iter().take(SLOWEST_MESSAGES) {
+            log::info!(
+                "    `{}`: {:.1}s ({} bytes, {} retries)",
+                timing.id,
+                timing.elapsed.as_secs_f64(),
+                timing.payload_size,
+                timing.retries
+            );
+        }
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RetryConfig {
+    /// how many times a request is sent before giving up on it
+    pub max_attempts: usize,
+    /// how long to wait before sending a request again the first time, which
+    /// doubles with each attempt
+    pub initial_delay_ms: u64,
+    /// the longest to wait between attempts
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> RetryConfig {
+        RetryConfig {
+            max_attempts: 4,
+            initial_delay_ms: 500,
+            max_delay_ms: 30_000,
+        }
+    }
+}
+
+/// How a run's backends send requests again when they fail, from the
+/// `[retry]` config of the project being translated
+#[derive(Clone, Debug, PartialEq)]
+pub struct Retry {
+    max_attempts: usize,
+    initial: Duration,
+    max: Duration,
+    /// paces every attempt, retries included
+    throttle: super::throttle::Throttle,
+}
+
+impl Default for Retry {
+    fn default() -> Retry {
+        Retry::new(
+            &RetryConfig::default(),
+            super::throttle::Throttle::default(),
+        )
+    }
+}
+
+/// How many responses have said that requests were sent too quickly, for
+/// `--concurrency auto` to send fewer at once when it goes up
+static THROTTLED: AtomicUsize = AtomicUsize::new(0);
+
+/// How many rate limited responses there have been so far
+pub fn throttled() -> usize {
+    THROTTLED.load(Ordering::Relaxed)
+}
+
+/// Count `response` if it was rate limited
+fn count_throttled<T>(
+    response: Result<(StatusCode, T), Box<dyn Error>>,
+) -> Result<(StatusCode, T), Box<dyn Error>> {
+    if let Ok((StatusCode::TOO_MANY_REQUESTS, _)) = response {
+        THROTTLED.fetch_add(1, Ordering::Relaxed);
+    }
+    response
+}
+
+/// Whether a response with `status` might succeed if it's sent again, such as
+/// when it was rate limited or the server had a problem
+fn retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Whether `error` is a network problem that might go away, rather than a
+/// request that can never be sent
+fn transient(error: &(dyn Error + 'static)) -> bool {
+    match error.downcast_ref::<reqwest::Error>() {
+        Some(e) => e.is_timeout() || !(e.is_builder() || e.is_redirect() || e.is_status()),
+        None => false,
+    }
+}
+
+/// Tell timeouts apart from other failures, since they usually mean that the
+/// timeouts are too short for the backend rather than that it's down
+fn timed_out(backend: &str, error: Box<dyn Error>) -> Box<dyn Error> {
+    match error.downcast_ref::<reqwest::Error>() {
+        Some(e) if e.is_timeout() => {
+            log::error!("{} request timed out: {}", backend, e);
+            Box::new(super::errors::Errors::Timeout)
+        }
+        _ => error,
+    }
+}
+
+/// How long to wait after the `attempt`th failed attempt, from 1: doubling
+/// each time up to `max`, and scaled by `jitter` from 0 to 1 so that clients
+/// that failed together don't all retry together
+fn delay(attempt: usize, initial: Duration, max: Duration, jitter: f64) -> Duration {
+    let exponential = initial
+        .checked_mul(1 << (attempt - 1).min(16))
+        .unwrap_or(max)
+        .min(max);
+    // wait at least half, so a retry is never immediate
+    exponential.mul_f64(0.5 + jitter.clamp(0.0, 1.0) / 2.0)
+}
+
+/// A number from 0 to 1 that's different each time, which is random enough
+/// to spread out retries
+fn jitter() -> f64 {
+    use std::hash::{BuildHasher, Hasher};
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|now| now.as_nanos())
+            .unwrap_or_default(),
+    );
+    (hasher.finish() % 1_000_000) as f64 / 1_000_000.0
+}
+
+impl Retry {
+    pub fn new(config: &RetryConfig, throttle: super::throttle::Throttle) -> Retry {
+        Retry {
+            // a request is always sent at least once
+            max_attempts: config.max_attempts.max(1),
+            initial: Duration::from_millis(config.initial_delay_ms),
+            max: Duration::from_millis(config.max_delay_ms),
+            throttle,
+        }
+    }
+
+    /// Send `request` like `dump::send`, sending it again after a while when
+    /// it's rate limited, the server fails or the network does, up to the
+    /// configured number of attempts. The last response or error is returned
+    /// if none of them succeed, with timeouts as `Errors::Timeout`.
+    pub fn send(
+        &self,
+        client: &Client,
+        backend: &str,
+        request: RequestBuilder,
+    ) -> Result<(StatusCode, String), Box<dyn Error>> {
+        let mut attempt = 1;
+        loop {
+            // requests with streamed bodies can't be sent again
+            let retry = match request.try_clone() {
+                Some(retry) if attempt < self.max_attempts => retry,
+                _ => {
+                    self.throttle.wait();
+                    return count_throttled(super::dump::send(client, backend, request))
+                        .map_err(|e| timed_out(backend, e));
+                }
+            };
+            self.throttle.wait();
+            let problem = match count_throttled(super::dump::send(client, backend, retry)) {
+                Ok((status, _)) if retryable(status) => format!("status {}", status),
+                Ok(response) => return Ok(response),
+                Err(e) if transient(e.as_ref()) => e.to_string(),
+                Err(e) => return Err(e),
+            };
+            let wait = delay(attempt, self.initial, self.max, jitter());
+            log::warn!(
+                "{} request failed with {}, trying again in {:.1}s ({}/{})",
+                backend,
+                problem,
+                wait.as_secs_f64(),
+                attempt,
+                self.max_attempts
+            );
+            std::thread::sleep(wait);
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_off() {
+        let initial = Duration::from_millis(500);
+        let max = Duration::from_secs(5);
+        assert_eq!(delay(1, initial, max, 1.0), Duration::from_millis(500));
+        assert_eq!(delay(1, initial, max, 0.0), Duration::from_millis(250));
+        assert_eq!(delay(3, initial, max, 1.0), Duration::from_secs(2));
+        assert_eq!(delay(5, initial, max, 1.0), max);
+        assert_eq!(delay(100, initial, max, 1.0), max);
+
+        assert!(retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(retryable(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!retryable(StatusCode::BAD_REQUEST));
+        assert!(!retryable(StatusCode::OK));
+        let j = jitter();
+        assert!((0.0..1.0).contains(&j));
+    }
+
+    #[test]
+    fn always_sends_once() {
+        let retry = Retry::new(
+            &RetryConfig {
+                max_attempts: 0,
+                initial_delay_ms: 100,
+                max_delay_ms: 1000,
+            },
+            super::super::throttle::Throttle::default(),
+        );
+        assert_eq!(retry.max_attempts, 1);
+        assert_eq!(retry.initial, Duration::from_millis(100));
+        assert_eq!(Retry::default().max_attempts, 4);
+    }
+}
@@ -0,0 +1,429 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::prelude::*;
+use std::path::Path;
+
+use super::compare::csv_field;
+
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ReviewConfig {
+    /// the file that the review queue is kept in
+    pub file: String,
+    /// mark the queued translations as needing review in the locale's file too
+    pub mark: bool,
+}
+
+impl Default for ReviewConfig {
+    fn default() -> ReviewConfig {
+        ReviewConfig {
+            file: "tt-review.json".to_owned(),
+            mark: true,
+        }
+    }
+}
+
+/// Why a translation needs a person to look at it
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Reason {
+    /// no backend could translate it, so the source was written instead
+    Failed,
+    /// it was translated by a fallback backend, or has placeables, protected
+    /// spans or entities that don't match the source
+    LowConfidence,
+    /// the source message is marked for review
+    Flagged,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum State {
+    Pending,
+    Accepted,
+    /// replaced with the reviewer's translation
+    Edited,
+    /// removed from the locale, so that it's translated again
+    Rejected,
+}
+
+impl Reason {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Reason::Failed => "failed",
+            Reason::LowConfidence => "low-confidence",
+            Reason::Flagged => "flagged",
+        }
+    }
+}
+
+impl State {
+    pub fn name(&self) -> &'static str {
+        match self {
+            State::Pending => "pending",
+            State::Accepted => "accepted",
+            State::Edited => "edited",
+            State::Rejected => "rejected",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<State> {
+        [
+            State::Pending,
+            State::Accepted,
+            State::Edited,
+            State::Rejected,
+        ]
+        .iter()
+        .copied()
+        .find(|state| state.name() == name)
+    }
+}
+
+/// A translation waiting for, or given, a review
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct Item {
+    pub locale: String,
+    pub id: String,
+    pub reason: Reason,
+    /// the message's value in the source and in the locale, as Fluent
+    pub source: String,
+    pub translation: String,
+    pub state: State,
+    /// the reviewer's translation, once edited
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edit: Option<String>,
+}
+
+/// The translations to review, kept between runs so that a review can be
+/// stopped and picked up again later
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Queue {
+    version: u64,
+    pub items: Vec<Item>,
+}
+
+impl Default for Queue {
+    fn default() -> Queue {
+        Queue {
+            version: super::migrate::REVIEW.version(),
+            items: Vec::new(),
+        }
+    }
+}
+
+/// The columns of an exported queue
+const CSV_HEADER: [&str; 7] = [
+    "locale",
+    "id",
+    "reason",
+    "state",
+    "source",
+    "translation",
+    "edit",
+];
+
+impl Queue {
+    /// Load the queue, treating a missing file as empty and upgrading one
+    /// from an older version of `tt`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Queue, Box<dyn Error>> {
+        let contents = super::catalog::read_or_empty(path.as_ref())?;
+        if contents.trim().is_empty() {
+            return Ok(Queue::default());
+        }
+        let invalid =
+            |e: String| format!("invalid review queue {}: {}", path.as_ref().display(), e);
+        let mut document: serde_json::Value =
+            serde_json::from_str(&contents).map_err(|e| invalid(e.to_string()))?;
+        super::migrate::REVIEW
+            .migrate(&mut document)
+            .map_err(invalid)?;
+        serde_json::from_value(document).map_err(|e| Box::from(invalid(e.to_string())))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let mut contents = serde_json::to_string_pretty(self)?;
+        contents.push('\n');
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Queue a translation for review. A new translation of a message that's
+    /// already queued goes back to pending, while the same translation keeps
+    /// its review.
+    pub fn add(&mut self, item: Item) {
+        match self
+            .items
+            .iter_mut()
+            .find(|i| i.locale == item.locale && i.id == item.id)
+        {
+            Some(existing)
+                if existing.source == item.source && existing.translation == item.translation => {}
+            Some(existing) => *existing = item,
+            None => self.items.push(item),
+        }
+    }
+
+    /// The positions of the pending items in any of `locales`, or in every
+    /// locale if none are given
+    pub fn pending(&self, locales: &[String]) -> Vec<usize> {
+        self.items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| {
+                item.state == State::Pending
+                    && (locales.is_empty() || locales.contains(&item.locale))
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    pub fn write_csv<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        writeln!(out, "{}", CSV_HEADER.join(","))?;
+        for item in self.items.iter() {
+            let fields = [
+                item.locale.as_str(),
+                item.id.as_str(),
+                item.reason.name(),
+                item.state.name(),
+                item.source.as_str(),
+                item.translation.as_str(),
+                item.edit.as_deref().unwrap_or(""),
+            ];
+            let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+            writeln!(out, "{}", fields.join(","))?;
+        }
+        Ok(())
+    }
+
+    /// Take the states and edits from a CSV written by `write_csv`, returning
+    /// the positions of the items whose review changed. Rows for messages
+    /// that aren't queued are ignored.
+    pub fn read_csv(&mut self, text: &str) -> Result<Vec<usize>, Box<dyn Error>> {
+        let mut rows = parse_csv(text)?.into_iter();
+        let header = rows.next().unwrap_or_default();
+        let column = |name: &str| {
+            header
+                .iter()
+                .position(|h| h == name)
+                .ok_or_else(|| format!("the review CSV has no `{}` column", name))
+        };
+        let (locale, id, state, edit) = (
+            column("locale")?,
+            column("id")?,
+            column("state")?,
+            column("edit")?,
+        );
+
+        let mut changed = Vec::new();
+        for (line, row) in rows.enumerate() {
+            let field = |i: usize| row.get(i).map(String::as_str).unwrap_or("");
+            let new_state = State::from_name(field(state)).ok_or_else(|| {
+                format!(
+                    "row {} of the review CSV has an unknown state `{}`",
+                    line + 2,
+                    field(state)
+                )
+            })?;
+            let new_edit = Some(field(edit).to_owned()).filter(|e| !e.is_empty());
+            if new_state == State::Edited && new_edit.is_none() {
+                return Err(Box::from(format!(
+                    "row {} of the review CSV is edited, but has no edit",
+                    line + 2
+                )));
+            }
+            if let Some(i) = self
+                .items
+                .iter()
+                .position(|item| item.locale == field(locale) && item.id == field(id))
+            {
+                let item = &mut self.items[i];
+                if item.state != new_state || item.edit != new_edit {
+                    item.state = new_state;
+                    item.edit = new_edit;
+                    changed.push(i);
+                }
+            }
+        }
+        Ok(changed)
+    }
+}
+
+/// Split CSV into rows of fields, allowing quoted fields with commas, quotes
+/// and newlines in them
+pub fn parse_csv(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("the CSV ends inside a quoted field".to_owned());
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Prompt for what to do with `item`, returning `None` to stop reviewing
+pub fn ask(item: &Item, position: usize, total: usize) -> std::io::Result<Option<State>> {
+    println!(
+        "[{}/{}] {}: {} ({})",
+        position,
+        total,
+        item.locale,
+        item.id,
+        item.reason.name()
+    );
+    println!("  source:      {}", item.source);
+    println!("  translation: {}", item.translation);
+    loop {
+        print!("[a]ccept, [e]dit, [r]eject, [s]kip or [q]uit? ");
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+            return Ok(None);
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "a" => return Ok(Some(State::Accepted)),
+            "e" => return Ok(Some(State::Edited)),
+            "r" => return Ok(Some(State::Rejected)),
+            "s" | "" => return Ok(Some(State::Pending)),
+            "q" => return Ok(None),
+            _ => println!("please answer a, e, r, s or q"),
+        }
+    }
+}
+
+/// Prompt for what to do with a translation as it comes back with
+/// `--interactive`, skipping it if there's no answer
+pub fn ask_translation(
+    locale: &str,
+    id: &str,
+    source: &str,
+    translation: &str,
+) -> std::io::Result<State> {
+    println!("{}: {}", locale, id);
+    println!("  source:      {}", source);
+    println!("  translation: {}", translation);
+    loop {
+        print!("[a]ccept, [e]dit or [s]kip? ");
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+            return Ok(State::Pending);
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "a" | "" => return Ok(State::Accepted),
+            "e" => return Ok(State::Edited),
+            "s" => return Ok(State::Pending),
+            _ => println!("please answer a, e or s"),
+        }
+    }
+}
+
+/// Prompt for the reviewer's translation, as Fluent
+pub fn ask_edit() -> std::io::Result<Option<String>> {
+    print!("new translation: ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(Some(answer.trim().to_owned()).filter(|answer| !answer.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, translation: &str) -> Item {
+        Item {
+            locale: "fr".to_owned(),
+            id: id.to_owned(),
+            reason: Reason::LowConfidence,
+            source: "Hello, { $name }".to_owned(),
+            translation: translation.to_owned(),
+            state: State::Pending,
+            edit: None,
+        }
+    }
+
+    #[test]
+    fn keeps_reviews_of_the_same_translation() {
+        let mut queue = Queue::default();
+        queue.add(item("hello", "Bonjour, { $name }"));
+        queue.items[0].state = State::Accepted;
+        queue.add(item("hello", "Bonjour, { $name }"));
+        assert_eq!(queue.items[0].state, State::Accepted);
+        queue.add(item("hello", "Salut, { $name }"));
+        assert_eq!(queue.items[0].state, State::Pending);
+        assert_eq!(queue.items.len(), 1);
+    }
+
+    #[test]
+    fn round_trips_csv() {
+        let mut queue = Queue::default();
+        queue.add(item("hello", "Bonjour, { $name }"));
+        queue.add(item("bye", "Au revoir"));
+        let mut out = Vec::new();
+        queue.write_csv(&mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        assert_eq!(
+            csv,
+            "locale,id,reason,state,source,translation,edit\n\
+             fr,hello,low-confidence,pending,\"Hello, { $name }\",\"Bonjour, { $name }\",\n\
+             fr,bye,low-confidence,pending,\"Hello, { $name }\",Au revoir,\n"
+        );
+
+        let reviewed = csv
+            .replace(
+                "pending,\"Hello, { $name }\",\"Bonjour, { $name }\",",
+                "edited,\"Hello, { $name }\",\"Bonjour, { $name }\",\"Salut, \"\"{ $name }\"\"\"",
+            )
+            .replace("pending,\"Hello, { $name }\",Au revoir", "accepted,x,y");
+        assert_eq!(queue.read_csv(&reviewed).unwrap(), vec![0, 1]);
+        assert_eq!(queue.items[0].state, State::Edited);
+        assert_eq!(queue.items[0].edit.as_deref(), Some("Salut, \"{ $name }\""));
+        assert_eq!(queue.items[1].state, State::Accepted);
+        assert!(queue.read_csv(&reviewed).unwrap().is_empty());
+        assert!(queue
+            .read_csv(&reviewed.replace("accepted", "maybe"))
+            .is_err());
+    }
+}
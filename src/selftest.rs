@@ -0,0 +1,82 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fluent_syntax::ast::{Entry, ResourceEntry};
+
+/// The catalog that `tt selftest` translates, small enough to cost next to
+/// nothing while still having a placeable to put back
+pub const SAMPLE: &str = "\
+### A sample catalog for `tt selftest`
+
+hello = Hello, { $name }!
+";
+
+/// The id of the message in [`SAMPLE`]
+const SAMPLE_ID: &str = "hello";
+
+/// Check that `written`, the translation of [`SAMPLE`] that was written out,
+/// reads back in with its message translated and its placeable intact,
+/// returning the translation
+pub fn check_translation(written: &str) -> Result<String, String> {
+    let source = fluent_syntax::parser::parse(SAMPLE).expect("the sample is valid Fluent");
+    let resource = fluent_syntax::parser::parse(written)
+        .map_err(|(_, errors)| format!("the translation isn't valid Fluent: {:?}", errors))?;
+    let message = resource
+        .body
+        .iter()
+        .find_map(|entry| match entry {
+            ResourceEntry::Entry(Entry::Message(message)) if message.id.name == SAMPLE_ID => {
+                Some(message)
+            }
+            _ => None,
+        })
+        .ok_or_else(|| format!("`{}` is missing from the translation", SAMPLE_ID))?;
+    let value = message
+        .value
+        .as_ref()
+        .ok_or_else(|| format!("`{}` has no value", SAMPLE_ID))?;
+    let source_value = super::catalog::find_message(&source, SAMPLE_ID)
+        .and_then(|message| message.value.as_ref())
+        .expect("the sample has its message");
+
+    let mut text: Vec<u8> = Vec::new();
+    super::writer::write_pattern(&mut text, value).expect("can write to a Vec");
+    let text = String::from_utf8_lossy(&text).into_owned();
+    if super::placeables::of(value) != super::placeables::of(source_value) {
+        return Err(format!("the placeables of `{}` changed", text));
+    }
+    if !super::placeables::markers(&text).is_empty() {
+        return Err(format!("`{}` still has a placeable's marker in it", text));
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_translations() {
+        assert_eq!(
+            check_translation("hello = Bonjour, { $name } !\n"),
+            Ok("Bonjour, { $name } !".to_owned())
+        );
+        assert!(check_translation("hello = Bonjour !\n").is_err());
+        assert!(check_translation("bye = Salut\n").is_err());
+        assert!(
+            check_translation("hello = Bonjour <span translate=\"no\" id=\"ph1\"></span> !\n")
+                .is_err()
+        );
+    }
+}
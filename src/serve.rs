@@ -0,0 +1,323 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::TcpListener;
+use std::time::Duration;
+
+use super::compare::html_escape;
+
+/// The most bytes that a form can be, which is plenty for one translation
+const MAX_BODY: usize = 1 << 20;
+
+/// How long a connection can sit idle before it's dropped, since requests are
+/// answered one at a time and an idle one would hold up every other reviewer
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A translation that's waiting for a reviewer
+pub struct Pending {
+    pub locale: String,
+    pub id: String,
+    /// why it needs review, such as `needs-review` or the queue's reason
+    pub reason: String,
+    pub source: String,
+    pub translation: String,
+}
+
+/// A request to the server, with the fields of its form if it was posted one
+#[derive(Debug, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// the `Host` and `Origin` headers, if they were sent
+    pub host: Option<String>,
+    pub origin: Option<String>,
+    pub form: HashMap<String, String>,
+}
+
+impl Request {
+    /// Whether the request came from a page served from the host it was sent
+    /// to, so that other sites can't post forms to `/save`
+    pub fn same_origin(&self) -> bool {
+        match (&self.host, &self.origin) {
+            (Some(host), Some(origin)) => origin.strip_prefix("http://") == Some(host.as_str()),
+            _ => false,
+        }
+    }
+}
+
+/// Read a request off of `reader`
+pub fn read_request<R: BufRead>(reader: &mut R) -> Result<Request, Box<dyn Error>> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_owned(), target),
+        _ => return Err(Box::from(format!("bad request line `{}`", line.trim()))),
+    };
+    let path = target.split('?').next().unwrap_or("/").to_owned();
+
+    let mut length = 0;
+    let mut host = None;
+    let mut origin = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let name = name.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse()?;
+            } else if name.eq_ignore_ascii_case("host") {
+                host = Some(value.trim().to_owned());
+            } else if name.eq_ignore_ascii_case("origin") {
+                origin = Some(value.trim().to_owned());
+            }
+        }
+    }
+    if length > MAX_BODY {
+        return Err(Box::from(format!("a {} byte form is too big", length)));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    let form = if body.is_empty() {
+        HashMap::new()
+    } else {
+        serde_urlencoded::from_bytes(&body)?
+    };
+    Ok(Request {
+        method,
+        path,
+        host,
+        origin,
+        form,
+    })
+}
+
+/// Write the page listing each of the `pending` translations with a box to
+/// edit it in, along with `notice` if there is one, such as an error
+pub fn write_page<W: Write>(
+    out: &mut W,
+    pending: &[Pending],
+    notice: Option<&str>,
+) -> std::io::Result<()> {
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>")?;
+    writeln!(out, "<head>")?;
+    writeln!(out, "<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>Translations to review</title>")?;
+    writeln!(
+        out,
+        "<style>body {{ font-family: sans-serif; }} .source {{ white-space: pre-wrap; }} textarea {{ width: 100%; }} .notice {{ color: #b00; }}</style>"
+    )?;
+    writeln!(out, "</head>")?;
+    writeln!(out, "<body>")?;
+    if let Some(notice) = notice {
+        writeln!(out, "<p class=\"notice\">{}</p>", html_escape(notice))?;
+    }
+    if pending.is_empty() {
+        writeln!(out, "<p>There's nothing to review.</p>")?;
+    }
+    let mut locale = None;
+    for item in pending.iter() {
+        if locale != Some(&item.locale) {
+            writeln!(out, "<h2>{}</h2>", html_escape(&item.locale))?;
+            locale = Some(&item.locale);
+        }
+        writeln!(out, "<form method=\"post\" action=\"/save\">")?;
+        writeln!(
+            out,
+            "<h3>{} <small>({})</small></h3>",
+            html_escape(&item.id),
+            html_escape(&item.reason)
+        )?;
+        writeln!(out, "<p class=\"source\">{}</p>", html_escape(&item.source))?;
+        writeln!(
+            out,
+            "<input type=\"hidden\" name=\"locale\" value=\"{}\">",
+            html_escape(&item.locale)
+        )?;
+        writeln!(
+            out,
+            "<input type=\"hidden\" name=\"id\" value=\"{}\">",
+            html_escape(&item.id)
+        )?;
+        writeln!(
+            out,
+            "<textarea name=\"translation\" rows=\"{}\">{}</textarea>",
+            item.translation.lines().count().max(2),
+            html_escape(&item.translation)
+        )?;
+        writeln!(out, "<button type=\"submit\">Save</button>")?;
+        writeln!(out, "</form>")?;
+    }
+    writeln!(out, "</body>")?;
+    writeln!(out, "</html>")
+}
+
+fn respond<W: Write>(out: &mut W, status: &str, headers: &str, body: &[u8]) -> std::io::Result<()> {
+    write!(
+        out,
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n",
+        status,
+        body.len(),
+        headers
+    )?;
+    out.write_all(body)?;
+    out.flush()
+}
+
+/// Respond to a request, logging rather than failing if the client has gone
+fn reply<W: Write>(out: &mut W, status: &str, headers: &str, body: &[u8]) {
+    if let Err(e) = respond(out, status, headers, body) {
+        log::warn!("failed to respond: {}", e);
+    }
+}
+
+/// Answer requests one at a time until the process is stopped, listing the
+/// translations from `list` on `/` and saving each form posted to `/save`
+/// with `save`, given the locale, id and translation
+pub fn serve<L, S>(listener: TcpListener, mut list: L, mut save: S) -> Result<(), Box<dyn Error>>
+where
+    L: FnMut() -> Result<Vec<Pending>, Box<dyn Error>>,
+    S: FnMut(&str, &str, &str) -> Result<(), Box<dyn Error>>,
+{
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::warn!("failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = stream
+            .set_read_timeout(Some(TIMEOUT))
+            .and_then(|_| stream.set_write_timeout(Some(TIMEOUT)))
+        {
+            log::warn!("failed to set a timeout on a connection: {}", e);
+            continue;
+        }
+        let request = match read_request(&mut BufReader::new(&stream)) {
+            Ok(request) => request,
+            Err(e) => {
+                log::warn!("bad request: {}", e);
+                reply(&mut stream, "400 Bad Request", "", b"");
+                continue;
+            }
+        };
+        log::debug!("{} {}", request.method, request.path);
+
+        let field = |name: &str| request.form.get(name).map(String::as_str).unwrap_or("");
+        let (status, notice) = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/") => ("200 OK", None),
+            ("POST", "/save") if !request.same_origin() => {
+                log::warn!(
+                    "refusing a form from {} posted to {}",
+                    request.origin.as_deref().unwrap_or("no origin"),
+                    request.host.as_deref().unwrap_or("no host")
+                );
+                reply(&mut stream, "403 Forbidden", "", b"");
+                continue;
+            }
+            ("POST", "/save") => {
+                match save(
+                    field("locale"),
+                    field("id").trim(),
+                    field("translation").trim(),
+                ) {
+                    Ok(()) => {
+                        reply(&mut stream, "303 See Other", "Location: /\r\n", b"");
+                        continue;
+                    }
+                    Err(e) => {
+                        log::error!("{}", e);
+                        ("422 Unprocessable Entity", Some(e.to_string()))
+                    }
+                }
+            }
+            _ => {
+                reply(&mut stream, "404 Not Found", "", b"");
+                continue;
+            }
+        };
+        let mut page = Vec::new();
+        match list() {
+            Ok(pending) => write_page(&mut page, &pending, notice.as_deref())?,
+            Err(e) => {
+                log::error!("{}", e);
+                write_page(&mut page, &[], Some(&e.to_string()))?;
+            }
+        }
+        reply(
+            &mut stream,
+            status,
+            "Content-Type: text/html; charset=utf-8\r\n",
+            &page,
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_requests() {
+        let text = "POST /save?x=1 HTTP/1.1\r\nHost: localhost:8080\r\nOrigin: http://localhost:8080\r\nContent-Type: application/x-www-form-urlencoded\r\nContent-Length: 52\r\n\r\nlocale=fr&id=hello&translation=Bonjour%2C+%7B+%24who";
+        let request = read_request(&mut text.as_bytes()).unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/save");
+        assert_eq!(request.form["id"], "hello");
+        assert_eq!(request.form["translation"], "Bonjour, { $who");
+        assert!(request.same_origin());
+
+        // forms posted from other sites, or by something that doesn't say
+        // where it's from, are refused
+        let text =
+            "POST /save HTTP/1.1\r\nHost: 10.0.0.2:8080\r\nOrigin: https://example.com\r\n\r\n";
+        assert!(!read_request(&mut text.as_bytes()).unwrap().same_origin());
+        let text = "POST /save HTTP/1.1\r\nHost: 10.0.0.2:8080\r\n\r\n";
+        assert!(!read_request(&mut text.as_bytes()).unwrap().same_origin());
+
+        let request = read_request(&mut "GET / HTTP/1.1\r\n\r\n".as_bytes()).unwrap();
+        assert_eq!(request.path, "/");
+        assert!(request.form.is_empty());
+        assert!(read_request(&mut "\r\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn escapes_the_page() {
+        let mut page = Vec::new();
+        write_page(
+            &mut page,
+            &[Pending {
+                locale: "fr".to_owned(),
+                id: "html-test".to_owned(),
+                reason: "needs-review".to_owned(),
+                source: "I am <em>very</em> glad".to_owned(),
+                translation: "Je suis <em>très</em> content".to_owned(),
+            }],
+            None,
+        )
+        .unwrap();
+        let page = String::from_utf8(page).unwrap();
+        assert!(page.contains("<h2>fr</h2>"));
+        assert!(page.contains(">Je suis &lt;em&gt;très&lt;/em&gt; content</textarea>"));
+    }
+}
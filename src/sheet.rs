@@ -0,0 +1,203 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fluent_syntax::ast::{Comment, Message};
+use std::error::Error;
+use std::io::prelude::*;
+use std::path::Path;
+
+use super::compare::csv_field;
+
+/// The name of the format with `--format`, for the spreadsheet of every
+/// locale's translations
+pub const FORMAT: &str = "csv";
+
+/// Whether `--format` or the extension of `path` says that it's the
+/// spreadsheet
+pub fn is_sheet(format: Option<&str>, path: &Path) -> bool {
+    match format {
+        Some(format) => format == FORMAT,
+        None => path.extension().and_then(|e| e.to_str()) == Some(FORMAT),
+    }
+}
+
+/// The columns of the spreadsheet, in order
+const HEADER: [&str; 6] = ["locale", "id", "comment", "source", "translation", "status"];
+
+/// A message of one locale, as a row of the spreadsheet
+#[derive(Debug, PartialEq)]
+pub struct Row {
+    pub locale: String,
+    pub id: String,
+    /// the source message's comment, such as its `tt-context`
+    pub comment: String,
+    pub source: String,
+    /// the locale's current translation, empty if it doesn't have one
+    pub translation: String,
+    pub status: String,
+}
+
+/// The lines of `comment`, if it's a message's own comment
+pub fn comment_text(comment: &Option<Comment>) -> String {
+    match comment {
+        Some(Comment::Comment { content }) => content.join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// The value and attributes of `message` as Fluent, without the id and with
+/// one level of indentation taken out, since it's easier to edit that way in
+/// a spreadsheet
+pub fn message_text(message: &Message) -> String {
+    let mut text: Vec<u8> = Vec::new();
+    if let Some(value) = &message.value {
+        super::writer::write_pattern(&mut text, value).expect("can write to a Vec");
+    }
+    super::writer::write_attributes(&mut text, &message.attributes).expect("can write to a Vec");
+    String::from_utf8(text)
+        .expect("valid utf-8")
+        .trim()
+        .lines()
+        .map(|line| line.strip_prefix("    ").unwrap_or(line))
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+/// `text`, as `message_text` writes it, as the Fluent message `id` with the
+/// indentation put back
+pub fn message_entry(id: &str, text: &str) -> String {
+    let mut entry = format!("{} =", id);
+    for (i, line) in text.trim().lines().enumerate() {
+        if i == 0 && !line.starts_with('.') {
+            entry.push(' ');
+        } else if line.is_empty() {
+            entry.push('\n');
+            continue;
+        } else {
+            entry.push_str("\n    ");
+        }
+        entry.push_str(line);
+    }
+    entry.push('\n');
+    entry
+}
+
+/// A row of the spreadsheet that was given a translation
+#[derive(Debug, PartialEq)]
+pub struct Filled {
+    /// the row's number, counting the header as the first
+    pub row: usize,
+    pub locale: String,
+    pub id: String,
+    pub translation: String,
+}
+
+/// Read the rows of a filled-in spreadsheet that have a translation
+pub fn read_csv(text: &str) -> Result<Vec<Filled>, Box<dyn Error>> {
+    let mut rows = super::review::parse_csv(text)?.into_iter();
+    let header = rows.next().unwrap_or_default();
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.trim() == name)
+            .ok_or_else(|| format!("the spreadsheet has no `{}` column", name))
+    };
+    let (locale, id, translation) = (column("locale")?, column("id")?, column("translation")?);
+
+    let mut filled = Vec::new();
+    for (line, row) in rows.enumerate() {
+        let field = |i: usize| row.get(i).map(|f| f.trim()).unwrap_or("");
+        if field(translation).is_empty() {
+            continue;
+        }
+        filled.push(Filled {
+            row: line + 2,
+            locale: field(locale).to_owned(),
+            id: field(id).to_owned(),
+            translation: field(translation).to_owned(),
+        });
+    }
+    Ok(filled)
+}
+
+pub fn write_csv<W: Write>(out: &mut W, rows: &[Row]) -> std::io::Result<()> {
+    writeln!(out, "{}", HEADER.join(","))?;
+    for row in rows.iter() {
+        let fields = [
+            &row.locale,
+            &row.id,
+            &row.comment,
+            &row.source,
+            &row.translation,
+            &row.status,
+        ];
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_spreadsheets() {
+        let resource = fluent_syntax::parser::parse(
+            "# tt-context: a greeting\nhello = Hello, { $name }\n    .title = Hi\nbye =\n    .title = Bye\n",
+        )
+        .unwrap();
+        let message = super::super::catalog::find_message(&resource, "hello").unwrap();
+        assert_eq!(message_text(message), "Hello, { $name }\n.title = Hi");
+        let bye = super::super::catalog::find_message(&resource, "bye").unwrap();
+        assert_eq!(message_text(bye), ".title = Bye");
+
+        let mut out = Vec::new();
+        write_csv(
+            &mut out,
+            &[Row {
+                locale: "fr".to_owned(),
+                id: "hello".to_owned(),
+                comment: comment_text(&message.comment),
+                source: message_text(message),
+                translation: String::new(),
+                status: "missing".to_owned(),
+            }],
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "locale,id,comment,source,translation,status\nfr,hello,tt-context: a greeting,\"Hello, { $name }\n.title = Hi\",,missing\n"
+        );
+    }
+
+    #[test]
+    fn reads_spreadsheets() {
+        let text = "id,locale,translation,status\nhello,fr,\"Bonjour, { $name }\n.title = Salut\",missing\nbye,fr,,missing\nbye,de,\".title = Tschüss\",missing\n";
+        let filled = read_csv(text).unwrap();
+        assert_eq!(filled.len(), 2);
+        assert_eq!(filled[1].row, 4);
+        assert_eq!(
+            message_entry(&filled[0].id, &filled[0].translation),
+            "hello = Bonjour, { $name }\n    .title = Salut\n"
+        );
+        assert_eq!(
+            message_entry(&filled[1].id, &filled[1].translation),
+            "bye =\n    .title = Tschüss\n"
+        );
+        assert!(read_csv("locale,id\nfr,hello\n").is_err());
+        assert!(is_sheet(None, Path::new("fr.csv")));
+        assert!(!is_sheet(Some("json"), Path::new("fr.csv")));
+    }
+}
@@ -0,0 +1,191 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct SpellcheckConfig {
+    /// check the spelling of translations with hunspell
+    pub enabled: bool,
+    /// the hunspell dictionary of each locale, such as `de_DE`, instead of the
+    /// locale itself
+    pub dictionaries: BTreeMap<String, String>,
+    /// words that are never misspelled, such as product names
+    pub ignore: Vec<String>,
+}
+
+/// The `hunspell` executable, overridable with the `TT_HUNSPELL` environment
+/// variable
+fn binary() -> String {
+    std::env::var("TT_HUNSPELL").unwrap_or_else(|_| "hunspell".to_owned())
+}
+
+/// The text of `translated` that's worth checking, with its placeables' markers,
+/// tags, entities and `protected` spans blanked out
+pub fn checked_text(translated: &str, protected: &[String]) -> String {
+    let mut text = super::placeables::strip_markers(translated, " ").replace('\n', " ");
+    for span in protected.iter().filter(|span| !span.is_empty()) {
+        text = text.replace(span.as_str(), " ");
+    }
+    let mut checked = String::with_capacity(text.len());
+    let mut rest = text.as_str();
+    while let Some(c) = rest.chars().next() {
+        let skip = match c {
+            '<' => rest.find('>').map(|end| end + 1),
+            '&' => rest
+                .find(';')
+                .filter(|end| {
+                    rest[1..*end]
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '#')
+                })
+                .map(|end| end + 1),
+            _ => None,
+        };
+        match skip {
+            Some(len) => {
+                checked.push(' ');
+                rest = &rest[len..];
+            }
+            None => {
+                checked.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    checked
+}
+
+/// Read the misspelled words of each of `lines` lines from hunspell's `-a`
+/// output, where each line's results end with an empty line
+fn parse_pipe(output: &str, lines: usize) -> Vec<Vec<String>> {
+    let mut misspelled = vec![Vec::new(); lines];
+    let mut line = 0;
+    // the first line is hunspell's version
+    for result in output.lines().skip(1) {
+        if result.trim().is_empty() {
+            line += 1;
+            continue;
+        }
+        if line >= lines {
+            break;
+        }
+        // `& word count offset: suggestions` or `# word offset`
+        if result.starts_with('&') || result.starts_with('#') {
+            if let Some(word) = result.split_whitespace().nth(1) {
+                misspelled[line].push(word.to_owned());
+            }
+        }
+    }
+    misspelled
+}
+
+impl SpellcheckConfig {
+    fn dictionary(&self, locale: &str) -> String {
+        match self.dictionaries.get(locale) {
+            Some(dictionary) => dictionary.clone(),
+            None => locale.replace('-', "_"),
+        }
+    }
+
+    /// The misspelled words in each of `texts`, which are already
+    /// [`checked_text`], using `locale`'s dictionary
+    pub fn misspellings(
+        &self,
+        locale: &str,
+        texts: &[String],
+    ) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+        let dictionary = self.dictionary(locale);
+        let mut child = Command::new(binary())
+            .arg("-a")
+            .arg("-d")
+            .arg(&dictionary)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to run `{}`, is hunspell installed? {}", binary(), e))?;
+        {
+            let mut stdin = child.stdin.take().expect("stdin is piped");
+            for text in texts.iter() {
+                // `^` stops a line from being taken as a command
+                writeln!(stdin, "^{}", text)?;
+            }
+        }
+        let output = child.wait_with_output()?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() || stderr.contains("Can't open") {
+            return Err(Box::from(format!(
+                "hunspell failed with the `{}` dictionary: {}",
+                dictionary,
+                stderr.trim()
+            )));
+        }
+        let misspelled = parse_pipe(&String::from_utf8_lossy(&output.stdout), texts.len());
+        Ok(misspelled
+            .into_iter()
+            .map(|words| {
+                words
+                    .into_iter()
+                    .filter(|word| !self.ignore.iter().any(|ignored| ignored == word))
+                    .collect()
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blanks_out_what_isnt_checked() {
+        let checked = checked_text(
+            "Bonjour <span translate=\"no\" id=\"ph1\"></span>, <b>bienvenue</b> sur Acme™ &amp; plus",
+            &["Acme™".to_owned()],
+        );
+        assert_eq!(
+            checked.split_whitespace().collect::<Vec<&str>>(),
+            vec!["Bonjour", ",", "bienvenue", "sur", "plus"]
+        );
+    }
+
+    #[test]
+    fn parses_hunspell_output() {
+        let output = "@(#) International Ispell Version 3.2.06 (but really Hunspell 1.7.0)
+*
+& Bonjuor 2 0: Bonjour, Bonjours
+*
+
+*
+
+# Zxqv 0
+*
+
+";
+        assert_eq!(
+            parse_pipe(output, 3),
+            vec![
+                vec!["Bonjuor".to_owned()],
+                Vec::new(),
+                vec!["Zxqv".to_owned()]
+            ]
+        );
+    }
+}
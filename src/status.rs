@@ -0,0 +1,161 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::Serialize;
+
+use super::catalog::State;
+
+/// How much of the source a locale's translation covers
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Coverage {
+    pub locale: String,
+    /// the number of messages in the source
+    pub total: usize,
+    /// messages with an up to date translation, by hand or by machine
+    pub translated: usize,
+    pub hand_translated: usize,
+    /// hand translations whose source changed since, which are counted in
+    /// `hand_translated` too
+    pub stale_hand_translated: usize,
+    pub machine_translated: usize,
+    pub missing: usize,
+    pub stale: usize,
+    /// the ids of the missing and stale messages, including stale hand
+    /// translations, in source order
+    #[serde(skip)]
+    pub outdated: Vec<(String, State)>,
+}
+
+impl Coverage {
+    /// The share of the source that is translated, as a percentage
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            100.0
+        } else {
+            self.translated as f64 * 100.0 / self.total as f64
+        }
+    }
+}
+
+/// Classify every message in `source` for `locale`
+pub fn coverage<F: Fn(&fluent_syntax::ast::Message) -> bool>(
+    locale: &str,
+    source: &fluent_syntax::ast::Resource,
+    unchanged: F,
+    target_existing: &fluent_syntax::ast::Resource,
+    markers: &super::config::Markers,
+) -> Coverage {
+    let mut coverage = Coverage {
+        locale: locale.to_owned(),
+        total: 0,
+        translated: 0,
+        hand_translated: 0,
+        stale_hand_translated: 0,
+        machine_translated: 0,
+        missing: 0,
+        stale: 0,
+        outdated: Vec::new(),
+    };
+    for entry in source.body.iter() {
+        if let fluent_syntax::ast::ResourceEntry::Entry(fluent_syntax::ast::Entry::Message(
+            message,
+        )) = entry
+        {
+            coverage.total += 1;
+            let state = super::catalog::classify(message, &unchanged, target_existing, markers);
+            match state {
+                State::Missing => coverage.missing += 1,
+                State::Stale => coverage.stale += 1,
+                State::HandTranslated => coverage.hand_translated += 1,
+                State::StaleHandTranslated => {
+                    coverage.hand_translated += 1;
+                    coverage.stale_hand_translated += 1;
+                }
+                State::Translated => coverage.machine_translated += 1,
+            }
+            if state != State::HandTranslated && state != State::Translated {
+                coverage.outdated.push((message.id.name.to_owned(), state));
+            }
+        }
+    }
+    coverage.translated = coverage.hand_translated + coverage.machine_translated;
+    coverage
+}
+
+/// Print a table with a row for each locale
+pub fn print_table(coverages: &[Coverage], palette: super::color::Palette) {
+    println!(
+        "{:<10} {:>6} {:>10} {:>6} {:>8} {:>8} {:>6} {:>9}",
+        "locale", "total", "translated", "hand", "machine", "missing", "stale", "coverage"
+    );
+    for c in coverages.iter() {
+        // padded before coloring, since the escape codes would count towards the width
+        let count = |n: usize, width: usize, paint: fn(&super::color::Palette, &str) -> String| {
+            let text = format!("{:>1$}", n, width);
+            if n > 0 {
+                paint(&palette, &text)
+            } else {
+                text
+            }
+        };
+        println!(
+            "{:<10} {:>6} {} {:>6} {:>8} {} {} {:>8.1}%",
+            c.locale,
+            c.total,
+            count(c.translated, 10, super::color::Palette::added),
+            c.hand_translated,
+            c.machine_translated,
+            count(c.missing, 8, super::color::Palette::failed),
+            count(c.stale, 6, super::color::Palette::stale),
+            c.percent()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_coverage() {
+        let target = fluent_syntax::parser::parse(
+            "a = Un\n# tt-hand-translated\nb = Deux\nc = Trois\n# tt-hand-translated\nd = Quatre\n",
+        )
+        .unwrap();
+        let source = fluent_syntax::parser::parse("a = A\nb = B\nc = C\nd = D\ne = E\n").unwrap();
+        let coverage = coverage(
+            "fr",
+            &source,
+            |message| message.id.name == "a" || message.id.name == "b",
+            &target,
+            &crate::config::Markers::default(),
+        );
+        assert_eq!(coverage.total, 5);
+        assert_eq!(coverage.machine_translated, 1);
+        assert_eq!(coverage.hand_translated, 2);
+        assert_eq!(coverage.stale_hand_translated, 1);
+        assert_eq!(coverage.translated, 3);
+        assert_eq!(coverage.stale, 1);
+        assert_eq!(coverage.missing, 1);
+        assert_eq!(
+            coverage.outdated,
+            vec![
+                ("c".to_owned(), State::Stale),
+                ("d".to_owned(), State::StaleHandTranslated),
+                ("e".to_owned(), State::Missing)
+            ]
+        );
+        assert_eq!(coverage.percent(), 60.0);
+    }
+}
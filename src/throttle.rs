@@ -0,0 +1,107 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Spaces out the requests of a run to its `--qps`. Clones share when the
+/// next request can be sent, so every thread sending them keeps to it.
+#[derive(Clone, Debug, Default)]
+pub struct Throttle {
+    /// how long to leave between requests, or zero for no limit
+    interval: Duration,
+    next: Arc<Mutex<Option<Instant>>>,
+}
+
+/// Throttles are the same when they space out requests the same
+impl PartialEq for Throttle {
+    fn eq(&self, other: &Throttle) -> bool {
+        self.interval == other.interval
+    }
+}
+
+/// When a request wanting to be sent at `now` can go, and when the one after
+/// it can, given when the next one could have gone
+fn schedule(now: Instant, next: Option<Instant>, interval: Duration) -> (Instant, Instant) {
+    let slot = match next {
+        Some(next) if next > now => next,
+        _ => now,
+    };
+    (slot, slot + interval)
+}
+
+impl Throttle {
+    /// Send at most `qps` requests a second, or as many as possible if it's
+    /// `None`
+    pub fn new(qps: Option<f64>) -> Throttle {
+        Throttle {
+            interval: match qps {
+                Some(qps) if qps > 0.0 => Duration::from_secs_f64(1.0 / qps),
+                _ => Duration::ZERO,
+            },
+            next: Arc::default(),
+        }
+    }
+
+    /// Wait until a request can be sent without going over the limit
+    pub fn wait(&self) {
+        if self.interval.is_zero() {
+            return;
+        }
+        let now = Instant::now();
+        let slot = {
+            let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+            let (slot, after) = schedule(now, *next, self.interval);
+            *next = Some(after);
+            slot
+        };
+        if slot > now {
+            std::thread::sleep(slot - now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spaces_out_requests() {
+        let now = Instant::now();
+        let interval = Duration::from_millis(250);
+        let (slot, next) = schedule(now, None, interval);
+        assert_eq!((slot, next), (now, now + interval));
+        // the next two have to wait their turn
+        let (slot, next) = schedule(now, Some(next), interval);
+        assert_eq!(slot, now + interval);
+        let (slot, _) = schedule(now, Some(next), interval);
+        assert_eq!(slot, now + interval * 2);
+        // but a request after a quiet spell goes straight away
+        let later = now + Duration::from_secs(5);
+        assert_eq!(schedule(later, Some(next), interval).0, later);
+    }
+
+    #[test]
+    fn shares_the_schedule_between_clones() {
+        assert!(Throttle::new(None).interval.is_zero());
+        assert!(Throttle::new(Some(0.0)).interval.is_zero());
+        let throttle = Throttle::new(Some(4.0));
+        assert_eq!(throttle.interval, Duration::from_millis(250));
+        let clone = throttle.clone();
+        throttle.wait();
+        assert!(clone.next.lock().unwrap().is_some());
+        // a throttle made for another run starts its own
+        assert!(Throttle::new(Some(4.0)).next.lock().unwrap().is_none());
+    }
+}
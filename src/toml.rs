@@ -0,0 +1,626 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A small parser for the subset of TOML used by `tt.toml`: tables, arrays of
+//! tables, dotted keys, strings, integers, floats, booleans, arrays and inline
+//! tables. Bare dates (`2020-12-24`) are read as strings. The parsed document is
+//! returned as a `serde_json::Value` so it can be deserialized with serde.
+
+use serde_json::{Map, Value};
+use std::error::Error;
+
+pub fn parse(source: &str) -> Result<Value, Box<dyn Error>> {
+    let mut parser = Parser {
+        chars: source.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    parser.document()
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn error<T>(&self, message: &str) -> Result<T, Box<dyn Error>> {
+        Err(Box::from(format!("line {}: {}", self.line, message)))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek();
+        if let Some(c) = c {
+            self.pos += 1;
+            if c == '\n' {
+                self.line += 1;
+            }
+        }
+        c
+    }
+
+    fn eat(&mut self, expected: char) -> Result<(), Box<dyn Error>> {
+        match self.bump() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => self.error(&format!("expected `{}`, found `{}`", expected, c)),
+            None => self.error(&format!("expected `{}`, found end of file", expected)),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ') | Some('\t') = self.peek() {
+            self.bump();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), Some('\n') | None) {
+                self.bump();
+            }
+        }
+    }
+
+    /// Skip whitespace, comments and newlines, as allowed between array elements
+    fn skip_blank(&mut self) {
+        loop {
+            self.skip_whitespace();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n') | Some('\r') => {
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), Box<dyn Error>> {
+        self.skip_whitespace();
+        self.skip_comment();
+        match self.peek() {
+            None => Ok(()),
+            Some('\r') | Some('\n') => {
+                if self.bump() == Some('\r') {
+                    self.eat('\n')?;
+                }
+                Ok(())
+            }
+            Some(c) => self.error(&format!("unexpected `{}` after value", c)),
+        }
+    }
+
+    fn document(&mut self) -> Result<Value, Box<dyn Error>> {
+        let mut root = Map::new();
+        let mut current: Vec<String> = Vec::new();
+
+        loop {
+            self.skip_blank();
+            match self.peek() {
+                None => break,
+                Some('[') => {
+                    self.bump();
+                    let is_array = self.peek() == Some('[');
+                    if is_array {
+                        self.bump();
+                    }
+                    self.skip_whitespace();
+                    let path = self.key()?;
+                    self.skip_whitespace();
+                    self.eat(']')?;
+                    if is_array {
+                        self.eat(']')?;
+                        let (last, parents) = path.split_last().expect("keys are never empty");
+                        let parent = self.table(&mut root, parents)?;
+                        let entry = parent
+                            .entry(last.clone())
+                            .or_insert_with(|| Value::Array(Vec::new()));
+                        match entry {
+                            Value::Array(tables) => tables.push(Value::Object(Map::new())),
+                            _ => {
+                                return self.error(&format!("`{}` is not an array of tables", last))
+                            }
+                        }
+                    } else {
+                        self.table(&mut root, &path)?;
+                    }
+                    current = path;
+                    self.end_of_line()?;
+                }
+                Some(_) => {
+                    let path = self.key()?;
+                    self.skip_whitespace();
+                    self.eat('=')?;
+                    self.skip_whitespace();
+                    let value = self.value()?;
+                    let table = self.table(&mut root, &current)?;
+                    self.insert(table, &path, value)?;
+                    self.end_of_line()?;
+                }
+            }
+        }
+
+        Ok(Value::Object(root))
+    }
+
+    /// Find (creating as needed) the table at `path`, descending into the last
+    /// element of any arrays of tables along the way
+    fn table<'a>(
+        &self,
+        root: &'a mut Map<String, Value>,
+        path: &[String],
+    ) -> Result<&'a mut Map<String, Value>, Box<dyn Error>> {
+        let mut table = root;
+        for key in path {
+            let entry = table
+                .entry(key.clone())
+                .or_insert_with(|| Value::Object(Map::new()));
+            let entry = match entry {
+                Value::Array(tables) => match tables.last_mut() {
+                    Some(last) => last,
+                    None => return self.error(&format!("`{}` is an empty array", key)),
+                },
+                entry => entry,
+            };
+            table = match entry {
+                Value::Object(t) => t,
+                _ => return self.error(&format!("`{}` is not a table", key)),
+            };
+        }
+        Ok(table)
+    }
+
+    fn insert(
+        &self,
+        table: &mut Map<String, Value>,
+        path: &[String],
+        value: Value,
+    ) -> Result<(), Box<dyn Error>> {
+        let (last, parents) = path.split_last().expect("keys are never empty");
+        let table = self.table(table, parents)?;
+        if table.contains_key(last) {
+            return self.error(&format!("duplicate key `{}`", last));
+        }
+        table.insert(last.clone(), value);
+        Ok(())
+    }
+
+    fn key(&mut self) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut path = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let part = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let mut part = String::new();
+                    while let Some(c) = self.peek() {
+                        if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                            part.push(c);
+                            self.bump();
+                        } else {
+                            break;
+                        }
+                    }
+                    if part.is_empty() {
+                        return self.error("expected a key");
+                    }
+                    part
+                }
+            };
+            path.push(part);
+            self.skip_whitespace();
+            if self.peek() == Some('.') {
+                self.bump();
+            } else {
+                return Ok(path);
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, Box<dyn Error>> {
+        match self.peek() {
+            Some('"') => Ok(Value::String(self.basic_string()?)),
+            Some('\'') => Ok(Value::String(self.literal_string()?)),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            Some(_) => self.scalar(),
+            None => self.error("expected a value, found end of file"),
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, Box<dyn Error>> {
+        self.eat('[')?;
+        let mut values = Vec::new();
+        loop {
+            self.skip_blank();
+            if self.peek() == Some(']') {
+                self.bump();
+                return Ok(Value::Array(values));
+            }
+            values.push(self.value()?);
+            self.skip_blank();
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                }
+                Some(']') => {}
+                _ => return self.error("expected `,` or `]` in array"),
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Value, Box<dyn Error>> {
+        self.eat('{')?;
+        let mut table = Map::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.bump();
+            return Ok(Value::Object(table));
+        }
+        loop {
+            let path = self.key()?;
+            self.skip_whitespace();
+            self.eat('=')?;
+            self.skip_whitespace();
+            let value = self.value()?;
+            self.insert(&mut table, &path, value)?;
+            self.skip_whitespace();
+            match self.bump() {
+                Some(',') => self.skip_whitespace(),
+                Some('}') => return Ok(Value::Object(table)),
+                _ => return self.error("expected `,` or `}` in inline table"),
+            }
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, Box<dyn Error>> {
+        let multiline = self.peek_at(1) == Some('"') && self.peek_at(2) == Some('"');
+        let delimiters = if multiline { 3 } else { 1 };
+        for _ in 0..delimiters {
+            self.eat('"')?;
+        }
+        if multiline && self.peek() == Some('\n') {
+            self.bump();
+        }
+
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                None => return self.error("unterminated string"),
+                Some('\n') if !multiline => return self.error("newline in string"),
+                Some('"') if !multiline => return Ok(s),
+                Some('"') if self.peek() == Some('"') && self.peek_at(1) == Some('"') => {
+                    self.bump();
+                    self.bump();
+                    return Ok(s);
+                }
+                Some('\\') => match self.bump() {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('r') => s.push('\r'),
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('u') => s.push(self.unicode_escape(4)?),
+                    Some('U') => s.push(self.unicode_escape(8)?),
+                    Some('\n') if multiline => {
+                        while let Some(' ') | Some('\t') | Some('\n') | Some('\r') = self.peek() {
+                            self.bump();
+                        }
+                    }
+                    _ => return self.error("invalid escape sequence in string"),
+                },
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self, digits: usize) -> Result<char, Box<dyn Error>> {
+        let mut code = String::new();
+        for _ in 0..digits {
+            match self.bump() {
+                Some(c) => code.push(c),
+                None => return self.error("unterminated unicode escape"),
+            }
+        }
+        match u32::from_str_radix(&code, 16)
+            .ok()
+            .and_then(std::char::from_u32)
+        {
+            Some(c) => Ok(c),
+            None => self.error(&format!("invalid unicode escape `{}`", code)),
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, Box<dyn Error>> {
+        let multiline = self.peek_at(1) == Some('\'') && self.peek_at(2) == Some('\'');
+        let delimiters = if multiline { 3 } else { 1 };
+        for _ in 0..delimiters {
+            self.eat('\'')?;
+        }
+        if multiline && self.peek() == Some('\n') {
+            self.bump();
+        }
+
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                None => return self.error("unterminated string"),
+                Some('\n') if !multiline => return self.error("newline in string"),
+                Some('\'') if !multiline => return Ok(s),
+                Some('\'') if self.peek() == Some('\'') && self.peek_at(1) == Some('\'') => {
+                    self.bump();
+                    self.bump();
+                    return Ok(s);
+                }
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn scalar(&mut self) -> Result<Value, Box<dyn Error>> {
+        let mut token = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || c == ',' || c == ']' || c == '}' || c == '#' {
+                break;
+            }
+            token.push(c);
+            self.bump();
+        }
+
+        match token.as_str() {
+            "true" => return Ok(Value::Bool(true)),
+            "false" => return Ok(Value::Bool(false)),
+            _ => {}
+        }
+
+        let number = token.replace('_', "");
+        if let Ok(i) = number.parse::<i64>() {
+            return Ok(Value::from(i));
+        }
+        if let Ok(f) = number.parse::<f64>() {
+            return Ok(Value::from(f));
+        }
+
+        // dates and times are kept as strings
+        let looks_like_date = token.starts_with(|c: char| c.is_ascii_digit())
+            && token
+                .chars()
+                .all(|c| c.is_ascii_digit() || "-:TZ.+".contains(c));
+        if looks_like_date {
+            return Ok(Value::String(token));
+        }
+
+        self.error(&format!("invalid value `{}`", token))
+    }
+}
+
+/// Write a document back out as TOML. Comments and formatting aren't kept, and
+/// `null`s are left out since TOML has no way to write them.
+pub fn write(document: &Value) -> String {
+    let mut out = String::new();
+    if let Value::Object(table) = document {
+        write_table(&mut out, &[], table);
+    }
+    out
+}
+
+/// Set a top-level key in TOML text to `value`, which is already written as
+/// TOML, leaving the rest of the text as it is. A new key goes after any
+/// comments at the top, and an array that went over several lines is
+/// replaced by the one line.
+pub fn set_top_level(text: &str, key: &str, value: &str) -> String {
+    let line = format!("{} = {}", write_key(key), value);
+    let mut lines: Vec<String> = text.lines().map(str::to_owned).collect();
+    let tables = lines
+        .iter()
+        .position(|l| l.trim_start().starts_with('['))
+        .unwrap_or(lines.len());
+    match lines[..tables]
+        .iter()
+        .position(|l| l.split('=').next().map(str::trim) == Some(key))
+    {
+        Some(i) => {
+            // the brackets still open after each line of the old value
+            let mut open = 0;
+            let mut end = i;
+            for (j, l) in lines.iter().enumerate().skip(i) {
+                open += l.matches('[').count() as isize - l.matches(']').count() as isize;
+                end = j;
+                if open <= 0 {
+                    break;
+                }
+            }
+            lines.splice(i..=end, std::iter::once(line));
+        }
+        None => {
+            let comments = lines[..tables]
+                .iter()
+                .take_while(|l| l.trim_start().starts_with('#'))
+                .count();
+            lines.insert(comments, line);
+        }
+    }
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+/// Whether `value` is written as an array of tables instead of inline
+fn is_table_array(value: &Value) -> bool {
+    match value {
+        Value::Array(items) => !items.is_empty() && items.iter().all(Value::is_object),
+        _ => false,
+    }
+}
+
+fn write_table(out: &mut String, path: &[String], table: &Map<String, Value>) {
+    for (key, value) in table.iter() {
+        if !value.is_null() && !value.is_object() && !is_table_array(value) {
+            out.push_str(&format!("{} = {}\n", write_key(key), write_value(value)));
+        }
+    }
+    for (key, value) in table.iter() {
+        let mut path = path.to_vec();
+        path.push(write_key(key));
+        match value {
+            Value::Object(table) => {
+                out.push_str(&format!("\n[{}]\n", path.join(".")));
+                write_table(out, &path, table);
+            }
+            Value::Array(items) if is_table_array(value) => {
+                for item in items.iter() {
+                    out.push_str(&format!("\n[[{}]]\n", path.join(".")));
+                    if let Value::Object(table) = item {
+                        write_table(out, &path, table);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn write_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        key.to_owned()
+    } else {
+        Value::from(key).to_string()
+    }
+}
+
+pub fn write_value(value: &Value) -> String {
+    match value {
+        Value::Array(items) => {
+            let items: Vec<String> = items
+                .iter()
+                .filter(|item| !item.is_null())
+                .map(write_value)
+                .collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(table) => {
+            let entries: Vec<String> = table
+                .iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| format!("{} = {}", write_key(key), write_value(value)))
+                .collect();
+            format!("{{ {} }}", entries.join(", "))
+        }
+        // JSON strings, numbers and booleans are also valid TOML
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_tables_and_values() {
+        let doc = parse(
+            r#"
+# a comment
+name = "tt" # trailing comment
+[protect]
+symbols = ["™", '®']
+spans = [
+    "All rights reserved.",
+]
+nested.depth = 2
+
+[[group]]
+locales = ["fr", "de"]
+enabled = true
+[[group]]
+locales = []
+window = { start = 2020-12-20, ratio = 1.5 }
+"#,
+        )
+        .expect("can parse document");
+
+        assert_eq!(doc["name"], "tt");
+        assert_eq!(doc["protect"]["symbols"][1], "®");
+        assert_eq!(doc["protect"]["spans"][0], "All rights reserved.");
+        assert_eq!(doc["protect"]["nested"]["depth"], 2);
+        assert_eq!(doc["group"][0]["enabled"], true);
+        assert_eq!(doc["group"][1]["window"]["start"], "2020-12-20");
+        assert_eq!(doc["group"][1]["window"]["ratio"], 1.5);
+    }
+
+    #[test]
+    fn can_write_documents() {
+        let source = r#"
+version = 1
+name = "tt \"2\""
+locales = ["fr", "de"]
+[protect]
+symbols = ["™"]
+nested.depth = 2
+[[credentials]]
+locales = ["fr"]
+file = "a.json"
+[[credentials]]
+locales = []
+window = { start = 2020-12-20, ratio = 1.5 }
+"#;
+        let doc = parse(source).unwrap();
+        let written = write(&doc);
+        assert_eq!(parse(&written).unwrap(), doc);
+        assert!(written.starts_with("locales = [\"fr\", \"de\"]\n"));
+        assert!(written.contains("\n[protect.nested]\ndepth = 2\n"));
+        assert!(written.contains("\n[[credentials]]\nfile = \"a.json\"\n"));
+    }
+
+    #[test]
+    fn can_set_top_level_keys() {
+        let text = "# my project\nsource = \"en.flt\"\n\n[output]\nversion = 3\n";
+        let text = set_top_level(text, "version", "1");
+        assert_eq!(
+            text,
+            "# my project\nversion = 1\nsource = \"en.flt\"\n\n[output]\nversion = 3\n"
+        );
+        assert_eq!(
+            set_top_level(&text, "version", "2"),
+            text.replacen("version = 1", "version = 2", 1)
+        );
+        assert_eq!(
+            set_top_level(
+                "locales = [\n    \"fr\",\n    \"de\",\n]\nsource = \"en.flt\"\n",
+                "locales",
+                "[\"fr\", \"de\", \"sv\"]"
+            ),
+            "locales = [\"fr\", \"de\", \"sv\"]\nsource = \"en.flt\"\n"
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_keys() {
+        assert!(parse("a = 1\na = 2\n").is_err());
+        assert!(parse("a = \"unterminated\n").is_err());
+    }
+}
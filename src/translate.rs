@@ -14,6 +14,7 @@
 
 use serde::{Deserialize, Serialize};
 use std::boxed::Box;
+use std::collections::BTreeMap;
 use std::error::Error;
 
 use reqwest::blocking::Client;
@@ -25,14 +26,62 @@ pub struct GlossaryConfig<'a> {
     pub ignore_case: Option<bool>,
 }
 
+/// How the text sent to a backend should be interpreted
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum MimeType {
+    #[serde(rename = "text/plain")]
+    Text,
+    #[serde(rename = "text/html")]
+    Html,
+}
+
+impl MimeType {
+    /// Parse `--mime-type`, where `auto` means detecting it for each message
+    pub fn from_arg(arg: &str) -> Option<MimeType> {
+        match arg {
+            "text" => Some(MimeType::Text),
+            "html" => Some(MimeType::Html),
+            _ => None,
+        }
+    }
+
+    /// Treat text as HTML if it looks like it contains any tags
+    pub fn detect(text: &str) -> MimeType {
+        let mut rest = text;
+        while let Some(start) = rest.find('<') {
+            rest = &rest[start + 1..];
+            let tag_like = rest
+                .chars()
+                .next()
+                .map(|c| c.is_ascii_alphabetic() || c == '/' || c == '!')
+                .unwrap_or(false);
+            if tag_like && rest.contains('>') {
+                return MimeType::Html;
+            }
+        }
+        MimeType::Text
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MimeType::Text => "text/plain",
+            MimeType::Html => "text/html",
+        }
+    }
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct TranslateQuery<'a, 'b, 'c> {
     contents: Vec<&'a str>,
     mime_type: &'static str,
-    source_language_code: &'static str,
+    source_language_code: &'a str,
     target_language_code: &'b str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<&'a str>,
     glossary_config: Option<GlossaryConfig<'c>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    labels: &'a BTreeMap<String, String>,
 }
 
 #[derive(Deserialize)]
@@ -48,7 +97,7 @@ struct TRData {
     glossary_translations: Option<Vec<TRTranslation>>,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct LRLanguage {
     pub language_code: String,
@@ -57,104 +106,225 @@ pub struct LRLanguage {
     pub support_target: bool,
 }
 
-#[derive(Deserialize)]
-struct LRData {
-    languages: Vec<LRLanguage>,
+#[derive(Serialize, Deserialize)]
+pub struct LRData {
+    pub languages: Vec<LRLanguage>,
+}
+
+/// Figure out why a query failed, so that quota and unsupported language errors
+/// can fall back to another backend
+fn query_error(status: reqwest::StatusCode, body: &str) -> super::errors::Errors {
+    let body = body.to_lowercase();
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS || body.contains("resource_exhausted") {
+        super::errors::Errors::QuotaExceeded
+    } else if status == reqwest::StatusCode::BAD_REQUEST
+        && body.contains("language")
+        && (body.contains("invalid") || body.contains("support"))
+    {
+        super::errors::Errors::UnsupportedLanguage
+    } else {
+        super::errors::Errors::FailedQuery
+    }
+}
+
+/// Turn the translation of `phrase` as it comes back from Google or DeepL
+/// into Fluent
+pub fn finish(
+    phrase: &str,
+    translation: &str,
+    mime_type: MimeType,
+) -> Result<String, Box<dyn Error>> {
+    // entities are only escaped when the text was translated as HTML
+    let translation = match mime_type {
+        MimeType::Html => super::entities::decode(phrase, translation)?,
+        MimeType::Text => translation.to_owned(),
+    };
+    Ok(translation.replace("\n", "\n    ").replace("\u{a0}", " "))
+}
+
+/// Check that `label` is a `key=value` pair that Google accepts: lowercase
+/// letters, digits, `_` and `-`, with a key that starts with a letter
+pub fn validate_label(label: String) -> Result<(), String> {
+    let valid = |part: &str| {
+        part.len() <= 63
+            && part
+                .chars()
+                .all(|c| c.is_lowercase() || c.is_numeric() || c == '_' || c == '-')
+    };
+    match label.split_once('=') {
+        Some((key, value))
+            if key.starts_with(char::is_lowercase) && valid(key) && valid(value) =>
+        {
+            Ok(())
+        }
+        Some(_) => Err(format!(
+            "invalid label `{}`, keys and values can only have lowercase letters, digits, `_` and `-`",
+            label
+        )),
+        None => Err(format!("invalid label `{}`, expected KEY=VALUE", label)),
+    }
 }
 
 pub struct Translator<'a, 'b> {
     client: Client,
     token: &'a str,
     project_id: &'a str,
+    /// the base URL of the translation API
+    api: String,
+    /// the Google Cloud location to translate in
+    location: String,
+    source_language: String,
+    /// the model to translate with, as a resource name
+    model: Option<String>,
+    labels: BTreeMap<String, String>,
+    retry: super::retry::Retry,
     language: &'b str,
 }
 
+/// The resource name of `model`, which can be given by its id within the
+/// project and location, like `general/nmt`
+pub fn model_name(project_id: &str, location: &str, model: &str) -> String {
+    if model.starts_with("projects/") {
+        model.to_owned()
+    } else {
+        format!(
+            "projects/{}/locations/{}/models/{}",
+            project_id, location, model
+        )
+    }
+}
+
 impl<'a, 'b> Translator<'a, 'b> {
-    pub fn new(token: &'a str, project_id: &'a str, language: &'b str) -> Translator<'a, 'b> {
-        Translator {
-            client: Client::new(),
+    pub fn new(
+        token: &'a str,
+        project_id: &'a str,
+        connection: &super::backend::Connection,
+        model: Option<&str>,
+        language: &'b str,
+    ) -> Result<Translator<'a, 'b>, Box<dyn Error>> {
+        let location = connection.endpoints.location.clone();
+        Ok(Translator {
+            client: connection.http.client()?,
             token,
             project_id,
+            api: connection.endpoints.api.clone(),
+            source_language: connection.source_language.clone(),
+            model: model.map(|model| model_name(project_id, &location, model)),
+            labels: connection.labels.clone(),
+            retry: connection.retry.clone(),
+            location,
             language,
+        })
+    }
+
+    fn get_languages_response(&self) -> Result<LRData, Box<dyn Error>> {
+        let (status, res) = self.retry.send(
+            &self.client,
+            "google",
+            self.client
+                .get(&format!(
+                    "{}/v3/projects/{}/locations/{}/supportedLanguages?displayLanguageCode={}",
+                    self.api, self.project_id, self.location, self.language
+                ))
+                .bearer_auth(self.token),
+        )?;
+
+        if !status.is_success() {
+            eprintln!("query error: {}", res);
+            return Err(Box::from(query_error(status, &res)));
         }
+
+        serde_json::from_str(&res).map_err(|err| {
+            eprintln!("failed to parse response as json: {:?}", err);
+            eprintln!("response was:");
+            eprintln!("{}", res);
+            Box::from(err)
+        })
+    }
+}
+
+impl<'a, 'b> super::backend::Backend for Translator<'a, 'b> {
+    fn name(&self) -> &'static str {
+        "google"
     }
 
-    pub fn translate<'c>(
+    fn translate<'c>(
         &self,
         phrase: &str,
+        mime_type: MimeType,
         glossary: &Option<GlossaryConfig<'c>>,
     ) -> Result<String, Box<dyn Error>> {
-        // don't translate en -> en, just copy it over
-        if self.language == "en" {
-            return Ok(phrase.to_owned());
+        let mut translations = self.translate_batch(&[phrase], mime_type, glossary)?;
+        translations
+            .pop()
+            .ok_or_else(|| Box::from(super::errors::Errors::NoTranslations))
+    }
+
+    fn translate_batch<'c>(
+        &self,
+        phrases: &[&str],
+        mime_type: MimeType,
+        glossary: &Option<GlossaryConfig<'c>>,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        // don't translate into the source language, just copy it over
+        if self.language == self.source_language {
+            return Ok(phrases.iter().map(|phrase| (*phrase).to_owned()).collect());
         }
 
         let query = TranslateQuery {
-            contents: vec![phrase],
-            mime_type: "text/html",
-            source_language_code: "en",
+            contents: phrases.to_vec(),
+            mime_type: mime_type.as_str(),
+            source_language_code: &self.source_language,
             target_language_code: self.language,
+            model: self.model.as_deref(),
             glossary_config: glossary.clone(),
+            labels: &self.labels,
         };
         let query = serde_json::to_string(&query)?;
 
-        let res = self
-            .client
-            .post(&format!(
-                "https://translation.googleapis.com/v3/projects/{}/locations/us-central1:translateText",
-                self.project_id
-            ))
-            .bearer_auth(self.token)
-            .header(reqwest::header::CONTENT_TYPE, "application/json")
-            .body(query)
-            .send()?;
-
-        if !res.status().is_success() {
-            let res = res.text()?;
+        let (status, res) = self.retry.send(
+            &self.client,
+            "google",
+            self.client
+                .post(&format!(
+                    "{}/v3/projects/{}/locations/{}:translateText",
+                    self.api, self.project_id, self.location
+                ))
+                .bearer_auth(self.token)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(query),
+        )?;
+
+        if !status.is_success() {
             eprintln!("query error: {}", res);
-            return Err(Box::from(super::errors::Errors::FailedQuery));
+            return Err(Box::from(query_error(status, &res)));
         }
 
-        let res = res.text()?;
-        let mut res: TRData = serde_json::from_str(&res)?;
-        if res.translations.is_empty() {
+        let res: TRData = serde_json::from_str(&res)?;
+        let translations = match res.glossary_translations {
+            Some(glossary_translations) => glossary_translations,
+            None => res.translations,
+        };
+        if translations.len() != phrases.len() {
             return Err(Box::from(super::errors::Errors::NoTranslations));
         }
 
-        let translation = &if let Some(mut glossary_translations) = res.glossary_translations {
-            glossary_translations.pop().unwrap().translated_text
-        } else {
-            res.translations.pop().unwrap().translated_text
-        };
-        let translation = escaper::decode_html(translation)
-            .map_err(|e| format!("failed to decode HTML entities: {:?}", e))?;
-
-        Ok(translation.replace("\n", "\n    ").replace(" ", " "))
+        phrases
+            .iter()
+            .zip(translations)
+            .map(|(phrase, translation)| finish(phrase, &translation.translated_text, mime_type))
+            .collect()
     }
 
-    fn get_languages_response(&self) -> Result<LRData, Box<dyn Error>> {
-        let res = self
-            .client
-            .get(&format!("https://translation.googleapis.com/v3/projects/{}/locations/us-central1/supportedLanguages?displayLanguageCode={}", self.project_id, self.language))
-            .bearer_auth(self.token)
-            .send()?;
-
-        if !res.status().is_success() {
-            let res = res.text()?;
-            eprintln!("query error: {}", res);
-            return Err(Box::from(super::errors::Errors::FailedQuery));
-        }
-
-        let res = res.text()?;
-        serde_json::from_str(&res).map_err(|err| {
-            eprintln!("failed to parse response as json: {:?}", err);
-            eprintln!("response was:");
-            eprintln!("{}", res);
-            Box::from(err)
+    fn batch_limit(&self) -> Option<super::backend::BatchLimit> {
+        // the most that translateText accepts at once
+        Some(super::backend::BatchLimit {
+            texts: 1024,
+            chars: 30_000,
         })
     }
 
-    pub fn available_languages(&self) -> Result<Vec<LRLanguage>, Box<dyn Error>> {
+    fn available_languages(&self) -> Result<Vec<LRLanguage>, Box<dyn Error>> {
         let res = self.get_languages_response().map_err(|e| {
             eprintln!("failed to query languages: {:?}", e);
             e
@@ -166,7 +336,7 @@ impl<'a, 'b> Translator<'a, 'b> {
             .collect())
     }
 
-    pub fn get_lang_name(&self) -> Result<String, Box<dyn Error>> {
+    fn get_lang_name(&self) -> Result<String, Box<dyn Error>> {
         let res = self.get_languages_response()?;
         for lang in res.languages {
             if lang.language_code == self.language {
@@ -176,3 +346,51 @@ impl<'a, 'b> Translator<'a, 'b> {
         Ok("<INSERT LANGUAGE NAME HERE>".to_owned())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finishes_translations() {
+        assert_eq!(
+            finish("Hi\nthere", "Salut\u{a0}!\nvous", MimeType::Text).unwrap(),
+            "Salut !\n    vous"
+        );
+        assert_eq!(
+            finish(
+                "<b>Hi</b>\nthere",
+                "<b>Salut</b>\u{a0}!\nvous",
+                MimeType::Html
+            )
+            .unwrap(),
+            "<b>Salut</b> !\n    vous"
+        );
+    }
+
+    #[test]
+    fn names_models() {
+        assert_eq!(
+            model_name("proj", "us-central1", "general/nmt"),
+            "projects/proj/locations/us-central1/models/general/nmt"
+        );
+        assert_eq!(
+            model_name(
+                "proj",
+                "europe-west1",
+                "projects/other/locations/europe-west1/models/TRL123"
+            ),
+            "projects/other/locations/europe-west1/models/TRL123"
+        );
+    }
+
+    #[test]
+    fn validates_labels() {
+        assert!(validate_label("team=mobile".to_owned()).is_ok());
+        assert!(validate_label("cost_centre=".to_owned()).is_ok());
+        assert!(validate_label("Team=mobile".to_owned()).is_err());
+        assert!(validate_label("team=mobile app".to_owned()).is_err());
+        assert!(validate_label("1team=mobile".to_owned()).is_err());
+        assert!(validate_label("team".to_owned()).is_err());
+    }
+}
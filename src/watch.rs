@@ -0,0 +1,63 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// How often the watched files are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// When each file was last modified, or `None` if it doesn't exist
+fn modified(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// Block until any of `paths` is modified, created or removed, and then until
+/// nothing has changed for `debounce` so that a burst of saves reruns once
+pub fn wait_for_change(paths: &[PathBuf], debounce: Duration) {
+    let initial = modified(paths);
+    let mut last = loop {
+        std::thread::sleep(POLL_INTERVAL);
+        let current = modified(paths);
+        if current != initial {
+            break current;
+        }
+    };
+    loop {
+        std::thread::sleep(debounce);
+        let current = modified(paths);
+        if current == last {
+            break;
+        }
+        last = current;
+    }
+    log::debug!("change detected in {:?}", paths);
+}
+
+/// The name of the file that the last translated version of `source` is kept
+/// in, which is unique to each source file
+pub fn baseline_name(source: &Path) -> String {
+    let source = source
+        .canonicalize()
+        .unwrap_or_else(|_| source.to_path_buf());
+    let name: String = source
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("watch-{}.flt", name.trim_matches('_'))
+}
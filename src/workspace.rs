@@ -0,0 +1,76 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Directories that never hold projects, and can be huge
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules"];
+
+/// Find every project config under `root`, in a stable order. Hidden
+/// directories are skipped, along with build and dependency directories.
+pub fn discover(root: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut configs = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let config = dir.join(super::config::DEFAULT_CONFIG_FILE);
+        if config.is_file() {
+            configs.push(config);
+        }
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if entry.file_type()?.is_dir()
+                && !name.starts_with('.')
+                && !SKIPPED_DIRS.contains(&name.as_ref())
+            {
+                dirs.push(entry.path());
+            }
+        }
+    }
+    configs.sort();
+    Ok(configs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discovers_configs() {
+        let root = std::env::temp_dir().join(format!("tt-workspace-{}", std::process::id()));
+        for dir in ["b", "a/nested", ".git", "node_modules/dep", "c"].iter() {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for dir in ["", "b", "a/nested", ".git", "node_modules/dep"].iter() {
+            std::fs::write(root.join(dir).join("tt.toml"), "").unwrap();
+        }
+
+        let configs = discover(&root).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        let configs: Vec<&Path> = configs
+            .iter()
+            .map(|config| config.strip_prefix(&root).unwrap())
+            .collect();
+        assert_eq!(
+            configs,
+            vec![
+                Path::new("a/nested/tt.toml"),
+                Path::new("b/tt.toml"),
+                Path::new("tt.toml")
+            ]
+        );
+    }
+}
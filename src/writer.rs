@@ -0,0 +1,333 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::prelude::*;
+
+pub fn write_comment<'ast, W: Write>(
+    wtr: &mut W,
+    comment: Option<&fluent_syntax::ast::Comment<'ast>>,
+) -> std::io::Result<()> {
+    if let Some(comment) = comment {
+        match comment {
+            fluent_syntax::ast::Comment::Comment { content } => {
+                for c in content {
+                    writeln!(wtr, "# {}", c)?;
+                }
+            }
+            fluent_syntax::ast::Comment::GroupComment { content } => {
+                for c in content {
+                    writeln!(wtr, "## {}", c)?;
+                }
+            }
+            fluent_syntax::ast::Comment::ResourceComment { content } => {
+                for c in content {
+                    writeln!(wtr, "### {}", c)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn write_expression<'ast, W: Write>(
+    wtr: &mut W,
+    expression: &fluent_syntax::ast::Expression<'ast>,
+) -> std::io::Result<()> {
+    match expression {
+        fluent_syntax::ast::Expression::InlineExpression(ie) => {
+            write!(wtr, "{{ ")?;
+            write_inline_expression(wtr, ie)?;
+            write!(wtr, " }}")?;
+        }
+        fluent_syntax::ast::Expression::SelectExpression { selector, variants } => {
+            let variants: Vec<super::plurals::Variant> = variants
+                .iter()
+                .map(|variant| {
+                    let mut value: Vec<u8> = Vec::default();
+                    write_pattern(&mut value, &variant.value)?;
+                    let value = String::from_utf8_lossy(&value).into_owned();
+                    Ok((variant_key(variant).to_owned(), variant.default, value))
+                })
+                .collect::<std::io::Result<_>>()?;
+            write_select(wtr, selector, &variants)?;
+        }
+    }
+    Ok(())
+}
+
+fn variant_key<'a>(variant: &'a fluent_syntax::ast::Variant) -> &'a str {
+    match &variant.key {
+        fluent_syntax::ast::VariantKey::Identifier { name } => name,
+        fluent_syntax::ast::VariantKey::NumberLiteral { value } => value,
+    }
+}
+
+/// Write a select expression with the given key, default and text for each
+/// of its variants
+fn write_select<'ast, W: Write>(
+    wtr: &mut W,
+    selector: &fluent_syntax::ast::InlineExpression<'ast>,
+    variants: &[super::plurals::Variant],
+) -> std::io::Result<()> {
+    write!(wtr, "{{ ")?;
+    write_inline_expression(wtr, selector)?;
+    write!(wtr, " ->")?;
+    for (key, default, value) in variants {
+        let marker = if *default { "*" } else { " " };
+        // lines after the first have to be indented to stay in the variant
+        let value = value.replace('\n', "\n            ");
+        write!(wtr, "\n       {}[{}] {}", marker, key, value)?;
+    }
+    write!(wtr, "\n    }}")
+}
+
+/// Write an expression as it appears inside of a placeable's braces
+fn write_inline_expression<'ast, W: Write>(
+    wtr: &mut W,
+    expression: &fluent_syntax::ast::InlineExpression<'ast>,
+) -> std::io::Result<()> {
+    match expression {
+        fluent_syntax::ast::InlineExpression::StringLiteral { value } => {
+            write!(wtr, "\"{}\"", *value)?;
+        }
+        fluent_syntax::ast::InlineExpression::NumberLiteral { value } => {
+            write!(wtr, "{}", *value)?;
+        }
+        fluent_syntax::ast::InlineExpression::FunctionReference { id, arguments } => {
+            write!(wtr, "{}", id.name)?;
+            write_call_arguments(wtr, arguments.as_ref())?;
+        }
+        fluent_syntax::ast::InlineExpression::MessageReference { id, attribute } => {
+            write!(wtr, "{}", id.name)?;
+            if let Some(attribute) = attribute {
+                write!(wtr, ".{}", attribute.name)?;
+            }
+        }
+        fluent_syntax::ast::InlineExpression::TermReference {
+            id,
+            attribute,
+            arguments,
+        } => {
+            write!(wtr, "-{}", id.name)?;
+            if let Some(attribute) = attribute {
+                write!(wtr, ".{}", attribute.name)?;
+            }
+            if let Some(arguments) = arguments {
+                write_call_arguments(wtr, Some(arguments))?;
+            }
+        }
+        fluent_syntax::ast::InlineExpression::VariableReference { id } => {
+            write!(wtr, "${}", id.name)?;
+        }
+        fluent_syntax::ast::InlineExpression::Placeable { expression } => {
+            write_expression(wtr, expression)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_call_arguments<'ast, W: Write>(
+    wtr: &mut W,
+    arguments: Option<&fluent_syntax::ast::CallArguments<'ast>>,
+) -> std::io::Result<()> {
+    write!(wtr, "(")?;
+    if let Some(arguments) = arguments {
+        let mut first = true;
+        let mut separate = |wtr: &mut W| {
+            let separator = if first { "" } else { ", " };
+            first = false;
+            write!(wtr, "{}", separator)
+        };
+        for argument in arguments.positional.iter() {
+            separate(wtr)?;
+            write_inline_expression(wtr, argument)?;
+        }
+        for argument in arguments.named.iter() {
+            separate(wtr)?;
+            write!(wtr, "{}: ", argument.name.name)?;
+            write_inline_expression(wtr, &argument.value)?;
+        }
+    }
+    write!(wtr, ")")
+}
+
+pub fn write_pattern<'ast, W: Write>(
+    wtr: &mut W,
+    pattern: &fluent_syntax::ast::Pattern<'ast>,
+) -> std::io::Result<()> {
+    for element in &pattern.elements {
+        match element {
+            fluent_syntax::ast::PatternElement::TextElement(s) => {
+                wtr.write_all((*s).as_bytes())?;
+            }
+            fluent_syntax::ast::PatternElement::Placeable(e) => {
+                write_expression(wtr, e)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn write_attributes<'ast, W: Write>(
+    wtr: &mut W,
+    attributes: &[fluent_syntax::ast::Attribute<'ast>],
+) -> std::io::Result<()> {
+    for attribute in attributes {
+        write!(wtr, "\n    .{} = ", attribute.id.name)?;
+        write_pattern(wtr, &attribute.value)?;
+    }
+    Ok(())
+}
+
+/// Put the placeables of `pattern` back into its translation, in place of the
+/// markers that were sent to the translator, by their ids. A marker whose id
+/// isn't one of the placeables is left as it is.
+pub fn fill_placeables<'ast>(
+    pattern: &fluent_syntax::ast::Pattern<'ast>,
+    translated: &str,
+) -> String {
+    fill_variants(pattern, translated, &mut std::iter::empty(), None)
+}
+
+/// Put the placeables of `pattern` back into its translation like
+/// `fill_placeables` does, with the variants of its select expressions
+/// replaced by their translations, taken from `variants` in the order that
+/// `catalog::variant_patterns` lists them. Variants without a translation
+/// are left as they are in the source. Plural selects are fitted to the
+/// `plurals` categories of the locale, if they're known.
+pub fn fill_variants<'ast, 'v, I: Iterator<Item = &'v String>>(
+    pattern: &fluent_syntax::ast::Pattern<'ast>,
+    translated: &str,
+    variants: &mut I,
+    plurals: Option<&[&str]>,
+) -> String {
+    // convert each of the placeables
+    let placeables: Vec<String> = pattern
+        .elements
+        .iter()
+        .filter_map(|e| match e {
+            fluent_syntax::ast::PatternElement::Placeable(e) => {
+                let mut text: Vec<u8> = Vec::default();
+                match e {
+                    fluent_syntax::ast::Expression::SelectExpression {
+                        selector,
+                        variants: source,
+                    } => {
+                        let filled: Vec<super::plurals::Variant> = source
+                            .iter()
+                            .map(|variant| {
+                                let value = match variants.next() {
+                                    Some(translated) => {
+                                        fill_variants(&variant.value, translated, variants, plurals)
+                                    }
+                                    None => fill_placeables(
+                                        &variant.value,
+                                        &super::catalog::source_text(&variant.value),
+                                    ),
+                                };
+                                (variant_key(variant).to_owned(), variant.default, value)
+                            })
+                            .collect();
+                        let filled = match plurals {
+                            Some(categories) => super::plurals::fit(filled, categories),
+                            None => filled,
+                        };
+                        write_select(&mut text, selector, &filled)
+                    }
+                    _ => write_expression(&mut text, e),
+                }
+                .expect("can write_expression on placeable");
+                let text = String::from_utf8(text).expect("valid utf-8");
+                Some(text)
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut msg = String::with_capacity(translated.len());
+    let mut last = 0;
+    for marker in super::placeables::markers(translated).into_iter() {
+        let placeable = marker
+            .id
+            .checked_sub(1)
+            .and_then(|i| placeables.get(i))
+            .map(String::as_str)
+            .unwrap_or(&translated[marker.range.clone()]);
+        msg.push_str(&translated[last..marker.range.start]);
+        msg.push_str(placeable);
+        last = marker.range.end;
+    }
+    msg.push_str(&translated[last..]);
+    msg
+}
+
+pub fn write_term<'ast, W: Write>(
+    wtr: &mut W,
+    term: &fluent_syntax::ast::Term<'ast>,
+) -> std::io::Result<()> {
+    write_comment(wtr, term.comment.as_ref())?;
+    write!(wtr, "-{} = ", term.id.name)?;
+    write_pattern(wtr, &term.value)?;
+    write_attributes(wtr, &term.attributes)
+}
+
+/// Write `message` as it is, such as one marked `tt-no-translate`
+pub fn write_message<'ast, W: Write>(
+    wtr: &mut W,
+    message: &fluent_syntax::ast::Message<'ast>,
+) -> std::io::Result<()> {
+    write_comment(wtr, message.comment.as_ref())?;
+    write!(wtr, "{} =", message.id.name)?;
+    if let Some(value) = &message.value {
+        write!(wtr, " ")?;
+        write_pattern(wtr, value)?;
+    }
+    write_attributes(wtr, &message.attributes)
+}
+
+/// Write `message` with its value and attributes replaced by their translations
+pub fn write_translated_message<'ast, W: Write>(
+    wtr: &mut W,
+    message: &fluent_syntax::ast::Message<'ast>,
+    segments: &super::catalog::Segments,
+) -> std::io::Result<()> {
+    if let Some(comment) = &segments.comment {
+        for line in comment.lines() {
+            writeln!(wtr, "# {}", line)?;
+        }
+    }
+    write!(wtr, "{} =", message.id.name)?;
+    let mut variants = segments.variants.iter();
+    if let (Some(pattern), Some(value)) = (&message.value, &segments.value) {
+        let msg = fill_variants(
+            pattern,
+            value,
+            &mut variants.next().into_iter().flatten(),
+            segments.plurals,
+        );
+        write!(wtr, " ")?;
+        wtr.write_all(msg.as_bytes())?;
+    }
+    for (attribute, value) in message.attributes.iter().zip(segments.attributes.iter()) {
+        let msg = fill_variants(
+            &attribute.value,
+            value,
+            &mut variants.next().into_iter().flatten(),
+            segments.plurals,
+        );
+        write!(wtr, "\n    .{} = ", attribute.id.name)?;
+        wtr.write_all(msg.as_bytes())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,364 @@
+// Copyright 2020 Kenton Hamaluik
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::error::Error;
+use std::io::prelude::*;
+use std::path::Path;
+
+use super::compare::html_escape;
+use super::formats::xml_attribute;
+
+/// The names of the versions with `--format`
+pub const FORMATS: [&str; 2] = ["xliff", "xliff2"];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Version {
+    /// XLIFF 1.2, which most CAT tools read
+    V1,
+    /// XLIFF 2.0
+    V2,
+}
+
+impl Version {
+    pub fn from_arg(name: &str) -> Option<Version> {
+        match name {
+            "xliff" => Some(Version::V1),
+            "xliff2" => Some(Version::V2),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `--format` or the extension of `path` says that it's XLIFF
+pub fn is_xliff(format: Option<&str>, path: &Path) -> bool {
+    match format {
+        Some(format) => FORMATS.contains(&format),
+        None => matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("xlf") | Some("xliff")
+        ),
+    }
+}
+
+/// A text to translate: a message's value, or one of its attributes as
+/// `id.attribute`
+pub struct Unit {
+    pub id: String,
+    /// the text with its placeables as markers
+    pub source: String,
+    /// the Fluent of each placeable, by the marker's id less one
+    pub placeables: Vec<String>,
+    /// the source message's comment
+    pub note: Option<String>,
+}
+
+/// The text of a unit as XLIFF, with each placeable's marker as a placeholder
+/// that shows the placeable's Fluent
+fn inline(text: &str, placeables: &[String], version: Version) -> String {
+    let mut out = String::new();
+    let mut last = 0;
+    for marker in super::placeables::markers(text).into_iter() {
+        out.push_str(&html_escape(&text[last..marker.range.start]));
+        let shown = marker
+            .id
+            .checked_sub(1)
+            .and_then(|i| placeables.get(i))
+            .map(String::as_str)
+            .unwrap_or("");
+        out.push_str(&match version {
+            Version::V1 => format!(
+                "<x id=\"{}\" equiv-text=\"{}\"/>",
+                marker.id,
+                html_escape(shown)
+            ),
+            Version::V2 => format!("<ph id=\"{}\" disp=\"{}\"/>", marker.id, html_escape(shown)),
+        });
+        last = marker.range.end;
+    }
+    out.push_str(&html_escape(&text[last..]));
+    out
+}
+
+/// Write the `units` to translate from `source_language` into
+/// `target_language`, where `original` names the file that they're from
+pub fn write<W: Write>(
+    out: &mut W,
+    version: Version,
+    original: &str,
+    source_language: &str,
+    target_language: &str,
+    units: &[Unit],
+) -> std::io::Result<()> {
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    match version {
+        Version::V1 => {
+            writeln!(
+                out,
+                "<xliff version=\"1.2\" xmlns=\"urn:oasis:names:tc:xliff:document:1.2\">"
+            )?;
+            writeln!(
+                out,
+                "  <file original=\"{}\" source-language=\"{}\" target-language=\"{}\" datatype=\"plaintext\">",
+                html_escape(original),
+                html_escape(source_language),
+                html_escape(target_language)
+            )?;
+            writeln!(out, "    <body>")?;
+            for unit in units.iter() {
+                writeln!(out, "      <trans-unit id=\"{}\">", html_escape(&unit.id))?;
+                writeln!(
+                    out,
+                    "        <source>{}</source>",
+                    inline(&unit.source, &unit.placeables, version)
+                )?;
+                if let Some(note) = &unit.note {
+                    writeln!(out, "        <note>{}</note>", html_escape(note))?;
+                }
+                writeln!(out, "      </trans-unit>")?;
+            }
+            writeln!(out, "    </body>")?;
+            writeln!(out, "  </file>")?;
+        }
+        Version::V2 => {
+            writeln!(
+                out,
+                "<xliff version=\"2.0\" xmlns=\"urn:oasis:names:tc:xliff:document:2.0\" srcLang=\"{}\" trgLang=\"{}\">",
+                html_escape(source_language),
+                html_escape(target_language)
+            )?;
+            writeln!(
+                out,
+                "  <file id=\"f1\" original=\"{}\">",
+                html_escape(original)
+            )?;
+            for unit in units.iter() {
+                writeln!(out, "    <unit id=\"{}\">", html_escape(&unit.id))?;
+                if let Some(note) = &unit.note {
+                    writeln!(out, "      <notes>")?;
+                    writeln!(out, "        <note>{}</note>", html_escape(note))?;
+                    writeln!(out, "      </notes>")?;
+                }
+                writeln!(out, "      <segment>")?;
+                writeln!(
+                    out,
+                    "        <source>{}</source>",
+                    inline(&unit.source, &unit.placeables, version)
+                )?;
+                writeln!(out, "      </segment>")?;
+                writeln!(out, "    </unit>")?;
+            }
+            writeln!(out, "  </file>")?;
+        }
+    }
+    writeln!(out, "</xliff>")
+}
+
+/// Decode the entities of XML text
+fn decode(text: &str) -> Result<String, Box<dyn Error>> {
+    escaper::decode_html(text)
+        .map_err(|e| Box::from(format!("invalid XML text `{}`: {:?}", text, e)))
+}
+
+/// The text of a translation's XLIFF, with its placeholders back as
+/// placeables' markers and any other inline markup left out
+fn from_inline(content: &str) -> Result<String, Box<dyn Error>> {
+    let mut text = String::new();
+    let mut rest = content;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&decode(&rest[..start])?);
+        let end = rest[start..]
+            .find('>')
+            .ok_or("unterminated XML tag in a target")?;
+        let tag = &rest[start..=start + end];
+        let name: String = tag[1..]
+            .chars()
+            .take_while(|c| !c.is_whitespace() && *c != '/' && *c != '>')
+            .collect();
+        rest = &rest[start + end + 1..];
+        if name == "x" || name == "ph" {
+            let id = xml_attribute(tag, "id")
+                .and_then(|id| id.parse().ok())
+                .ok_or_else(|| format!("`{}` doesn't have a placeable's id", tag))?;
+            text.push_str(&super::placeables::marker(id));
+            if !tag.ends_with("/>") {
+                let close = format!("</{}>", name);
+                let close_at = rest
+                    .find(&close)
+                    .ok_or_else(|| format!("unterminated `{}`", tag))?;
+                rest = &rest[close_at + close.len()..];
+            }
+        }
+    }
+    text.push_str(&decode(rest)?);
+    Ok(text)
+}
+
+/// The contents of each `<tag>` element in `text`
+fn elements<'t>(text: &'t str, tag: &str) -> Vec<(&'t str, &'t str)> {
+    let open = format!("<{}", tag);
+    let close = format!("</{}>", tag);
+    let mut found = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start..];
+        let after = &rest[open.len()..];
+        if !after.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+            rest = after;
+            continue;
+        }
+        let tag_end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+        let start_tag = &rest[..=tag_end];
+        if start_tag.ends_with("/>") {
+            found.push((start_tag, ""));
+            rest = &rest[tag_end + 1..];
+            continue;
+        }
+        match rest.find(&close) {
+            Some(end) => {
+                found.push((start_tag, &rest[tag_end + 1..end]));
+                rest = &rest[end + close.len()..];
+            }
+            None => break,
+        }
+    }
+    found
+}
+
+/// The translations of a completed XLIFF file
+pub struct Translated {
+    pub locale: String,
+    /// each unit's id and translation, with its placeables as markers
+    pub units: Vec<(String, String)>,
+}
+
+/// Read the translated units of an XLIFF 1.2 or 2.0 file, leaving out the
+/// ones without a translation
+pub fn read(text: &str) -> Result<Translated, Box<dyn Error>> {
+    let root = elements(text, "xliff")
+        .into_iter()
+        .next()
+        .ok_or("there's no <xliff> element")?;
+    let (locale, units) = match xml_attribute(root.0, "version") {
+        Some(version) if version.starts_with('1') => {
+            let file = elements(root.1, "file")
+                .into_iter()
+                .next()
+                .ok_or("there's no <file> element")?;
+            let locale = xml_attribute(file.0, "target-language")
+                .ok_or("the <file> has no target-language")?;
+            let units = elements(file.1, "trans-unit")
+                .into_iter()
+                .map(|(tag, body)| {
+                    let target = elements(body, "target")
+                        .into_iter()
+                        .next()
+                        .map(|(_, content)| content);
+                    (tag, target.map(|content| vec![content]).unwrap_or_default())
+                })
+                .collect::<Vec<_>>();
+            (locale, units)
+        }
+        Some(version) if version.starts_with('2') => {
+            let locale = xml_attribute(root.0, "trgLang").ok_or("the <xliff> has no trgLang")?;
+            let units = elements(root.1, "unit")
+                .into_iter()
+                .map(|(tag, body)| {
+                    let targets = elements(body, "segment")
+                        .into_iter()
+                        .filter_map(|(_, segment)| elements(segment, "target").into_iter().next())
+                        .map(|(_, content)| content)
+                        .collect();
+                    (tag, targets)
+                })
+                .collect::<Vec<_>>();
+            (locale, units)
+        }
+        _ => return Err(Box::from("only XLIFF 1.2 and 2.0 can be imported")),
+    };
+
+    let mut translated = Vec::new();
+    for (tag, targets) in units.into_iter() {
+        let id = xml_attribute(tag, "id").ok_or_else(|| format!("`{}` has no id", tag))?;
+        let mut translation = String::new();
+        for target in targets.iter() {
+            translation.push_str(&from_inline(target)?);
+        }
+        if !translation.trim().is_empty() {
+            translated.push((decode(id)?, translation));
+        }
+    }
+    Ok(Translated {
+        locale: decode(locale)?,
+        units: translated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit() -> Unit {
+        Unit {
+            id: "hello".to_owned(),
+            source: format!(
+                "Hello, {} & <b>welcome</b>",
+                super::super::placeables::marker(1)
+            ),
+            placeables: vec!["{ $name }".to_owned()],
+            note: Some("shown on the home page".to_owned()),
+        }
+    }
+
+    #[test]
+    fn writes_xliff() {
+        let mut out = Vec::new();
+        write(&mut out, Version::V1, "en.flt", "en", "fr", &[unit()]).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("<source>Hello, <x id=\"1\" equiv-text=\"{ $name }\"/> &amp; &lt;b&gt;welcome&lt;/b&gt;</source>"));
+        assert!(text.contains("<note>shown on the home page</note>"));
+
+        let mut out = Vec::new();
+        write(&mut out, Version::V2, "en.flt", "en", "fr", &[unit()]).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("trgLang=\"fr\""));
+        assert!(text.contains("<ph id=\"1\" disp=\"{ $name }\"/>"));
+    }
+
+    #[test]
+    fn reads_xliff() {
+        let marker = super::super::placeables::marker(1);
+        let v1 = "<?xml version=\"1.0\"?>\n<xliff version=\"1.2\">\n<file target-language=\"fr\" source-language=\"en\">\n<body>\n<trans-unit id=\"hello\"><source>Hello, <x id=\"1\"/></source><target state=\"translated\">Bonjour, <g id=\"b\">cher</g> <x id=\"1\" equiv-text=\"{ $name }\"/> &amp; bienvenue</target></trans-unit>\n<trans-unit id=\"bye.title\"><source>Bye</source></trans-unit>\n</body>\n</file>\n</xliff>\n";
+        let translated = read(v1).unwrap();
+        assert_eq!(translated.locale, "fr");
+        assert_eq!(
+            translated.units,
+            vec![(
+                "hello".to_owned(),
+                format!("Bonjour, cher {} & bienvenue", marker)
+            )]
+        );
+
+        let v2 = "<xliff version=\"2.0\" srcLang=\"en\" trgLang=\"de\"><file id=\"f1\"><unit id=\"bye.title\"><segment><source>Bye</source><target>Tschüss</target></segment><segment><source> <ph id=\"1\"/></source><target> <ph id=\"1\"></ph></target></segment></unit></file></xliff>";
+        let translated = read(v2).unwrap();
+        assert_eq!(translated.locale, "de");
+        assert_eq!(
+            translated.units,
+            vec![("bye.title".to_owned(), format!("Tschüss {}", marker))]
+        );
+        assert!(read("<xliff version=\"3.0\"></xliff>").is_err());
+    }
+}